@@ -114,6 +114,11 @@ pub fn main() -> Result<()> {
         tool: ToolType::Thin,
         engine_type: EngineType::Sync,
         use_metadata_snap: false,
+        overlay: None,
+        trace: None,
+        io_stats: None,
+        block_cache: None,
+        report: None,
     };
 
     let report = mk_report(false);
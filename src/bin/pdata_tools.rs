@@ -13,15 +13,23 @@ fn register_commands<'a>() -> Vec<Box<dyn Command<'a>>> {
     vec![
         Box::new(cache_check::CacheCheckCommand),
         Box::new(cache_dump::CacheDumpCommand),
+        Box::new(cache_metadata_pack::CacheMetadataPackCommand),
         Box::new(cache_metadata_size::CacheMetadataSizeCommand),
+        Box::new(cache_metadata_unpack::CacheMetadataUnpackCommand),
         Box::new(cache_repair::CacheRepairCommand),
+        Box::new(cache_resize::CacheResizeCommand),
         Box::new(cache_restore::CacheRestoreCommand),
+        Box::new(cache_stat::CacheStatCommand),
         Box::new(cache_writeback::CacheWritebackCommand),
         Box::new(era_check::EraCheckCommand),
         Box::new(era_dump::EraDumpCommand),
         Box::new(era_invalidate::EraInvalidateCommand),
+        Box::new(era_metadata_pack::EraMetadataPackCommand),
+        Box::new(era_metadata_unpack::EraMetadataUnpackCommand),
         Box::new(era_repair::EraRepairCommand),
         Box::new(era_restore::EraRestoreCommand),
+        Box::new(overlay_commit::OverlayCommitCommand),
+        Box::new(overlay_discard::OverlayDiscardCommand),
         Box::new(thin_check::ThinCheckCommand),
         Box::new(thin_delta::ThinDeltaCommand),
         Box::new(thin_dump::ThinDumpCommand),
@@ -14,6 +14,7 @@ fn register_commands<'a>() -> Vec<Box<dyn Command<'a>>> {
         Box::new(era_generate_metadata::EraGenerateMetadataCommand),
         Box::new(cache_generate_metadata::CacheGenerateMetadataCommand),
         Box::new(cache_generate_damage::CacheGenerateDamageCommand),
+        Box::new(io_replay::IoReplayCommand),
         Box::new(thin_explore::ThinExploreCommand),
         Box::new(thin_generate_metadata::ThinGenerateMetadataCommand),
         Box::new(thin_generate_damage::ThinGenerateDamageCommand),
@@ -220,21 +220,93 @@ mod format2 {
 
 //------------------------------------------
 
-struct HintChecker;
+struct HintChecker {
+    smq: bool,
+    nr_entries: Mutex<u64>,
+}
 
 impl HintChecker {
-    fn new() -> HintChecker {
-        HintChecker
+    fn new(smq: bool) -> HintChecker {
+        HintChecker {
+            smq,
+            nr_entries: Mutex::new(0),
+        }
+    }
+
+    // The length of the array, as far as the blocks visited tell
+    fn nr_entries(&self) -> u64 {
+        *self.nr_entries.lock().unwrap()
     }
-}
 
-impl ArrayVisitor<Hint> for HintChecker {
-    fn visit(&self, _index: u64, _b: ArrayBlock<Hint>) -> array::Result<()> {
-        // TODO: check hints
+    fn check_level(&self, hint: &[u8], cblock: u32) -> array::Result<()> {
+        let level = u32::from_le_bytes(hint[0..4].try_into().unwrap());
+        if level >= SMQ_NR_LEVELS {
+            return Err(array::value_err(format!(
+                "smq hint level {} out of range for cache block {}",
+                level, cblock
+            )));
+        }
         Ok(())
     }
 }
 
+impl<const WIDTH: usize> ArrayVisitor<WideHint<WIDTH>> for HintChecker {
+    fn visit(&self, index: u64, b: ArrayBlock<WideHint<WIDTH>>) -> array::Result<()> {
+        let mut errs: Vec<ArrayError> = Vec::new();
+
+        let cbegin = index as u32 * b.header.max_entries;
+        let cend = cbegin + b.header.nr_entries;
+        {
+            let mut nr_entries = self.nr_entries.lock().unwrap();
+            *nr_entries = std::cmp::max(*nr_entries, cend as u64);
+        }
+
+        if self.smq {
+            for (h, cblock) in b.values.iter().zip(cbegin..cend) {
+                if let Err(e) = self.check_level(&h.hint, cblock) {
+                    errs.push(e);
+                }
+            }
+        }
+
+        // FIXME: duplicate to BTreeWalker::build_aggregate()
+        match errs.len() {
+            0 => Ok(()),
+            1 => Err(errs[0].clone()),
+            _ => Err(array::aggregate_error(errs)),
+        }
+    }
+}
+
+// Walks the hint array with the value type matching the hint width, which
+// must be a multiple of 4 up to MAX_HINT_WIDTH.
+macro_rules! walk_wide_hints {
+    ($walker: expr, $checker: expr, $root: expr, $width: expr, [$($w: literal),*]) => {
+        match $width {
+            $($w => $walker.walk::<WideHint<$w>>($checker, $root),)*
+            w => return Err(anyhow!("invalid policy hint size {}", w)),
+        }
+    };
+}
+
+fn walk_hints(
+    w: &ArrayWalker,
+    c: &HintChecker,
+    hint_width: u32,
+    root: u64,
+) -> anyhow::Result<array::Result<()>> {
+    Ok(walk_wide_hints!(
+        w,
+        c,
+        root,
+        hint_width,
+        [
+            4, 8, 12, 16, 20, 24, 28, 32, 36, 40, 44, 48, 52, 56, 60, 64, 68, 72, 76, 80, 84, 88,
+            92, 96, 100, 104, 108, 112, 116, 120, 124, 128
+        ]
+    ))
+}
+
 //------------------------------------------
 
 pub struct CacheCheckOptions<'a> {
@@ -275,6 +347,42 @@ fn check_superblock(sb: &Superblock) -> anyhow::Result<()> {
     Ok(())
 }
 
+fn check_hints(
+    ctx: &Context,
+    sb: &Superblock,
+    metadata_sm: ASpaceMap,
+    ignore_non_fatal: bool,
+) -> anyhow::Result<()> {
+    let smq = is_smq_policy(&sb.policy_name);
+    if smq && sb.policy_hint_size != SMQ_HINT_WIDTH {
+        ctx.report.fatal(&format!(
+            "policy {} expects a hint size of {}, but the superblock gives {}",
+            String::from_utf8_lossy(&sb.policy_name),
+            SMQ_HINT_WIDTH,
+            sb.policy_hint_size
+        ));
+        return Ok(());
+    }
+
+    let w = ArrayWalker::new_with_sm(ctx.engine.as_ref(), metadata_sm, ignore_non_fatal)?;
+    let c = HintChecker::new(smq);
+    if let Err(e) = walk_hints(&w, &c, sb.policy_hint_size, sb.hint_root)? {
+        ctx.report.fatal(&format!("{}", e));
+    }
+
+    // The hints are rewritten for every cache block on a clean shutdown,
+    // and ignored after a crash.
+    if sb.flags.clean_shutdown && c.nr_entries() != sb.cache_blocks as u64 {
+        ctx.report.fatal(&format!(
+            "hint array has {} entries, but there are {} cache blocks",
+            c.nr_entries(),
+            sb.cache_blocks
+        ));
+    }
+
+    Ok(())
+}
+
 pub fn check(opts: CacheCheckOptions) -> anyhow::Result<()> {
     let ctx = mk_context(&opts)?;
 
@@ -336,15 +444,7 @@ pub fn check(opts: CacheCheckOptions) -> anyhow::Result<()> {
     }
 
     if !opts.skip_hints && sb.hint_root != 0 && sb.policy_hint_size != 0 {
-        if sb.policy_hint_size != 4 {
-            return Err(anyhow!("cache_check only supports policy hint size of 4"));
-        }
-        let w =
-            ArrayWalker::new_with_sm(engine.as_ref(), metadata_sm.clone(), opts.ignore_non_fatal)?;
-        let c = HintChecker::new();
-        if let Err(e) = w.walk(&c, sb.hint_root) {
-            ctx.report.fatal(&format!("{}", e));
-        }
+        check_hints(&ctx, &sb, metadata_sm.clone(), opts.ignore_non_fatal)?;
     }
 
     // The discard bitset might not be available if the cache has never been suspended,
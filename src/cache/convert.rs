@@ -0,0 +1,297 @@
+use anyhow::{anyhow, Result};
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::cache::ir;
+use crate::cache::relocate::Entry;
+use crate::copier::CopyOp;
+
+//------------------------------------------
+
+// Maps cache metadata onto a different cache block size.
+//
+// Splitting a block into k smaller ones leaves its data in place, since the
+// i'th part of cblock c lands in cblock c * k + i.  Merging k blocks into
+// one needs every part of the larger block to be cached, and the parts have
+// to be gathered into consecutive cblocks on the fast device.  They're only
+// ever copied into cblocks that the old metadata leaves unmapped, so the old
+// metadata stays valid until the new metadata replaces it.
+
+pub(crate) struct Conversion {
+    pub sb: ir::Superblock,
+    // keyed by the new cblock
+    pub entries: BTreeMap<u32, Entry>,
+    // copies within the fast device, in units of the old block size
+    pub copies: Vec<CopyOp>,
+    // clean blocks given up because they were only partly cached, or
+    // there was no room to gather them
+    pub nr_dropped: u64,
+}
+
+pub(crate) fn convert_block_size(
+    sb: &ir::Superblock,
+    entries: BTreeMap<u32, Entry>,
+    block_size: u32,
+    smq: bool,
+) -> Result<Conversion> {
+    if !(64..=2097152).contains(&block_size) || (block_size & 0x3F != 0) {
+        return Err(anyhow!("invalid cache block size: {}", block_size));
+    }
+
+    if block_size == sb.block_size {
+        return Ok(Conversion {
+            sb: sb.clone(),
+            entries,
+            copies: Vec::new(),
+            nr_dropped: 0,
+        });
+    }
+
+    if sb.block_size.is_multiple_of(block_size) {
+        split(sb, entries, block_size)
+    } else if block_size.is_multiple_of(sb.block_size) {
+        merge(sb, entries, block_size, smq)
+    } else {
+        Err(anyhow!(
+            "cannot convert a block size of {} sectors to {}, one must be a multiple of the other",
+            sb.block_size,
+            block_size
+        ))
+    }
+}
+
+fn split(
+    sb: &ir::Superblock,
+    entries: BTreeMap<u32, Entry>,
+    block_size: u32,
+) -> Result<Conversion> {
+    let k = sb.block_size / block_size;
+    let nr_cache_blocks = sb
+        .nr_cache_blocks
+        .checked_mul(k)
+        .ok_or_else(|| anyhow!("too many cache blocks at a block size of {}", block_size))?;
+
+    let mut new_entries = BTreeMap::new();
+    for e in entries.into_values() {
+        for i in 0..k {
+            let cblock = e.cblock * k + i;
+            new_entries.insert(
+                cblock,
+                Entry {
+                    cblock,
+                    oblock: e.oblock * k as u64 + i as u64,
+                    dirty: e.dirty,
+                    hint: e.hint.clone(),
+                },
+            );
+        }
+    }
+
+    Ok(Conversion {
+        sb: ir::Superblock {
+            block_size,
+            nr_cache_blocks,
+            ..sb.clone()
+        },
+        entries: new_entries,
+        copies: Vec::new(),
+        nr_dropped: 0,
+    })
+}
+
+// The merged entry takes the hint of its hottest part
+fn merge_parts(cblock: u32, oblock: u64, parts: &[Entry], smq: bool) -> Entry {
+    let hint = parts
+        .iter()
+        .max_by_key(|e| e.hint_level(smq))
+        .and_then(|e| e.hint.clone());
+    Entry {
+        cblock,
+        oblock,
+        dirty: parts.iter().any(|e| e.dirty),
+        hint,
+    }
+}
+
+fn merge(
+    sb: &ir::Superblock,
+    entries: BTreeMap<u32, Entry>,
+    block_size: u32,
+    smq: bool,
+) -> Result<Conversion> {
+    let k = block_size / sb.block_size;
+    let nr_cache_blocks = sb.nr_cache_blocks / k;
+    let mapped: BTreeSet<u32> = entries.keys().cloned().collect();
+
+    // group the parts by their new origin block
+    let mut groups: BTreeMap<u64, Vec<Entry>> = BTreeMap::new();
+    for e in entries.into_values() {
+        groups.entry(e.oblock / k as u64).or_default().push(e);
+    }
+
+    let mut nr_dropped = 0;
+    let mut new_entries = BTreeMap::new();
+    let mut to_move = Vec::new();
+    for (oblock, mut parts) in groups {
+        if parts.len() < k as usize {
+            if parts.iter().any(|e| e.dirty) {
+                return Err(anyhow!(
+                    "origin block {} at the new block size is partly cached and dirty, write it back first",
+                    oblock
+                ));
+            }
+            nr_dropped += parts.len() as u64;
+            continue;
+        }
+        parts.sort_by_key(|e| e.oblock);
+
+        // Leave a block where it is if its parts are already in place
+        let first = parts[0].cblock;
+        let in_place = first.is_multiple_of(k)
+            && first / k < nr_cache_blocks
+            && parts
+                .iter()
+                .enumerate()
+                .all(|(i, e)| e.cblock == first + i as u32);
+        if in_place {
+            let cblock = first / k;
+            new_entries.insert(cblock, merge_parts(cblock, oblock, &parts, smq));
+        } else {
+            to_move.push((oblock, parts));
+        }
+    }
+
+    // Dirty blocks get the free room first, then the hottest clean ones
+    to_move.sort_by_key(|(_, parts)| {
+        Reverse((
+            parts.iter().any(|e| e.dirty),
+            parts.iter().map(|e| e.hint_level(smq)).max(),
+        ))
+    });
+    let mut free =
+        (0..nr_cache_blocks).filter(|c| mapped.range(c * k..(c + 1) * k).next().is_none());
+
+    let mut copies = Vec::new();
+    for (oblock, parts) in to_move {
+        let Some(cblock) = free.next() else {
+            if parts.iter().any(|e| e.dirty) {
+                return Err(anyhow!(
+                    "no unmapped cache blocks left to gather origin block {} into, write it back first",
+                    oblock
+                ));
+            }
+            nr_dropped += parts.len() as u64;
+            continue;
+        };
+
+        for (i, e) in parts.iter().enumerate() {
+            copies.push(CopyOp {
+                src: e.cblock as u64,
+                dst: (cblock * k) as u64 + i as u64,
+            });
+        }
+        new_entries.insert(cblock, merge_parts(cblock, oblock, &parts, smq));
+    }
+
+    Ok(Conversion {
+        sb: ir::Superblock {
+            block_size,
+            nr_cache_blocks,
+            ..sb.clone()
+        },
+        entries: new_entries,
+        copies,
+        nr_dropped,
+    })
+}
+
+//------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sb(nr_cache_blocks: u32) -> ir::Superblock {
+        ir::Superblock {
+            uuid: String::new(),
+            block_size: 64,
+            nr_cache_blocks,
+            policy: String::from("smq"),
+            hint_width: 4,
+        }
+    }
+
+    fn entries(maps: &[(u32, u64, bool)]) -> BTreeMap<u32, Entry> {
+        maps.iter()
+            .map(|&(cblock, oblock, dirty)| {
+                (
+                    cblock,
+                    Entry {
+                        cblock,
+                        oblock,
+                        dirty,
+                        hint: None,
+                    },
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn merge_only_copies_into_unmapped_blocks() {
+        let maps = [
+            (0, 3, false),
+            (1, 2, false),
+            (2, 1, false),
+            (5, 0, true),
+            (6, 8, false),
+            (7, 9, false),
+        ];
+        let conv = convert_block_size(&sb(16), entries(&maps), 128, true).unwrap();
+        for op in &conv.copies {
+            assert!(!maps.iter().any(|m| m.0 as u64 == op.dst));
+        }
+
+        // origin block 4 was already in place
+        let summary: Vec<(u32, u64, bool)> = conv
+            .entries
+            .values()
+            .map(|e| (e.cblock, e.oblock, e.dirty))
+            .collect();
+        assert_eq!(summary, vec![(3, 4, false), (4, 0, true), (5, 1, false)]);
+        assert_eq!(conv.nr_dropped, 0);
+    }
+
+    #[test]
+    fn merge_drops_clean_blocks_without_room() {
+        let maps = [
+            (0, 3, false),
+            (1, 2, false),
+            (2, 1, false),
+            (5, 0, true),
+            (7, 9, false),
+        ];
+        let conv = convert_block_size(&sb(10), entries(&maps), 128, true).unwrap();
+        let summary: Vec<(u32, u64)> = conv
+            .entries
+            .values()
+            .map(|e| (e.cblock, e.oblock))
+            .collect();
+        assert_eq!(summary, vec![(4, 0)]);
+        assert_eq!(conv.nr_dropped, 3);
+    }
+
+    #[test]
+    fn merge_fails_if_dirty_blocks_have_no_room() {
+        let maps = [
+            (0, 3, false),
+            (1, 2, false),
+            (2, 1, false),
+            (5, 0, true),
+            (7, 9, false),
+        ];
+        assert!(convert_block_size(&sb(8), entries(&maps), 128, true).is_err());
+    }
+}
+
+//------------------------------------------
@@ -1,8 +1,6 @@
 use anyhow::{anyhow, Context};
 use fixedbitset::FixedBitSet;
 use std::fs::File;
-use std::io::BufWriter;
-use std::io::Write;
 use std::path::Path;
 use std::sync::{Arc, Mutex};
 
@@ -12,6 +10,7 @@ use crate::cache::mapping::Mapping;
 use crate::cache::superblock::*;
 use crate::cache::xml;
 use crate::commands::engine::*;
+use crate::compression;
 use crate::dump_utils::{self, *};
 use crate::io_engine::*;
 use crate::pdata::array::ArrayBlock;
@@ -368,15 +367,17 @@ pub fn dump(opts: CacheDumpOptions) -> anyhow::Result<()> {
     let ctx = mk_context(&opts)?;
     let sb = read_superblock(ctx.engine.as_ref(), SUPERBLOCK_LOCATION)?;
 
-    let writer: Box<dyn Write> = if opts.output.is_some() {
-        let f = File::create(opts.output.unwrap()).context(OutputError)?;
-        Box::new(BufWriter::new(f))
+    let mut writer = if let Some(path) = opts.output {
+        let f = File::create(path).context(OutputError)?;
+        compression::mk_writer(f, path).context(OutputError)?
     } else {
-        Box::new(BufWriter::new(std::io::stdout()))
+        compression::Writer::stdout()
     };
-    let mut out = xml::XmlWriter::new(writer);
+    let mut out = xml::XmlWriter::new(&mut writer);
 
-    dump_metadata(ctx.engine, &mut out, &sb, opts.repair)
+    dump_metadata(ctx.engine, &mut out, &sb, opts.repair)?;
+    drop(out);
+    writer.finish().context(OutputError)
 }
 
 //------------------------------------------
@@ -38,3 +38,40 @@ impl Pack for Hint {
 }
 
 //------------------------------------------
+
+/// The smq policy stores the level of each block in its hint.  mq and
+/// default are aliases of smq.
+pub const SMQ_HINT_WIDTH: u32 = 4;
+pub const SMQ_NR_LEVELS: u32 = 64;
+
+pub fn is_smq_policy(policy_name: &[u8]) -> bool {
+    [&b"smq"[..], b"mq", b"default"].contains(&policy_name)
+}
+
+/// Policies may store up to this many bytes of hint per cache block, in
+/// multiples of 4.
+pub const MAX_HINT_WIDTH: u32 = 128;
+
+/// A hint of any supported width.  `Hint` is the 4 byte hint used by the
+/// kernel's policies; this is for checking metadata with wider hints.
+#[derive(Clone, Copy)]
+pub struct WideHint<const WIDTH: usize> {
+    pub hint: [u8; WIDTH],
+}
+
+impl<const WIDTH: usize> Unpack for WideHint<WIDTH> {
+    fn disk_size() -> u32 {
+        WIDTH as u32
+    }
+
+    fn unpack(i: &[u8]) -> IResult<&[u8], WideHint<WIDTH>> {
+        Ok((
+            &i[WIDTH..],
+            WideHint {
+                hint: i[0..WIDTH].try_into().unwrap(),
+            },
+        ))
+    }
+}
+
+//------------------------------------------
@@ -17,7 +17,7 @@ pub enum MappingFlags {
     Dirty = 2,
 }
 
-#[derive(Clone, Copy, Default)]
+#[derive(Clone, Copy, Default, PartialEq, Eq)]
 pub struct Mapping {
     pub oblock: u64,
     pub flags: u32,
@@ -1,11 +1,15 @@
 pub mod check;
+pub mod convert;
 pub mod dump;
 pub mod hint;
 pub mod ir;
 pub mod mapping;
 pub mod metadata_size;
+pub mod relocate;
 pub mod repair;
+pub mod resize;
 pub mod restore;
+pub mod stat;
 pub mod superblock;
 pub mod writeback;
 pub mod xml;
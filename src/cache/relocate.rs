@@ -0,0 +1,188 @@
+use anyhow::Result;
+use std::collections::BTreeMap;
+use std::fs::OpenOptions;
+use std::path::Path;
+use std::sync::mpsc;
+use std::sync::Arc;
+
+use crate::cache::hint::*;
+use crate::cache::ir::{self, MetadataVisitor, Visit};
+use crate::copier::wrapper::ThreadedCopier;
+use crate::copier::*;
+
+//------------------------------------------
+
+// Tools that move cache blocks about, such as cache_resize, gather all the
+// mappings first, work out where they go, move the data on the fast device,
+// then write out the new metadata.  Data is only moved into cache blocks
+// that the old metadata leaves unmapped.
+
+pub(crate) struct Entry {
+    pub cblock: u32,
+    pub oblock: u64,
+    pub dirty: bool,
+    pub hint: Option<Vec<u8>>,
+}
+
+impl Entry {
+    /// The smq level held in the hint, or zero for other policies
+    pub fn hint_level(&self, smq: bool) -> u32 {
+        match (&self.hint, smq) {
+            (Some(h), true) if h.len() == SMQ_HINT_WIDTH as usize => {
+                u32::from_le_bytes(h[..].try_into().unwrap())
+            }
+            _ => 0,
+        }
+    }
+}
+
+/// Gathers the mappings and hints of the source metadata.
+#[derive(Default)]
+pub(crate) struct MetadataCollector {
+    pub sb: Option<ir::Superblock>,
+    pub entries: BTreeMap<u32, Entry>,
+    pub nr_discards: u64,
+}
+
+impl MetadataVisitor for MetadataCollector {
+    fn superblock_b(&mut self, sb: &ir::Superblock) -> Result<Visit> {
+        self.sb = Some(sb.clone());
+        Ok(Visit::Continue)
+    }
+
+    fn superblock_e(&mut self) -> Result<Visit> {
+        Ok(Visit::Continue)
+    }
+
+    fn mappings_b(&mut self) -> Result<Visit> {
+        Ok(Visit::Continue)
+    }
+
+    fn mappings_e(&mut self) -> Result<Visit> {
+        Ok(Visit::Continue)
+    }
+
+    fn mapping(&mut self, m: &ir::Map) -> Result<Visit> {
+        self.entries.insert(
+            m.cblock,
+            Entry {
+                cblock: m.cblock,
+                oblock: m.oblock,
+                dirty: m.dirty,
+                hint: None,
+            },
+        );
+        Ok(Visit::Continue)
+    }
+
+    fn hints_b(&mut self) -> Result<Visit> {
+        Ok(Visit::Continue)
+    }
+
+    fn hints_e(&mut self) -> Result<Visit> {
+        Ok(Visit::Continue)
+    }
+
+    fn hint(&mut self, h: &ir::Hint) -> Result<Visit> {
+        if let Some(e) = self.entries.get_mut(&h.cblock) {
+            e.hint = Some(h.data.clone());
+        }
+        Ok(Visit::Continue)
+    }
+
+    fn discards_b(&mut self) -> Result<Visit> {
+        Ok(Visit::Continue)
+    }
+
+    fn discards_e(&mut self) -> Result<Visit> {
+        Ok(Visit::Continue)
+    }
+
+    fn discard(&mut self, _d: &ir::Discard) -> Result<Visit> {
+        self.nr_discards += 1;
+        Ok(Visit::Continue)
+    }
+
+    fn eof(&mut self) -> Result<Visit> {
+        Ok(Visit::Continue)
+    }
+}
+
+//------------------------------------------
+
+/// Copies blocks within the fast device.  The ops must be independent of
+/// each other.  The copies are synced before returning, since dm-cache reads
+/// the device from below the page cache, and the new metadata will point at
+/// them.
+pub(crate) fn copy_blocks(
+    fast_dev: &Path,
+    ops: &[CopyOp],
+    block_size: usize,
+    copier: &CopierOptions,
+    progress: Arc<dyn CopyProgress + Send + Sync>,
+) -> Result<()> {
+    let file = OpenOptions::new().read(true).write(true).open(fast_dev)?;
+    let sync_file = file.try_clone()?;
+    let buffer_size = std::cmp::max(block_size, 64 * 1024 * 1024);
+    let c = mk_copier(
+        copier.copier_type,
+        buffer_size,
+        block_size,
+        CopierDevs::InFile(file),
+    )?;
+    let c = ThreadedCopier::new(c).limits(&copier.limits, block_size);
+
+    let (tx, rx) = mpsc::sync_channel::<Vec<CopyOp>>(1);
+    let handle = c.run(rx, progress);
+    for batch in ops.chunks(1_000_000) {
+        if tx.send(batch.to_vec()).is_err() {
+            // the copier has given up, its error is returned below
+            break;
+        }
+    }
+
+    drop(tx);
+    handle.join().unwrap()?;
+    sync_file.sync_all()?;
+
+    Ok(())
+}
+
+//------------------------------------------
+
+/// Writes out the metadata for the given entries, which are keyed by cblock.
+pub(crate) fn write_metadata(
+    out: &mut dyn MetadataVisitor,
+    sb: &ir::Superblock,
+    entries: &BTreeMap<u32, Entry>,
+) -> Result<()> {
+    out.superblock_b(sb)?;
+
+    out.mappings_b()?;
+    for e in entries.values() {
+        out.mapping(&ir::Map {
+            cblock: e.cblock,
+            oblock: e.oblock,
+            dirty: e.dirty,
+        })?;
+    }
+    out.mappings_e()?;
+
+    out.hints_b()?;
+    for e in entries.values() {
+        if let Some(data) = &e.hint {
+            out.hint(&ir::Hint {
+                cblock: e.cblock,
+                data: data.clone(),
+            })?;
+        }
+    }
+    out.hints_e()?;
+
+    out.superblock_e()?;
+    out.eof()?;
+
+    Ok(())
+}
+
+//------------------------------------------
@@ -0,0 +1,231 @@
+use anyhow::{anyhow, Result};
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::cache::dump::dump_metadata;
+use crate::cache::hint::*;
+use crate::cache::relocate::*;
+use crate::cache::restore::Restorer;
+use crate::cache::superblock::*;
+use crate::commands::engine::*;
+use crate::copier::*;
+use crate::file_utils;
+use crate::io_engine::*;
+use crate::pdata::bitset::read_bitset;
+use crate::pdata::space_map::metadata::*;
+use crate::report::*;
+use crate::write_batcher::*;
+
+//------------------------------------------
+
+pub struct CacheResizeOptions<'a> {
+    pub input: &'a Path,
+    pub output: &'a Path,
+    pub fast_dev: &'a Path,
+    pub nr_cache_blocks: u32,
+    pub engine_opts: EngineOptions,
+    pub copier: CopierOptions,
+    pub report: Arc<Report>,
+}
+
+struct Context {
+    report: Arc<Report>,
+    engine_in: Arc<dyn IoEngine + Send + Sync>,
+    engine_out: Arc<dyn IoEngine + Send + Sync>,
+}
+
+fn new_context(opts: &CacheResizeOptions) -> Result<Context> {
+    let engine_in = EngineBuilder::new(opts.input, &opts.engine_opts).build()?;
+    let engine_out = EngineBuilder::new(opts.output, &opts.engine_opts)
+        .write(true)
+        .build()?;
+
+    Ok(Context {
+        report: opts.report.clone(),
+        engine_in,
+        engine_out,
+    })
+}
+
+//------------------------------------------
+
+struct Plan {
+    // entries that survive the resize, keyed by their new cblock
+    kept: BTreeMap<u32, Entry>,
+    moves: Vec<CopyOp>,
+    nr_evicted: u64,
+}
+
+/// Works out which entries are kept, and where they go.  Entries below the
+/// new end stay where they are, and those beyond it are moved into cache
+/// blocks that are unmapped in the input, so no block the input metadata
+/// refers to is overwritten.  If they don't all fit, clean blocks beyond the
+/// end are given up in order of increasing smq level.  Dirty blocks are
+/// never dropped.
+fn plan_resize(
+    entries: BTreeMap<u32, Entry>,
+    nr_cache_blocks: u32,
+    smq: bool,
+    all_dirty: bool,
+) -> Result<Plan> {
+    let mut free: Vec<u32> = (0..nr_cache_blocks)
+        .filter(|b| !entries.contains_key(b))
+        .collect();
+
+    let mut kept = BTreeMap::new();
+    let mut above = Vec::new();
+    for e in entries.into_values() {
+        if e.cblock < nr_cache_blocks {
+            kept.insert(e.cblock, e);
+        } else {
+            above.push(e);
+        }
+    }
+
+    let nr_dirty = above.iter().filter(|e| all_dirty || e.dirty).count();
+    if nr_dirty > free.len() {
+        return Err(anyhow!(
+            "{} dirty blocks beyond the new end do not fit in {} free cache blocks, write them back first",
+            nr_dirty,
+            free.len()
+        ));
+    }
+
+    let nr_evicted = above.len().saturating_sub(free.len());
+    if nr_evicted > 0 {
+        let mut candidates: Vec<(u32, u32)> = above
+            .iter()
+            .filter(|e| !(all_dirty || e.dirty))
+            .map(|e| (e.hint_level(smq), e.cblock))
+            .collect();
+        candidates.sort_unstable();
+        let evicted: BTreeSet<u32> = candidates
+            .iter()
+            .take(nr_evicted)
+            .map(|(_, cblock)| *cblock)
+            .collect();
+        above.retain(|e| !evicted.contains(&e.cblock));
+    }
+
+    let mut moves = Vec::with_capacity(above.len());
+    free.reverse();
+    for mut e in above {
+        // there are enough free cblocks since the evictions above
+        let dst = free.pop().unwrap();
+        moves.push(CopyOp {
+            src: e.cblock as u64,
+            dst: dst as u64,
+        });
+        e.cblock = dst;
+        kept.insert(dst, e);
+    }
+
+    Ok(Plan {
+        kept,
+        moves,
+        nr_evicted: nr_evicted as u64,
+    })
+}
+
+//------------------------------------------
+
+// The dump doesn't visit the discards, so they're read separately.  A
+// bitset that can't be read is lost all the same.
+fn has_discards(engine: &dyn IoEngine, sb: &Superblock) -> bool {
+    if sb.discard_root == 0 || sb.discard_nr_blocks == 0 {
+        return false;
+    }
+
+    read_bitset(
+        engine,
+        sb.discard_root,
+        sb.discard_nr_blocks as usize,
+        false,
+    )
+    .map_or(true, |discards| discards.count_ones(..) > 0)
+}
+
+pub fn resize(opts: CacheResizeOptions) -> Result<()> {
+    if opts.nr_cache_blocks == 0 {
+        return Err(anyhow!(
+            "the number of cache blocks must be greater than zero"
+        ));
+    }
+
+    let ctx = new_context(&opts)?;
+    let sb = read_superblock(ctx.engine_in.as_ref(), SUPERBLOCK_LOCATION)?;
+
+    // The restorer only writes the 4 byte hints of the kernel's policies
+    if sb.policy_hint_size != SMQ_HINT_WIDTH {
+        return Err(anyhow!(
+            "unsupported policy hint size {}",
+            sb.policy_hint_size
+        ));
+    }
+
+    let block_size = (sb.data_block_size as u64) << SECTOR_SHIFT;
+    let fast_dev_size = file_utils::file_size(opts.fast_dev)?;
+    if fast_dev_size < opts.nr_cache_blocks as u64 * block_size {
+        return Err(anyhow!(
+            "fast device is too small for {} cache blocks",
+            opts.nr_cache_blocks
+        ));
+    }
+
+    // 1st pass
+    let mut collector = MetadataCollector::default();
+    dump_metadata(ctx.engine_in.clone(), &mut collector, &sb, false)?;
+    let mut xml_sb = collector
+        .sb
+        .take()
+        .ok_or_else(|| anyhow!("no superblock found"))?;
+
+    // Without a clean shutdown every block may be dirty
+    let clean = sb.flags.clean_shutdown;
+    let plan = plan_resize(
+        collector.entries,
+        opts.nr_cache_blocks,
+        is_smq_policy(&sb.policy_name),
+        !clean,
+    )?;
+
+    if !plan.moves.is_empty() {
+        ctx.report.set_title("Moving cache blocks");
+        copy_blocks(
+            opts.fast_dev,
+            &plan.moves,
+            block_size as usize,
+            &opts.copier,
+            Arc::new(ProgressReporter::new(
+                ctx.report.clone(),
+                plan.moves.len() as u64,
+                block_size as usize,
+            )),
+        )?;
+    }
+
+    // 2nd pass
+    xml_sb.nr_cache_blocks = opts.nr_cache_blocks;
+    let sm = core_metadata_sm(ctx.engine_out.get_nr_blocks(), u32::MAX);
+    let batch_size = ctx.engine_out.get_batch_size();
+    let mut w = WriteBatcher::new(ctx.engine_out, sm, batch_size);
+    let mut restorer = Restorer::new(&mut w, sb.version as u8);
+    if !clean {
+        restorer.omit_clean_shutdown()?;
+    }
+    write_metadata(&mut restorer, &xml_sb, &plan.kept)?;
+
+    ctx.report.info(&format!(
+        "moved {} blocks, evicted {} clean blocks",
+        plan.moves.len(),
+        plan.nr_evicted
+    ));
+    if has_discards(ctx.engine_in.as_ref(), &sb) {
+        ctx.report.info("the discard bitset is not carried over");
+    }
+
+    Ok(())
+}
+
+//------------------------------------------
@@ -5,12 +5,16 @@ use std::fs::OpenOptions;
 use std::path::Path;
 use std::sync::Arc;
 
-use crate::cache::hint::Hint;
+use crate::cache::convert::convert_block_size;
+use crate::cache::hint::{is_smq_policy, Hint};
 use crate::cache::ir::{self, MetadataVisitor, Visit};
 use crate::cache::mapping::{Mapping, MappingFlags};
+use crate::cache::relocate::*;
 use crate::cache::superblock::*;
 use crate::cache::xml;
 use crate::commands::engine::*;
+use crate::compression;
+use crate::copier::{CopierOptions, ProgressReporter};
 use crate::io_engine::*;
 use crate::math::*;
 use crate::pdata::array_builder::*;
@@ -28,10 +32,13 @@ pub struct CacheRestoreOptions<'a> {
     pub engine_opts: EngineOptions,
     pub report: Arc<Report>,
     pub omit_clean_shutdown: bool,
+    pub block_size: Option<u32>,
+    pub fast_dev: Option<&'a Path>,
+    pub copier: CopierOptions,
 }
 
 struct Context {
-    _report: Arc<Report>,
+    report: Arc<Report>,
     engine: Arc<dyn IoEngine + Send + Sync>,
 }
 
@@ -41,7 +48,7 @@ fn mk_context(opts: &CacheRestoreOptions) -> anyhow::Result<Context> {
         .build()?;
 
     Ok(Context {
-        _report: opts.report.clone(),
+        report: opts.report.clone(),
         engine,
     })
 }
@@ -318,14 +325,64 @@ pub fn restore(opts: CacheRestoreOptions) -> Result<()> {
     let ctx = mk_context(&opts)?;
 
     let sm = core_metadata_sm(ctx.engine.get_nr_blocks(), u32::MAX);
-    let mut w = WriteBatcher::new(ctx.engine.clone(), sm.clone(), ctx.engine.get_batch_size());
+    let mut w = WriteBatcher::new_pipelined(
+        ctx.engine.clone(),
+        sm.clone(),
+        ctx.engine.get_batch_size(),
+        PIPELINE_DEPTH,
+    );
 
     // build cache mappings
     let mut restorer = Restorer::new(&mut w, opts.metadata_version);
     if opts.omit_clean_shutdown {
         restorer.omit_clean_shutdown()?;
     }
-    xml::read(input, &mut restorer)?;
+
+    if let Some(block_size) = opts.block_size {
+        let mut collector = MetadataCollector::default();
+        xml::read(compression::mk_reader(input)?, &mut collector)?;
+        let sb = collector
+            .sb
+            .take()
+            .ok_or_else(|| anyhow!("no superblock found"))?;
+
+        let conv = convert_block_size(
+            &sb,
+            collector.entries,
+            block_size,
+            is_smq_policy(sb.policy.as_bytes()),
+        )?;
+
+        if !conv.copies.is_empty() {
+            let fast_dev = opts
+                .fast_dev
+                .ok_or_else(|| anyhow!("the fast device is needed to merge cache blocks"))?;
+            let block_size = (sb.block_size as usize) << SECTOR_SHIFT;
+            ctx.report.set_title("Gathering cache blocks");
+            copy_blocks(
+                fast_dev,
+                &conv.copies,
+                block_size,
+                &opts.copier,
+                Arc::new(ProgressReporter::new(
+                    ctx.report.clone(),
+                    conv.copies.len() as u64,
+                    block_size,
+                )),
+            )?;
+        }
+
+        write_metadata(&mut restorer, &conv.sb, &conv.entries)?;
+        if conv.nr_dropped > 0 {
+            ctx.report
+                .info(&format!("dropped {} clean cache blocks", conv.nr_dropped));
+        }
+        if collector.nr_discards > 0 {
+            ctx.report.info("the discard bitset is not carried over");
+        }
+    } else {
+        xml::read(compression::mk_reader(input)?, &mut restorer)?;
+    }
 
     Ok(())
 }
@@ -0,0 +1,493 @@
+use anyhow::anyhow;
+use fixedbitset::FixedBitSet;
+use std::collections::BTreeMap;
+use std::io::{self, Write};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+use crate::cache::hint::*;
+use crate::cache::mapping::*;
+use crate::cache::superblock::*;
+use crate::commands::engine::*;
+use crate::commands::utils::json_string;
+use crate::io_engine::*;
+use crate::math::div_up;
+use crate::pdata::array::{self, ArrayBlock};
+use crate::pdata::array_walker::*;
+use crate::pdata::bitset::read_bitset;
+
+//------------------------------------------
+
+// The origin is split into this many regions for the distribution
+const NR_ORIGIN_REGIONS: u64 = 16;
+
+struct MappingCollector {
+    inner: Mutex<Mappings>,
+}
+
+struct Mappings {
+    valid: FixedBitSet,
+    dirty: FixedBitSet,
+    // (cblock, oblock) of each valid mapping
+    entries: Vec<(u32, u64)>,
+}
+
+impl MappingCollector {
+    fn new(nr_cache_blocks: usize) -> MappingCollector {
+        MappingCollector {
+            inner: Mutex::new(Mappings {
+                valid: FixedBitSet::with_capacity(nr_cache_blocks),
+                dirty: FixedBitSet::with_capacity(nr_cache_blocks),
+                entries: Vec::new(),
+            }),
+        }
+    }
+
+    fn complete(self) -> Mappings {
+        self.inner.into_inner().unwrap()
+    }
+}
+
+impl ArrayVisitor<Mapping> for MappingCollector {
+    fn visit(&self, index: u64, b: ArrayBlock<Mapping>) -> array::Result<()> {
+        let mut inner = self.inner.lock().unwrap();
+
+        let cbegin = index as u32 * b.header.max_entries;
+        let cend = cbegin + b.header.nr_entries;
+        for (m, cblock) in b.values.iter().zip(cbegin..cend) {
+            if !m.is_valid() || cblock as usize >= inner.valid.len() {
+                continue;
+            }
+            inner.valid.insert(cblock as usize);
+            // only format 1 keeps the dirty flag in the mapping
+            inner.dirty.set(cblock as usize, m.is_dirty());
+            inner.entries.push((cblock, m.oblock));
+        }
+
+        Ok(())
+    }
+}
+
+struct HintLevelCounter {
+    valid: FixedBitSet,
+    histogram: Mutex<BTreeMap<u32, u64>>,
+}
+
+impl HintLevelCounter {
+    fn new(valid: FixedBitSet) -> HintLevelCounter {
+        HintLevelCounter {
+            valid,
+            histogram: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    fn complete(self) -> BTreeMap<u32, u64> {
+        self.histogram.into_inner().unwrap()
+    }
+}
+
+impl ArrayVisitor<Hint> for HintLevelCounter {
+    fn visit(&self, index: u64, b: ArrayBlock<Hint>) -> array::Result<()> {
+        let mut histogram = self.histogram.lock().unwrap();
+
+        let cbegin = index as u32 * b.header.max_entries;
+        let cend = cbegin + b.header.nr_entries;
+        for (h, cblock) in b.values.iter().zip(cbegin..cend) {
+            // hints of unmapped blocks mean nothing
+            if self.valid.contains(cblock as usize) {
+                *histogram.entry(u32::from_le_bytes(h.hint)).or_insert(0) += 1;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+//------------------------------------------
+
+struct OriginRegion {
+    begin: u64,
+    end: u64,
+    nr_mapped: u64,
+    nr_dirty: u64,
+}
+
+struct DiscardStats {
+    block_size: u64,
+    nr_blocks: u64,
+    nr_discarded: u64,
+}
+
+struct CacheStats {
+    nr_cache_blocks: u32,
+    block_size: u32,
+    policy: String,
+    version: u32,
+    clean_shutdown: bool,
+
+    nr_valid: u64,
+    nr_dirty: u64,
+
+    nr_origin_blocks: u64,
+    regions: Vec<OriginRegion>,
+
+    // Runs are bucketed by the power of two below their length
+    run_lengths: BTreeMap<u32, u64>,
+    nr_runs: u64,
+    longest_run: u64,
+
+    hint_levels: Option<BTreeMap<u32, u64>>,
+    discards: Option<DiscardStats>,
+}
+
+// Reads the mappings, along with the valid and dirty cache blocks
+fn read_mappings(engine: &dyn IoEngine, sb: &Superblock) -> anyhow::Result<Mappings> {
+    let w = ArrayWalker::new(engine, false);
+    let c = MappingCollector::new(sb.cache_blocks as usize);
+    w.walk(&c, sb.mapping_root)?;
+    let mut mappings = c.complete();
+
+    match sb.version {
+        1 => {}
+        2 => {
+            let root = sb
+                .dirty_root
+                .ok_or_else(|| anyhow!("format 2 selected, but no dirty bitset present"))?;
+            let mut dirty = read_bitset(engine, root, sb.cache_blocks as usize, false)?;
+            dirty.intersect_with(&mappings.valid);
+            mappings.dirty = dirty;
+        }
+        v => return Err(anyhow!("unsupported metadata version: {}", v)),
+    }
+
+    // The dirty bits aren't kept up to date while the cache is running,
+    // so after a crash the kernel treats every mapped block as dirty.
+    if !sb.flags.clean_shutdown {
+        mappings.dirty = mappings.valid.clone();
+    }
+
+    Ok(mappings)
+}
+
+fn origin_regions(mappings: &Mappings, nr_origin_blocks: u64) -> Vec<OriginRegion> {
+    if nr_origin_blocks == 0 {
+        return Vec::new();
+    }
+
+    let region_size = div_up(nr_origin_blocks, NR_ORIGIN_REGIONS);
+    let mut regions: Vec<OriginRegion> = (0..div_up(nr_origin_blocks, region_size))
+        .map(|i| OriginRegion {
+            begin: i * region_size,
+            end: std::cmp::min((i + 1) * region_size, nr_origin_blocks),
+            nr_mapped: 0,
+            nr_dirty: 0,
+        })
+        .collect();
+
+    for (cblock, oblock) in &mappings.entries {
+        let region = &mut regions[(oblock / region_size) as usize];
+        region.nr_mapped += 1;
+        if mappings.dirty.contains(*cblock as usize) {
+            region.nr_dirty += 1;
+        }
+    }
+
+    regions
+}
+
+fn run_lengths(mappings: &[(u32, u64)]) -> (BTreeMap<u32, u64>, u64, u64) {
+    let mut sorted: Vec<u64> = mappings.iter().map(|(_, oblock)| *oblock).collect();
+    sorted.sort_unstable();
+
+    let mut histogram = BTreeMap::new();
+    let mut nr_runs = 0;
+    let mut longest = 0;
+    let mut add_run = |len: u64| {
+        *histogram.entry(len.ilog2()).or_insert(0) += 1;
+        nr_runs += 1;
+        longest = std::cmp::max(longest, len);
+    };
+
+    let mut run: Option<(u64, u64)> = None;
+    for b in sorted {
+        run = match run {
+            Some((begin, end)) if end == b => Some((begin, b + 1)),
+            Some((begin, end)) => {
+                add_run(end - begin);
+                Some((b, b + 1))
+            }
+            None => Some((b, b + 1)),
+        };
+    }
+    if let Some((begin, end)) = run {
+        add_run(end - begin);
+    }
+
+    (histogram, nr_runs, longest)
+}
+
+fn hint_levels(
+    engine: &dyn IoEngine,
+    sb: &Superblock,
+    valid: &FixedBitSet,
+) -> anyhow::Result<Option<BTreeMap<u32, u64>>> {
+    if !is_smq_policy(&sb.policy_name) || sb.policy_hint_size != SMQ_HINT_WIDTH || sb.hint_root == 0
+    {
+        return Ok(None);
+    }
+
+    let w = ArrayWalker::new(engine, false);
+    let c = HintLevelCounter::new(valid.clone());
+    w.walk(&c, sb.hint_root)?;
+    Ok(Some(c.complete()))
+}
+
+fn discard_stats(engine: &dyn IoEngine, sb: &Superblock) -> anyhow::Result<Option<DiscardStats>> {
+    if sb.discard_root == 0 || sb.discard_nr_blocks == 0 {
+        return Ok(None);
+    }
+
+    let discards = read_bitset(
+        engine,
+        sb.discard_root,
+        sb.discard_nr_blocks as usize,
+        false,
+    )?;
+    Ok(Some(DiscardStats {
+        block_size: sb.discard_block_size,
+        nr_blocks: sb.discard_nr_blocks,
+        nr_discarded: discards.count_ones(..) as u64,
+    }))
+}
+
+fn gather_stats(engine: &dyn IoEngine, sb: &Superblock) -> anyhow::Result<CacheStats> {
+    let mappings = read_mappings(engine, sb)?;
+
+    // The discard bitset covers the whole origin, if it's there.  Otherwise
+    // all we know is that the origin reaches the highest mapped block.
+    let mapped_end = mappings
+        .entries
+        .iter()
+        .map(|(_, oblock)| oblock + 1)
+        .max()
+        .unwrap_or(0);
+    let nr_origin_blocks = if sb.discard_block_size > 0 && sb.discard_nr_blocks > 0 {
+        let discarded_end =
+            sb.discard_block_size * sb.discard_nr_blocks / sb.data_block_size as u64;
+        std::cmp::max(discarded_end, mapped_end)
+    } else {
+        mapped_end
+    };
+
+    let regions = origin_regions(&mappings, nr_origin_blocks);
+    let (run_lengths, nr_runs, longest_run) = run_lengths(&mappings.entries);
+
+    Ok(CacheStats {
+        nr_cache_blocks: sb.cache_blocks,
+        block_size: sb.data_block_size,
+        policy: String::from_utf8_lossy(&sb.policy_name).to_string(),
+        version: sb.version,
+        clean_shutdown: sb.flags.clean_shutdown,
+        nr_valid: mappings.valid.count_ones(..) as u64,
+        nr_dirty: mappings.dirty.count_ones(..) as u64,
+        nr_origin_blocks,
+        regions,
+        run_lengths,
+        nr_runs,
+        longest_run,
+        hint_levels: hint_levels(engine, sb, &mappings.valid)?,
+        discards: discard_stats(engine, sb)?,
+    })
+}
+
+//------------------------------------------
+
+fn percent(n: u64, total: u64) -> f64 {
+    if total == 0 {
+        0.0
+    } else {
+        n as f64 * 100.0 / total as f64
+    }
+}
+
+fn print_text<W: Write>(w: &mut W, stats: &CacheStats) -> io::Result<()> {
+    let nr_clean = stats.nr_valid - stats.nr_dirty;
+    writeln!(w, "cache blocks\t{}", stats.nr_cache_blocks)?;
+    writeln!(w, "block size\t{} sectors", stats.block_size)?;
+    writeln!(w, "policy\t\t{}", stats.policy)?;
+    writeln!(w, "version\t\t{}", stats.version)?;
+    writeln!(
+        w,
+        "clean shutdown\t{}",
+        if stats.clean_shutdown {
+            "yes"
+        } else {
+            "no, so every valid block counts as dirty"
+        }
+    )?;
+    writeln!(
+        w,
+        "valid\t\t{}\t{:.2}%",
+        stats.nr_valid,
+        percent(stats.nr_valid, stats.nr_cache_blocks as u64)
+    )?;
+    writeln!(
+        w,
+        "dirty\t\t{}\t{:.2}%",
+        stats.nr_dirty,
+        percent(stats.nr_dirty, stats.nr_cache_blocks as u64)
+    )?;
+    writeln!(
+        w,
+        "clean\t\t{}\t{:.2}%",
+        nr_clean,
+        percent(nr_clean, stats.nr_cache_blocks as u64)
+    )?;
+
+    writeln!(w)?;
+    writeln!(w, "origin blocks\tmapped\tdirty")?;
+    for r in &stats.regions {
+        writeln!(w, "{}..{}\t{}\t{}", r.begin, r.end, r.nr_mapped, r.nr_dirty)?;
+    }
+
+    writeln!(w)?;
+    writeln!(w, "run length\tcounts\tpercentage")?;
+    for (k, v) in &stats.run_lengths {
+        let min = 1u64 << k;
+        let max = (min << 1) - 1;
+        let len = if min == max {
+            format!("{}", min)
+        } else {
+            format!("{}-{}", min, max)
+        };
+        writeln!(w, "{}\t\t{}\t{:.4}", len, v, percent(*v, stats.nr_runs))?;
+    }
+    writeln!(
+        w,
+        "{} runs in {} origin blocks, longest {}",
+        stats.nr_runs, stats.nr_origin_blocks, stats.longest_run
+    )?;
+
+    if let Some(levels) = &stats.hint_levels {
+        writeln!(w)?;
+        writeln!(w, "hint level\tcounts\tpercentage")?;
+        for (k, v) in levels {
+            writeln!(w, "{}\t\t{}\t{:.4}", k, v, percent(*v, stats.nr_valid))?;
+        }
+    }
+
+    if let Some(d) = &stats.discards {
+        writeln!(w)?;
+        writeln!(w, "discard block size\t{} sectors", d.block_size)?;
+        writeln!(
+            w,
+            "discarded\t\t{}/{}\t{:.2}%",
+            d.nr_discarded,
+            d.nr_blocks,
+            percent(d.nr_discarded, d.nr_blocks)
+        )?;
+    }
+
+    Ok(())
+}
+
+fn print_json<W: Write>(w: &mut W, stats: &CacheStats) -> io::Result<()> {
+    let regions: Vec<String> = stats
+        .regions
+        .iter()
+        .map(|r| {
+            format!(
+                "{{\"begin\": {}, \"end\": {}, \"mapped\": {}, \"dirty\": {}}}",
+                r.begin, r.end, r.nr_mapped, r.nr_dirty
+            )
+        })
+        .collect();
+    let runs: Vec<String> = stats
+        .run_lengths
+        .iter()
+        .map(|(k, v)| {
+            let min = 1u64 << k;
+            format!(
+                "{{\"min\": {}, \"max\": {}, \"count\": {}}}",
+                min,
+                (min << 1) - 1,
+                v
+            )
+        })
+        .collect();
+    let hint_levels = match &stats.hint_levels {
+        Some(levels) => {
+            let levels: Vec<String> = levels
+                .iter()
+                .map(|(k, v)| format!("{{\"level\": {}, \"count\": {}}}", k, v))
+                .collect();
+            format!("[{}]", levels.join(", "))
+        }
+        None => "null".to_string(),
+    };
+    let discards = match &stats.discards {
+        Some(d) => format!(
+            "{{\"block_size\": {}, \"nr_blocks\": {}, \"discarded\": {}}}",
+            d.block_size, d.nr_blocks, d.nr_discarded
+        ),
+        None => "null".to_string(),
+    };
+
+    writeln!(
+        w,
+        concat!(
+            "{{\"cache_blocks\": {}, \"block_size\": {}, \"policy\": {}, ",
+            "\"version\": {}, \"clean_shutdown\": {}, ",
+            "\"valid\": {}, \"dirty\": {}, \"clean\": {}, ",
+            "\"origin_blocks\": {}, \"origin_regions\": [{}], ",
+            "\"runs\": {{\"count\": {}, \"longest\": {}, \"lengths\": [{}]}}, ",
+            "\"hint_levels\": {}, \"discards\": {}}}"
+        ),
+        stats.nr_cache_blocks,
+        stats.block_size,
+        json_string(&stats.policy),
+        stats.version,
+        stats.clean_shutdown,
+        stats.nr_valid,
+        stats.nr_dirty,
+        stats.nr_valid - stats.nr_dirty,
+        stats.nr_origin_blocks,
+        regions.join(", "),
+        stats.nr_runs,
+        stats.longest_run,
+        runs.join(", "),
+        hint_levels,
+        discards
+    )
+}
+
+//------------------------------------------
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum StatFormat {
+    Text,
+    Json,
+}
+
+pub struct CacheStatOptions<'a> {
+    pub input: &'a Path,
+    pub engine_opts: EngineOptions,
+    pub format: StatFormat,
+}
+
+pub fn stat(opts: CacheStatOptions) -> anyhow::Result<()> {
+    let engine: Arc<dyn IoEngine + Send + Sync> =
+        EngineBuilder::new(opts.input, &opts.engine_opts).build()?;
+    let sb = read_superblock(engine.as_ref(), SUPERBLOCK_LOCATION)?;
+    let stats = gather_stats(engine.as_ref(), &sb)?;
+
+    let stdout = io::stdout();
+    let mut stdout = stdout.lock();
+    match opts.format {
+        StatFormat::Text => print_text(&mut stdout, &stats)?,
+        StatFormat::Json => print_json(&mut stdout, &stats)?,
+    }
+    Ok(())
+}
+
+//------------------------------------------
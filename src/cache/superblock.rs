@@ -11,7 +11,7 @@ use crate::io_engine::*;
 pub const SPACE_MAP_ROOT_SIZE: usize = 128;
 pub const SUPERBLOCK_LOCATION: u64 = 0;
 
-const MAGIC: u64 = 0o6142003; // 0x18c403 in hex
+pub const MAGIC: u64 = 0o6142003; // 0x18c403 in hex
 const POLICY_NAME_SIZE: usize = 16;
 const UUID_SIZE: usize = 16;
 
@@ -1,7 +1,9 @@
 use anyhow::anyhow;
+use rangemap::RangeSet;
 use roaring::RoaringBitmap;
-use std::fs::File;
+use std::fs::{File, OpenOptions};
 use std::io::Cursor;
+use std::ops::Range;
 use std::path::Path;
 use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::mpsc::{self, SyncSender};
@@ -13,9 +15,13 @@ use crate::cache::superblock::*;
 use crate::checksum;
 use crate::commands::engine::*;
 use crate::copier::batcher::CopyOpBatcher;
+use crate::copier::rescue_copier::pass_granularity;
+use crate::copier::rescue_map::BlockState;
+use crate::copier::throttle::Throttle;
 use crate::copier::*;
-use crate::io_engine::utils::{SimpleBlockIo, VectoredBlockIo};
+use crate::io_engine::utils::SimpleBlockIo;
 use crate::io_engine::{self, *};
+use crate::math::div_up;
 use crate::pdata::array::{self, *};
 use crate::pdata::array_walker::*;
 use crate::pdata::bitset::read_bitset_checked;
@@ -32,11 +38,18 @@ struct WritebackStats {
     /// Number of blocks that were successfully copied
     nr_copied: u64,
 
+    /// Number of copied blocks that didn't need writing, with the compare
+    /// copier
+    nr_skipped: u64,
+
     /// Number of read errors
     nr_read_errors: u64,
 
     /// Number of write errors
     nr_write_errors: u64,
+
+    /// Number of blocks that didn't match the cache when read back
+    nr_verify_errors: u64,
 }
 
 //-----------------------------------------
@@ -105,12 +118,88 @@ impl WritebackSelector for AllSelector {
 
 //---------------
 
-struct MappingCounter<F: Fn(&Mapping) -> bool> {
+/// Restricts the writeback to part of the cache
+#[derive(Clone, Debug, PartialEq)]
+pub enum WritebackFilter {
+    /// Regions of the origin, in sectors
+    OriginRanges(Vec<Range<u64>>),
+    CacheBlocks(RoaringBitmap),
+}
+
+/// Selects the blocks another selector does, that are in the filter
+struct FilteredSelector {
+    inner: Box<dyn WritebackSelector>,
+    nr_blocks: u32,
+    oblocks: RangeSet<u64>,
+    cblocks: Option<RoaringBitmap>,
+}
+
+impl FilteredSelector {
+    fn new(
+        engine: &Arc<dyn IoEngine + Sync + Send>,
+        sb: &Superblock,
+        inner: Box<dyn WritebackSelector>,
+        filter: &WritebackFilter,
+    ) -> anyhow::Result<Self> {
+        let mut selector = FilteredSelector {
+            inner,
+            nr_blocks: 0,
+            oblocks: RangeSet::new(),
+            cblocks: None,
+        };
+
+        match filter {
+            WritebackFilter::OriginRanges(ranges) => {
+                // Any block touching a range is written back
+                let bs = sb.data_block_size as u64;
+                for r in ranges {
+                    selector
+                        .oblocks
+                        .insert(r.start / bs..div_up(r.end, bs).max(r.start / bs + 1));
+                }
+            }
+            WritebackFilter::CacheBlocks(cblocks) => {
+                if let Some(max) = cblocks.max() {
+                    if max >= sb.cache_blocks {
+                        return Err(anyhow!(
+                            "cache block {} is beyond the end of the cache ({} blocks)",
+                            max,
+                            sb.cache_blocks
+                        ));
+                    }
+                }
+                selector.cblocks = Some(cblocks.clone());
+            }
+        }
+
+        selector.nr_blocks =
+            count_mappings(engine, sb, |cblock, m| selector.needs_writeback(cblock, m))?;
+        Ok(selector)
+    }
+}
+
+impl WritebackSelector for FilteredSelector {
+    fn get_nr_to_writeback(&self) -> u32 {
+        self.nr_blocks
+    }
+
+    fn needs_writeback(&self, cblock: u32, m: &Mapping) -> bool {
+        let selected = match &self.cblocks {
+            Some(cblocks) => cblocks.contains(cblock),
+            None => self.oblocks.contains(&m.oblock),
+        };
+        selected && self.inner.needs_writeback(cblock, m)
+    }
+}
+
+//---------------
+
+struct MappingCounter<F: Fn(u32, &Mapping) -> bool> {
     count: AtomicU64,
     predicate: F,
 }
 
-impl<F: Fn(&Mapping) -> bool> MappingCounter<F> {
+impl<F: Fn(u32, &Mapping) -> bool> MappingCounter<F> {
     fn new(predicate: F) -> Self {
         Self {
             count: AtomicU64::default(),
@@ -119,10 +208,11 @@ impl<F: Fn(&Mapping) -> bool> MappingCounter<F> {
     }
 }
 
-impl<F: Fn(&Mapping) -> bool> ArrayVisitor<Mapping> for MappingCounter<F> {
-    fn visit(&self, _index: u64, b: ArrayBlock<Mapping>) -> array::Result<()> {
-        for m in b.values {
-            if m.is_valid() && (self.predicate)(&m) {
+impl<F: Fn(u32, &Mapping) -> bool> ArrayVisitor<Mapping> for MappingCounter<F> {
+    fn visit(&self, index: u64, b: ArrayBlock<Mapping>) -> array::Result<()> {
+        let cbegin = index as u32 * b.header.max_entries;
+        for (m, cblock) in b.values.into_iter().zip(cbegin..) {
+            if m.is_valid() && (self.predicate)(cblock, &m) {
                 self.count.fetch_add(1, Ordering::SeqCst);
             }
         }
@@ -133,7 +223,7 @@ impl<F: Fn(&Mapping) -> bool> ArrayVisitor<Mapping> for MappingCounter<F> {
 
 /// Counts the valid mappings in the metadata that match the given
 /// predicate.
-fn count_mappings<F: Fn(&Mapping) -> bool>(
+fn count_mappings<F: Fn(u32, &Mapping) -> bool>(
     engine: &Arc<dyn IoEngine + Sync + Send>,
     sb: &Superblock,
     predicate: F,
@@ -174,15 +264,30 @@ fn v2_read_dirty_bitset(
 
 //---------------
 
-/// Build a selector object for this metadata.
+/// Build a selector object for this metadata, restricted to the filter
+/// if there is one.
 fn mk_selector(
     engine: Arc<dyn IoEngine + Sync + Send>,
     sb: &Superblock,
+    filter: Option<&WritebackFilter>,
+) -> anyhow::Result<Box<dyn WritebackSelector>> {
+    let selector = mk_base_selector(engine.clone(), sb)?;
+    match filter {
+        Some(filter) => Ok(Box::new(FilteredSelector::new(
+            &engine, sb, selector, filter,
+        )?)),
+        None => Ok(selector),
+    }
+}
+
+fn mk_base_selector(
+    engine: Arc<dyn IoEngine + Sync + Send>,
+    sb: &Superblock,
 ) -> anyhow::Result<Box<dyn WritebackSelector>> {
     if sb.flags.clean_shutdown {
         match sb.version {
             1 => Ok(Box::new(V1Selector {
-                nr_blocks: count_mappings(&engine, sb, |m| m.is_dirty())?,
+                nr_blocks: count_mappings(&engine, sb, |_, m| m.is_dirty())?,
             })),
             2 => {
                 let (nr_blocks, dirty) = v2_read_dirty_bitset(engine, sb)?;
@@ -196,7 +301,7 @@ fn mk_selector(
     } else {
         // assume everything is dirty
         Ok(Box::new(AllSelector {
-            nr_blocks: count_mappings(&engine, sb, |_| true)?,
+            nr_blocks: count_mappings(&engine, sb, |_, _| true)?,
         }))
     }
 }
@@ -385,11 +490,17 @@ impl Drop for BitsetUpdater {
 
 struct ThreadedCopier {
     copier: Box<dyn Copier + Send>,
+    limits: CopyLimits,
+    block_size: usize,
 }
 
 impl ThreadedCopier {
-    fn new(copier: Box<dyn Copier + Send>) -> ThreadedCopier {
-        ThreadedCopier { copier }
+    fn new(copier: Box<dyn Copier + Send>, limits: &CopyLimits, block_size: usize) -> Self {
+        ThreadedCopier {
+            copier,
+            limits: *limits,
+            block_size,
+        }
     }
 
     fn run(
@@ -397,18 +508,25 @@ impl ThreadedCopier {
         rx: mpsc::Receiver<Vec<CopyOp>>,
         progress: Arc<ProgressReporter>,
     ) -> thread::JoinHandle<anyhow::Result<(RoaringBitmap, RoaringBitmap, RoaringBitmap)>> {
-        thread::spawn(move || Self::run_(rx, self.copier, progress))
+        thread::spawn(move || Self::run_(rx, self, progress))
     }
 
     fn run_(
         rx: mpsc::Receiver<Vec<CopyOp>>,
-        mut copier: Box<dyn Copier + Send>,
+        mut tc: ThreadedCopier,
         progress: Arc<ProgressReporter>,
     ) -> anyhow::Result<(RoaringBitmap, RoaringBitmap, RoaringBitmap)> {
         let mut cleaned = RoaringBitmap::new();
         let mut read_failed = RoaringBitmap::new();
         let mut write_failed = RoaringBitmap::new();
 
+        if let Some(ioprio) = tc.limits.ioprio {
+            ioprio
+                .apply()
+                .map_err(|e| anyhow!("couldn't set the io priority: {}", e))?;
+        }
+
+        let mut throttle = Throttle::new(&tc.limits, tc.block_size);
         while let Ok(ops) = rx.recv() {
             {
                 // We assume the copies will succeed, and then remove
@@ -418,8 +536,8 @@ impl ThreadedCopier {
                 }
             }
 
-            let stats = copier
-                .copy(&ops, progress.clone())
+            let stats = throttle
+                .copy(tc.copier.as_mut(), &ops, progress.clone())
                 .map_err(|e| anyhow!("copy failed: {}", e))?;
 
             {
@@ -531,7 +649,11 @@ pub struct CacheWritebackOptions<'a> {
     pub buffer_size: Option<usize>,     // sectors
     pub list_failed_blocks: bool,
     pub update_metadata: bool,
+    pub verify: bool,
     pub retry_count: u32,
+    pub rescue_map: Option<&'a Path>,
+    pub filter: Option<WritebackFilter>,
+    pub copier: CopierOptions,
     pub report: Arc<Report>,
 }
 
@@ -551,6 +673,15 @@ fn mk_context(opts: &CacheWritebackOptions) -> anyhow::Result<Context> {
     })
 }
 
+// Identifies the state of the cache for the rescue map.  Clearing the dirty
+// flags leaves the superblock alone, but the kernel rewrites it, with new
+// statistics, whenever the cache is used.
+fn rescue_map_source(ctx: &Context) -> anyhow::Result<String> {
+    let b = ctx.engine.read(SUPERBLOCK_LOCATION)?;
+    let csum = u32::from_le_bytes(b.get_data()[0..4].try_into().unwrap());
+    Ok(format!("cache superblock {:08x}", csum))
+}
+
 fn copy_dirty_blocks(
     ctx: &Context,
     sb: &Superblock,
@@ -566,24 +697,61 @@ fn copy_dirty_blocks(
         .unwrap_or_else(|| std::cmp::max(sb.data_block_size as usize, 128 * 1024))
         << SECTOR_SHIFT;
 
-    // Copy all the dirty blocks
-    let (nr_blocks, mut cleaned, mut read_failed, mut write_failed) = {
-        let copier = Box::new(
-            SyncCopier::<VectoredBlockIo<File>>::from_path(
-                buffer_size,
-                block_size as usize,
-                opts.fast_dev,
-                opts.origin_dev,
-            )?
-            .src_offset(fast_dev_offset)?
-            .dest_offset(origin_dev_offset)?,
-        );
+    // Each pass records its progress in the rescue map, if there is one.
+    // Blocks that failed in an earlier run are left for the rescue passes.
+    let map = match opts.rescue_map {
+        Some(path) => Some(Arc::new(Mutex::new(RescueMap::open(
+            path,
+            &rescue_map_source(ctx)?,
+            block_size as usize,
+            sb.cache_blocks as u64,
+        )?))),
+        None => None,
+    };
+    let known_bad: RoaringBitmap = match &map {
+        Some(map) => map
+            .lock()
+            .unwrap()
+            .blocks(BlockState::Bad)
+            .into_iter()
+            .map(|b| b as u32)
+            .collect(),
+        None => RoaringBitmap::new(),
+    };
+    let mapped = |copier: Box<dyn Copier + Send>, retry_bad: bool| -> Box<dyn Copier + Send> {
+        match &map {
+            Some(map) => Box::new(MappedCopier::new(copier, map.clone()).retry_bad(retry_bad)),
+            None => copier,
+        }
+    };
 
-        copy_all_dirty_blocks(ctx.engine.clone(), sb, copier, ctx.report.clone())?
+    // Copy all the dirty blocks
+    let (nr_blocks, nr_skipped, mut cleaned, mut read_failed, mut write_failed) = {
+        let copier = mk_copier(
+            opts.copier.copier_type,
+            buffer_size,
+            block_size as usize,
+            CopierDevs::Paths {
+                src: opts.fast_dev,
+                dst: opts.origin_dev,
+                src_offset: fast_dev_offset,
+                dst_offset: origin_dev_offset,
+            },
+        )?;
+
+        copy_all_dirty_blocks(
+            ctx.engine.clone(),
+            sb,
+            opts.filter.as_ref(),
+            mapped(copier, false),
+            &opts.copier.limits,
+            ctx.report.clone(),
+        )?
     };
 
     // Retry blocks ignored by vectored io
-    if !read_failed.is_empty() || !write_failed.is_empty() {
+    let failed = &(&read_failed | &write_failed) - &known_bad;
+    if !failed.is_empty() {
         let copier = Box::new(
             SyncCopier::<SimpleBlockIo<File>>::from_path(
                 buffer_size,
@@ -595,50 +763,178 @@ fn copy_dirty_blocks(
             .dest_offset(origin_dev_offset)?,
         );
 
-        let failed = &read_failed | &write_failed;
-        let c;
-        (c, read_failed, write_failed) =
-            copy_selected_blocks(ctx.engine.clone(), sb, copier, &failed, ctx.report.clone())?;
+        let (c, r, w) = copy_selected_blocks(
+            ctx.engine.clone(),
+            sb,
+            mapped(copier, true),
+            &opts.copier.limits,
+            &failed,
+            ctx.report.clone(),
+        )?;
         cleaned |= c;
+        read_failed = (&read_failed - &failed) | r;
+        write_failed = (&write_failed - &failed) | w;
     }
 
-    // Retry failed block
+    // Retry failed blocks, working backwards through them with smaller
+    // pieces each pass.
     let mut retries = 0;
     while (!read_failed.is_empty() || !write_failed.is_empty()) && retries < opts.retry_count {
+        let granularity = pass_granularity(retries, opts.retry_count, block_size as usize);
         let copier = Box::new(
             RescueCopier::<File>::from_path(block_size as usize, opts.fast_dev, opts.origin_dev)?
                 .src_offset(fast_dev_offset)?
-                .dest_offset(origin_dev_offset)?,
+                .dest_offset(origin_dev_offset)?
+                .granularity(granularity)?
+                .reverse(true),
         );
 
         let failed = &read_failed | &write_failed;
         let c;
-        (c, read_failed, write_failed) =
-            copy_selected_blocks(ctx.engine.clone(), sb, copier, &failed, ctx.report.clone())?;
+        (c, read_failed, write_failed) = copy_selected_blocks(
+            ctx.engine.clone(),
+            sb,
+            mapped(copier, true),
+            &opts.copier.limits,
+            &failed,
+            ctx.report.clone(),
+        )?;
         cleaned |= c;
 
         retries += 1;
     }
 
+    // Read back what was written, and rewrite any blocks that don't match
+    let mut mismatched = RoaringBitmap::new();
+    if opts.verify {
+        let verify = |blocks: &RoaringBitmap| -> anyhow::Result<(RoaringBitmap, RoaringBitmap)> {
+            ctx.report.set_title("Verifying written blocks");
+
+            // Flush the origin first, so the writes have been committed.
+            // The reads skip the page cache, but the drive may still serve
+            // them from its own cache, so this can't prove what's on the
+            // media.
+            OpenOptions::new()
+                .write(true)
+                .open(opts.origin_dev)?
+                .sync_all()?;
+
+            let copier = Box::new(
+                CompareCopier::from_path(
+                    buffer_size,
+                    block_size as usize,
+                    opts.fast_dev,
+                    opts.origin_dev,
+                )?
+                .src_offset(fast_dev_offset)?
+                .dest_offset(origin_dev_offset)?
+                .verify_only(true),
+            );
+            let (good, r, w) = copy_selected_blocks(
+                ctx.engine.clone(),
+                sb,
+                copier,
+                &opts.copier.limits,
+                blocks,
+                ctx.report.clone(),
+            )?;
+
+            // A block that didn't match mustn't be skipped if the copy is
+            // resumed.
+            let bad = &r | &w;
+            if let Some(map) = &map {
+                let mut map = map.lock().unwrap();
+                for b in &bad {
+                    map.set(b as u64, BlockState::Bad);
+                }
+                map.save()?;
+            }
+            Ok((good, bad))
+        };
+
+        (cleaned, mismatched) = verify(&cleaned)?;
+
+        let mut retries = 0;
+        while !mismatched.is_empty() && retries < opts.retry_count {
+            ctx.report.set_title("Rewriting mismatched blocks");
+            let copier = Box::new(
+                SyncCopier::<SimpleBlockIo<File>>::from_path(
+                    buffer_size,
+                    block_size as usize,
+                    opts.fast_dev,
+                    opts.origin_dev,
+                )?
+                .src_offset(fast_dev_offset)?
+                .dest_offset(origin_dev_offset)?,
+            );
+
+            let (c, r, w) = copy_selected_blocks(
+                ctx.engine.clone(),
+                sb,
+                mapped(copier, true),
+                &opts.copier.limits,
+                &mismatched,
+                ctx.report.clone(),
+            )?;
+            read_failed |= r;
+            write_failed |= w;
+
+            let good;
+            (good, mismatched) = verify(&c)?;
+            cleaned |= good;
+
+            retries += 1;
+        }
+    }
+
+    if opts.list_failed_blocks {
+        list_failed_blocks(&ctx.report, &read_failed, &write_failed, &mismatched);
+    }
+
     let stats = WritebackStats {
         nr_blocks: nr_blocks as u64,
         nr_copied: cleaned.len(),
+        nr_skipped,
         nr_read_errors: read_failed.len(),
         nr_write_errors: write_failed.len(),
+        nr_verify_errors: mismatched.len(),
     };
 
     Ok((stats, cleaned))
 }
 
+// Lists the failed cache blocks in order, in a form --cblocks-file accepts
+fn list_failed_blocks(
+    report: &Report,
+    read_failed: &RoaringBitmap,
+    write_failed: &RoaringBitmap,
+    mismatched: &RoaringBitmap,
+) {
+    let failed = read_failed | write_failed | mismatched;
+    for b in &failed {
+        let reason = if read_failed.contains(b) {
+            "read error"
+        } else if write_failed.contains(b) {
+            "write error"
+        } else {
+            "verify mismatch"
+        };
+        report.to_stdout(&format!("{} # {}", b, reason));
+    }
+}
+
 fn copy_all_dirty_blocks(
     engine: Arc<dyn IoEngine + Send + Sync>,
     sb: &Superblock,
+    filter: Option<&WritebackFilter>,
     copier: Box<dyn Copier + Send>,
+    limits: &CopyLimits,
     report: Arc<Report>,
-) -> anyhow::Result<(u32, RoaringBitmap, RoaringBitmap, RoaringBitmap)> {
-    let selector = mk_selector(engine.clone(), sb)?;
+) -> anyhow::Result<(u32, u64, RoaringBitmap, RoaringBitmap, RoaringBitmap)> {
+    let selector = mk_selector(engine.clone(), sb, filter)?;
     let nr_blocks = selector.get_nr_to_writeback();
-    let progress = Arc::new(ProgressReporter::new(report, nr_blocks as u64));
+    let block_size = (sb.data_block_size as usize) << SECTOR_SHIFT;
+    let progress = Arc::new(ProgressReporter::new(report, nr_blocks as u64, block_size));
 
     // We pass work to the copy thread via a sync channel with a limit
     // of a single entry, this allows us to prepare one vector of copy ops
@@ -646,8 +942,8 @@ fn copy_all_dirty_blocks(
     let (tx, rx) = mpsc::sync_channel::<Vec<CopyOp>>(1);
 
     // launch the copy thread
-    let copier = ThreadedCopier::new(copier);
-    let copy_thread = copier.run(rx, progress);
+    let copier = ThreadedCopier::new(copier, limits, block_size);
+    let copy_thread = copier.run(rx, progress.clone());
 
     // Build batches of copy operations and pass them to the copy thread
     let batcher = WritebackBatcher::new(1_000_000, tx, selector);
@@ -659,20 +955,36 @@ fn copy_all_dirty_blocks(
     batcher.complete()?;
     let (cleaned, read_failed, write_failed) = copy_thread.join().unwrap()?;
 
-    Ok((nr_blocks, cleaned, read_failed, write_failed))
+    Ok((
+        nr_blocks,
+        progress.nr_skipped(),
+        cleaned,
+        read_failed,
+        write_failed,
+    ))
 }
 
 fn copy_selected_blocks(
     engine: Arc<dyn IoEngine + Send + Sync>,
     sb: &Superblock,
     copier: Box<dyn Copier + Send>,
+    limits: &CopyLimits,
     blocks: &RoaringBitmap,
     report: Arc<Report>,
 ) -> anyhow::Result<(RoaringBitmap, RoaringBitmap, RoaringBitmap)> {
+    if blocks.is_empty() {
+        return Ok((
+            RoaringBitmap::new(),
+            RoaringBitmap::new(),
+            RoaringBitmap::new(),
+        ));
+    }
+
     let (tx, rx) = mpsc::sync_channel::<Vec<CopyOp>>(1);
-    let progress = Arc::new(ProgressReporter::new(report, blocks.len()));
+    let block_size = (sb.data_block_size as usize) << SECTOR_SHIFT;
+    let progress = Arc::new(ProgressReporter::new(report, blocks.len(), block_size));
 
-    let copier = ThreadedCopier::new(copier);
+    let copier = ThreadedCopier::new(copier, limits, block_size);
     let copy_thread = copier.run(rx, progress);
 
     let mut batcher = CopyOpBatcher::new(1_000_000, tx);
@@ -709,11 +1021,20 @@ fn report_stats(report: Arc<Report>, stats: &WritebackStats) {
         "{}/{} blocks successfully copied",
         stats.nr_copied, stats.nr_blocks
     ));
+    if stats.nr_skipped > 0 {
+        report.to_stdout(&format!("{} blocks needed no writing", stats.nr_skipped));
+    }
 
     let nr_errors = stats.nr_read_errors + stats.nr_write_errors;
     if nr_errors > 0 {
         report.fatal(&format!("{} blocks were not copied", nr_errors));
     }
+    if stats.nr_verify_errors > 0 {
+        report.fatal(&format!(
+            "{} blocks didn't match the cache when read back",
+            stats.nr_verify_errors
+        ));
+    }
 }
 
 pub fn writeback(opts: CacheWritebackOptions) -> anyhow::Result<()> {
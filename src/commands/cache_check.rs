@@ -78,7 +78,7 @@ impl CacheCheckCommand {
                     .required(true)
                     .index(1),
             );
-        verbose_args(engine_args(version_args(cmd)))
+        verbose_args(overlay_args(engine_args(version_args(cmd))))
     }
 }
 
@@ -107,7 +107,7 @@ impl<'a> Command<'a> for CacheCheckCommand {
             return to_exit_code::<()>(&report, Err(e));
         }
 
-        let engine_opts = parse_engine_opts(ToolType::Cache, &matches);
+        let engine_opts = parse_engine_opts(ToolType::Cache, &matches, report.clone());
         if engine_opts.is_err() {
             return to_exit_code(&report, engine_opts);
         }
@@ -126,7 +126,10 @@ impl<'a> Command<'a> for CacheCheckCommand {
             report: report.clone(),
         };
 
-        to_exit_code(&report, check(opts))
+        let io_stats = opts.engine_opts.io_stats.clone();
+        let result = check(opts);
+        report_io_stats(&report, io_stats.as_deref());
+        to_exit_code(&report, result)
     }
 }
 
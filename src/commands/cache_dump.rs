@@ -66,7 +66,7 @@ impl<'a> Command<'a> for CacheDumpCommand {
             return to_exit_code::<()>(&report, Err(e));
         }
 
-        let engine_opts = parse_engine_opts(ToolType::Cache, &matches);
+        let engine_opts = parse_engine_opts(ToolType::Cache, &matches, report.clone());
         if engine_opts.is_err() {
             return to_exit_code(&report, engine_opts);
         }
@@ -79,7 +79,10 @@ impl<'a> Command<'a> for CacheDumpCommand {
             repair: matches.get_flag("REPAIR"),
         };
 
-        to_exit_code(&report, dump(opts))
+        let io_stats = opts.engine_opts.io_stats.clone();
+        let result = dump(opts);
+        report_io_stats(&report, io_stats.as_deref());
+        to_exit_code(&report, result)
     }
 }
 
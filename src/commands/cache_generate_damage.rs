@@ -76,7 +76,7 @@ impl<'a> Command<'a> for CacheGenerateDamageCommand {
 
         let report = mk_report(false);
 
-        let engine_opts = parse_engine_opts(ToolType::Cache, &matches);
+        let engine_opts = parse_engine_opts(ToolType::Cache, &matches, report.clone());
         if engine_opts.is_err() {
             return to_exit_code(&report, engine_opts);
         }
@@ -99,7 +99,10 @@ impl<'a> Command<'a> for CacheGenerateDamageCommand {
             output: Path::new(matches.get_one::<String>("OUTPUT").unwrap()),
         };
 
-        to_exit_code(&report, damage_metadata(opts))
+        let io_stats = opts.engine_opts.io_stats.clone();
+        let result = damage_metadata(opts);
+        report_io_stats(&report, io_stats.as_deref());
+        to_exit_code(&report, result)
     }
 }
 
@@ -146,7 +146,7 @@ impl<'a> Command<'a> for CacheGenerateMetadataCommand {
         let output_file = Path::new(matches.get_one::<String>("OUTPUT").unwrap());
 
         let report = mk_report(false);
-        let engine_opts = parse_engine_opts(ToolType::Cache, &matches);
+        let engine_opts = parse_engine_opts(ToolType::Cache, &matches, report.clone());
         if engine_opts.is_err() {
             return to_exit_code(&report, engine_opts);
         }
@@ -184,7 +184,10 @@ impl<'a> Command<'a> for CacheGenerateMetadataCommand {
             output: output_file,
         };
 
-        to_exit_code(&report, generate_metadata(opts))
+        let io_stats = opts.engine_opts.io_stats.clone();
+        let result = generate_metadata(opts);
+        report_io_stats(&report, io_stats.as_deref());
+        to_exit_code(&report, result)
     }
 }
 
@@ -0,0 +1,20 @@
+use crate::commands::metadata_pack::*;
+use crate::commands::Command;
+
+pub struct CacheMetadataPackCommand;
+
+impl CacheMetadataPackCommand {
+    fn cli(&self) -> clap::Command {
+        pack_cli(self.name(), &CACHE_METADATA)
+    }
+}
+
+impl<'a> Command<'a> for CacheMetadataPackCommand {
+    fn name(&self) -> &'a str {
+        "cache_metadata_pack"
+    }
+
+    fn run(&self, args: &mut dyn Iterator<Item = std::ffi::OsString>) -> exitcode::ExitCode {
+        run_pack(self.cli(), &CACHE_METADATA, args)
+    }
+}
@@ -0,0 +1,20 @@
+use crate::commands::metadata_pack::*;
+use crate::commands::Command;
+
+pub struct CacheMetadataUnpackCommand;
+
+impl CacheMetadataUnpackCommand {
+    fn cli(&self) -> clap::Command {
+        unpack_cli(self.name(), &CACHE_METADATA)
+    }
+}
+
+impl<'a> Command<'a> for CacheMetadataUnpackCommand {
+    fn name(&self) -> &'a str {
+        "cache_metadata_unpack"
+    }
+
+    fn run(&self, args: &mut dyn Iterator<Item = std::ffi::OsString>) -> exitcode::ExitCode {
+        run_unpack(self.cli(), args)
+    }
+}
@@ -0,0 +1,123 @@
+extern crate clap;
+
+use clap::{value_parser, Arg, ArgAction};
+use std::path::Path;
+
+use crate::cache::resize::{resize, CacheResizeOptions};
+use crate::commands::engine::*;
+use crate::commands::utils::*;
+use crate::commands::Command;
+use crate::report::*;
+use crate::version::*;
+
+//------------------------------------------
+
+pub struct CacheResizeCommand;
+
+impl CacheResizeCommand {
+    fn cli(&self) -> clap::Command {
+        let cmd = clap::Command::new(self.name())
+            .next_display_order(None)
+            .version(crate::tools_version!())
+            .disable_version_flag(true)
+            .about("Rewrite cache metadata for a new number of cache blocks, moving data on the fast device")
+            .arg(
+                Arg::new("QUIET")
+                    .help("Suppress output messages, return only exit code.")
+                    .short('q')
+                    .long("quiet")
+                    .action(ArgAction::SetTrue),
+            )
+            // options
+            .arg(
+                Arg::new("INPUT")
+                    .help("Specify the input device")
+                    .short('i')
+                    .long("input")
+                    .value_name("FILE")
+                    .required(true),
+            )
+            .arg(
+                Arg::new("OUTPUT")
+                    .help("Specify the output device")
+                    .short('o')
+                    .long("output")
+                    .value_name("FILE")
+                    .required(true),
+            )
+            .arg(
+                Arg::new("FAST_DEV")
+                    .help("Specify the fast device holding the cached data")
+                    .long("fast-device")
+                    .value_name("FILE")
+                    .required(true),
+            )
+            .arg(
+                Arg::new("NR_CACHE_BLOCKS")
+                    .help("Specify the new number of cache blocks")
+                    .long("nr-cache-blocks")
+                    .value_name("NUM")
+                    .value_parser(value_parser!(u32).range(1..))
+                    .required(true),
+            );
+        verbose_args(copier_args(overlay_args(engine_args(version_args(cmd)))))
+    }
+}
+
+impl<'a> Command<'a> for CacheResizeCommand {
+    fn name(&self) -> &'a str {
+        "cache_resize"
+    }
+
+    fn run(&self, args: &mut dyn Iterator<Item = std::ffi::OsString>) -> exitcode::ExitCode {
+        let matches = self.cli().get_matches_from(args);
+        display_version(&matches);
+
+        let report = mk_report(matches.get_flag("QUIET"));
+        let log_level = match parse_log_level(&matches) {
+            Ok(level) => level,
+            Err(e) => return to_exit_code::<()>(&report, Err(anyhow::Error::msg(e))),
+        };
+        report.set_level(log_level);
+
+        let input_file = Path::new(matches.get_one::<String>("INPUT").unwrap());
+        let output_file = Path::new(matches.get_one::<String>("OUTPUT").unwrap());
+        let fast_dev = Path::new(matches.get_one::<String>("FAST_DEV").unwrap());
+
+        if let Err(e) = check_input_file(input_file)
+            .and_then(check_file_not_tiny)
+            .and_then(check_not_xml)
+            .and_then(|_| check_input_file(fast_dev))
+            .and_then(|_| check_output_file(output_file))
+        {
+            return to_exit_code::<()>(&report, Err(e));
+        }
+
+        let engine_opts = parse_engine_opts(ToolType::Cache, &matches, report.clone());
+        if engine_opts.is_err() {
+            return to_exit_code(&report, engine_opts);
+        }
+
+        let copier = match parse_copier_opts(&matches) {
+            Ok(copier) => copier,
+            Err(e) => return to_exit_code::<()>(&report, Err(e)),
+        };
+
+        let opts = CacheResizeOptions {
+            input: input_file,
+            output: output_file,
+            fast_dev,
+            nr_cache_blocks: *matches.get_one::<u32>("NR_CACHE_BLOCKS").unwrap(),
+            engine_opts: engine_opts.unwrap(),
+            copier,
+            report: report.clone(),
+        };
+
+        let io_stats = opts.engine_opts.io_stats.clone();
+        let result = resize(opts);
+        report_io_stats(&report, io_stats.as_deref());
+        to_exit_code(&report, result)
+    }
+}
+
+//------------------------------------------
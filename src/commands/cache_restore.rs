@@ -1,7 +1,7 @@
 extern crate clap;
 
 use clap::builder::{PossibleValuesParser, TypedValueParser};
-use clap::{Arg, ArgAction};
+use clap::{value_parser, Arg, ArgAction};
 use std::path::Path;
 
 use crate::cache::restore::{restore, CacheRestoreOptions};
@@ -59,8 +59,22 @@ impl CacheRestoreCommand {
                     .long("output")
                     .value_name("FILE")
                     .required(true),
+            )
+            .arg(
+                Arg::new("BLOCK_SIZE")
+                    .help("Convert the metadata to a different cache block size")
+                    .long("block-size")
+                    .value_name("SECTORS")
+                    .value_parser(value_parser!(u32)),
+            )
+            .arg(
+                Arg::new("FAST_DEV")
+                    .help("Specify the fast device, to move the data of merged blocks")
+                    .long("fast-device")
+                    .value_name("FILE")
+                    .requires("BLOCK_SIZE"),
             );
-        verbose_args(engine_args(version_args(cmd)))
+        verbose_args(copier_args(overlay_args(engine_args(version_args(cmd)))))
     }
 }
 
@@ -83,15 +97,26 @@ impl<'a> Command<'a> for CacheRestoreCommand {
         };
         report.set_level(log_level);
 
-        if let Err(e) = check_input_file(input_file).and_then(|_| check_output_file(output_file)) {
+        let fast_dev = matches.get_one::<String>("FAST_DEV").map(Path::new);
+
+        let mut r = check_input_file(input_file).and_then(|_| check_output_file(output_file));
+        if let Some(fast_dev) = fast_dev {
+            r = r.and_then(|_| check_input_file(fast_dev));
+        }
+        if let Err(e) = r {
             return to_exit_code::<()>(&report, Err(e));
         }
 
-        let engine_opts = parse_engine_opts(ToolType::Cache, &matches);
+        let engine_opts = parse_engine_opts(ToolType::Cache, &matches, report.clone());
         if engine_opts.is_err() {
             return to_exit_code(&report, engine_opts);
         }
 
+        let copier = match parse_copier_opts(&matches) {
+            Ok(copier) => copier,
+            Err(e) => return to_exit_code::<()>(&report, Err(e)),
+        };
+
         let opts = CacheRestoreOptions {
             input: input_file,
             output: output_file,
@@ -99,8 +124,14 @@ impl<'a> Command<'a> for CacheRestoreCommand {
             engine_opts: engine_opts.unwrap(),
             report: report.clone(),
             omit_clean_shutdown: matches.get_flag("OMIT_CLEAN_SHUTDOWN"),
+            block_size: matches.get_one::<u32>("BLOCK_SIZE").cloned(),
+            fast_dev,
+            copier,
         };
 
-        to_exit_code(&report, restore(opts))
+        let io_stats = opts.engine_opts.io_stats.clone();
+        let result = restore(opts);
+        report_io_stats(&report, io_stats.as_deref());
+        to_exit_code(&report, result)
     }
 }
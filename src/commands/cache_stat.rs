@@ -0,0 +1,90 @@
+extern crate clap;
+
+use clap::Arg;
+use std::path::Path;
+
+use crate::cache::stat::{stat, CacheStatOptions, StatFormat};
+use crate::commands::engine::*;
+use crate::commands::utils::*;
+use crate::commands::Command;
+use crate::report::*;
+use crate::version::*;
+
+//------------------------------------------
+
+pub struct CacheStatCommand;
+
+impl CacheStatCommand {
+    fn cli(&self) -> clap::Command {
+        let cmd = clap::Command::new(self.name())
+            .next_display_order(None)
+            .version(crate::tools_version!())
+            .disable_version_flag(true)
+            .about("Summarises the contents of cache metadata.")
+            // options
+            .arg(
+                Arg::new("FORMAT")
+                    .help("Choose the output format")
+                    .long("format")
+                    .value_name("TYPE")
+                    .value_parser(["text", "json"])
+                    .default_value("text"),
+            )
+            // arguments
+            .arg(
+                Arg::new("INPUT")
+                    .help("Specify the input device")
+                    .required(true)
+                    .index(1),
+            );
+        verbose_args(engine_args(version_args(cmd)))
+    }
+}
+
+impl<'a> Command<'a> for CacheStatCommand {
+    fn name(&self) -> &'a str {
+        "cache_stat"
+    }
+
+    fn run(&self, args: &mut dyn Iterator<Item = std::ffi::OsString>) -> exitcode::ExitCode {
+        let matches = self.cli().get_matches_from(args);
+        display_version(&matches);
+
+        let input_file = Path::new(matches.get_one::<String>("INPUT").unwrap());
+
+        let report = mk_report(false);
+        let log_level = match parse_log_level(&matches) {
+            Ok(level) => level,
+            Err(e) => return to_exit_code::<()>(&report, Err(anyhow::Error::msg(e))),
+        };
+        report.set_level(log_level);
+
+        if let Err(e) = check_input_file(input_file)
+            .and_then(check_file_not_tiny)
+            .and_then(check_not_xml)
+        {
+            return to_exit_code::<()>(&report, Err(e));
+        }
+
+        let engine_opts = parse_engine_opts(ToolType::Cache, &matches, report.clone());
+        if engine_opts.is_err() {
+            return to_exit_code(&report, engine_opts);
+        }
+
+        let opts = CacheStatOptions {
+            input: input_file,
+            engine_opts: engine_opts.unwrap(),
+            format: match matches.get_one::<String>("FORMAT").unwrap().as_str() {
+                "json" => StatFormat::Json,
+                _ => StatFormat::Text,
+            },
+        };
+
+        let io_stats = opts.engine_opts.io_stats.clone();
+        let result = stat(opts);
+        report_io_stats(&report, io_stats.as_deref());
+        to_exit_code(&report, result)
+    }
+}
+
+//------------------------------------------
@@ -1,8 +1,13 @@
+use anyhow::{anyhow, Context, Result};
 use clap::{value_parser, Arg, ArgAction};
+use roaring::RoaringBitmap;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::ops::Range;
 use std::path::Path;
 
 use crate::cache::check::{check, CacheCheckOptions};
-use crate::cache::writeback::{writeback, CacheWritebackOptions};
+use crate::cache::writeback::{writeback, CacheWritebackOptions, WritebackFilter};
 use crate::commands::engine::*;
 use crate::commands::utils::*;
 use crate::commands::Command;
@@ -11,6 +16,63 @@ use crate::version::*;
 
 pub struct CacheWritebackCommand;
 
+// Reads a list of cache blocks, one block or BEGIN..END range per line.
+// Blank lines and anything after a '#' are ignored.
+fn read_cblocks_file(path: &Path) -> Result<RoaringBitmap> {
+    let input = BufReader::new(
+        File::open(path).with_context(|| format!("couldn't open {}", path.display()))?,
+    );
+
+    let mut cblocks = RoaringBitmap::new();
+    for (n, line) in input.lines().enumerate() {
+        let line = line?;
+        let line = line.split('#').next().unwrap().trim();
+        if line.is_empty() {
+            continue;
+        }
+
+        let parsed = if line.contains("..") {
+            line.parse::<RangeU64>()
+                .ok()
+                .filter(|r| r.end <= u32::MAX as u64)
+                .map(|r| r.start as u32..r.end as u32)
+        } else {
+            line.parse::<u32>()
+                .ok()
+                .and_then(|b| Some(b..b.checked_add(1)?))
+        };
+        let blocks = parsed.ok_or_else(|| {
+            anyhow!(
+                "{}: line {}: couldn't parse '{}'",
+                path.display(),
+                n + 1,
+                line
+            )
+        })?;
+        cblocks.insert_range(blocks);
+    }
+    Ok(cblocks)
+}
+
+fn parse_filter(matches: &clap::ArgMatches) -> Result<Option<WritebackFilter>> {
+    if let Some(path) = matches.get_one::<String>("CBLOCKS_FILE") {
+        return Ok(Some(WritebackFilter::CacheBlocks(read_cblocks_file(
+            Path::new(path),
+        )?)));
+    }
+
+    Ok(matches.get_many::<RangeU64>("ORIGIN_RANGE").map(|ranges| {
+        WritebackFilter::OriginRanges(
+            ranges
+                .map(|r| Range::<u64> {
+                    start: r.start,
+                    end: r.end,
+                })
+                .collect(),
+        )
+    }))
+}
+
 impl CacheWritebackCommand {
     fn cli(&self) -> clap::Command {
         let cmd = clap::Command::new(self.name())
@@ -31,6 +93,12 @@ impl CacheWritebackCommand {
                     .long("no-metadata-update")
                     .action(ArgAction::SetTrue),
             )
+            .arg(
+                Arg::new("VERIFY")
+                    .help("Read back the written blocks and check they match the cache")
+                    .long("verify")
+                    .action(ArgAction::SetTrue),
+            )
             .arg(
                 Arg::new("LIST_FAILED_BLOCKS")
                     .help("List any blocks that failed the writeback process")
@@ -89,8 +157,29 @@ impl CacheWritebackCommand {
                     .value_name("CNT")
                     .value_parser(value_parser!(u32))
                     .default_value("0"),
+            )
+            .arg(
+                Arg::new("RESCUE_MAP")
+                    .help("Record the progress of the copy in a map file, and resume from it")
+                    .long("rescue-map")
+                    .value_name("FILE"),
+            )
+            .arg(
+                Arg::new("ORIGIN_RANGE")
+                    .help("Only write back blocks within this range of the origin, in sectors")
+                    .long("origin-range")
+                    .action(ArgAction::Append)
+                    .value_name("BEGIN..END")
+                    .value_parser(value_parser!(RangeU64)),
+            )
+            .arg(
+                Arg::new("CBLOCKS_FILE")
+                    .help("Only write back the cache blocks listed in this file")
+                    .long("cblocks-file")
+                    .value_name("FILE")
+                    .conflicts_with("ORIGIN_RANGE"),
             );
-        verbose_args(engine_args(version_args(cmd)))
+        verbose_args(copier_args(overlay_args(engine_args(version_args(cmd)))))
     }
 }
 
@@ -121,11 +210,21 @@ impl<'a> Command<'a> for CacheWritebackCommand {
             return to_exit_code::<()>(&report, Err(e));
         }
 
-        let engine_opts = match parse_engine_opts(ToolType::Cache, &matches) {
+        let engine_opts = match parse_engine_opts(ToolType::Cache, &matches, report.clone()) {
             Ok(opt) => opt,
             Err(_) => return exitcode::USAGE,
         };
 
+        let copier = match parse_copier_opts(&matches) {
+            Ok(copier) => copier,
+            Err(e) => return to_exit_code::<()>(&report, Err(e)),
+        };
+
+        let filter = match parse_filter(&matches) {
+            Ok(filter) => filter,
+            Err(e) => return to_exit_code::<()>(&report, Err(e)),
+        };
+
         let check_opts = CacheCheckOptions {
             dev: metadata_dev,
             engine_opts: engine_opts.clone(),
@@ -159,10 +258,17 @@ impl<'a> Command<'a> for CacheWritebackCommand {
                 .map(|v| *v * 2048),
             list_failed_blocks: matches.get_flag("LIST_FAILED_BLOCKS"),
             update_metadata: !matches.get_flag("NO_METADATA_UPDATE"),
+            verify: matches.get_flag("VERIFY"),
             retry_count: *matches.get_one::<u32>("RETRY_COUNT").unwrap(),
+            rescue_map: matches.get_one::<String>("RESCUE_MAP").map(Path::new),
+            filter,
+            copier,
             report: report.clone(),
         };
 
-        to_exit_code(&report, writeback(opts))
+        let io_stats = opts.engine_opts.io_stats.clone();
+        let result = writeback(opts);
+        report_io_stats(&report, io_stats.as_deref());
+        to_exit_code(&report, result)
     }
 }
@@ -1,23 +1,54 @@
 use anyhow::{anyhow, Result};
 use clap::ArgMatches;
 use roaring::*;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 
+use crate::copier::{CopierOptions, CopierType, CopyLimits, IoPriority};
+use crate::io_engine::device_info::{self, DeviceInfo};
+use crate::io_engine::fault::FaultSpec;
+use crate::io_engine::nbd::is_nbd_url;
+use crate::io_engine::overlay::OverlayIoEngine;
+use crate::io_engine::stats::IoStats;
+use crate::io_engine::trace::RecordingIoEngine;
 use crate::io_engine::*;
+use crate::pack::toplevel::is_pack_file;
 use crate::pdata::space_map::allocated_blocks::*;
 use crate::pdata::space_map::common::*;
 use crate::pdata::unpack::*;
+use crate::report::Report;
 use crate::thin::superblock::*;
 
 //------------------------------------------
 
-#[derive(Clone, PartialEq, Eq)]
+#[cfg(test)]
+mod tests;
+
+//------------------------------------------
+
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub enum EngineType {
+    // Chosen when the engine is built, to suit the device
+    Auto,
     #[cfg(feature = "io_uring")]
     Async,
     Sync,
     Spindle,
+    // A sync engine with faults injected, as described by the spec
+    Fault(String),
+}
+
+impl EngineType {
+    fn name(&self) -> &str {
+        match self {
+            EngineType::Auto => "auto",
+            #[cfg(feature = "io_uring")]
+            EngineType::Async => "async",
+            EngineType::Sync => "sync",
+            EngineType::Spindle => "spindle",
+            EngineType::Fault(_) => "fault",
+        }
+    }
 }
 
 #[derive(Clone, PartialEq, Eq)]
@@ -33,20 +64,117 @@ pub struct EngineOptions {
     pub tool: ToolType,
     pub engine_type: EngineType,
     pub use_metadata_snap: bool,
+    // Writes go to this side file rather than the device
+    pub overlay: Option<PathBuf>,
+    // Every io is logged to this file
+    pub trace: Option<PathBuf>,
+    // The engines gather their io stats here
+    pub io_stats: Option<Arc<IoStats>>,
+    // Recently read metadata blocks are kept in memory, up to this many
+    pub block_cache: Option<usize>,
+    // Told which engine was picked, when it's picked automatically
+    pub report: Option<Arc<Report>>,
 }
 
 //------------------------------------------
 
 // Add in the standard engine choice flags
 pub fn engine_args(cmd: clap::Command) -> clap::Command {
+    use clap::{value_parser, Arg};
+
+    // Only offer async if it's been built in
+    let engine_help = if cfg!(feature = "io_uring") {
+        "Select an io engine to use: auto, sync, async or spindle"
+    } else {
+        "Select an io engine to use: auto, sync or spindle"
+    };
+
+    let cmd = cmd
+        .arg(
+            Arg::new("IO_ENGINE")
+                .help(engine_help)
+                .long("io-engine")
+                .value_name("IO_ENGINE"),
+        )
+        // Only shown by the tools that write, see overlay_args()
+        .arg(
+            Arg::new("OVERLAY")
+                .help("Send writes to an overlay file, leaving the device untouched")
+                .long("overlay")
+                .value_name("FILE")
+                .hide(true),
+        )
+        // Traces are only read by io_replay, which is a dev tool
+        .arg(
+            Arg::new("IO_TRACE")
+                .help("Record the io to a trace file")
+                .long("io-trace")
+                .value_name("FILE")
+                .hide(true),
+        )
+        .arg(
+            Arg::new("BLOCK_CACHE")
+                .help("Keep up to this many megabytes of metadata in memory")
+                .long("block-cache")
+                .value_name("MEGABYTES")
+                .value_parser(value_parser!(usize)),
+        );
+
+    io_stats_args(cmd)
+}
+
+// Shows the overlay option of a tool that writes metadata
+pub fn overlay_args(cmd: clap::Command) -> clap::Command {
+    cmd.mut_arg("OVERLAY", |arg| arg.hide(false))
+}
+
+// For the tools that do their io without the engine choice flags
+pub fn io_stats_args(cmd: clap::Command) -> clap::Command {
     use clap::Arg;
 
     cmd.arg(
-        Arg::new("IO_ENGINE")
-            .help("Select an io engine to use")
-            .long("io-engine")
-            .value_name("IO_ENGINE")
-            .hide(true),
+        Arg::new("IO_STATS")
+            .help("Report io statistics at exit")
+            .long("io-stats")
+            .action(clap::ArgAction::SetTrue),
+    )
+}
+
+// Add in the flags that select how data is copied, and how hard
+pub fn copier_args(cmd: clap::Command) -> clap::Command {
+    use clap::{value_parser, Arg};
+
+    let copier_help = if cfg!(feature = "io_uring") {
+        "Select how data is copied: sync, async or compare"
+    } else {
+        "Select how data is copied: sync or compare"
+    };
+
+    cmd.arg(
+        Arg::new("COPIER")
+            .help(copier_help)
+            .long("copier")
+            .value_name("COPIER"),
+    )
+    .arg(
+        Arg::new("MAX_BANDWIDTH")
+            .help("Limit the copy to this many megabytes per second")
+            .long("max-bandwidth")
+            .value_name("MB")
+            .value_parser(value_parser!(u64).range(1..)),
+    )
+    .arg(
+        Arg::new("MAX_IOPS")
+            .help("Limit the copy to this many reads and writes per second")
+            .long("max-iops")
+            .value_name("IOPS")
+            .value_parser(value_parser!(u64).range(1..)),
+    )
+    .arg(
+        Arg::new("IOPRIO")
+            .help("Set the io priority of the copy: idle, best-effort[:LEVEL] or realtime[:LEVEL]")
+            .long("ioprio")
+            .value_name("CLASS"),
     )
 }
 
@@ -55,8 +183,15 @@ pub fn engine_args(cmd: clap::Command) -> clap::Command {
 fn parse_type(matches: &ArgMatches) -> Result<EngineType> {
     let engine_type = if let Some(engine) = matches.get_one::<String>("IO_ENGINE") {
         match engine.as_str() {
+            "auto" => EngineType::Auto,
             "sync" => EngineType::Sync,
             "spindle" => EngineType::Spindle,
+            fault if fault.starts_with("fault:") => {
+                let spec = &fault["fault:".len()..];
+                // catch a bad spec now, rather than when the engine is built
+                FaultSpec::parse(spec)?;
+                EngineType::Fault(spec.to_string())
+            }
             #[cfg(feature = "io_uring")]
             "async" => EngineType::Async,
             #[cfg(not(feature = "io_uring"))]
@@ -70,12 +205,46 @@ fn parse_type(matches: &ArgMatches) -> Result<EngineType> {
             }
         }
     } else {
-        EngineType::Sync
+        EngineType::Auto
     };
 
     Ok(engine_type)
 }
 
+fn parse_copier_type(matches: &ArgMatches) -> Result<CopierType> {
+    match matches.get_one::<String>("COPIER").map(|s| s.as_str()) {
+        None | Some("sync") => Ok(CopierType::Sync),
+        Some("compare") => Ok(CopierType::Compare),
+        #[cfg(feature = "io_uring")]
+        Some("async") => Ok(CopierType::Async),
+        #[cfg(not(feature = "io_uring"))]
+        Some("async") => Err(anyhow!(
+            "This tool has not been compiled with async copier support"
+        )),
+        Some(copier) => Err(anyhow!(format!("unknown copier type '{}'", copier))),
+    }
+}
+
+pub fn parse_copier_opts(matches: &ArgMatches) -> Result<CopierOptions> {
+    let copier_type = parse_copier_type(matches)?;
+    let ioprio = matches
+        .get_one::<String>("IOPRIO")
+        .map(|s| IoPriority::parse(s))
+        .transpose()?;
+    let limits = CopyLimits {
+        max_bandwidth: matches
+            .get_one::<u64>("MAX_BANDWIDTH")
+            .map(|mb| mb * 1024 * 1024),
+        max_iops: matches.get_one::<u64>("MAX_IOPS").cloned(),
+        ioprio,
+    };
+
+    Ok(CopierOptions {
+        copier_type,
+        limits,
+    })
+}
+
 fn metadata_snap_flag(matches: &ArgMatches) -> bool {
     if !matches!(matches.try_contains_id("METADATA_SNAPSHOT"), Ok(true)) {
         // This tool doesn't use metadata snaps, or the METADATA_SNAPSHOT
@@ -89,18 +258,96 @@ fn metadata_snap_flag(matches: &ArgMatches) -> bool {
     )
 }
 
-pub fn parse_engine_opts(tool: ToolType, matches: &ArgMatches) -> Result<EngineOptions> {
+pub fn parse_engine_opts(
+    tool: ToolType,
+    matches: &ArgMatches,
+    report: Arc<Report>,
+) -> Result<EngineOptions> {
     let engine_type = parse_type(matches)?;
     let use_metadata_snap =
         (tool == ToolType::Thin || tool == ToolType::Era) && metadata_snap_flag(matches);
 
+    let overlay = matches.get_one::<String>("OVERLAY").map(PathBuf::from);
+    let trace = matches.get_one::<String>("IO_TRACE").map(PathBuf::from);
+    let io_stats = parse_io_stats(matches);
+    let block_cache = matches
+        .get_one::<usize>("BLOCK_CACHE")
+        .map(|mb| mb * (1024 * 1024 / BLOCK_SIZE));
+
     Ok(EngineOptions {
         tool,
         engine_type,
         use_metadata_snap,
+        overlay,
+        trace,
+        io_stats,
+        block_cache,
+        report: Some(report),
     })
 }
 
+pub fn parse_io_stats(matches: &ArgMatches) -> Option<Arc<IoStats>> {
+    matches
+        .get_flag("IO_STATS")
+        .then(|| Arc::new(IoStats::default()))
+}
+
+// Prints the stats gathered while the tool ran, if they were asked for.
+// They're neither warnings nor subject to the log level, but a quiet
+// report still drops them.
+pub fn report_io_stats(report: &Report, io_stats: Option<&IoStats>) {
+    if let Some(io_stats) = io_stats {
+        report.to_stderr("IO stats:");
+        for line in io_stats.summary() {
+            report.to_stderr(&format!("  {}", line));
+        }
+    }
+}
+
+//------------------------------------------
+
+// Devices that will queue at least this many requests are worth keeping
+// busy with io_uring.
+#[cfg(feature = "io_uring")]
+const ASYNC_MIN_QUEUE_DEPTH: u64 = 32;
+
+// Picks an engine for the device, returning the reason for the choice
+// too.  The spindle engine reads all of the metadata up front, in order,
+// which saves seeking about a rotational disk.  But it can't write, and
+// only knows how to find the allocated blocks in thin metadata.
+fn auto_engine_type(
+    info: &DeviceInfo,
+    tool: &ToolType,
+    write: bool,
+    exclusive: bool,
+) -> (EngineType, String) {
+    match info {
+        DeviceInfo::File => (EngineType::Sync, "a regular file".to_string()),
+        DeviceInfo::Block {
+            rotational: true, ..
+        } => {
+            if *tool == ToolType::Thin && !write && exclusive {
+                (EngineType::Spindle, "a rotational device".to_string())
+            } else {
+                (EngineType::Sync, "a rotational device".to_string())
+            }
+        }
+        #[cfg(feature = "io_uring")]
+        DeviceInfo::Block {
+            rotational: false,
+            queue_depth,
+        } if *queue_depth >= ASYNC_MIN_QUEUE_DEPTH => (
+            EngineType::Async,
+            format!("a solid state device with a queue depth of {}", queue_depth),
+        ),
+        DeviceInfo::Block { queue_depth, .. } => (
+            EngineType::Sync,
+            format!("a solid state device with a queue depth of {}", queue_depth),
+        ),
+        DeviceInfo::Unknown => (EngineType::Sync, "an unrecognised device".to_string()),
+    }
+}
+
 //------------------------------------------
 
 fn all_blocks(nr_blocks: u32) -> RoaringBitmap {
@@ -122,27 +369,27 @@ fn thin_read_sb(
     Ok(sb)
 }
 
-// use a Sync engine to read the metadata space map, if this fails we
-// assume all blocks are valid.
-fn thin_valid_blocks<P: AsRef<Path>>(path: P, opts: &EngineOptions) -> RoaringBitmap {
-    let e = Arc::new(SyncIoEngine::new(path, false).expect("unable to open input file"));
-    let sb = thin_read_sb(e.clone(), opts.use_metadata_snap);
-    if sb.is_err() {
-        return all_blocks(e.get_nr_blocks() as u32);
-    }
-    let sb = sb.unwrap();
+// use a Sync engine to read the metadata space map.  If the space map
+// can't be read we only assume all blocks are valid when the spindle
+// engine was asked for; an automatic choice falls back to sync instead.
+fn thin_valid_blocks<P: AsRef<Path>>(path: P, opts: &EngineOptions) -> Result<RoaringBitmap> {
+    let e = Arc::new(SyncIoEngine::new(path, false)?);
+    let valid_blocks = thin_read_sb(e.clone(), opts.use_metadata_snap).and_then(|sb| {
+        let metadata_root = unpack::<SMRoot>(&sb.metadata_sm_root[0..])?;
+        allocated_blocks(
+            e.as_ref(),
+            metadata_root.bitmap_root,
+            metadata_root.nr_blocks,
+        )
+    });
 
-    let metadata_root = unpack::<SMRoot>(&sb.metadata_sm_root[0..]);
-    if metadata_root.is_err() {
-        return all_blocks(e.get_nr_blocks() as u32);
+    match valid_blocks {
+        Ok(blocks) => Ok(blocks),
+        Err(_) if opts.engine_type == EngineType::Spindle => {
+            Ok(all_blocks(e.get_nr_blocks() as u32))
+        }
+        Err(e) => Err(anyhow!("couldn't read the metadata space map: {}", e)),
     }
-    let metadata_root = metadata_root.unwrap();
-    let valid_blocks = allocated_blocks(
-        e.as_ref(),
-        metadata_root.bitmap_root,
-        metadata_root.nr_blocks,
-    );
-    valid_blocks.unwrap_or_else(|_| all_blocks(e.get_nr_blocks() as u32))
 }
 
 fn cache_valid_blocks<P: AsRef<Path>>(_path: P, _opts: &EngineOptions) -> Result<RoaringBitmap> {
@@ -189,34 +436,129 @@ impl<'a, P: AsRef<Path>> EngineBuilder<'a, P> {
     }
 
     pub fn build(self) -> Result<Arc<dyn IoEngine + Send + Sync>> {
-        let engine: Arc<dyn IoEngine + Send + Sync> = match self.opts.engine_type {
-            #[cfg(feature = "io_uring")]
-            EngineType::Async => Arc::new(AsyncIoEngine::new_with(
-                self.path,
-                self.write,
-                self.exclusive,
-            )?),
-            EngineType::Sync => Arc::new(SyncIoEngine::new_with(
-                self.path,
-                self.write,
-                self.exclusive,
+        let overlay = self.opts.overlay.clone();
+        let trace = self.opts.trace.clone();
+        let block_cache = self.opts.block_cache;
+        let io_stats = self.opts.io_stats.clone();
+        let engine: Arc<dyn IoEngine + Send + Sync> = match overlay {
+            // the device itself is only ever read
+            Some(overlay) => Arc::new(OverlayIoEngine::new(
+                self.write(false).build_engine()?,
+                overlay,
             )?),
-            EngineType::Spindle => {
-                let valid_blocks = match self.opts.tool {
-                    ToolType::Thin => thin_valid_blocks(self.path.as_ref(), self.opts),
-                    ToolType::Cache => cache_valid_blocks(self.path.as_ref(), self.opts)?,
-                    ToolType::Era => era_valid_blocks(self.path.as_ref(), self.opts)?,
-                    ToolType::Other => {
-                        let nr_blocks = get_nr_blocks(self.path.as_ref())?;
-                        all_blocks(nr_blocks as u32)
-                    }
-                };
+            None => self.build_engine()?,
+        };
+        let engine: Arc<dyn IoEngine + Send + Sync> = match trace {
+            Some(trace) => Arc::new(RecordingIoEngine::new(engine, trace)?),
+            None => engine,
+        };
+        // on top, so the trace shows the ios that missed
+        match block_cache {
+            Some(nr_blocks) => Ok(Arc::new(BlockCacheIoEngine::new(
+                engine,
+                nr_blocks,
+                io_stats.unwrap_or_default(),
+            ))),
+            None => Ok(engine),
+        }
+    }
+
+    fn choose_engine_type(&self) -> EngineType {
+        let path = self.path.as_ref();
+        let info = device_info::probe(path).unwrap_or(DeviceInfo::Unknown);
+        let (engine_type, reason) =
+            auto_engine_type(&info, &self.opts.tool, self.write, self.exclusive);
+        if let Some(report) = &self.opts.report {
+            report.info(&format!(
+                "using the {} io engine for {}, which is {}",
+                engine_type.name(),
+                path.display(),
+                reason
+            ));
+        }
+        engine_type
+    }
+
+    fn build_engine(self) -> Result<Arc<dyn IoEngine + Send + Sync>> {
+        // Remote metadata is always read over the network
+        if is_nbd_url(self.path.as_ref()) {
+            let url = self.path.as_ref().to_str().unwrap();
+            let stats = self.opts.io_stats.clone().unwrap_or_default();
+            return Ok(Arc::new(
+                NbdIoEngine::new(url, self.write)
+                    .map_err(|e| anyhow!("couldn't open {}: {}", url, e))?
+                    .with_stats(stats),
+            ));
+        }
+
+        // Packed metadata is served directly, whatever engine was asked for
+        if is_pack_file(self.path.as_ref()) {
+            if self.write {
+                return Err(anyhow!("pack files can only be opened read only"));
+            }
+            return Ok(Arc::new(PackIoEngine::new(self.path)?));
+        }
 
-                Arc::new(SpindleIoEngine::new(self.path, valid_blocks, self.write)?)
+        let engine_type = match &self.opts.engine_type {
+            EngineType::Auto => self.choose_engine_type(),
+            t => t.clone(),
+        };
+
+        let stats = self.opts.io_stats.clone().unwrap_or_default();
+        let engine: Arc<dyn IoEngine + Send + Sync> = match &engine_type {
+            EngineType::Auto => unreachable!(),
+            #[cfg(feature = "io_uring")]
+            EngineType::Async => Arc::new(
+                AsyncIoEngine::new_with(self.path, self.write, self.exclusive)?.with_stats(stats),
+            ),
+            EngineType::Sync => Arc::new(
+                SyncIoEngine::new_with(self.path, self.write, self.exclusive)?.with_stats(stats),
+            ),
+            // Prefetching is only an optimisation, so if the blocks
+            // can't all be read up front the sync engine is used instead,
+            // and reports errors block by block.
+            EngineType::Spindle => match self.build_spindle(stats.clone()) {
+                Ok(engine) => engine,
+                Err(e) => {
+                    if let Some(report) = &self.opts.report {
+                        report.info(&format!("falling back to the sync io engine: {}", e));
+                    }
+                    Arc::new(
+                        SyncIoEngine::new_with(self.path, self.write, self.exclusive)?
+                            .with_stats(stats),
+                    )
+                }
+            },
+            EngineType::Fault(spec) => {
+                let inner = Arc::new(
+                    SyncIoEngine::new_with(self.path, self.write, self.exclusive)?
+                        .with_stats(stats),
+                );
+                Arc::new(FaultyIoEngine::new(inner, FaultSpec::parse(spec)?))
             }
         };
         Ok(engine)
     }
+
+    fn build_spindle(&self, stats: Arc<IoStats>) -> Result<Arc<dyn IoEngine + Send + Sync>> {
+        let path = self.path.as_ref();
+        let valid_blocks = match self.opts.tool {
+            ToolType::Thin => thin_valid_blocks(path, self.opts)?,
+            ToolType::Cache => cache_valid_blocks(path, self.opts)?,
+            ToolType::Era => era_valid_blocks(path, self.opts)?,
+            ToolType::Other => {
+                let nr_blocks = get_nr_blocks(path)?;
+                all_blocks(nr_blocks as u32)
+            }
+        };
+
+        Ok(Arc::new(SpindleIoEngine::new_with_stats(
+            path,
+            valid_blocks,
+            self.exclusive,
+            stats,
+        )?))
+    }
 }
 
 //------------------------------------------
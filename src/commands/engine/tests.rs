@@ -0,0 +1,118 @@
+use super::*;
+
+//------------------------------------------
+
+fn hdd() -> DeviceInfo {
+    DeviceInfo::Block {
+        rotational: true,
+        queue_depth: 64,
+    }
+}
+
+fn ssd(queue_depth: u64) -> DeviceInfo {
+    DeviceInfo::Block {
+        rotational: false,
+        queue_depth,
+    }
+}
+
+fn choose(info: &DeviceInfo, tool: ToolType, write: bool, exclusive: bool) -> EngineType {
+    auto_engine_type(info, &tool, write, exclusive).0
+}
+
+#[test]
+fn test_files_use_sync() {
+    assert_eq!(
+        choose(&DeviceInfo::File, ToolType::Thin, false, true),
+        EngineType::Sync
+    );
+    assert_eq!(
+        choose(&DeviceInfo::Unknown, ToolType::Thin, false, true),
+        EngineType::Sync
+    );
+}
+
+#[test]
+fn test_rotational_thin_reads_use_spindle() {
+    assert_eq!(
+        choose(&hdd(), ToolType::Thin, false, true),
+        EngineType::Spindle
+    );
+}
+
+#[test]
+fn test_spindle_only_when_it_can_cope() {
+    // can't write
+    assert_eq!(choose(&hdd(), ToolType::Thin, true, true), EngineType::Sync);
+    // live metadata, opened shared
+    assert_eq!(
+        choose(&hdd(), ToolType::Thin, false, false),
+        EngineType::Sync
+    );
+    // only knows thin metadata
+    assert_eq!(
+        choose(&hdd(), ToolType::Cache, false, true),
+        EngineType::Sync
+    );
+    assert_eq!(choose(&hdd(), ToolType::Era, false, true), EngineType::Sync);
+}
+
+#[test]
+fn test_shallow_ssd_uses_sync() {
+    assert_eq!(
+        choose(&ssd(4), ToolType::Thin, true, true),
+        EngineType::Sync
+    );
+}
+
+#[cfg(feature = "io_uring")]
+#[test]
+fn test_deep_ssd_uses_async() {
+    assert_eq!(
+        choose(&ssd(ASYNC_MIN_QUEUE_DEPTH), ToolType::Cache, true, true),
+        EngineType::Async
+    );
+}
+
+#[cfg(not(feature = "io_uring"))]
+#[test]
+fn test_deep_ssd_without_io_uring_uses_sync() {
+    assert_eq!(
+        choose(&ssd(1024), ToolType::Thin, true, true),
+        EngineType::Sync
+    );
+}
+
+//------------------------------------------
+
+fn mk_opts(engine_type: EngineType) -> EngineOptions {
+    EngineOptions {
+        tool: ToolType::Thin,
+        engine_type,
+        use_metadata_snap: false,
+        overlay: None,
+        trace: None,
+        io_stats: None,
+        block_cache: None,
+        report: None,
+    }
+}
+
+#[test]
+fn test_valid_blocks_of_a_missing_device_is_an_error() {
+    let dir = tempfile::TempDir::new().unwrap();
+    let path = dir.path().join("missing");
+    assert!(thin_valid_blocks(&path, &mk_opts(EngineType::Auto)).is_err());
+}
+
+#[test]
+fn test_unreadable_space_map_only_prefetches_everything_on_request() {
+    let file = tempfile::NamedTempFile::new().unwrap();
+    file.as_file().set_len(16 * BLOCK_SIZE as u64).unwrap();
+
+    assert!(thin_valid_blocks(file.path(), &mk_opts(EngineType::Auto)).is_err());
+    let blocks = thin_valid_blocks(file.path(), &mk_opts(EngineType::Spindle)).unwrap();
+    assert_eq!(blocks.len(), 16);
+}
+
+//------------------------------------------
@@ -77,7 +77,7 @@ impl<'a> Command<'a> for EraCheckCommand {
             return to_exit_code::<()>(&report, Err(e));
         }
 
-        let engine_opts = parse_engine_opts(ToolType::Era, &matches);
+        let engine_opts = parse_engine_opts(ToolType::Era, &matches, report.clone());
         if engine_opts.is_err() {
             return to_exit_code(&report, engine_opts);
         }
@@ -90,7 +90,10 @@ impl<'a> Command<'a> for EraCheckCommand {
             report: report.clone(),
         };
 
-        to_exit_code(&report, check(&opts))
+        let io_stats = opts.engine_opts.io_stats.clone();
+        let result = check(&opts);
+        report_io_stats(&report, io_stats.as_deref());
+        to_exit_code(&report, result)
     }
 }
 
@@ -85,7 +85,7 @@ impl<'a> Command<'a> for EraGenerateMetadataCommand {
         let output_file = Path::new(matches.get_one::<String>("OUTPUT").unwrap());
 
         let report = mk_report(false);
-        let engine_opts = parse_engine_opts(ToolType::Era, &matches);
+        let engine_opts = parse_engine_opts(ToolType::Era, &matches, report.clone());
         if engine_opts.is_err() {
             return to_exit_code(&report, engine_opts);
         }
@@ -109,7 +109,10 @@ impl<'a> Command<'a> for EraGenerateMetadataCommand {
             output: output_file,
         };
 
-        to_exit_code(&report, generate_metadata(opts))
+        let io_stats = opts.engine_opts.io_stats.clone();
+        let result = generate_metadata(opts);
+        report_io_stats(&report, io_stats.as_deref());
+        to_exit_code(&report, result)
     }
 }
 
@@ -71,7 +71,7 @@ impl<'a> Command<'a> for EraInvalidateCommand {
             return to_exit_code::<()>(&report, Err(e));
         }
 
-        let engine_opts = parse_engine_opts(ToolType::Era, &matches);
+        let engine_opts = parse_engine_opts(ToolType::Era, &matches, report.clone());
         if engine_opts.is_err() {
             return to_exit_code(&report, engine_opts);
         }
@@ -83,7 +83,10 @@ impl<'a> Command<'a> for EraInvalidateCommand {
             threshold: matches.get_one::<u32>("WRITTEN_SINCE").map_or(0, |v| *v),
         };
 
-        to_exit_code(&report, invalidate(&opts))
+        let io_stats = opts.engine_opts.io_stats.clone();
+        let result = invalidate(&opts);
+        report_io_stats(&report, io_stats.as_deref());
+        to_exit_code(&report, result)
     }
 }
 
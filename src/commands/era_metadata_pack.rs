@@ -0,0 +1,20 @@
+use crate::commands::metadata_pack::*;
+use crate::commands::Command;
+
+pub struct EraMetadataPackCommand;
+
+impl EraMetadataPackCommand {
+    fn cli(&self) -> clap::Command {
+        pack_cli(self.name(), &ERA_METADATA)
+    }
+}
+
+impl<'a> Command<'a> for EraMetadataPackCommand {
+    fn name(&self) -> &'a str {
+        "era_metadata_pack"
+    }
+
+    fn run(&self, args: &mut dyn Iterator<Item = std::ffi::OsString>) -> exitcode::ExitCode {
+        run_pack(self.cli(), &ERA_METADATA, args)
+    }
+}
@@ -0,0 +1,20 @@
+use crate::commands::metadata_pack::*;
+use crate::commands::Command;
+
+pub struct EraMetadataUnpackCommand;
+
+impl EraMetadataUnpackCommand {
+    fn cli(&self) -> clap::Command {
+        unpack_cli(self.name(), &ERA_METADATA)
+    }
+}
+
+impl<'a> Command<'a> for EraMetadataUnpackCommand {
+    fn name(&self) -> &'a str {
+        "era_metadata_unpack"
+    }
+
+    fn run(&self, args: &mut dyn Iterator<Item = std::ffi::OsString>) -> exitcode::ExitCode {
+        run_unpack(self.cli(), args)
+    }
+}
@@ -43,7 +43,7 @@ impl EraRepairCommand {
                     .value_name("FILE")
                     .required(true),
             );
-        verbose_args(engine_args(version_args(cmd)))
+        verbose_args(overlay_args(engine_args(version_args(cmd))))
     }
 }
 
@@ -73,7 +73,7 @@ impl<'a> Command<'a> for EraRepairCommand {
             return to_exit_code::<()>(&report, Err(e));
         }
 
-        let engine_opts = parse_engine_opts(ToolType::Era, &matches);
+        let engine_opts = parse_engine_opts(ToolType::Era, &matches, report.clone());
         if engine_opts.is_err() {
             return to_exit_code(&report, engine_opts);
         }
@@ -85,6 +85,9 @@ impl<'a> Command<'a> for EraRepairCommand {
             report: report.clone(),
         };
 
-        to_exit_code(&report, repair(opts))
+        let io_stats = opts.engine_opts.io_stats.clone();
+        let result = repair(opts);
+        report_io_stats(&report, io_stats.as_deref());
+        to_exit_code(&report, result)
     }
 }
@@ -44,7 +44,7 @@ impl EraRestoreCommand {
                     .value_name("FILE")
                     .required(true),
             );
-        verbose_args(engine_args(version_args(cmd)))
+        verbose_args(overlay_args(engine_args(version_args(cmd))))
     }
 }
 
@@ -71,7 +71,7 @@ impl<'a> Command<'a> for EraRestoreCommand {
             return to_exit_code::<()>(&report, Err(e));
         }
 
-        let engine_opts = parse_engine_opts(ToolType::Era, &matches);
+        let engine_opts = parse_engine_opts(ToolType::Era, &matches, report.clone());
         if engine_opts.is_err() {
             return to_exit_code(&report, engine_opts);
         }
@@ -83,6 +83,9 @@ impl<'a> Command<'a> for EraRestoreCommand {
             report: report.clone(),
         };
 
-        to_exit_code(&report, restore(opts))
+        let io_stats = opts.engine_opts.io_stats.clone();
+        let result = restore(opts);
+        report_io_stats(&report, io_stats.as_deref());
+        to_exit_code(&report, result)
     }
 }
@@ -0,0 +1,119 @@
+use anyhow::Result;
+use clap::Arg;
+use std::path::Path;
+
+use crate::commands::engine::*;
+use crate::commands::utils::*;
+use crate::io_engine::trace::*;
+use crate::report::mk_simple_report;
+use crate::version::*;
+
+//------------------------------------------
+use crate::commands::Command;
+
+pub struct IoReplayCommand;
+
+impl IoReplayCommand {
+    fn cli(&self) -> clap::Command {
+        let cmd = clap::Command::new(self.name())
+            .next_display_order(None)
+            .version(crate::tools_version!())
+            .disable_version_flag(true)
+            .about("Analyse an io trace, and optionally replay it against a device")
+            .arg(
+                Arg::new("REPLAY_WRITES")
+                    .help("Replay the writes too, overwriting the device with zeroed blocks")
+                    .long("replay-writes")
+                    .action(clap::ArgAction::SetTrue)
+                    .requires("INPUT"),
+            )
+            .arg(
+                Arg::new("INPUT")
+                    .help("Specify the device to replay against")
+                    .short('i')
+                    .long("input")
+                    .value_name("DEV"),
+            )
+            .arg(
+                Arg::new("TRACE")
+                    .help("Specify the trace recorded with --io-trace")
+                    .required(true)
+                    .index(1),
+            );
+
+        engine_args(version_args(cmd))
+    }
+}
+
+fn print_stats(stats: &TraceStats) {
+    println!("reads: {}", stats.nr_reads);
+    println!("writes: {}", stats.nr_writes);
+    println!("batches: {}", stats.nr_batches);
+    println!("threads: {}", stats.nr_threads);
+    println!("seeks: {}", stats.nr_seeks);
+    println!("seek distance: {}", stats.seek_distance);
+    println!("recorded time: {:.3}s", stats.duration.as_secs_f64());
+}
+
+fn io_replay(
+    trace: &Path,
+    input: Option<&Path>,
+    engine_opts: &EngineOptions,
+    replay_writes: bool,
+) -> Result<()> {
+    let trace = read_trace(trace)?;
+    print_stats(&trace_stats(&trace));
+
+    if let Some(input) = input {
+        let engine = EngineBuilder::new(input, engine_opts)
+            .write(replay_writes)
+            .build()?;
+        let stats = replay(engine.as_ref(), &trace, replay_writes)?;
+        println!("replayed ios: {}", stats.nr_ios);
+        println!("replay errors: {}", stats.nr_errors);
+        println!("replay time: {:.3}s", stats.elapsed.as_secs_f64());
+    }
+
+    Ok(())
+}
+
+impl<'a> Command<'a> for IoReplayCommand {
+    fn name(&self) -> &'a str {
+        "io_replay"
+    }
+
+    fn run(&self, args: &mut dyn Iterator<Item = std::ffi::OsString>) -> exitcode::ExitCode {
+        let matches = self.cli().get_matches_from(args);
+        display_version(&matches);
+        let report = std::sync::Arc::new(mk_simple_report());
+
+        let engine_opts = parse_engine_opts(ToolType::Other, &matches, report.clone());
+        if engine_opts.is_err() {
+            return to_exit_code(&report, engine_opts);
+        }
+        let engine_opts = engine_opts.unwrap();
+
+        let trace = Path::new(matches.get_one::<String>("TRACE").unwrap());
+        let input = matches.get_one::<String>("INPUT").map(Path::new);
+        let replay_writes = matches.get_flag("REPLAY_WRITES");
+        if let Some(input) = input {
+            if let Err(e) = check_input_file(input) {
+                return to_exit_code::<()>(&report, Err(e));
+            }
+
+            // Replayed writes destroy whatever is on the device, so it
+            // should be a scratch copy.
+            if replay_writes {
+                if let Err(e) = check_overwrite_metadata(&report, input) {
+                    return to_exit_code::<()>(&report, Err(e));
+                }
+            }
+        }
+
+        let result = io_replay(trace, input, &engine_opts, replay_writes);
+        report_io_stats(&report, engine_opts.io_stats.as_deref());
+        to_exit_code(&report, result)
+    }
+}
+
+//------------------------------------------
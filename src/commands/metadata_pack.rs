@@ -0,0 +1,214 @@
+use clap::{Arg, ArgAction, ArgMatches};
+use std::path::Path;
+
+use crate::checksum::BT;
+use crate::commands::engine::{io_stats_args, parse_io_stats, report_io_stats};
+use crate::commands::utils::*;
+use crate::pack::toplevel::{check_superblock, pack_incremental, unpack_incremental};
+use crate::report::mk_simple_report;
+use crate::version::*;
+
+//------------------------------------------
+
+// The cache and era pack and unpack commands are the same, bar the
+// descriptions, and which superblock the input is checked for.
+pub struct MetadataKind {
+    pub name: &'static str,
+    pub dev_help: &'static str,
+    pub superblock: BT,
+}
+
+pub const CACHE_METADATA: MetadataKind = MetadataKind {
+    name: "cache",
+    dev_help: "Specify cache metadata binary device/file",
+    superblock: BT::CACHE_SUPERBLOCK,
+};
+
+pub const ERA_METADATA: MetadataKind = MetadataKind {
+    name: "era",
+    dev_help: "Specify era metadata binary device/file",
+    superblock: BT::ERA_SUPERBLOCK,
+};
+
+fn force_arg() -> Arg {
+    Arg::new("FORCE")
+        .help("Force overwrite the output file")
+        .short('f')
+        .long("force")
+        .action(ArgAction::SetTrue)
+}
+
+fn base_files(matches: &ArgMatches) -> Vec<&Path> {
+    matches
+        .get_many::<String>("BASE")
+        .map_or_else(Vec::new, |files| files.map(Path::new).collect())
+}
+
+//------------------------------------------
+
+pub fn pack_cli(name: &'static str, kind: &MetadataKind) -> clap::Command {
+    let cmd = clap::Command::new(name)
+        .next_display_order(None)
+        .version(crate::tools_version!())
+        .disable_version_flag(true)
+        .about(format!(
+            "Produces a compressed file of {} metadata.  Only packs metadata blocks that are actually used.",
+            kind.name
+        ))
+        // flags
+        .arg(force_arg())
+        // options
+        .arg(
+            Arg::new("BASE")
+                .help("Only pack blocks changed since the given base pack(s)")
+                .long("base")
+                .action(ArgAction::Append)
+                .value_name("FILE"),
+        )
+        .arg(
+            Arg::new("INPUT")
+                .help(kind.dev_help)
+                .required(true)
+                .short('i')
+                .long("input")
+                .value_name("DEV"),
+        )
+        .arg(
+            Arg::new("OUTPUT")
+                .help("Specify packed output file")
+                .required(true)
+                .short('o')
+                .long("output")
+                .value_name("FILE"),
+        );
+
+    io_stats_args(version_args(cmd))
+}
+
+pub fn run_pack(
+    cli: clap::Command,
+    kind: &MetadataKind,
+    args: &mut dyn Iterator<Item = std::ffi::OsString>,
+) -> exitcode::ExitCode {
+    let matches = cli.get_matches_from(args);
+    display_version(&matches);
+
+    let input_file = Path::new(matches.get_one::<String>("INPUT").unwrap());
+    let output_file = Path::new(matches.get_one::<String>("OUTPUT").unwrap());
+    let base_files = base_files(&matches);
+
+    let report = mk_simple_report();
+
+    if let Err(e) = check_input_file(input_file)
+        .and_then(check_file_not_tiny)
+        .and_then(check_not_xml)
+        .and_then(|input| check_superblock(input, kind.superblock).map(|_| input))
+    {
+        return to_exit_code::<()>(&report, Err(e));
+    }
+
+    for base in &base_files {
+        if let Err(e) = check_input_file(base) {
+            return to_exit_code::<()>(&report, Err(e));
+        }
+    }
+
+    if !matches.get_flag("FORCE") {
+        if let Err(e) = check_overwrite_metadata(&report, output_file) {
+            return to_exit_code::<()>(&report, Err(e));
+        }
+    }
+
+    let io_stats = parse_io_stats(&matches);
+    let result = pack_incremental(
+        input_file,
+        output_file,
+        &base_files,
+        io_stats.clone().unwrap_or_default(),
+    );
+    report_io_stats(&report, io_stats.as_deref());
+    to_exit_code(&report, result)
+}
+
+//------------------------------------------
+
+pub fn unpack_cli(name: &'static str, kind: &MetadataKind) -> clap::Command {
+    let cmd = clap::Command::new(name)
+        .next_display_order(None)
+        .version(crate::tools_version!())
+        .disable_version_flag(true)
+        .about(format!(
+            "Unpack a compressed file of {} metadata.",
+            kind.name
+        ))
+        // flags
+        .arg(force_arg())
+        // options
+        .arg(
+            Arg::new("BASE")
+                .help("Specify the base pack(s) of an incremental input")
+                .long("base")
+                .action(ArgAction::Append)
+                .value_name("FILE"),
+        )
+        .arg(
+            Arg::new("INPUT")
+                .help("Specify packed input file")
+                .required(true)
+                .short('i')
+                .long("input")
+                .value_name("FILE"),
+        )
+        .arg(
+            Arg::new("OUTPUT")
+                .help(kind.dev_help)
+                .required(true)
+                .short('o')
+                .long("output")
+                .value_name("DEV"),
+        );
+
+    io_stats_args(version_args(cmd))
+}
+
+pub fn run_unpack(
+    cli: clap::Command,
+    args: &mut dyn Iterator<Item = std::ffi::OsString>,
+) -> exitcode::ExitCode {
+    let matches = cli.get_matches_from(args);
+    display_version(&matches);
+
+    let input_file = Path::new(matches.get_one::<String>("INPUT").unwrap());
+    let output_file = Path::new(matches.get_one::<String>("OUTPUT").unwrap());
+    let base_files = base_files(&matches);
+
+    let report = mk_simple_report();
+
+    if let Err(e) = check_input_file(input_file) {
+        return to_exit_code::<()>(&report, Err(e));
+    }
+
+    for base in &base_files {
+        if let Err(e) = check_input_file(base) {
+            return to_exit_code::<()>(&report, Err(e));
+        }
+    }
+
+    if !matches.get_flag("FORCE") {
+        if let Err(e) = check_overwrite_metadata(&report, output_file) {
+            return to_exit_code::<()>(&report, Err(e));
+        }
+    }
+
+    let io_stats = parse_io_stats(&matches);
+    let result = unpack_incremental(
+        input_file,
+        output_file,
+        &base_files,
+        io_stats.clone().unwrap_or_default(),
+    );
+    report_io_stats(&report, io_stats.as_deref());
+    to_exit_code(&report, result)
+}
+
+//------------------------------------------
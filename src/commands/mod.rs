@@ -1,15 +1,24 @@
 pub mod cache_check;
 pub mod cache_dump;
+pub mod cache_metadata_pack;
 pub mod cache_metadata_size;
+pub mod cache_metadata_unpack;
 pub mod cache_repair;
+pub mod cache_resize;
 pub mod cache_restore;
+pub mod cache_stat;
 pub mod cache_writeback;
 pub mod engine;
 pub mod era_check;
 pub mod era_dump;
 pub mod era_invalidate;
+pub mod era_metadata_pack;
+pub mod era_metadata_unpack;
 pub mod era_repair;
 pub mod era_restore;
+pub mod metadata_pack;
+pub mod overlay_commit;
+pub mod overlay_discard;
 pub mod thin_check;
 pub mod thin_delta;
 pub mod thin_dump;
@@ -32,6 +41,8 @@ pub mod cache_generate_metadata;
 #[cfg(feature = "devtools")]
 pub mod era_generate_metadata;
 #[cfg(feature = "devtools")]
+pub mod io_replay;
+#[cfg(feature = "devtools")]
 pub mod thin_explore;
 #[cfg(feature = "devtools")]
 pub mod thin_generate_damage;
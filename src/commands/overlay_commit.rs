@@ -0,0 +1,87 @@
+extern crate clap;
+
+use anyhow::Result;
+use clap::{Arg, ArgAction};
+use std::fs::OpenOptions;
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::commands::utils::*;
+use crate::io_engine::overlay::commit_overlay;
+use crate::io_engine::SyncIoEngine;
+use crate::report::Report;
+use crate::version::*;
+
+//------------------------------------------
+use crate::commands::Command;
+
+pub struct OverlayCommitCommand;
+
+impl OverlayCommitCommand {
+    fn cli(&self) -> clap::Command {
+        let cmd = clap::Command::new(self.name())
+            .next_display_order(None)
+            .version(crate::tools_version!())
+            .disable_version_flag(true)
+            .about("Write the blocks held in an overlay file to the device it was made for")
+            .arg(
+                Arg::new("QUIET")
+                    .help("Suppress output messages, return only exit code.")
+                    .short('q')
+                    .long("quiet")
+                    .action(ArgAction::SetTrue),
+            )
+            // options
+            .arg(
+                Arg::new("OUTPUT")
+                    .help("Specify the device to commit to")
+                    .short('o')
+                    .long("output")
+                    .value_name("FILE")
+                    .required(true),
+            )
+            // arguments
+            .arg(
+                Arg::new("INPUT")
+                    .help("Specify the overlay file")
+                    .required(true)
+                    .index(1),
+            );
+        version_args(cmd)
+    }
+}
+
+fn overlay_commit(report: &Report, overlay: &Path, output: &Path) -> Result<()> {
+    let dev = Arc::new(SyncIoEngine::new_with(output, true, true)?);
+    let nr_blocks = commit_overlay(dev, overlay)?;
+
+    // The overlay is the only other copy of the blocks, so make sure they
+    // have reached the device before it goes.
+    OpenOptions::new().write(true).open(output)?.sync_all()?;
+    std::fs::remove_file(overlay)?;
+    report.info(&format!("committed {} blocks", nr_blocks));
+    Ok(())
+}
+
+impl<'a> Command<'a> for OverlayCommitCommand {
+    fn name(&self) -> &'a str {
+        "overlay_commit"
+    }
+
+    fn run(&self, args: &mut dyn Iterator<Item = std::ffi::OsString>) -> exitcode::ExitCode {
+        let matches = self.cli().get_matches_from(args);
+        display_version(&matches);
+
+        let input = Path::new(matches.get_one::<String>("INPUT").unwrap());
+        let output = Path::new(matches.get_one::<String>("OUTPUT").unwrap());
+        let report = mk_report(matches.get_flag("QUIET"));
+
+        if let Err(e) = check_input_file(input).and_then(|_| check_output_file(output)) {
+            return to_exit_code::<()>(&report, Err(e));
+        }
+
+        to_exit_code(&report, overlay_commit(&report, input, output))
+    }
+}
+
+//------------------------------------------
@@ -0,0 +1,60 @@
+extern crate clap;
+
+use anyhow::Result;
+use clap::Arg;
+use std::path::Path;
+
+use crate::commands::utils::*;
+use crate::io_engine::overlay::check_overlay;
+use crate::version::*;
+
+//------------------------------------------
+use crate::commands::Command;
+
+pub struct OverlayDiscardCommand;
+
+impl OverlayDiscardCommand {
+    fn cli(&self) -> clap::Command {
+        let cmd = clap::Command::new(self.name())
+            .next_display_order(None)
+            .version(crate::tools_version!())
+            .disable_version_flag(true)
+            .about("Throw away an overlay file, leaving its device as it was")
+            .arg(
+                Arg::new("INPUT")
+                    .help("Specify the overlay file")
+                    .required(true)
+                    .index(1),
+            );
+        version_args(cmd)
+    }
+}
+
+// Only files that really are overlays get removed.
+fn overlay_discard(overlay: &Path) -> Result<()> {
+    check_overlay(overlay)?;
+    std::fs::remove_file(overlay)?;
+    Ok(())
+}
+
+impl<'a> Command<'a> for OverlayDiscardCommand {
+    fn name(&self) -> &'a str {
+        "overlay_discard"
+    }
+
+    fn run(&self, args: &mut dyn Iterator<Item = std::ffi::OsString>) -> exitcode::ExitCode {
+        let matches = self.cli().get_matches_from(args);
+        display_version(&matches);
+
+        let input = Path::new(matches.get_one::<String>("INPUT").unwrap());
+        let report = mk_report(false);
+
+        if let Err(e) = check_input_file(input) {
+            return to_exit_code::<()>(&report, Err(e));
+        }
+
+        to_exit_code(&report, overlay_discard(input))
+    }
+}
+
+//------------------------------------------
@@ -99,7 +99,7 @@ impl ThinCheckCommand {
                     .required(true)
                     .index(1),
             );
-        verbose_args(engine_args(version_args(cmd)))
+        verbose_args(overlay_args(engine_args(version_args(cmd))))
     }
 }
 
@@ -128,7 +128,7 @@ impl<'a> Command<'a> for ThinCheckCommand {
             return to_exit_code::<()>(&report, Err(e));
         }
 
-        let engine_opts = parse_engine_opts(ToolType::Thin, &matches);
+        let engine_opts = parse_engine_opts(ToolType::Thin, &matches, report.clone());
         if engine_opts.is_err() {
             return to_exit_code(&report, engine_opts.map(|_| ()));
         }
@@ -147,6 +147,9 @@ impl<'a> Command<'a> for ThinCheckCommand {
             report: report.clone(),
         };
 
-        to_exit_code(&report, check(opts))
+        let io_stats = opts.engine_opts.io_stats.clone();
+        let result = check(opts);
+        report_io_stats(&report, io_stats.as_deref());
+        to_exit_code(&report, result)
     }
 }
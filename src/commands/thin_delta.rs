@@ -133,7 +133,7 @@ impl<'a> Command<'a> for ThinDeltaCommand {
             }
         };
 
-        let engine_opts = parse_engine_opts(ToolType::Thin, &matches);
+        let engine_opts = parse_engine_opts(ToolType::Thin, &matches, report.clone());
         if engine_opts.is_err() {
             return to_exit_code(&report, engine_opts);
         }
@@ -147,7 +147,10 @@ impl<'a> Command<'a> for ThinDeltaCommand {
             verbose: matches.get_flag("VERBOSE"),
         };
 
-        to_exit_code(&report, delta(opts))
+        let io_stats = opts.engine_opts.io_stats.clone();
+        let result = delta(opts);
+        report_io_stats(&report, io_stats.as_deref());
+        to_exit_code(&report, result)
     }
 }
 
@@ -137,7 +137,7 @@ impl<'a> Command<'a> for ThinDumpCommand {
             return to_exit_code::<()>(&report, Err(e));
         }
 
-        let engine_opts = parse_engine_opts(ToolType::Thin, &matches);
+        let engine_opts = parse_engine_opts(ToolType::Thin, &matches, report.clone());
         if engine_opts.is_err() {
             return to_exit_code(&report, engine_opts);
         }
@@ -162,6 +162,9 @@ impl<'a> Command<'a> for ThinDumpCommand {
             format: matches.get_one::<OutputFormat>("FORMAT").unwrap().clone(),
         };
 
-        to_exit_code(&report, dump(opts))
+        let io_stats = opts.engine_opts.io_stats.clone();
+        let result = dump(opts);
+        report_io_stats(&report, io_stats.as_deref());
+        to_exit_code(&report, result)
     }
 }
@@ -1,7 +1,8 @@
 extern crate clap;
 
 use anyhow::{anyhow, Result};
-use clap::Arg;
+use clap::{value_parser, Arg, ArgAction, ArgGroup};
+use std::collections::BTreeSet;
 use std::fmt;
 use std::io::{self, Write};
 use std::path::Path;
@@ -28,12 +29,23 @@ use ratatui::{
     Frame, Terminal,
 };
 
+use nom::{number::complete::*, IResult};
+
+use crate::cache::mapping::Mapping;
+use crate::cache::superblock as cache_sb;
+use crate::checksum::*;
+use crate::commands::engine::*;
 use crate::commands::utils::*;
 use crate::commands::Command;
+use crate::era::superblock as era_sb;
+use crate::era::writeset::Writeset;
 use crate::io_engine::*;
+use crate::pdata::array::ArrayBlockHeader;
 use crate::pdata::btree;
 use crate::pdata::btree_error;
-use crate::pdata::space_map::common::SMRoot;
+use crate::pdata::btree_walker::btree_to_map;
+use crate::pdata::space_map::common::*;
+use crate::pdata::space_map::metadata::*;
 use crate::pdata::unpack::*;
 use crate::thin::block_time::*;
 use crate::thin::device_detail::*;
@@ -150,99 +162,221 @@ fn ls_previous(ls: &mut ListState) {
 
 //------------------------------------
 
-struct SBWidget<'a> {
-    sb: &'a Superblock,
-}
-
-impl StatefulWidget for SBWidget<'_> {
-    type State = ListState;
-
-    fn render(self, area: Rect, buf: &mut Buffer, state: &mut ListState) {
-        let chunks = Layout::default()
-            .direction(Direction::Vertical)
-            .constraints([Constraint::Min(18), Constraint::Percentage(80)].as_ref())
-            .split(area);
-
-        let sb = self.sb;
-        let flags = vec!["flags".to_string(), format!("{}", sb.flags)];
-        let block = vec!["block".to_string(), format!("{}", sb.block)];
-        let uuid = vec!["uuid".to_string(), "-".to_string()];
-        let version = vec!["version".to_string(), format!("{}", sb.version)];
-        let time = vec!["time".to_string(), format!("{}", sb.time)];
-        let transaction_id = vec![
+fn sb_fields(sb: &Superblock) -> Vec<(String, String)> {
+    let metadata_root = unpack::<SMRoot>(&sb.metadata_sm_root[0..]).unwrap();
+    let data_root = unpack::<SMRoot>(&sb.data_sm_root[0..]).unwrap();
+
+    vec![
+        ("flags".to_string(), format!("{}", sb.flags)),
+        ("block".to_string(), format!("{}", sb.block)),
+        ("uuid".to_string(), "-".to_string()),
+        ("version".to_string(), format!("{}", sb.version)),
+        ("time".to_string(), format!("{}", sb.time)),
+        (
             "transaction_id".to_string(),
             format!("{}", sb.transaction_id),
-        ];
-        let metadata_snap = vec![
+        ),
+        (
             "metadata_snap".to_string(),
             if sb.metadata_snap == 0 {
                 "-".to_string()
             } else {
                 format!("{}", sb.metadata_snap)
             },
-        ];
-        let metadata_root = unpack::<SMRoot>(&sb.metadata_sm_root[0..]).unwrap();
-        let data_root = unpack::<SMRoot>(&sb.data_sm_root[0..]).unwrap();
-        let metadata_allocated = vec![
+        ),
+        (
             "metadata allocated".to_string(),
             format!("{}/{}", metadata_root.nr_allocated, metadata_root.nr_blocks),
-        ];
-        let meta_sm_roots = vec![
+        ),
+        (
             "metadata roots".to_string(),
             format!(
                 "[{}, {}]",
                 metadata_root.bitmap_root, metadata_root.ref_count_root
             ),
-        ];
-        let data_allocated = vec![
+        ),
+        (
             "data allocated".to_string(),
             format!("{}/{}", data_root.nr_allocated, data_root.nr_blocks),
-        ];
-        let data_sm_roots = vec![
+        ),
+        (
             "data roots".to_string(),
             format!("[{}, {}]", data_root.bitmap_root, data_root.ref_count_root),
-        ];
-        let mapping_root = vec!["mapping root".to_string(), format!("{}", sb.mapping_root)];
-        let details_root = vec!["details root".to_string(), format!("{}", sb.details_root)];
-        let data_block_size = vec![
+        ),
+        ("mapping root".to_string(), format!("{}", sb.mapping_root)),
+        ("details root".to_string(), format!("{}", sb.details_root)),
+        (
+            "data block size".to_string(),
+            format!("{}k", sb.data_block_size / 2),
+        ),
+    ]
+}
+
+fn sm_root_fields(sm_root: &[u8]) -> Vec<(String, String)> {
+    let metadata_root = unpack::<SMRoot>(sm_root).unwrap();
+
+    vec![
+        (
+            "metadata allocated".to_string(),
+            format!("{}/{}", metadata_root.nr_allocated, metadata_root.nr_blocks),
+        ),
+        (
+            "metadata roots".to_string(),
+            format!(
+                "[{}, {}]",
+                metadata_root.bitmap_root, metadata_root.ref_count_root
+            ),
+        ),
+    ]
+}
+
+fn cache_sb_fields(sb: &cache_sb::Superblock) -> Vec<(String, String)> {
+    let mut flags = Vec::new();
+    if sb.flags.clean_shutdown {
+        flags.push("CLEAN_SHUTDOWN");
+    }
+    if sb.flags.needs_check {
+        flags.push("NEEDS_CHECK");
+    }
+    if flags.is_empty() {
+        flags.push("-");
+    }
+    let opt_root = |root: Option<u64>| root.map_or_else(|| "-".to_string(), |r| r.to_string());
+
+    let mut fields = vec![
+        ("flags".to_string(), flags.join(" | ")),
+        ("block".to_string(), format!("{}", sb.block)),
+        ("version".to_string(), format!("{}", sb.version)),
+        (
+            "policy".to_string(),
+            format!(
+                "{} {:?}",
+                String::from_utf8_lossy(&sb.policy_name),
+                sb.policy_version
+            ),
+        ),
+        ("hint size".to_string(), format!("{}", sb.policy_hint_size)),
+    ];
+    fields.append(&mut sm_root_fields(&sb.metadata_sm_root));
+    fields.append(&mut vec![
+        ("mapping root".to_string(), format!("{}", sb.mapping_root)),
+        ("hint root".to_string(), format!("{}", sb.hint_root)),
+        ("dirty root".to_string(), opt_root(sb.dirty_root)),
+        ("discard root".to_string(), format!("{}", sb.discard_root)),
+        (
+            "discard blocks".to_string(),
+            format!("{} x {}k", sb.discard_nr_blocks, sb.discard_block_size / 2),
+        ),
+        (
+            "data block size".to_string(),
+            format!("{}k", sb.data_block_size / 2),
+        ),
+        ("cache blocks".to_string(), format!("{}", sb.cache_blocks)),
+        (
+            "read hits/misses".to_string(),
+            format!("{}/{}", sb.read_hits, sb.read_misses),
+        ),
+        (
+            "write hits/misses".to_string(),
+            format!("{}/{}", sb.write_hits, sb.write_misses),
+        ),
+    ]);
+    fields
+}
+
+fn era_sb_fields(sb: &era_sb::Superblock) -> Vec<(String, String)> {
+    let mut fields = vec![
+        (
+            "flags".to_string(),
+            if sb.flags.clean_shutdown {
+                "CLEAN_SHUTDOWN".to_string()
+            } else {
+                "-".to_string()
+            },
+        ),
+        ("block".to_string(), format!("{}", sb.block)),
+        ("version".to_string(), format!("{}", sb.version)),
+        (
+            "metadata_snap".to_string(),
+            if sb.metadata_snap == 0 {
+                "-".to_string()
+            } else {
+                format!("{}", sb.metadata_snap)
+            },
+        ),
+    ];
+    fields.append(&mut sm_root_fields(&sb.metadata_sm_root));
+    fields.append(&mut vec![
+        (
             "data block size".to_string(),
             format!("{}k", sb.data_block_size / 2),
-        ];
+        ),
+        ("nr blocks".to_string(), format!("{}", sb.nr_blocks)),
+        ("current era".to_string(), format!("{}", sb.current_era)),
+        (
+            "current writeset".to_string(),
+            format!("{}", sb.current_writeset),
+        ),
+        (
+            "writeset root".to_string(),
+            format!("{}", sb.writeset_tree_root),
+        ),
+        (
+            "era array root".to_string(),
+            format!("{}", sb.era_array_root),
+        ),
+    ]);
+    fields
+}
+
+fn mk_field_rows<'a>(fields: Vec<(String, String)>) -> Vec<Row<'a>> {
+    fields
+        .into_iter()
+        .map(|(field, value)| Row::new(vec![field, value]))
+        .collect()
+}
+
+struct SBWidget<'a> {
+    title: &'a str,
+    fields: &'a [(String, String)],
+    names: Vec<&'a str>,
+}
+
+impl StatefulWidget for SBWidget<'_> {
+    type State = ListState;
+
+    fn render(self, area: Rect, buf: &mut Buffer, state: &mut ListState) {
+        let chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints(
+                [
+                    Constraint::Min(self.fields.len() as u16 + 3),
+                    Constraint::Percentage(80),
+                ]
+                .as_ref(),
+            )
+            .split(area);
 
         let table = Table::new(
-            vec![
-                Row::new(flags),
-                Row::new(block),
-                Row::new(uuid),
-                Row::new(version),
-                Row::new(time),
-                Row::new(transaction_id),
-                Row::new(metadata_snap),
-                Row::new(metadata_allocated),
-                Row::new(meta_sm_roots),
-                Row::new(data_allocated),
-                Row::new(data_sm_roots),
-                Row::new(mapping_root),
-                Row::new(details_root),
-                Row::new(data_block_size),
-            ],
+            mk_field_rows(self.fields.to_vec()),
             [Constraint::Length(20), Constraint::Length(60)],
         )
         .header(Row::new(vec!["Field", "Value"]).style(Style::default().fg(Color::Yellow)))
         .block(
             Block::default()
                 .borders(Borders::ALL)
-                .title("Superblock".to_string()),
+                .title(self.title.to_string()),
         )
         .style(Style::default().fg(Color::White))
         .column_spacing(1);
 
         Widget::render(table, chunks[0], buf);
 
-        let items = vec![
-            ListItem::new(Span::raw("Device tree".to_string())),
-            ListItem::new(Span::raw("Mapping tree".to_string())),
-        ];
+        let items: Vec<ListItem> = self
+            .names
+            .iter()
+            .map(|name| ListItem::new(Span::raw(name.to_string())))
+            .collect();
 
         let items = List::new(items)
             .block(Block::default().borders(Borders::ALL).title("Entries"))
@@ -263,29 +397,26 @@ struct HeaderWidget<'a> {
     hdr: &'a btree::NodeHeader,
 }
 
-impl Widget for HeaderWidget<'_> {
-    fn render(self, area: Rect, buf: &mut Buffer) {
-        let hdr = &self.hdr;
-        let block = vec!["block".to_string(), format!("{}", hdr.block)];
-        let kind = vec![
+fn header_fields(hdr: &btree::NodeHeader) -> Vec<(String, String)> {
+    vec![
+        ("block".to_string(), format!("{}", hdr.block)),
+        (
             "type".to_string(),
             match hdr.is_leaf {
                 true => "LEAF".to_string(),
                 false => "INTERNAL".to_string(),
             },
-        ];
-        let nr_entries = vec!["nr_entries".to_string(), format!("{}", hdr.nr_entries)];
-        let max_entries = vec!["max_entries".to_string(), format!("{}", hdr.max_entries)];
-        let value_size = vec!["value size".to_string(), format!("{}", hdr.value_size)];
+        ),
+        ("nr_entries".to_string(), format!("{}", hdr.nr_entries)),
+        ("max_entries".to_string(), format!("{}", hdr.max_entries)),
+        ("value size".to_string(), format!("{}", hdr.value_size)),
+    ]
+}
 
+impl Widget for HeaderWidget<'_> {
+    fn render(self, area: Rect, buf: &mut Buffer) {
         let table = Table::new(
-            vec![
-                Row::new(block),
-                Row::new(kind),
-                Row::new(nr_entries),
-                Row::new(max_entries),
-                Row::new(value_size),
-            ],
+            mk_field_rows(header_fields(self.hdr)),
             [Constraint::Length(20), Constraint::Length(60)],
         )
         .header(Row::new(vec!["Field", "Value"]).style(Style::default().fg(Color::Yellow)))
@@ -335,6 +466,61 @@ impl Adjacent for DeviceDetail {
     }
 }
 
+impl Adjacent for IndexEntry {
+    fn adjacent(&self, _rhs: &Self) -> bool {
+        false
+    }
+}
+
+impl Adjacent for Writeset {
+    fn adjacent(&self, _rhs: &Self) -> bool {
+        false
+    }
+}
+
+// Reference counts, from either a bitmap or the overflow tree.  Runs
+// are formed from equal counts rather than consecutive ones.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum RefCount {
+    Count(u32),
+    Overflow,
+}
+
+impl fmt::Display for RefCount {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RefCount::Count(n) => write!(f, "{}", n),
+            RefCount::Overflow => write!(f, "overflow"),
+        }
+    }
+}
+
+impl Unpack for RefCount {
+    fn disk_size() -> u32 {
+        4
+    }
+
+    fn unpack(i: &[u8]) -> IResult<&[u8], RefCount> {
+        let (i, n) = le_u32(i)?;
+        Ok((i, RefCount::Count(n)))
+    }
+}
+
+impl From<&BitmapEntry> for RefCount {
+    fn from(e: &BitmapEntry) -> Self {
+        match e {
+            BitmapEntry::Small(n) => RefCount::Count(*n as u32),
+            BitmapEntry::Overflow => RefCount::Overflow,
+        }
+    }
+}
+
+impl Adjacent for RefCount {
+    fn adjacent(&self, rhs: &Self) -> bool {
+        self == rhs
+    }
+}
+
 impl<X: Adjacent, Y: Adjacent> Adjacent for (X, Y) {
     fn adjacent(&self, rhs: &Self) -> bool {
         self.0.adjacent(&rhs.0) && self.1.adjacent(&rhs.1)
@@ -386,14 +572,18 @@ struct NodeWidget<'a, V: Unpack + Adjacent + Clone> {
     node: &'a btree::Node<V>,
 }
 
-fn mk_item<'a, V: fmt::Display>(k: u64, v: &V, len: usize) -> ListItem<'a> {
+fn fmt_run<V: fmt::Display>(k: u64, v: &V, len: usize) -> String {
     if len > 1 {
-        ListItem::new(Span::raw(format!("{} x {} -> {}", k, len as u64, v)))
+        format!("{} x {} -> {}", k, len as u64, v)
     } else {
-        ListItem::new(Span::raw(format!("{} -> {}", k, v)))
+        format!("{} -> {}", k, v)
     }
 }
 
+fn mk_item<'a, V: fmt::Display>(k: u64, v: &V, len: usize) -> ListItem<'a> {
+    ListItem::new(Span::raw(fmt_run(k, v, len)))
+}
+
 fn mk_items<'a, V>(keys: &[u64], values: &[V], selected: usize) -> (Vec<ListItem<'a>>, usize)
 where
     V: Adjacent + Copy + fmt::Display,
@@ -477,91 +667,357 @@ impl<V: Unpack + fmt::Display + Adjacent + Copy> StatefulWidget for NodeWidget<'
 
 //------------------------------------
 
+// Draws a panel from its description, for blocks that aren't btree
+// nodes.
+struct DescriptionWidget<'a> {
+    desc: &'a Description,
+}
+
+impl StatefulWidget for DescriptionWidget<'_> {
+    type State = ListState;
+
+    fn render(self, area: Rect, buf: &mut Buffer, state: &mut ListState) {
+        let chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints(
+                [
+                    Constraint::Min(self.desc.fields.len() as u16 + 3),
+                    Constraint::Percentage(80),
+                ]
+                .as_ref(),
+            )
+            .split(area);
+
+        let table = Table::new(
+            mk_field_rows(self.desc.fields.clone()),
+            [Constraint::Length(20), Constraint::Length(60)],
+        )
+        .header(Row::new(vec!["Field", "Value"]).style(Style::default().fg(Color::Yellow)))
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title(self.desc.title.clone()),
+        )
+        .style(Style::default().fg(Color::White))
+        .column_spacing(1);
+
+        Widget::render(table, chunks[0], buf);
+
+        let items: Vec<ListItem> = self
+            .desc
+            .entries
+            .iter()
+            .map(|e| mk_item(e.key, &e.value, e.len))
+            .collect();
+
+        let items = List::new(items)
+            .block(Block::default().borders(Borders::ALL).title("Entries"))
+            .highlight_style(
+                Style::default()
+                    .bg(Color::LightGreen)
+                    .add_modifier(Modifier::BOLD),
+            );
+
+        StatefulWidget::render(items, chunks[1], buf, state);
+    }
+}
+
+//------------------------------------
+
 enum Action {
     PushDeviceDetail(u64),
     PushTopLevel(u64),
     PushBottomLevel(u32, u64),
+    PushMetadataIndex(u64),
+    PushIndexTree(u64),
+    PushBitmap(u64, u64),
+    PushRefCounts(u64),
+    PushArray(u64, ArrayKind),
+    PushArrayBlock(u64, ArrayKind, u64),
+    PushWritesets(u64),
+    WriteBlock(u64, BT, Vec<u8>),
     PopPanel,
 }
 
 use Action::*;
 
+// The contents of a panel in a form that can be printed, rather than
+// drawn, by the non-interactive queries.
+struct Description {
+    title: String,
+    fields: Vec<(String, String)>,
+    entries: Vec<Entry>,
+}
+
+struct Entry {
+    key: u64,
+    len: usize,
+    value: String,
+}
+
+fn mk_entries<V>(keys: &[u64], values: &[V]) -> Vec<Entry>
+where
+    V: Adjacent + Copy + fmt::Display,
+{
+    mk_runs(keys, values)
+        .into_iter()
+        .map(|((key, v), len)| Entry {
+            key,
+            len,
+            value: v.to_string(),
+        })
+        .collect()
+}
+
+fn describe_node<V>(title: String, node: &btree::Node<V>) -> Description
+where
+    V: Unpack + Adjacent + Copy + fmt::Display,
+{
+    let entries = match node {
+        btree::Node::Internal { keys, values, .. } => mk_entries(keys, values),
+        btree::Node::Leaf { keys, values, .. } => mk_entries(keys, values),
+    };
+
+    Description {
+        title,
+        fields: header_fields(node.get_header()),
+        entries,
+    }
+}
+
 trait Panel {
     fn render(&mut self, area: Rect, f: &mut Frame);
     fn input(&mut self, k: Key) -> Option<Action>;
     fn path_action(&mut self, child: u64) -> Option<Action>;
+    fn describe(&self) -> Description;
+
+    // The block being shown, and the type its checksum is salted with
+    fn location(&self) -> (u64, BT);
 }
 
 //------------------------------------
 
+// The superblock of any of the metadata formats.  Each of the trees
+// hanging off it is an entry that can be opened.
 struct SBPanel {
-    sb: Superblock,
+    kind: BT,
+    fields: Vec<(String, String)>,
+    roots: Vec<(&'static str, u64, TreeKind)>,
     state: ListState,
 }
 
 impl SBPanel {
-    fn new(sb: Superblock) -> SBPanel {
+    fn new(
+        kind: BT,
+        fields: Vec<(String, String)>,
+        mut roots: Vec<(&'static str, u64, TreeKind)>,
+    ) -> SBPanel {
         let mut state = ListState::default();
         state.select(Some(0));
 
-        SBPanel { sb, state }
+        // Optional trees have a root of zero when they're not present,
+        // since that's where the superblock lives.
+        roots.retain(|(_, root, _)| *root != SUPERBLOCK_LOCATION);
+
+        SBPanel {
+            kind,
+            fields,
+            roots,
+            state,
+        }
+    }
+
+    fn thin(sb: Superblock) -> SBPanel {
+        let metadata_root = unpack::<SMRoot>(&sb.metadata_sm_root[0..]).unwrap();
+        let data_root = unpack::<SMRoot>(&sb.data_sm_root[0..]).unwrap();
+
+        SBPanel::new(
+            BT::THIN_SUPERBLOCK,
+            sb_fields(&sb),
+            vec![
+                ("Device tree", sb.details_root, TreeKind::Details),
+                ("Mapping tree", sb.mapping_root, TreeKind::TopLevel),
+                (
+                    "Metadata space map",
+                    metadata_root.bitmap_root,
+                    TreeKind::MetadataIndex,
+                ),
+                (
+                    "Metadata ref counts",
+                    metadata_root.ref_count_root,
+                    TreeKind::RefCounts,
+                ),
+                ("Data space map", data_root.bitmap_root, TreeKind::IndexTree),
+                (
+                    "Data ref counts",
+                    data_root.ref_count_root,
+                    TreeKind::RefCounts,
+                ),
+            ],
+        )
+    }
+
+    fn cache(sb: cache_sb::Superblock) -> SBPanel {
+        let metadata_root = unpack::<SMRoot>(&sb.metadata_sm_root[0..]).unwrap();
+
+        SBPanel::new(
+            BT::CACHE_SUPERBLOCK,
+            cache_sb_fields(&sb),
+            vec![
+                (
+                    "Mappings",
+                    sb.mapping_root,
+                    TreeKind::Array(ArrayKind::Mappings),
+                ),
+                ("Hints", sb.hint_root, TreeKind::Array(ArrayKind::Hints)),
+                (
+                    "Dirty bits",
+                    sb.dirty_root.unwrap_or(SUPERBLOCK_LOCATION),
+                    TreeKind::Array(ArrayKind::Dirty),
+                ),
+                (
+                    "Discard bits",
+                    sb.discard_root,
+                    TreeKind::Array(ArrayKind::Discard),
+                ),
+                (
+                    "Metadata space map",
+                    metadata_root.bitmap_root,
+                    TreeKind::MetadataIndex,
+                ),
+                (
+                    "Metadata ref counts",
+                    metadata_root.ref_count_root,
+                    TreeKind::RefCounts,
+                ),
+            ],
+        )
+    }
+
+    fn era(sb: era_sb::Superblock) -> SBPanel {
+        let metadata_root = unpack::<SMRoot>(&sb.metadata_sm_root[0..]).unwrap();
+
+        SBPanel::new(
+            BT::ERA_SUPERBLOCK,
+            era_sb_fields(&sb),
+            vec![
+                ("Writesets", sb.writeset_tree_root, TreeKind::Writesets),
+                (
+                    "Current writeset",
+                    sb.current_writeset.root,
+                    TreeKind::Array(ArrayKind::Writeset),
+                ),
+                (
+                    "Era array",
+                    sb.era_array_root,
+                    TreeKind::Array(ArrayKind::Eras),
+                ),
+                (
+                    "Metadata space map",
+                    metadata_root.bitmap_root,
+                    TreeKind::MetadataIndex,
+                ),
+                (
+                    "Metadata ref counts",
+                    metadata_root.ref_count_root,
+                    TreeKind::RefCounts,
+                ),
+            ],
+        )
+    }
+
+    fn title(&self) -> &'static str {
+        match self.kind {
+            BT::CACHE_SUPERBLOCK => "Cache Superblock",
+            BT::ERA_SUPERBLOCK => "Era Superblock",
+            _ => "Superblock",
+        }
+    }
+
+    fn child_action(&self, index: usize) -> Option<Action> {
+        let (_, root, kind) = self.roots.get(index)?;
+        Some(kind.root_action(*root))
     }
 }
 
 impl Panel for SBPanel {
     fn render(&mut self, area: Rect, f: &mut Frame) {
-        let w = SBWidget { sb: &self.sb };
+        let w = SBWidget {
+            title: self.title(),
+            fields: &self.fields,
+            names: self.roots.iter().map(|(name, _, _)| *name).collect(),
+        };
         f.render_stateful_widget(w, area, &mut self.state);
     }
 
     fn input(&mut self, k: Key) -> Option<Action> {
         match k {
             Key::Char('j') | Key::Down => {
-                ls_next(&mut self.state, 2);
+                ls_next(&mut self.state, self.roots.len());
                 None
             }
             Key::Char('k') | Key::Up => {
                 ls_previous(&mut self.state);
                 None
             }
-            Key::Char('l') | Key::Right => {
-                if self.state.selected().unwrap() == 0 {
-                    Some(PushDeviceDetail(self.sb.details_root))
-                } else {
-                    Some(PushTopLevel(self.sb.mapping_root))
-                }
-            }
+            Key::Char('l') | Key::Right => self.child_action(self.state.selected().unwrap()),
             Key::Char('h') | Key::Left => Some(PopPanel),
             _ => None,
         }
     }
 
     fn path_action(&mut self, child: u64) -> Option<Action> {
-        if child == self.sb.mapping_root {
-            Some(PushTopLevel(child))
-        } else if child == self.sb.details_root {
-            Some(PushDeviceDetail(child))
-        } else {
-            None
+        let index = self.roots.iter().position(|(_, root, _)| *root == child)?;
+        self.state.select(Some(index));
+        self.child_action(index)
+    }
+
+    fn describe(&self) -> Description {
+        Description {
+            title: self.title().to_string(),
+            fields: self.fields.clone(),
+            entries: Vec::new(),
         }
     }
+
+    fn location(&self) -> (u64, BT) {
+        (SUPERBLOCK_LOCATION, self.kind)
+    }
+}
+
+// Reads the superblock, whichever sort of metadata it belongs to.
+fn read_sb_panel(engine: &dyn IoEngine) -> Result<SBPanel> {
+    let b = engine.read(SUPERBLOCK_LOCATION)?;
+    match metadata_block_type(b.get_data()) {
+        BT::CACHE_SUPERBLOCK => Ok(SBPanel::cache(cache_sb::read_superblock(
+            engine,
+            SUPERBLOCK_LOCATION,
+        )?)),
+        BT::ERA_SUPERBLOCK => Ok(SBPanel::era(era_sb::read_superblock(
+            engine,
+            SUPERBLOCK_LOCATION,
+        )?)),
+        _ => Ok(SBPanel::thin(read_superblock(engine, SUPERBLOCK_LOCATION)?)),
+    }
 }
 
 //------------------------------------
 
 struct DeviceDetailPanel {
+    loc: u64,
     node: btree::Node<DeviceDetail>,
     nr_entries: usize,
     state: ListState,
 }
 
 impl DeviceDetailPanel {
-    fn new(node: btree::Node<DeviceDetail>) -> DeviceDetailPanel {
+    fn new(loc: u64, node: btree::Node<DeviceDetail>) -> DeviceDetailPanel {
         let nr_entries = node.get_header().nr_entries as usize;
         let mut state = ListState::default();
         state.select(Some(0));
 
         DeviceDetailPanel {
+            loc,
             node,
             nr_entries,
             state,
@@ -615,23 +1071,33 @@ impl Panel for DeviceDetailPanel {
             btree::Node::Leaf { .. } => None,
         }
     }
+
+    fn describe(&self) -> Description {
+        describe_node("Device Details".to_string(), &self.node)
+    }
+
+    fn location(&self) -> (u64, BT) {
+        (self.loc, BT::NODE)
+    }
 }
 
 //------------------------------------
 
 struct TopLevelPanel {
+    loc: u64,
     node: btree::Node<u64>,
     nr_entries: usize,
     state: ListState,
 }
 
 impl TopLevelPanel {
-    fn new(node: btree::Node<u64>) -> TopLevelPanel {
+    fn new(loc: u64, node: btree::Node<u64>) -> TopLevelPanel {
         let nr_entries = node.get_header().nr_entries as usize;
         let mut state = ListState::default();
         state.select(Some(0));
 
         TopLevelPanel {
+            loc,
             node,
             nr_entries,
             state,
@@ -698,9 +1164,18 @@ impl Panel for TopLevelPanel {
             }
         }
     }
+
+    fn describe(&self) -> Description {
+        describe_node("Top Level".to_string(), &self.node)
+    }
+
+    fn location(&self) -> (u64, BT) {
+        (self.loc, BT::NODE)
+    }
 }
 
 struct BottomLevelPanel {
+    loc: u64,
     thin_id: u32,
     node: btree::Node<BlockTime>,
     nr_entries: usize,
@@ -708,12 +1183,13 @@ struct BottomLevelPanel {
 }
 
 impl BottomLevelPanel {
-    fn new(thin_id: u32, node: btree::Node<BlockTime>) -> BottomLevelPanel {
+    fn new(loc: u64, thin_id: u32, node: btree::Node<BlockTime>) -> BottomLevelPanel {
         let nr_entries = node.get_header().nr_entries as usize;
         let mut state = ListState::default();
         state.select(Some(0));
 
         BottomLevelPanel {
+            loc,
             thin_id,
             node,
             nr_entries,
@@ -770,67 +1246,1045 @@ impl Panel for BottomLevelPanel {
             btree::Node::Leaf { .. } => None,
         }
     }
+
+    fn describe(&self) -> Description {
+        describe_node(format!("Thin dev #{}", self.thin_id), &self.node)
+    }
+
+    fn location(&self) -> (u64, BT) {
+        (self.loc, BT::NODE)
+    }
 }
 
 //------------------------------------
 
-fn perform_action(
-    panels: &mut Vec<Box<dyn Panel>>,
-    engine: &dyn IoEngine,
-    action: Action,
-) -> Result<()> {
-    match action {
-        PushDeviceDetail(b) => {
-            let node = read_node::<DeviceDetail>(engine, b)?;
-            panels.push(Box::new(DeviceDetailPanel::new(node)));
-        }
-        PushTopLevel(b) => {
-            let node = read_node::<u64>(engine, b)?;
-            panels.push(Box::new(TopLevelPanel::new(node)));
-        }
-        PushBottomLevel(thin_id, b) => {
-            let node = read_node::<BlockTime>(engine, b)?;
-            panels.push(Box::new(BottomLevelPanel::new(thin_id, node)));
-        }
-        PopPanel => {
-            if panels.len() > 1 {
-                panels.pop();
-            }
-        }
-    };
-    Ok(())
+// The metadata space map keeps its bitmap index in a single block,
+// rather than a btree.
+struct MetadataIndexPanel {
+    index: MetadataIndex,
+    state: ListState,
 }
 
-fn explore(path: &Path, node_path: Option<Vec<u64>>) -> Result<()> {
-    let engine = SyncIoEngine::new(path, false)?;
-
-    let mut panels: Vec<Box<dyn Panel>> = Vec::new();
+impl MetadataIndexPanel {
+    fn new(index: MetadataIndex) -> MetadataIndexPanel {
+        let mut state = ListState::default();
+        state.select(Some(0));
 
-    if let Some(path) = node_path {
-        eprintln!("using path: {:?}", path);
-        assert_eq!(path[0], 0);
-        let sb = read_superblock(&engine, path[0])?;
-        panels.push(Box::new(SBPanel::new(sb)));
-        for b in &path[1..] {
-            let action = panels.last_mut().unwrap().path_action(*b);
-            if let Some(action) = action {
-                perform_action(&mut panels, &engine, action)?;
-            } else {
-                return Err(anyhow!("bad node path: couldn't find child node {}", b));
-            }
-        }
-    } else {
-        let sb = read_superblock(&engine, 0)?;
-        panels.push(Box::new(SBPanel::new(sb)));
+        MetadataIndexPanel { index, state }
     }
+}
 
-    let events = Events::new();
+impl Panel for MetadataIndexPanel {
+    fn render(&mut self, area: Rect, f: &mut Frame) {
+        let desc = self.describe();
+        let w = DescriptionWidget { desc: &desc };
+        f.render_stateful_widget(w, area, &mut self.state);
+    }
 
-    let stdout = io::stdout();
-    let mut stdout = stdout.lock().into_raw_mode()?;
-    write!(stdout, "{}", termion::clear::All)?;
-    let backend = TermionBackend::new(stdout);
-    let mut terminal = Terminal::new(backend)?;
+    fn input(&mut self, k: Key) -> Option<Action> {
+        match k {
+            Key::Char('j') | Key::Down => {
+                ls_next(&mut self.state, self.index.indexes.len());
+                None
+            }
+            Key::Char('k') | Key::Up => {
+                ls_previous(&mut self.state);
+                None
+            }
+            Key::Char('l') | Key::Right => {
+                let i = self.state.selected().unwrap();
+                let ie = self.index.indexes.get(i)?;
+                Some(PushBitmap(ie.blocknr, (i * ENTRIES_PER_BITMAP) as u64))
+            }
+            Key::Char('h') | Key::Left => Some(PopPanel),
+            _ => None,
+        }
+    }
+
+    fn path_action(&mut self, child: u64) -> Option<Action> {
+        let i = self
+            .index
+            .indexes
+            .iter()
+            .position(|ie| ie.blocknr == child)?;
+        self.state.select(Some(i));
+        Some(PushBitmap(child, (i * ENTRIES_PER_BITMAP) as u64))
+    }
+
+    fn describe(&self) -> Description {
+        let keys: Vec<u64> = (0..self.index.indexes.len() as u64).collect();
+        Description {
+            title: "Metadata Space Map".to_string(),
+            fields: vec![
+                ("block".to_string(), format!("{}", self.index.blocknr)),
+                (
+                    "nr bitmaps".to_string(),
+                    format!("{}", self.index.indexes.len()),
+                ),
+            ],
+            entries: mk_entries(&keys, &self.index.indexes),
+        }
+    }
+
+    fn location(&self) -> (u64, BT) {
+        (self.index.blocknr, BT::INDEX)
+    }
+}
+
+//------------------------------------
+
+// The data space map indexes its bitmaps with a btree.
+struct IndexTreePanel {
+    loc: u64,
+    node: btree::Node<IndexEntry>,
+    nr_entries: usize,
+    state: ListState,
+}
+
+impl IndexTreePanel {
+    fn new(loc: u64, node: btree::Node<IndexEntry>) -> IndexTreePanel {
+        let nr_entries = node.get_header().nr_entries as usize;
+        let mut state = ListState::default();
+        state.select(Some(0));
+
+        IndexTreePanel {
+            loc,
+            node,
+            nr_entries,
+            state,
+        }
+    }
+
+    fn child_action(keys: &[u64], values: &[IndexEntry], index: usize) -> Action {
+        PushBitmap(
+            values[index].blocknr,
+            keys[index] * ENTRIES_PER_BITMAP as u64,
+        )
+    }
+}
+
+impl Panel for IndexTreePanel {
+    fn render(&mut self, area: Rect, f: &mut Frame) {
+        let w = NodeWidget {
+            title: "Data Space Map".to_string(),
+            node: &self.node,
+        };
+
+        f.render_stateful_widget(w, area, &mut self.state);
+    }
+
+    fn input(&mut self, k: Key) -> Option<Action> {
+        match k {
+            Key::Char('j') | Key::Down => {
+                ls_next(&mut self.state, self.nr_entries);
+                None
+            }
+            Key::Char('k') | Key::Up => {
+                ls_previous(&mut self.state);
+                None
+            }
+            Key::Char('l') | Key::Right => {
+                let index = self.state.selected().unwrap();
+                match &self.node {
+                    btree::Node::Internal { values, .. } => Some(PushIndexTree(values[index])),
+                    btree::Node::Leaf { keys, values, .. } => {
+                        Some(Self::child_action(keys, values, index))
+                    }
+                }
+            }
+            Key::Char('h') | Key::Left => Some(PopPanel),
+            _ => None,
+        }
+    }
+
+    fn path_action(&mut self, child: u64) -> Option<Action> {
+        match &self.node {
+            btree::Node::Internal { values, .. } => {
+                let i = values.iter().position(|v| *v == child)?;
+                self.state.select(Some(i));
+                Some(PushIndexTree(child))
+            }
+            btree::Node::Leaf { keys, values, .. } => {
+                let i = values.iter().position(|ie| ie.blocknr == child)?;
+                self.state.select(Some(i));
+                Some(Self::child_action(keys, values, i))
+            }
+        }
+    }
+
+    fn describe(&self) -> Description {
+        describe_node("Data Space Map".to_string(), &self.node)
+    }
+
+    fn location(&self) -> (u64, BT) {
+        (self.loc, BT::NODE)
+    }
+}
+
+//------------------------------------
+
+struct BitmapPanel {
+    loc: u64,
+    first: u64,
+    counts: Vec<RefCount>,
+    nr_runs: usize,
+    state: ListState,
+}
+
+impl BitmapPanel {
+    fn new(loc: u64, first: u64, bitmap: Bitmap) -> BitmapPanel {
+        let counts: Vec<RefCount> = bitmap.entries.iter().map(RefCount::from).collect();
+        let mut state = ListState::default();
+        state.select(Some(0));
+
+        let mut panel = BitmapPanel {
+            loc,
+            first,
+            counts,
+            nr_runs: 0,
+            state,
+        };
+        panel.nr_runs = panel.describe().entries.len();
+        panel
+    }
+}
+
+impl Panel for BitmapPanel {
+    fn render(&mut self, area: Rect, f: &mut Frame) {
+        let desc = self.describe();
+        let w = DescriptionWidget { desc: &desc };
+        f.render_stateful_widget(w, area, &mut self.state);
+    }
+
+    fn input(&mut self, k: Key) -> Option<Action> {
+        match k {
+            Key::Char('j') | Key::Down => {
+                ls_next(&mut self.state, self.nr_runs);
+                None
+            }
+            Key::Char('k') | Key::Up => {
+                ls_previous(&mut self.state);
+                None
+            }
+            Key::Char('h') | Key::Left => Some(PopPanel),
+            _ => None,
+        }
+    }
+
+    fn path_action(&mut self, _child: u64) -> Option<Action> {
+        None
+    }
+
+    fn describe(&self) -> Description {
+        let count = |rc: RefCount| self.counts.iter().filter(|c| **c == rc).count();
+        let keys: Vec<u64> = (self.first..(self.first + self.counts.len() as u64)).collect();
+
+        Description {
+            title: "Bitmap".to_string(),
+            fields: vec![
+                ("block".to_string(), format!("{}", self.loc)),
+                ("first entry".to_string(), format!("{}", self.first)),
+                ("free".to_string(), format!("{}", count(RefCount::Count(0)))),
+                (
+                    "count 1".to_string(),
+                    format!("{}", count(RefCount::Count(1))),
+                ),
+                (
+                    "count 2".to_string(),
+                    format!("{}", count(RefCount::Count(2))),
+                ),
+                (
+                    "overflow".to_string(),
+                    format!("{}", count(RefCount::Overflow)),
+                ),
+            ],
+            entries: mk_entries(&keys, &self.counts),
+        }
+    }
+
+    fn location(&self) -> (u64, BT) {
+        (self.loc, BT::BITMAP)
+    }
+}
+
+//------------------------------------
+
+// Holds the reference counts that don't fit in a bitmap entry
+struct RefCountPanel {
+    loc: u64,
+    node: btree::Node<RefCount>,
+    nr_entries: usize,
+    state: ListState,
+}
+
+impl RefCountPanel {
+    fn new(loc: u64, node: btree::Node<RefCount>) -> RefCountPanel {
+        let nr_entries = node.get_header().nr_entries as usize;
+        let mut state = ListState::default();
+        state.select(Some(0));
+
+        RefCountPanel {
+            loc,
+            node,
+            nr_entries,
+            state,
+        }
+    }
+}
+
+impl Panel for RefCountPanel {
+    fn render(&mut self, area: Rect, f: &mut Frame) {
+        let w = NodeWidget {
+            title: "Ref Counts".to_string(),
+            node: &self.node,
+        };
+
+        f.render_stateful_widget(w, area, &mut self.state);
+    }
+
+    fn input(&mut self, k: Key) -> Option<Action> {
+        match k {
+            Key::Char('j') | Key::Down => {
+                ls_next(&mut self.state, self.nr_entries);
+                None
+            }
+            Key::Char('k') | Key::Up => {
+                ls_previous(&mut self.state);
+                None
+            }
+            Key::Char('l') | Key::Right => match &self.node {
+                btree::Node::Internal { values, .. } => {
+                    Some(PushRefCounts(values[self.state.selected().unwrap()]))
+                }
+                btree::Node::Leaf { .. } => None,
+            },
+            Key::Char('h') | Key::Left => Some(PopPanel),
+            _ => None,
+        }
+    }
+
+    fn path_action(&mut self, child: u64) -> Option<Action> {
+        match &self.node {
+            btree::Node::Internal { values, .. } => {
+                let i = values.iter().position(|v| *v == child)?;
+                self.state.select(Some(i));
+                Some(PushRefCounts(child))
+            }
+            btree::Node::Leaf { .. } => None,
+        }
+    }
+
+    fn describe(&self) -> Description {
+        describe_node("Ref Counts".to_string(), &self.node)
+    }
+
+    fn location(&self) -> (u64, BT) {
+        (self.loc, BT::NODE)
+    }
+}
+
+//------------------------------------
+
+// The arrays found in cache and era metadata.  The bitsets are arrays
+// of u64 words.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum ArrayKind {
+    Mappings,
+    Hints,
+    Dirty,
+    Discard,
+    Writeset,
+    Eras,
+}
+
+impl ArrayKind {
+    fn title(&self) -> &'static str {
+        match self {
+            ArrayKind::Mappings => "Mappings",
+            ArrayKind::Hints => "Hints",
+            ArrayKind::Dirty => "Dirty Bits",
+            ArrayKind::Discard => "Discard Bits",
+            ArrayKind::Writeset => "Writeset",
+            ArrayKind::Eras => "Era Array",
+        }
+    }
+
+    fn is_bitset(&self) -> bool {
+        matches!(
+            self,
+            ArrayKind::Dirty | ArrayKind::Discard | ArrayKind::Writeset
+        )
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum ArrayValue {
+    Mapping(Mapping),
+    Raw(u64),
+    Bit(bool),
+    Era(u32),
+}
+
+impl fmt::Display for ArrayValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ArrayValue::Mapping(m) if !m.is_valid() => write!(f, "-"),
+            ArrayValue::Mapping(m) if m.is_dirty() => write!(f, "{} dirty", m.oblock),
+            ArrayValue::Mapping(m) => write!(f, "{}", m.oblock),
+            ArrayValue::Raw(v) => write!(f, "{:#x}", v),
+            ArrayValue::Bit(b) => write!(f, "{}", *b as u8),
+            ArrayValue::Era(era) => write!(f, "{}", era),
+        }
+    }
+}
+
+impl Adjacent for ArrayValue {
+    fn adjacent(&self, rhs: &Self) -> bool {
+        match (self, rhs) {
+            (ArrayValue::Mapping(lhs), ArrayValue::Mapping(rhs))
+                if lhs.is_valid() && lhs.flags == rhs.flags =>
+            {
+                lhs.oblock + 1 == rhs.oblock
+            }
+            _ => self == rhs,
+        }
+    }
+}
+
+// Hints wider than 8 bytes only have their first 8 bytes shown.
+fn decode_array_block(
+    kind: ArrayKind,
+    first: u64,
+    hdr: &ArrayBlockHeader,
+    data: &[u8],
+) -> (Vec<u64>, Vec<ArrayValue>) {
+    let value_size = hdr.value_size as usize;
+    let values_begin = ArrayBlockHeader::disk_size() as usize;
+    let mut keys = Vec::new();
+    let mut values = Vec::new();
+    if value_size == 0 {
+        return (keys, values);
+    }
+
+    let nr_entries = std::cmp::min(
+        hdr.nr_entries as usize,
+        (data.len() - values_begin) / value_size,
+    );
+    for i in 0..nr_entries {
+        let offset = values_begin + i * value_size;
+        let v = le_field(data, offset, std::cmp::min(value_size, 8));
+        let index = first + i as u64;
+        match kind {
+            ArrayKind::Mappings => {
+                let m = unpack::<Mapping>(&v.to_le_bytes()).unwrap();
+                keys.push(index);
+                values.push(ArrayValue::Mapping(m));
+            }
+            ArrayKind::Hints => {
+                keys.push(index);
+                values.push(ArrayValue::Raw(v));
+            }
+            ArrayKind::Eras => {
+                keys.push(index);
+                values.push(ArrayValue::Era(v as u32));
+            }
+            _ => {
+                for bit in 0..64 {
+                    keys.push(index * 64 + bit);
+                    values.push(ArrayValue::Bit((v >> bit) & 1 == 1));
+                }
+            }
+        }
+    }
+
+    (keys, values)
+}
+
+//------------------------------------
+
+// An array is a btree mapping the index of each array block to its
+// location.
+struct ArrayTreePanel {
+    loc: u64,
+    kind: ArrayKind,
+    node: btree::Node<u64>,
+    nr_entries: usize,
+    state: ListState,
+}
+
+impl ArrayTreePanel {
+    fn new(loc: u64, kind: ArrayKind, node: btree::Node<u64>) -> ArrayTreePanel {
+        let nr_entries = node.get_header().nr_entries as usize;
+        let mut state = ListState::default();
+        state.select(Some(0));
+
+        ArrayTreePanel {
+            loc,
+            kind,
+            node,
+            nr_entries,
+            state,
+        }
+    }
+
+    fn child_action(&self, index: usize) -> Action {
+        match &self.node {
+            btree::Node::Internal { values, .. } => PushArray(values[index], self.kind),
+            btree::Node::Leaf { keys, values, .. } => {
+                PushArrayBlock(values[index], self.kind, keys[index])
+            }
+        }
+    }
+}
+
+impl Panel for ArrayTreePanel {
+    fn render(&mut self, area: Rect, f: &mut Frame) {
+        let w = NodeWidget {
+            title: self.kind.title().to_string(),
+            node: &self.node,
+        };
+
+        f.render_stateful_widget(w, area, &mut self.state);
+    }
+
+    fn input(&mut self, k: Key) -> Option<Action> {
+        match k {
+            Key::Char('j') | Key::Down => {
+                ls_next(&mut self.state, self.nr_entries);
+                None
+            }
+            Key::Char('k') | Key::Up => {
+                ls_previous(&mut self.state);
+                None
+            }
+            Key::Char('l') | Key::Right if self.nr_entries > 0 => {
+                Some(self.child_action(self.state.selected().unwrap()))
+            }
+            Key::Char('h') | Key::Left => Some(PopPanel),
+            _ => None,
+        }
+    }
+
+    fn path_action(&mut self, child: u64) -> Option<Action> {
+        let values = match &self.node {
+            btree::Node::Internal { values, .. } => values,
+            btree::Node::Leaf { values, .. } => values,
+        };
+        let i = values.iter().position(|v| *v == child)?;
+        self.state.select(Some(i));
+        Some(self.child_action(i))
+    }
+
+    fn describe(&self) -> Description {
+        describe_node(self.kind.title().to_string(), &self.node)
+    }
+
+    fn location(&self) -> (u64, BT) {
+        (self.loc, BT::NODE)
+    }
+}
+
+//------------------------------------
+
+struct ArrayBlockPanel {
+    loc: u64,
+    kind: ArrayKind,
+    hdr: ArrayBlockHeader,
+    first: u64,
+    keys: Vec<u64>,
+    values: Vec<ArrayValue>,
+    nr_runs: usize,
+    state: ListState,
+}
+
+impl ArrayBlockPanel {
+    fn new(loc: u64, kind: ArrayKind, index: u64, data: &[u8]) -> Result<ArrayBlockPanel> {
+        let hdr = unpack::<ArrayBlockHeader>(data)?;
+        let first = index * hdr.max_entries as u64;
+        let (keys, values) = decode_array_block(kind, first, &hdr, data);
+        let nr_runs = mk_runs(&keys, &values).len();
+        let mut state = ListState::default();
+        state.select(Some(0));
+
+        Ok(ArrayBlockPanel {
+            loc,
+            kind,
+            hdr,
+            first,
+            keys,
+            values,
+            nr_runs,
+            state,
+        })
+    }
+}
+
+impl Panel for ArrayBlockPanel {
+    fn render(&mut self, area: Rect, f: &mut Frame) {
+        let desc = self.describe();
+        let w = DescriptionWidget { desc: &desc };
+        f.render_stateful_widget(w, area, &mut self.state);
+    }
+
+    fn input(&mut self, k: Key) -> Option<Action> {
+        match k {
+            Key::Char('j') | Key::Down => {
+                ls_next(&mut self.state, self.nr_runs);
+                None
+            }
+            Key::Char('k') | Key::Up => {
+                ls_previous(&mut self.state);
+                None
+            }
+            Key::Char('h') | Key::Left => Some(PopPanel),
+            _ => None,
+        }
+    }
+
+    fn path_action(&mut self, _child: u64) -> Option<Action> {
+        None
+    }
+
+    fn describe(&self) -> Description {
+        let first = if self.kind.is_bitset() {
+            format!("bit {}", self.first * 64)
+        } else {
+            format!("{}", self.first)
+        };
+
+        Description {
+            title: format!("{} Block", self.kind.title()),
+            fields: vec![
+                ("block".to_string(), format!("{}", self.hdr.blocknr)),
+                ("first entry".to_string(), first),
+                ("nr_entries".to_string(), format!("{}", self.hdr.nr_entries)),
+                (
+                    "max_entries".to_string(),
+                    format!("{}", self.hdr.max_entries),
+                ),
+                ("value size".to_string(), format!("{}", self.hdr.value_size)),
+            ],
+            entries: mk_entries(&self.keys, &self.values),
+        }
+    }
+
+    fn location(&self) -> (u64, BT) {
+        (self.loc, BT::ARRAY)
+    }
+}
+
+//------------------------------------
+
+// Era metadata keeps a bitset for each of the archived eras.
+struct WritesetPanel {
+    loc: u64,
+    node: btree::Node<Writeset>,
+    nr_entries: usize,
+    state: ListState,
+}
+
+impl WritesetPanel {
+    fn new(loc: u64, node: btree::Node<Writeset>) -> WritesetPanel {
+        let nr_entries = node.get_header().nr_entries as usize;
+        let mut state = ListState::default();
+        state.select(Some(0));
+
+        WritesetPanel {
+            loc,
+            node,
+            nr_entries,
+            state,
+        }
+    }
+}
+
+impl Panel for WritesetPanel {
+    fn render(&mut self, area: Rect, f: &mut Frame) {
+        let w = NodeWidget {
+            title: "Writesets".to_string(),
+            node: &self.node,
+        };
+
+        f.render_stateful_widget(w, area, &mut self.state);
+    }
+
+    fn input(&mut self, k: Key) -> Option<Action> {
+        match k {
+            Key::Char('j') | Key::Down => {
+                ls_next(&mut self.state, self.nr_entries);
+                None
+            }
+            Key::Char('k') | Key::Up => {
+                ls_previous(&mut self.state);
+                None
+            }
+            Key::Char('l') | Key::Right => {
+                let index = self.state.selected().unwrap();
+                match &self.node {
+                    btree::Node::Internal { values, .. } => {
+                        Some(PushWritesets(*values.get(index)?))
+                    }
+                    btree::Node::Leaf { values, .. } => {
+                        Some(PushArray(values.get(index)?.root, ArrayKind::Writeset))
+                    }
+                }
+            }
+            Key::Char('h') | Key::Left => Some(PopPanel),
+            _ => None,
+        }
+    }
+
+    fn path_action(&mut self, child: u64) -> Option<Action> {
+        match &self.node {
+            btree::Node::Internal { values, .. } => {
+                let i = values.iter().position(|v| *v == child)?;
+                self.state.select(Some(i));
+                Some(PushWritesets(child))
+            }
+            btree::Node::Leaf { values, .. } => {
+                let i = values.iter().position(|ws| ws.root == child)?;
+                self.state.select(Some(i));
+                Some(PushArray(child, ArrayKind::Writeset))
+            }
+        }
+    }
+
+    fn describe(&self) -> Description {
+        describe_node("Writesets".to_string(), &self.node)
+    }
+
+    fn location(&self) -> (u64, BT) {
+        (self.loc, BT::NODE)
+    }
+}
+
+//------------------------------------
+
+const HEX_ROW: usize = 16;
+
+// Shows the raw bytes of the block under the previous panel.  Bytes can
+// only be changed if the metadata was opened with --edit, and nothing
+// reaches the disk until the block is written with 'w', and the list of
+// changed bytes is confirmed with 'y'.
+struct HexPanel {
+    loc: u64,
+    kind: BT,
+    data: Vec<u8>,
+    orig: Vec<u8>,
+    editable: bool,
+    confirming: bool,
+    cursor: usize,
+    low_nibble: bool,
+    state: ListState,
+}
+
+impl HexPanel {
+    fn new(loc: u64, kind: BT, data: Vec<u8>, editable: bool) -> HexPanel {
+        let mut state = ListState::default();
+        state.select(Some(0));
+
+        HexPanel {
+            loc,
+            kind,
+            orig: data.clone(),
+            data,
+            editable,
+            confirming: false,
+            cursor: 0,
+            low_nibble: false,
+            state,
+        }
+    }
+
+    fn move_cursor(&mut self, delta: isize) {
+        let cursor = self.cursor as isize + delta;
+        if cursor >= 0 && (cursor as usize) < self.data.len() {
+            self.cursor = cursor as usize;
+            self.low_nibble = false;
+            self.state.select(Some(self.cursor / HEX_ROW));
+        }
+    }
+
+    fn set_nibble(&mut self, nibble: u8) {
+        let b = &mut self.data[self.cursor];
+        if self.low_nibble {
+            *b = (*b & 0xf0) | nibble;
+            self.move_cursor(1);
+        } else {
+            *b = (*b & 0x0f) | (nibble << 4);
+            self.low_nibble = true;
+        }
+    }
+
+    fn modified(&self) -> bool {
+        self.data != self.orig
+    }
+
+    // The offset, old and new value of each byte that has been changed
+    fn changes(&self) -> Vec<(usize, u8, u8)> {
+        self.orig
+            .iter()
+            .zip(&self.data)
+            .enumerate()
+            .filter(|(_, (old, new))| old != new)
+            .map(|(offset, (old, new))| (offset, *old, *new))
+            .collect()
+    }
+
+    fn render_confirm(&self, area: Rect, f: &mut Frame) {
+        let changes = self.changes();
+        let items: Vec<ListItem> = changes
+            .iter()
+            .map(|(offset, old, new)| {
+                ListItem::new(Span::raw(format!(
+                    "{:04x}: {:02x} -> {:02x}",
+                    offset, old, new
+                )))
+            })
+            .collect();
+
+        let title = format!(
+            "Write {} changed bytes, and a new checksum, to block {}? (y/n)",
+            changes.len(),
+            self.loc
+        );
+        let items = List::new(items).block(Block::default().borders(Borders::ALL).title(title));
+        f.render_widget(items, area);
+    }
+
+    fn title(&self) -> String {
+        let mut title = format!(
+            "Block {} ({:?}), offset {}",
+            self.loc, self.kind, self.cursor
+        );
+        if self.modified() {
+            title.push_str(" [modified]");
+        }
+        title
+    }
+
+    fn row(&self, row: usize) -> String {
+        let begin = row * HEX_ROW;
+        let bytes: Vec<String> = self.data[begin..(begin + HEX_ROW)]
+            .iter()
+            .enumerate()
+            .map(|(i, b)| {
+                if begin + i == self.cursor {
+                    format!("[{:02x}]", b)
+                } else {
+                    format!(" {:02x} ", b)
+                }
+            })
+            .collect();
+        format!("{:04x}:{}", begin, bytes.join(""))
+    }
+}
+
+impl Panel for HexPanel {
+    fn render(&mut self, area: Rect, f: &mut Frame) {
+        if self.confirming {
+            self.render_confirm(area, f);
+            return;
+        }
+
+        let items: Vec<ListItem> = (0..(self.data.len() / HEX_ROW))
+            .map(|row| ListItem::new(Span::raw(self.row(row))))
+            .collect();
+
+        let items = List::new(items)
+            .block(Block::default().borders(Borders::ALL).title(self.title()))
+            .highlight_style(
+                Style::default()
+                    .bg(Color::LightGreen)
+                    .add_modifier(Modifier::BOLD),
+            );
+
+        f.render_stateful_widget(items, area, &mut self.state);
+    }
+
+    fn input(&mut self, k: Key) -> Option<Action> {
+        // Any key but 'y' backs out of the write
+        if self.confirming {
+            self.confirming = false;
+            if k == Key::Char('y') {
+                self.orig = self.data.clone();
+                return Some(WriteBlock(self.loc, self.kind, self.data.clone()));
+            }
+            return None;
+        }
+
+        match k {
+            Key::Char('j') | Key::Down => {
+                self.move_cursor(HEX_ROW as isize);
+                None
+            }
+            Key::Char('k') | Key::Up => {
+                self.move_cursor(-(HEX_ROW as isize));
+                None
+            }
+            Key::Left => {
+                self.move_cursor(-1);
+                None
+            }
+            Key::Right => {
+                self.move_cursor(1);
+                None
+            }
+            Key::Char(c) if self.editable && c.is_ascii_hexdigit() => {
+                self.set_nibble(c.to_digit(16).unwrap() as u8);
+                None
+            }
+            Key::Char('w') if self.editable && self.modified() => {
+                self.confirming = true;
+                None
+            }
+            Key::Char('h') | Key::Esc => Some(PopPanel),
+            _ => None,
+        }
+    }
+
+    fn path_action(&mut self, _child: u64) -> Option<Action> {
+        None
+    }
+
+    fn describe(&self) -> Description {
+        Description {
+            title: self.title(),
+            fields: Vec::new(),
+            entries: Vec::new(),
+        }
+    }
+
+    fn location(&self) -> (u64, BT) {
+        (self.loc, self.kind)
+    }
+}
+
+//------------------------------------
+
+fn read_block(engine: &dyn IoEngine, loc: u64) -> Result<Vec<u8>> {
+    Ok(engine.read(loc)?.get_data().to_vec())
+}
+
+// Recomputes the checksum, so the kernel and the other tools will
+// accept the edited block.
+fn write_block(engine: &dyn IoEngine, loc: u64, kind: BT, mut data: Vec<u8>) -> Result<()> {
+    write_checksum(&mut data, kind)?;
+    let b = crate::io_engine::Block::new(loc);
+    b.get_data().copy_from_slice(&data);
+    engine.write(&b)?;
+    Ok(())
+}
+
+fn perform_action(
+    panels: &mut Vec<Box<dyn Panel>>,
+    engine: &dyn IoEngine,
+    action: Action,
+) -> Result<()> {
+    match action {
+        PushDeviceDetail(b) => {
+            let node = read_node::<DeviceDetail>(engine, b)?;
+            panels.push(Box::new(DeviceDetailPanel::new(b, node)));
+        }
+        PushTopLevel(b) => {
+            let node = read_node::<u64>(engine, b)?;
+            panels.push(Box::new(TopLevelPanel::new(b, node)));
+        }
+        PushBottomLevel(thin_id, b) => {
+            let node = read_node::<BlockTime>(engine, b)?;
+            panels.push(Box::new(BottomLevelPanel::new(b, thin_id, node)));
+        }
+        PushMetadataIndex(b) => {
+            let index = unpack::<MetadataIndex>(engine.read(b)?.get_data())?;
+            panels.push(Box::new(MetadataIndexPanel::new(index)));
+        }
+        PushIndexTree(b) => {
+            let node = read_node::<IndexEntry>(engine, b)?;
+            panels.push(Box::new(IndexTreePanel::new(b, node)));
+        }
+        PushBitmap(b, first) => {
+            let bitmap = unpack::<Bitmap>(engine.read(b)?.get_data())?;
+            panels.push(Box::new(BitmapPanel::new(b, first, bitmap)));
+        }
+        PushRefCounts(b) => {
+            let node = read_node::<RefCount>(engine, b)?;
+            panels.push(Box::new(RefCountPanel::new(b, node)));
+        }
+        PushArray(b, kind) => {
+            let node = read_node::<u64>(engine, b)?;
+            panels.push(Box::new(ArrayTreePanel::new(b, kind, node)));
+        }
+        PushArrayBlock(b, kind, index) => {
+            let data = read_block(engine, b)?;
+            panels.push(Box::new(ArrayBlockPanel::new(b, kind, index, &data)?));
+        }
+        PushWritesets(b) => {
+            let node = read_node::<Writeset>(engine, b)?;
+            panels.push(Box::new(WritesetPanel::new(b, node)));
+        }
+        WriteBlock(b, kind, data) => {
+            write_block(engine, b, kind, data)?;
+        }
+        PopPanel => {
+            if panels.len() > 1 {
+                panels.pop();
+            }
+        }
+    };
+    Ok(())
+}
+
+fn open_engine(path: &Path, edit: bool) -> Result<Arc<dyn IoEngine + Send + Sync>> {
+    let engine_opts = EngineOptions {
+        tool: ToolType::Thin,
+        engine_type: EngineType::Sync,
+        use_metadata_snap: false,
+        overlay: None,
+        trace: None,
+        io_stats: None,
+        block_cache: None,
+        report: None,
+    };
+    EngineBuilder::new(path, &engine_opts).write(edit).build()
+}
+
+// Opens a panel for each node along the path, starting at the superblock.
+fn follow_path(engine: &dyn IoEngine, path: &[u64]) -> Result<Vec<Box<dyn Panel>>> {
+    if path.first() != Some(&SUPERBLOCK_LOCATION) {
+        return Err(anyhow!("bad node path: it doesn't start at the superblock"));
+    }
+
+    let mut panels: Vec<Box<dyn Panel>> = vec![Box::new(read_sb_panel(engine)?)];
+    for b in &path[1..] {
+        let action = panels.last_mut().unwrap().path_action(*b);
+        if let Some(action) = action {
+            perform_action(&mut panels, engine, action)?;
+        } else {
+            return Err(anyhow!("bad node path: couldn't find child node {}", b));
+        }
+    }
+
+    Ok(panels)
+}
+
+fn explore(path: &Path, node_path: Option<Vec<u64>>, edit: bool) -> Result<()> {
+    let engine = open_engine(path, edit)?;
+    let engine = engine.as_ref();
+
+    let mut panels = if let Some(path) = node_path {
+        eprintln!("using path: {:?}", path);
+        follow_path(engine, &path)?
+    } else {
+        follow_path(engine, &[SUPERBLOCK_LOCATION])?
+    };
+
+    let events = Events::new();
+
+    let stdout = io::stdout();
+    let mut stdout = stdout.lock().into_raw_mode()?;
+    write!(stdout, "{}", termion::clear::All)?;
+    let backend = TermionBackend::new(stdout);
+    let mut terminal = Terminal::new(backend)?;
 
     'main: loop {
         let render_panels = |f: &mut Frame| {
@@ -858,9 +2312,14 @@ fn explore(path: &Path, node_path: Option<Vec<u64>>) -> Result<()> {
         if let Event::Input(key) = events.next()? {
             match key {
                 Key::Char('q') => break 'main,
+                Key::Char('x') => {
+                    let (loc, kind) = active_panel.location();
+                    let data = read_block(engine, loc)?;
+                    panels.push(Box::new(HexPanel::new(loc, kind, data, edit)));
+                }
                 _ => {
                     if let Some(action) = active_panel.input(key) {
-                        perform_action(&mut panels, &engine, action)?;
+                        perform_action(&mut panels, engine, action)?;
                     }
                 }
             }
@@ -872,6 +2331,626 @@ fn explore(path: &Path, node_path: Option<Vec<u64>>) -> Result<()> {
     Ok(())
 }
 
+//------------------------------------
+// Non-interactive queries
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum QueryFormat {
+    Text,
+    Json,
+}
+
+enum Query {
+    Block(u64),
+    NodePath(Vec<u64>),
+    FindThin(u32, u64),
+    FindData(u64),
+    Superblock,
+    Devices,
+    Edit(u64, Vec<Edit>),
+}
+
+enum Edit {
+    Field(String, u64),
+    Bytes(usize, Vec<u8>),
+}
+
+#[derive(Clone, Copy)]
+enum TreeKind {
+    Details,
+    TopLevel,
+    BottomLevel,
+    MetadataIndex,
+    IndexTree,
+    Bitmap,
+    RefCounts,
+    Array(ArrayKind),
+    ArrayBlock,
+    Writesets,
+}
+
+impl TreeKind {
+    // Opens the tree when it hangs off the superblock.
+    fn root_action(&self, root: u64) -> Action {
+        match self {
+            TreeKind::Details => PushDeviceDetail(root),
+            TreeKind::TopLevel => PushTopLevel(root),
+            TreeKind::MetadataIndex => PushMetadataIndex(root),
+            TreeKind::IndexTree => PushIndexTree(root),
+            TreeKind::RefCounts => PushRefCounts(root),
+            TreeKind::Array(kind) => PushArray(root, *kind),
+            TreeKind::Writesets => PushWritesets(root),
+            TreeKind::BottomLevel | TreeKind::Bitmap | TreeKind::ArrayBlock => {
+                unreachable!("not the root of a tree")
+            }
+        }
+    }
+}
+
+// Depth first search for the node at 'target', leaving its node path
+// in 'path'.  Shared subtrees are only searched once, and nodes that
+// can't be read are skipped since damaged metadata is the usual reason
+// for looking.
+fn search_tree(
+    engine: &dyn IoEngine,
+    seen: &mut BTreeSet<u64>,
+    path: &mut Vec<u64>,
+    root: u64,
+    kind: TreeKind,
+    target: u64,
+) -> bool {
+    path.push(root);
+    if root == target {
+        return true;
+    }
+
+    if seen.insert(root) {
+        let children: Vec<(u64, TreeKind)> = match kind {
+            TreeKind::Details => match read_node::<DeviceDetail>(engine, root) {
+                Ok(btree::Node::Internal { values, .. }) => {
+                    values.iter().map(|v| (*v, TreeKind::Details)).collect()
+                }
+                _ => Vec::new(),
+            },
+            TreeKind::TopLevel => match read_node::<u64>(engine, root) {
+                Ok(btree::Node::Internal { values, .. }) => {
+                    values.iter().map(|v| (*v, TreeKind::TopLevel)).collect()
+                }
+                Ok(btree::Node::Leaf { values, .. }) => {
+                    values.iter().map(|v| (*v, TreeKind::BottomLevel)).collect()
+                }
+                Err(_) => Vec::new(),
+            },
+            TreeKind::BottomLevel => match read_node::<BlockTime>(engine, root) {
+                Ok(btree::Node::Internal { values, .. }) => {
+                    values.iter().map(|v| (*v, TreeKind::BottomLevel)).collect()
+                }
+                _ => Vec::new(),
+            },
+            TreeKind::MetadataIndex => match engine
+                .read(root)
+                .map_err(anyhow::Error::from)
+                .and_then(|b| Ok(unpack::<MetadataIndex>(b.get_data())?))
+            {
+                Ok(index) => index
+                    .indexes
+                    .iter()
+                    .map(|ie| (ie.blocknr, TreeKind::Bitmap))
+                    .collect(),
+                Err(_) => Vec::new(),
+            },
+            TreeKind::IndexTree => match read_node::<IndexEntry>(engine, root) {
+                Ok(btree::Node::Internal { values, .. }) => {
+                    values.iter().map(|v| (*v, TreeKind::IndexTree)).collect()
+                }
+                Ok(btree::Node::Leaf { values, .. }) => values
+                    .iter()
+                    .map(|ie| (ie.blocknr, TreeKind::Bitmap))
+                    .collect(),
+                Err(_) => Vec::new(),
+            },
+            TreeKind::Bitmap | TreeKind::ArrayBlock => Vec::new(),
+            TreeKind::Array(_) => match read_node::<u64>(engine, root) {
+                Ok(btree::Node::Internal { values, .. }) => {
+                    values.iter().map(|v| (*v, kind)).collect()
+                }
+                Ok(btree::Node::Leaf { values, .. }) => {
+                    values.iter().map(|v| (*v, TreeKind::ArrayBlock)).collect()
+                }
+                Err(_) => Vec::new(),
+            },
+            TreeKind::Writesets => match read_node::<Writeset>(engine, root) {
+                Ok(btree::Node::Internal { values, .. }) => {
+                    values.iter().map(|v| (*v, TreeKind::Writesets)).collect()
+                }
+                Ok(btree::Node::Leaf { values, .. }) => values
+                    .iter()
+                    .map(|ws| (ws.root, TreeKind::Array(ArrayKind::Writeset)))
+                    .collect(),
+                Err(_) => Vec::new(),
+            },
+            TreeKind::RefCounts => match read_node::<RefCount>(engine, root) {
+                Ok(btree::Node::Internal { values, .. }) => {
+                    values.iter().map(|v| (*v, TreeKind::RefCounts)).collect()
+                }
+                _ => Vec::new(),
+            },
+        };
+
+        for (child, kind) in children {
+            if search_tree(engine, seen, path, child, kind, target) {
+                return true;
+            }
+        }
+    }
+
+    path.pop();
+    false
+}
+
+fn locate_node(engine: &dyn IoEngine, loc: u64) -> Option<Vec<u64>> {
+    let mut seen = BTreeSet::new();
+    let mut path = vec![SUPERBLOCK_LOCATION];
+    if loc == SUPERBLOCK_LOCATION {
+        return Some(path);
+    }
+
+    let sb = read_sb_panel(engine).ok()?;
+    for (_, root, kind) in sb.roots {
+        if search_tree(engine, &mut seen, &mut path, root, kind, loc) {
+            return Some(path);
+        }
+    }
+
+    None
+}
+
+fn describe_path(engine: &dyn IoEngine, path: &[u64]) -> Result<Description> {
+    let panels = follow_path(engine, path)?;
+    let mut desc = panels.last().unwrap().describe();
+    desc.fields
+        .push(("node path".to_string(), btree_error::encode_node_path(path)));
+    Ok(desc)
+}
+
+fn describe_block(engine: &dyn IoEngine, loc: u64) -> Result<Description> {
+    match locate_node(engine, loc) {
+        Some(path) => describe_path(engine, &path),
+        None => describe_unreachable(engine, loc),
+    }
+}
+
+// Without a path we don't know what the values are, so they're
+// decoded by size alone.
+fn describe_unreachable(engine: &dyn IoEngine, loc: u64) -> Result<Description> {
+    let title = format!("Unreachable node {}", loc);
+    if let Ok(node) = read_node::<DeviceDetail>(engine, loc) {
+        Ok(describe_node(title, &node))
+    } else if let Ok(node) = read_node::<u64>(engine, loc) {
+        Ok(describe_node(title, &node))
+    } else {
+        Err(anyhow!("block {} is not a btree node", loc))
+    }
+}
+
+// Follows the btree down to the entry for 'key', extending the node path
+// as it goes.
+fn lookup<V: Unpack + Copy>(
+    engine: &dyn IoEngine,
+    path: &mut Vec<u64>,
+    root: u64,
+    key: u64,
+) -> Result<Option<V>> {
+    let mut loc = root;
+    loop {
+        path.push(loc);
+        match read_node::<V>(engine, loc)? {
+            btree::Node::Internal { keys, values, .. } => {
+                match keys.iter().rposition(|k| *k <= key) {
+                    Some(i) => loc = values[i],
+                    None => return Ok(None),
+                }
+            }
+            btree::Node::Leaf { keys, values, .. } => {
+                return Ok(keys.iter().position(|k| *k == key).map(|i| values[i]));
+            }
+        }
+    }
+}
+
+fn find_thin(
+    engine: &dyn IoEngine,
+    sb: &Superblock,
+    thin_id: u32,
+    thin_block: u64,
+) -> Result<Description> {
+    let mut path = vec![SUPERBLOCK_LOCATION];
+    let root = lookup::<u64>(engine, &mut path, sb.mapping_root, thin_id as u64)?
+        .ok_or_else(|| anyhow!("couldn't find thin device {}", thin_id))?;
+    let mapping = lookup::<BlockTime>(engine, &mut path, root, thin_block)?;
+
+    let mut desc = describe_path(engine, &path)?;
+    let mut fields = vec![
+        ("thin id".to_string(), format!("{}", thin_id)),
+        ("thin block".to_string(), format!("{}", thin_block)),
+        (
+            "mapped to".to_string(),
+            mapping.map_or_else(|| "-".to_string(), |bt| bt.to_string()),
+        ),
+    ];
+    fields.append(&mut desc.fields);
+    desc.fields = fields;
+    Ok(desc)
+}
+
+// Visits every mapping, along with the node path of the leaf holding it.
+fn walk_mappings<F>(
+    engine: &dyn IoEngine,
+    path: &mut Vec<u64>,
+    root: u64,
+    thin_id: Option<u32>,
+    visit: &mut F,
+) -> Result<()>
+where
+    F: FnMut(u32, u64, &BlockTime, &[u64]),
+{
+    path.push(root);
+    match thin_id {
+        None => match read_node::<u64>(engine, root)? {
+            btree::Node::Internal { values, .. } => {
+                for v in values {
+                    walk_mappings(engine, path, v, None, visit)?;
+                }
+            }
+            btree::Node::Leaf { keys, values, .. } => {
+                for (k, v) in keys.iter().zip(values) {
+                    walk_mappings(engine, path, v, Some(*k as u32), visit)?;
+                }
+            }
+        },
+        Some(thin_id) => match read_node::<BlockTime>(engine, root)? {
+            btree::Node::Internal { values, .. } => {
+                for v in values {
+                    walk_mappings(engine, path, v, Some(thin_id), visit)?;
+                }
+            }
+            btree::Node::Leaf { keys, values, .. } => {
+                for (k, v) in keys.iter().zip(values.iter()) {
+                    visit(thin_id, *k, v, path);
+                }
+            }
+        },
+    }
+    path.pop();
+    Ok(())
+}
+
+fn find_data(engine: &dyn IoEngine, sb: &Superblock, data_block: u64) -> Result<Description> {
+    let mut entries = Vec::new();
+    let mut path = vec![SUPERBLOCK_LOCATION];
+    walk_mappings(
+        engine,
+        &mut path,
+        sb.mapping_root,
+        None,
+        &mut |thin_id, thin_block, bt, path| {
+            if bt.block == data_block {
+                entries.push(Entry {
+                    key: thin_id as u64,
+                    len: 1,
+                    value: format!(
+                        "{} @ {}, leaf {}, node path {}",
+                        thin_block,
+                        bt.time,
+                        path.last().unwrap(),
+                        btree_error::encode_node_path(path)
+                    ),
+                });
+            }
+        },
+    )?;
+
+    Ok(Description {
+        title: format!("Data block {}", data_block),
+        fields: vec![("nr mappings".to_string(), format!("{}", entries.len()))],
+        entries,
+    })
+}
+
+fn describe_devices(engine: &dyn IoEngine, sb: &Superblock) -> Result<Description> {
+    let details = btree_to_map::<DeviceDetail>(
+        &mut vec![SUPERBLOCK_LOCATION],
+        engine,
+        true,
+        sb.details_root,
+    )?;
+    let entries: Vec<Entry> = details
+        .iter()
+        .map(|(thin_id, detail)| Entry {
+            key: *thin_id,
+            len: 1,
+            value: detail.to_string(),
+        })
+        .collect();
+
+    Ok(Description {
+        title: "Device Details".to_string(),
+        fields: vec![("nr devices".to_string(), format!("{}", entries.len()))],
+        entries,
+    })
+}
+
+//------------------------------------
+// Editing
+
+// Offsets and widths of the named superblock fields
+const SB_FIELDS: [(&str, usize, usize); 20] = [
+    ("flags", 4, 4),
+    ("block", 8, 8),
+    ("magic", 32, 8),
+    ("version", 40, 4),
+    ("time", 44, 4),
+    ("transaction_id", 48, 8),
+    ("metadata_snap", 56, 8),
+    ("data_nr_blocks", 64, 8),
+    ("data_nr_allocated", 72, 8),
+    ("data_bitmap_root", 80, 8),
+    ("data_ref_count_root", 88, 8),
+    ("metadata_nr_blocks", 192, 8),
+    ("metadata_nr_allocated", 200, 8),
+    ("metadata_bitmap_root", 208, 8),
+    ("metadata_ref_count_root", 216, 8),
+    ("mapping_root", 320, 8),
+    ("details_root", 328, 8),
+    ("data_block_size", 336, 4),
+    ("metadata_block_size", 340, 4),
+    ("nr_metadata_blocks", 344, 8),
+];
+
+const NODE_FIELDS: [(&str, usize, usize); 5] = [
+    ("flags", 4, 4),
+    ("block", 8, 8),
+    ("nr_entries", 16, 4),
+    ("max_entries", 20, 4),
+    ("value_size", 24, 4),
+];
+
+const ARRAY_FIELDS: [(&str, usize, usize); 4] = [
+    ("max_entries", 4, 4),
+    ("nr_entries", 8, 4),
+    ("value_size", 12, 4),
+    ("block", 16, 8),
+];
+
+fn le_field(data: &[u8], offset: usize, width: usize) -> u64 {
+    let mut v = 0;
+    for (i, b) in data[offset..(offset + width)].iter().enumerate() {
+        v |= (*b as u64) << (8 * i);
+    }
+    v
+}
+
+// Splits 'key[3]' into 'key' and 3
+fn parse_indexed(name: &str) -> Option<(&str, usize)> {
+    let (name, index) = name.strip_suffix(']')?.split_once('[')?;
+    Some((name, index.parse().ok()?))
+}
+
+// Returns the offset and width of a named field within the block.
+fn field_range(kind: BT, data: &[u8], name: &str) -> Result<(usize, usize)> {
+    let find = |fields: &[(&str, usize, usize)]| {
+        fields
+            .iter()
+            .find(|(n, _, _)| *n == name)
+            .map(|(_, offset, width)| (*offset, *width))
+    };
+
+    let range = match kind {
+        BT::THIN_SUPERBLOCK => find(&SB_FIELDS),
+        BT::NODE => find(&NODE_FIELDS).or_else(|| {
+            let max_entries = le_field(data, 20, 4) as usize;
+            let value_size = le_field(data, 24, 4) as usize;
+            match parse_indexed(name) {
+                Some(("key", i)) if i < max_entries => Some((32 + 8 * i, 8)),
+                Some(("value", i)) if i < max_entries => {
+                    Some((32 + 8 * max_entries + value_size * i, value_size))
+                }
+                _ => None,
+            }
+        }),
+        BT::ARRAY => find(&ARRAY_FIELDS).or_else(|| {
+            let max_entries = le_field(data, 4, 4) as usize;
+            let value_size = le_field(data, 12, 4) as usize;
+            match parse_indexed(name) {
+                Some(("value", i)) if i < max_entries => Some((24 + value_size * i, value_size)),
+                _ => None,
+            }
+        }),
+        BT::INDEX | BT::BITMAP => find(&[("block", 8, 8)]),
+        _ => None,
+    };
+
+    match range {
+        Some((offset, width)) if offset + width <= data.len() && width <= 8 => Ok((offset, width)),
+        _ => Err(anyhow!("no field '{}' in a {:?} block", name, kind)),
+    }
+}
+
+fn apply_edit(kind: BT, data: &mut [u8], edit: &Edit) -> Result<()> {
+    match edit {
+        Edit::Field(name, value) => {
+            let (offset, width) = field_range(kind, data, name)?;
+            if width < 8 && *value >> (8 * width) != 0 {
+                return Err(anyhow!("value {} is too large for field '{}'", value, name));
+            }
+            data[offset..(offset + width)].copy_from_slice(&value.to_le_bytes()[0..width]);
+        }
+        Edit::Bytes(offset, bytes) => {
+            // the checksum is always recomputed, so it can't be set by hand
+            if *offset < 4 || offset + bytes.len() > data.len() {
+                return Err(anyhow!(
+                    "bytes {}..{} are outside the editable part of the block",
+                    offset,
+                    offset + bytes.len()
+                ));
+            }
+            data[*offset..(offset + bytes.len())].copy_from_slice(bytes);
+        }
+    }
+    Ok(())
+}
+
+// The superblocks can still be told apart by their magic numbers once
+// the checksum is broken.
+fn superblock_kind(data: &[u8]) -> BT {
+    match le_field(data, 32, 8) {
+        MAGIC => BT::THIN_SUPERBLOCK,
+        cache_sb::MAGIC => BT::CACHE_SUPERBLOCK,
+        era_sb::MAGIC => BT::ERA_SUPERBLOCK,
+        _ => BT::UNKNOWN,
+    }
+}
+
+// The checksum tells us what sort of block this is, unless it's been
+// damaged, in which case we fall back to where the block sits in the
+// metadata.
+fn block_kind(engine: &dyn IoEngine, loc: u64, data: &[u8]) -> Result<BT> {
+    match metadata_block_type(data) {
+        BT::UNKNOWN if loc == SUPERBLOCK_LOCATION => match superblock_kind(data) {
+            BT::UNKNOWN => Err(anyhow!("couldn't work out the type of the superblock")),
+            kind => Ok(kind),
+        },
+        BT::UNKNOWN => {
+            let path = locate_node(engine, loc)
+                .ok_or_else(|| anyhow!("couldn't work out the type of block {}", loc))?;
+            let panels = follow_path(engine, &path)?;
+            Ok(panels.last().unwrap().location().1)
+        }
+        kind => Ok(kind),
+    }
+}
+
+fn edit_block(engine: &dyn IoEngine, loc: u64, edits: &[Edit]) -> Result<Description> {
+    let mut data = read_block(engine, loc)?;
+    let kind = block_kind(engine, loc, &data)?;
+    for edit in edits {
+        apply_edit(kind, &mut data, edit)?;
+    }
+    write_block(engine, loc, kind, data)?;
+    describe_block(engine, loc)
+}
+
+fn parse_number(text: &str) -> Result<u64> {
+    let n = match text.strip_prefix("0x") {
+        Some(hex) => u64::from_str_radix(hex, 16),
+        None => text.parse::<u64>(),
+    };
+    n.map_err(|_| anyhow!("bad number '{}'", text))
+}
+
+fn parse_set_field(text: &str) -> Result<Edit> {
+    let (name, value) = text
+        .split_once('=')
+        .ok_or_else(|| anyhow!("expected NAME=VALUE, got '{}'", text))?;
+    Ok(Edit::Field(name.to_string(), parse_number(value)?))
+}
+
+fn parse_set_bytes(text: &str) -> Result<Edit> {
+    let (offset, hex) = text
+        .split_once('=')
+        .ok_or_else(|| anyhow!("expected OFFSET=HEX, got '{}'", text))?;
+    if hex.is_empty() || hex.len() % 2 != 0 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(anyhow!("bad hex bytes '{}'", hex));
+    }
+    let bytes = (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&hex[i..(i + 2)], 16).unwrap())
+        .collect();
+    Ok(Edit::Bytes(parse_number(offset)? as usize, bytes))
+}
+
+//------------------------------------
+
+fn print_text<W: Write>(w: &mut W, desc: &Description) -> io::Result<()> {
+    writeln!(w, "{}", desc.title)?;
+    for (field, value) in &desc.fields {
+        writeln!(w, "  {:<20}{}", field, value)?;
+    }
+
+    if !desc.entries.is_empty() {
+        writeln!(w, "Entries")?;
+        for e in &desc.entries {
+            writeln!(w, "  {}", fmt_run(e.key, &e.value, e.len))?;
+        }
+    }
+
+    Ok(())
+}
+
+fn print_json<W: Write>(w: &mut W, desc: &Description) -> io::Result<()> {
+    let fields: Vec<String> = desc
+        .fields
+        .iter()
+        .map(|(field, value)| format!("{}: {}", json_string(field), json_string(value)))
+        .collect();
+    let entries: Vec<String> = desc
+        .entries
+        .iter()
+        .map(|e| {
+            format!(
+                "{{\"key\": {}, \"len\": {}, \"value\": {}}}",
+                e.key,
+                e.len,
+                json_string(&e.value)
+            )
+        })
+        .collect();
+
+    writeln!(
+        w,
+        "{{\"title\": {}, \"fields\": {{{}}}, \"entries\": [{}]}}",
+        json_string(&desc.title),
+        fields.join(", "),
+        entries.join(", ")
+    )
+}
+
+// The queries about devices and mappings only make sense for thin
+// metadata.
+fn read_thin_superblock(engine: &dyn IoEngine) -> Result<Superblock> {
+    match read_sb_panel(engine)?.kind {
+        BT::THIN_SUPERBLOCK => read_superblock(engine, SUPERBLOCK_LOCATION),
+        _ => Err(anyhow!("this query needs thin metadata")),
+    }
+}
+
+fn query(path: &Path, query: Query, format: QueryFormat) -> Result<()> {
+    let engine = open_engine(path, matches!(query, Query::Edit(..)))?;
+    let engine = engine.as_ref();
+
+    let desc = match query {
+        Query::Block(loc) => describe_block(engine, loc)?,
+        Query::NodePath(path) => describe_path(engine, &path)?,
+        Query::FindThin(thin_id, thin_block) => {
+            find_thin(engine, &read_thin_superblock(engine)?, thin_id, thin_block)?
+        }
+        Query::FindData(data_block) => {
+            find_data(engine, &read_thin_superblock(engine)?, data_block)?
+        }
+        Query::Superblock => read_sb_panel(engine)?.describe(),
+        Query::Devices => describe_devices(engine, &read_thin_superblock(engine)?)?,
+        Query::Edit(loc, edits) => edit_block(engine, loc, &edits)?,
+    };
+
+    let stdout = io::stdout();
+    let mut stdout = stdout.lock();
+    match format {
+        QueryFormat::Text => print_text(&mut stdout, &desc)?,
+        QueryFormat::Json => print_json(&mut stdout, &desc)?,
+    }
+    Ok(())
+}
+
 //------------------------------------
 
 pub struct ThinExploreCommand;
@@ -882,7 +2961,72 @@ impl ThinExploreCommand {
             .next_display_order(None)
             .version(crate::tools_version!())
             .disable_version_flag(true)
-            .about("A text user interface for examining thin metadata.")
+            .about("A text user interface for examining thin, cache and era metadata.")
+            // flags
+            .arg(
+                Arg::new("DEVICES")
+                    .help("Print the device details")
+                    .long("devices")
+                    .action(ArgAction::SetTrue),
+            )
+            .arg(
+                Arg::new("EDIT")
+                    .help("Open the metadata for writing, allowing blocks to be changed")
+                    .long("edit")
+                    .action(ArgAction::SetTrue),
+            )
+            .arg(
+                Arg::new("PRINT")
+                    .help("Print the node at the end of the node path")
+                    .long("print")
+                    .action(ArgAction::SetTrue)
+                    .requires("NODE_PATH"),
+            )
+            .arg(
+                Arg::new("SUPERBLOCK")
+                    .help("Print the superblock")
+                    .long("superblock")
+                    .action(ArgAction::SetTrue),
+            )
+            // options
+            .arg(
+                Arg::new("BLOCK")
+                    .help("Print the node at the given block")
+                    .long("block")
+                    .value_name("BLOCKNR")
+                    .value_parser(value_parser!(u64)),
+            )
+            .arg(
+                Arg::new("DEV_ID")
+                    .help("Specify the thin device for --find-thin")
+                    .long("dev-id")
+                    .value_name("THIN_ID")
+                    .value_parser(value_parser!(u32)),
+            )
+            .arg(
+                Arg::new("FIND_DATA")
+                    .help("Find the mappings of a data block")
+                    .long("find-data")
+                    .value_name("BLOCKNR")
+                    .value_parser(value_parser!(u64)),
+            )
+            .arg(
+                Arg::new("FIND_THIN")
+                    .help("Find the leaf holding the mapping of a thin block")
+                    .long("find-thin")
+                    .value_name("BLOCKNR")
+                    .value_parser(value_parser!(u64))
+                    .requires("DEV_ID"),
+            )
+            .arg(
+                Arg::new("FORMAT")
+                    .help("Choose the output format of a query")
+                    .long("format")
+                    .value_name("TYPE")
+                    .value_parser(["text", "json"])
+                    .default_value("text")
+                    .requires("queries"),
+            )
             .arg(
                 Arg::new("NODE_PATH")
                     .help("Pass in a node path as output by thin_check")
@@ -890,12 +3034,36 @@ impl ThinExploreCommand {
                     .long("node-path")
                     .value_name("NODE_PATH"),
             )
+            .arg(
+                Arg::new("SET_BYTES")
+                    .help("Overwrite bytes of the block given by --block")
+                    .long("set-bytes")
+                    .value_name("OFFSET=HEX")
+                    .action(ArgAction::Append)
+                    .requires_all(["EDIT", "BLOCK"]),
+            )
+            .arg(
+                Arg::new("SET_FIELD")
+                    .help("Change a field of the block given by --block")
+                    .long("set-field")
+                    .value_name("NAME=VALUE")
+                    .action(ArgAction::Append)
+                    .requires_all(["EDIT", "BLOCK"]),
+            )
             .arg(
                 Arg::new("INPUT")
                     .help("Specify the input device to check")
                     .required(true)
                     .index(1),
-            );
+            )
+            .group(ArgGroup::new("queries").args([
+                "BLOCK",
+                "DEVICES",
+                "FIND_DATA",
+                "FIND_THIN",
+                "PRINT",
+                "SUPERBLOCK",
+            ]));
 
         version_args(cmd)
     }
@@ -916,7 +3084,60 @@ impl<'a> Command<'a> for ThinExploreCommand {
         let input_file = Path::new(matches.get_one::<String>("INPUT").unwrap());
         let report = mk_report(false);
 
-        to_exit_code(&report, explore(input_file, node_path))
+        let edits: Result<Vec<Edit>> = matches
+            .get_many::<String>("SET_FIELD")
+            .into_iter()
+            .flatten()
+            .map(|text| parse_set_field(text))
+            .chain(
+                matches
+                    .get_many::<String>("SET_BYTES")
+                    .into_iter()
+                    .flatten()
+                    .map(|text| parse_set_bytes(text)),
+            )
+            .collect();
+        let edits = match edits {
+            Ok(edits) => edits,
+            Err(e) => return to_exit_code::<()>(&report, Err(e)),
+        };
+
+        let q = if let Some(b) = matches.get_one::<u64>("BLOCK") {
+            if edits.is_empty() {
+                Some(Query::Block(*b))
+            } else {
+                Some(Query::Edit(*b, edits))
+            }
+        } else if matches.get_flag("DEVICES") {
+            Some(Query::Devices)
+        } else if let Some(b) = matches.get_one::<u64>("FIND_DATA") {
+            Some(Query::FindData(*b))
+        } else if let Some(b) = matches.get_one::<u64>("FIND_THIN") {
+            Some(Query::FindThin(
+                *matches.get_one::<u32>("DEV_ID").unwrap(),
+                *b,
+            ))
+        } else if matches.get_flag("PRINT") {
+            node_path.clone().map(Query::NodePath)
+        } else if matches.get_flag("SUPERBLOCK") {
+            Some(Query::Superblock)
+        } else {
+            None
+        };
+
+        match q {
+            Some(q) => {
+                let format = match matches.get_one::<String>("FORMAT").unwrap().as_str() {
+                    "json" => QueryFormat::Json,
+                    _ => QueryFormat::Text,
+                };
+                to_exit_code(&report, query(input_file, q, format))
+            }
+            None => to_exit_code(
+                &report,
+                explore(input_file, node_path, matches.get_flag("EDIT")),
+            ),
+        }
     }
 }
 
@@ -81,7 +81,7 @@ impl<'a> Command<'a> for ThinGenerateMetadataCommand {
 
         let report = mk_report(false);
 
-        let engine_opts = parse_engine_opts(ToolType::Thin, &matches);
+        let engine_opts = parse_engine_opts(ToolType::Thin, &matches, report.clone());
         if engine_opts.is_err() {
             return to_exit_code(&report, engine_opts);
         }
@@ -106,7 +106,10 @@ impl<'a> Command<'a> for ThinGenerateMetadataCommand {
             output: Path::new(matches.get_one::<String>("OUTPUT").unwrap()),
         };
 
-        to_exit_code(&report, generate_metadata(opts))
+        let io_stats = opts.engine_opts.io_stats.clone();
+        let result = generate_metadata(opts);
+        report_io_stats(&report, io_stats.as_deref());
+        to_exit_code(&report, result)
     }
 }
 
@@ -82,7 +82,7 @@ impl<'a> Command<'a> for ThinLsCommand {
             |fmt| fmt.cloned().collect(),
         );
 
-        let engine_opts = parse_engine_opts(ToolType::Thin, &matches);
+        let engine_opts = parse_engine_opts(ToolType::Thin, &matches, report.clone());
         if engine_opts.is_err() {
             return to_exit_code(&report, engine_opts);
         }
@@ -95,6 +95,9 @@ impl<'a> Command<'a> for ThinLsCommand {
             report: report.clone(),
         };
 
-        to_exit_code(&report, ls(opts))
+        let io_stats = opts.engine_opts.io_stats.clone();
+        let result = ls(opts);
+        report_io_stats(&report, io_stats.as_deref());
+        to_exit_code(&report, result)
     }
 }
@@ -3,6 +3,7 @@ extern crate clap;
 use clap::{Arg, ArgAction};
 use std::path::Path;
 
+use crate::commands::engine::{io_stats_args, parse_io_stats, report_io_stats};
 use crate::commands::utils::*;
 use crate::commands::Command;
 use crate::report::*;
@@ -24,6 +25,11 @@ impl ThinMetadataPackCommand {
                 .long("force")
                 .action(ArgAction::SetTrue))
             // options
+            .arg(Arg::new("BASE")
+                .help("Only pack blocks changed since the given base pack(s)")
+                .long("base")
+                .action(ArgAction::Append)
+                .value_name("FILE"))
             .arg(Arg::new("INPUT")
                 .help("Specify thinp metadata binary device/file")
                 .required(true)
@@ -37,7 +43,7 @@ impl ThinMetadataPackCommand {
                 .long("output")
                 .value_name("FILE"));
 
-        version_args(cmd)
+        io_stats_args(version_args(cmd))
     }
 }
 
@@ -52,6 +58,9 @@ impl<'a> Command<'a> for ThinMetadataPackCommand {
 
         let input_file = Path::new(matches.get_one::<String>("INPUT").unwrap());
         let output_file = Path::new(matches.get_one::<String>("OUTPUT").unwrap());
+        let base_files: Vec<&Path> = matches
+            .get_many::<String>("BASE")
+            .map_or_else(Vec::new, |files| files.map(Path::new).collect());
 
         let report = mk_simple_report();
 
@@ -62,16 +71,26 @@ impl<'a> Command<'a> for ThinMetadataPackCommand {
             return to_exit_code::<()>(&report, Err(e));
         }
 
+        for base in &base_files {
+            if let Err(e) = check_input_file(base) {
+                return to_exit_code::<()>(&report, Err(e));
+            }
+        }
+
         if !matches.get_flag("FORCE") {
             if let Err(e) = check_overwrite_metadata(&report, output_file) {
                 return to_exit_code::<()>(&report, Err(e));
             }
         }
 
-        let report = std::sync::Arc::new(report);
-        to_exit_code(
-            &report,
-            crate::pack::toplevel::pack(input_file, output_file),
-        )
+        let io_stats = parse_io_stats(&matches);
+        let result = crate::pack::toplevel::pack_incremental(
+            input_file,
+            output_file,
+            &base_files,
+            io_stats.clone().unwrap_or_default(),
+        );
+        report_io_stats(&report, io_stats.as_deref());
+        to_exit_code(&report, result)
     }
 }
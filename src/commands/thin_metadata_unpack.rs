@@ -6,7 +6,7 @@ use std::path::Path;
 use crate::commands::engine::*;
 use crate::commands::utils::*;
 use crate::commands::Command;
-use crate::pack::toplevel::unpack;
+use crate::pack::toplevel::unpack_incremental;
 use crate::report::mk_simple_report;
 use crate::version::*;
 
@@ -28,6 +28,13 @@ impl ThinMetadataUnpackCommand {
                     .action(ArgAction::SetTrue),
             )
             // options
+            .arg(
+                Arg::new("BASE")
+                    .help("Specify the base pack(s) of an incremental input")
+                    .long("base")
+                    .action(ArgAction::Append)
+                    .value_name("FILE"),
+            )
             .arg(
                 Arg::new("INPUT")
                     .help("Specify packed input file")
@@ -44,7 +51,11 @@ impl ThinMetadataUnpackCommand {
                     .long("output")
                     .value_name("DEV"),
             );
+        // Unpacking doesn't go through an io engine, but the engine options
+        // have always been accepted, so keep them out of the help instead
         engine_args(version_args(cmd))
+            .mut_arg("IO_ENGINE", |arg| arg.hide(true))
+            .mut_arg("BLOCK_CACHE", |arg| arg.hide(true))
     }
 }
 
@@ -59,6 +70,9 @@ impl<'a> Command<'a> for ThinMetadataUnpackCommand {
 
         let input_file = Path::new(matches.get_one::<String>("INPUT").unwrap());
         let output_file = Path::new(matches.get_one::<String>("OUTPUT").unwrap());
+        let base_files: Vec<&Path> = matches
+            .get_many::<String>("BASE")
+            .map_or_else(Vec::new, |files| files.map(Path::new).collect());
 
         let report = mk_simple_report();
 
@@ -66,13 +80,26 @@ impl<'a> Command<'a> for ThinMetadataUnpackCommand {
             return to_exit_code::<()>(&report, Err(e));
         }
 
+        for base in &base_files {
+            if let Err(e) = check_input_file(base) {
+                return to_exit_code::<()>(&report, Err(e));
+            }
+        }
+
         if !matches.get_flag("FORCE") {
             if let Err(e) = check_overwrite_metadata(&report, output_file) {
                 return to_exit_code::<()>(&report, Err(e));
             }
         }
 
-        let report = std::sync::Arc::new(report);
-        to_exit_code(&report, unpack(input_file, output_file))
+        let io_stats = parse_io_stats(&matches);
+        let result = unpack_incremental(
+            input_file,
+            output_file,
+            &base_files,
+            io_stats.clone().unwrap_or_default(),
+        );
+        report_io_stats(&report, io_stats.as_deref());
+        to_exit_code(&report, result)
     }
 }
@@ -61,6 +61,21 @@ impl ThinMigrateCommand {
                     .value_name("MB")
                     .value_parser(value_parser!(usize)),
             )
+            .arg(
+                Arg::new("RESCUE-MAP")
+                    .help("Record the progress of the copy in a map file, and resume from it")
+                    .long("rescue-map")
+                    .value_name("FILE"),
+            )
+            .arg(
+                Arg::new("RETRY-COUNT")
+                    .help("Specify how many times to retry the blocks that failed")
+                    .long("retry-count")
+                    .value_name("CNT")
+                    .value_parser(value_parser!(u32))
+                    .default_value("0")
+                    .requires("RESCUE-MAP"),
+            )
             .arg(
                 Arg::new("ZERO-DEST")
                     .help("Ensure all unwritten regions of the destination are zeroed")
@@ -68,7 +83,7 @@ impl ThinMigrateCommand {
                     .action(ArgAction::SetTrue)
                     .hide(true),
             );
-        verbose_args(engine_args(version_args(cmd)))
+        verbose_args(copier_args(engine_args(version_args(cmd))))
     }
 }
 
@@ -133,15 +148,32 @@ impl<'a> Command<'a> for ThinMigrateCommand {
 
         let zero_dest = matches.get_flag("ZERO-DEST");
 
+        let copier = match parse_copier_opts(&matches) {
+            Ok(copier) => copier,
+            Err(e) => return to_exit_code::<()>(&report, Err(e)),
+        };
+
+        let engine_opts = match parse_engine_opts(ToolType::Thin, &matches, report.clone()) {
+            Ok(engine_opts) => engine_opts,
+            Err(e) => return to_exit_code::<()>(&report, Err(e)),
+        };
+
         let opts = migrate::ThinMigrateOptions {
             source: source.unwrap(),
             dest: dest.unwrap(),
             zero_dest,
             buffer_size,
+            copier,
+            rescue_map: matches.get_one::<String>("RESCUE-MAP").map(PathBuf::from),
+            retry_count: *matches.get_one::<u32>("RETRY-COUNT").unwrap(),
             report: report.clone(),
+            engine_opts,
         };
 
-        to_exit_code(&report, migrate::migrate(opts))
+        let io_stats = opts.engine_opts.io_stats.clone();
+        let result = migrate::migrate(opts);
+        report_io_stats(&report, io_stats.as_deref());
+        to_exit_code(&report, result)
     }
 }
 
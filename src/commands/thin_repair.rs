@@ -68,7 +68,7 @@ impl ThinRepairCommand {
             // a dummy argument for compatibility with lvconvert
             .arg(Arg::new("DUMMY").required(false).hide(true).index(1));
 
-        verbose_args(engine_args(version_args(cmd)))
+        verbose_args(overlay_args(engine_args(version_args(cmd))))
     }
 }
 
@@ -98,7 +98,7 @@ impl<'a> Command<'a> for ThinRepairCommand {
             return to_exit_code::<()>(&report, Err(e));
         }
 
-        let engine_opts = parse_engine_opts(ToolType::Thin, &matches);
+        let engine_opts = parse_engine_opts(ToolType::Thin, &matches, report.clone());
         if engine_opts.is_err() {
             return to_exit_code(&report, engine_opts);
         }
@@ -115,6 +115,9 @@ impl<'a> Command<'a> for ThinRepairCommand {
             },
         };
 
-        to_exit_code(&report, repair(opts))
+        let io_stats = opts.engine_opts.io_stats.clone();
+        let result = repair(opts);
+        report_io_stats(&report, io_stats.as_deref());
+        to_exit_code(&report, result)
     }
 }
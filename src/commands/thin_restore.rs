@@ -65,7 +65,7 @@ impl ThinRestoreCommand {
                     .value_name("NUM")
                     .value_parser(value_parser!(u64)),
             );
-        verbose_args(engine_args(version_args(cmd)))
+        verbose_args(overlay_args(engine_args(version_args(cmd))))
     }
 }
 
@@ -92,7 +92,7 @@ impl<'a> Command<'a> for ThinRestoreCommand {
             return to_exit_code::<()>(&report, Err(e));
         }
 
-        let engine_opts = parse_engine_opts(ToolType::Thin, &matches);
+        let engine_opts = parse_engine_opts(ToolType::Thin, &matches, report.clone());
         if engine_opts.is_err() {
             return to_exit_code(&report, engine_opts);
         }
@@ -109,6 +109,9 @@ impl<'a> Command<'a> for ThinRestoreCommand {
             },
         };
 
-        to_exit_code(&report, restore(opts))
+        let io_stats = opts.engine_opts.io_stats.clone();
+        let result = restore(opts);
+        report_io_stats(&report, io_stats.as_deref());
+        to_exit_code(&report, result)
     }
 }
@@ -71,7 +71,7 @@ impl<'a> Command<'a> for ThinRmapCommand {
             })
             .collect();
 
-        let engine_opts = parse_engine_opts(ToolType::Thin, &matches);
+        let engine_opts = parse_engine_opts(ToolType::Thin, &matches, report.clone());
         if engine_opts.is_err() {
             return to_exit_code(&report, engine_opts);
         }
@@ -83,7 +83,10 @@ impl<'a> Command<'a> for ThinRmapCommand {
             report: report.clone(),
         };
 
-        to_exit_code(&report, rmap(opts))
+        let io_stats = opts.engine_opts.io_stats.clone();
+        let result = rmap(opts);
+        report_io_stats(&report, io_stats.as_deref());
+        to_exit_code(&report, result)
     }
 }
 
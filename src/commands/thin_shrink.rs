@@ -9,6 +9,9 @@ use std::ffi;
 use std::io;
 use std::path::Path;
 
+use crate::commands::engine::{
+    copier_args, io_stats_args, parse_copier_opts, parse_io_stats, report_io_stats,
+};
 use crate::commands::utils::*;
 use crate::commands::Command;
 use crate::report::*;
@@ -68,7 +71,7 @@ impl ThinShrinkCommand {
                     .action(ArgAction::SetTrue),
             );
 
-        version_args(cmd)
+        io_stats_args(copier_args(version_args(cmd)))
     }
 
     fn parse_args<I, T>(&self, args: I) -> io::Result<ThinShrinkOptions>
@@ -86,6 +89,8 @@ impl ThinShrinkCommand {
         let do_copy = !matches.get_flag("NOCOPY");
         let binary_mode = matches.get_flag("BINARY");
         let report = mk_report(false);
+        let copier = parse_copier_opts(&matches)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e.to_string()))?;
 
         Ok(ThinShrinkOptions {
             input: input.to_path_buf(),
@@ -94,7 +99,9 @@ impl ThinShrinkCommand {
             data_device: data_device.to_path_buf(),
             do_copy,
             binary_mode,
+            copier,
             report,
+            io_stats: parse_io_stats(&matches),
         })
     }
 }
@@ -105,14 +112,13 @@ impl<'a> Command<'a> for ThinShrinkCommand {
     }
 
     fn run(&self, args: &mut dyn Iterator<Item = ffi::OsString>) -> exitcode::ExitCode {
-        let opts = self.parse_args(args);
-        if opts.is_err() {
-            return exitcode::USAGE;
-        }
-        let opts = opts.unwrap();
-
         let report = std::sync::Arc::new(mk_simple_report());
 
+        let opts = match self.parse_args(args) {
+            Ok(opts) => opts,
+            Err(e) => return to_exit_code::<()>(&report, Err(e.into())),
+        };
+
         let mut r = check_input_file(&opts.input);
 
         if opts.binary_mode {
@@ -130,6 +136,9 @@ impl<'a> Command<'a> for ThinShrinkCommand {
             return to_exit_code::<()>(&report, Err(e));
         }
 
-        to_exit_code(&report, shrink(opts))
+        let io_stats = opts.io_stats.clone();
+        let result = shrink(opts);
+        report_io_stats(&report, io_stats.as_deref());
+        to_exit_code(&report, result)
     }
 }
@@ -46,7 +46,7 @@ impl<'a> Command<'a> for ThinStatCommand {
         display_version(&matches);
         let report = std::sync::Arc::new(mk_simple_report());
 
-        let engine_opts = parse_engine_opts(ToolType::Thin, &matches);
+        let engine_opts = parse_engine_opts(ToolType::Thin, &matches, report.clone());
         if engine_opts.is_err() {
             return to_exit_code(&report, engine_opts);
         }
@@ -62,7 +62,10 @@ impl<'a> Command<'a> for ThinStatCommand {
             },
         };
 
-        to_exit_code(&report, stat(opts))
+        let io_stats = opts.engine_opts.io_stats.clone();
+        let result = stat(opts);
+        report_io_stats(&report, io_stats.as_deref());
+        to_exit_code(&report, result)
     }
 }
 
@@ -74,7 +74,7 @@ impl<'a> Command<'a> for ThinTrimCommand {
             return to_exit_code::<()>(&report, Err(e));
         }
 
-        let engine_opts = match parse_engine_opts(ToolType::Thin, &matches) {
+        let engine_opts = match parse_engine_opts(ToolType::Thin, &matches, report.clone()) {
             Ok(opts) => opts,
             Err(_) => return exitcode::USAGE,
         };
@@ -107,6 +107,9 @@ impl<'a> Command<'a> for ThinTrimCommand {
             report: report.clone(),
         };
 
-        to_exit_code(&report, trim(opts))
+        let io_stats = opts.engine_opts.io_stats.clone();
+        let result = trim(opts);
+        report_io_stats(&report, io_stats.as_deref());
+        to_exit_code(&report, result)
     }
 }
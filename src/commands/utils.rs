@@ -7,6 +7,8 @@ use std::sync::Arc;
 
 use crate::checksum::{metadata_block_type, BT};
 use crate::file_utils;
+use crate::io_engine::nbd::is_nbd_url;
+use crate::pack::toplevel::is_pack_file;
 use crate::report::*;
 
 #[cfg(test)]
@@ -46,6 +48,11 @@ impl FromStr for RangeU64 {
 //------------------------------------------
 
 pub fn check_input_file(input_file: &Path) -> Result<&Path> {
+    // the engine checks remote exports when it connects
+    if is_nbd_url(input_file) {
+        return Ok(input_file);
+    }
+
     match file_utils::is_file_or_blk(input_file) {
         Ok(true) => Ok(input_file),
         Ok(false) => Err(anyhow!(
@@ -66,6 +73,11 @@ pub fn check_input_file(input_file: &Path) -> Result<&Path> {
 }
 
 pub fn check_file_not_tiny(input_file: &Path) -> Result<&Path> {
+    // packed metadata can legitimately be smaller than a block
+    if is_pack_file(input_file) || is_nbd_url(input_file) {
+        return Ok(input_file);
+    }
+
     match file_utils::file_size(input_file) {
         Ok(0..=4095) => Err(anyhow!(
             "Metadata device/file too small.  Is this binary metadata?"
@@ -155,6 +167,22 @@ pub fn check_overwrite_metadata(report: &Report, path: &Path) -> Result<()> {
     Ok(()) // file not found or not a metadata, or 'y' is entered
 }
 
+// Quotes a string for JSON output
+pub fn json_string(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
 pub fn to_exit_code<T>(report: &Report, result: anyhow::Result<T>) -> exitcode::ExitCode {
     if let Err(e) = result {
         let root_cause = e.root_cause();
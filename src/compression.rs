@@ -0,0 +1,175 @@
+use flate2::{read::MultiGzDecoder, write::GzEncoder, Compression};
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+
+//------------------------------------------
+
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+const ZSTD_MAGIC: [u8; 4] = [0x28, 0xb5, 0x2f, 0xfd];
+const XZ_MAGIC: [u8; 6] = [0xfd, 0x37, 0x7a, 0x58, 0x5a, 0x00];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Format {
+    None,
+    Gzip,
+    Zstd,
+    Xz,
+}
+
+fn detect_format(magic: &[u8]) -> Format {
+    if magic.starts_with(&GZIP_MAGIC) {
+        Format::Gzip
+    } else if magic.starts_with(&ZSTD_MAGIC) {
+        Format::Zstd
+    } else if magic.starts_with(&XZ_MAGIC) {
+        Format::Xz
+    } else {
+        Format::None
+    }
+}
+
+fn unsupported(name: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!(
+            "{} compressed input is not supported, please decompress it first",
+            name
+        ),
+    )
+}
+
+/// Selects the output compression from the file extension.
+pub fn format_from_path(path: &Path) -> Format {
+    match path.extension().and_then(|ext| ext.to_str()) {
+        Some("gz") => Format::Gzip,
+        Some("zst") => Format::Zstd,
+        Some("xz") => Format::Xz,
+        _ => Format::None,
+    }
+}
+
+//------------------------------------------
+
+/// Wraps an input file in a decoder if it starts with a known
+/// compression magic.  Plain files are returned unchanged.  The file
+/// is read from its start regardless of the current offset.
+pub fn mk_reader(mut file: File) -> io::Result<Box<dyn Read>> {
+    let mut magic = [0u8; 6];
+    file.seek(SeekFrom::Start(0))?;
+    let mut len = 0;
+    while len < magic.len() {
+        let n = file.read(&mut magic[len..])?;
+        if n == 0 {
+            break;
+        }
+        len += n;
+    }
+    file.seek(SeekFrom::Start(0))?;
+
+    match detect_format(&magic[..len]) {
+        Format::None => Ok(Box::new(file)),
+        Format::Gzip => Ok(Box::new(MultiGzDecoder::new(BufReader::new(file)))),
+        Format::Zstd => Err(unsupported("zstd")),
+        Format::Xz => Err(unsupported("xz")),
+    }
+}
+
+/// A buffered output stream, compressed or not.  It must be finished, so
+/// errors writing the buffered data or the compression trailer are seen.
+pub enum Writer {
+    Plain(BufWriter<File>),
+    Gzip(GzEncoder<BufWriter<File>>),
+    Stdout(BufWriter<io::Stdout>),
+}
+
+impl Writer {
+    pub fn stdout() -> Self {
+        Writer::Stdout(BufWriter::new(io::stdout()))
+    }
+
+    pub fn finish(self) -> io::Result<()> {
+        match self {
+            Writer::Plain(mut w) => w.flush(),
+            Writer::Gzip(w) => w.finish()?.flush(),
+            Writer::Stdout(mut w) => w.flush(),
+        }
+    }
+}
+
+impl Write for Writer {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Writer::Plain(w) => w.write(buf),
+            Writer::Gzip(w) => w.write(buf),
+            Writer::Stdout(w) => w.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Writer::Plain(w) => w.flush(),
+            Writer::Gzip(w) => w.flush(),
+            Writer::Stdout(w) => w.flush(),
+        }
+    }
+}
+
+/// Creates a buffered writer for the given output file, compressing
+/// the stream if the path has a compression extension (eg, '.gz').
+pub fn mk_writer(file: File, path: &Path) -> io::Result<Writer> {
+    let w = BufWriter::new(file);
+    match format_from_path(path) {
+        Format::None => Ok(Writer::Plain(w)),
+        Format::Gzip => Ok(Writer::Gzip(GzEncoder::new(w, Compression::default()))),
+        Format::Zstd => Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "zstd compressed output is not supported",
+        )),
+        Format::Xz => Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "xz compressed output is not supported",
+        )),
+    }
+}
+
+//------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detects_formats() {
+        assert_eq!(detect_format(&[0x1f, 0x8b, 0x08]), Format::Gzip);
+        assert_eq!(detect_format(&ZSTD_MAGIC), Format::Zstd);
+        assert_eq!(detect_format(&XZ_MAGIC), Format::Xz);
+        assert_eq!(detect_format(b"<superblock"), Format::None);
+        assert_eq!(detect_format(&[0x1f]), Format::None);
+    }
+
+    #[test]
+    fn format_from_extension() {
+        assert_eq!(format_from_path(Path::new("meta.xml.gz")), Format::Gzip);
+        assert_eq!(format_from_path(Path::new("meta.xml")), Format::None);
+        assert_eq!(format_from_path(Path::new("meta")), Format::None);
+    }
+
+    #[test]
+    fn gzip_round_trip() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("meta.xml.gz");
+        let mut w = mk_writer(File::create(&path).unwrap(), &path).unwrap();
+        w.write_all(b"<superblock/>").unwrap();
+        w.finish().unwrap();
+
+        let mut text = String::new();
+        mk_reader(File::open(&path).unwrap())
+            .unwrap()
+            .read_to_string(&mut text)
+            .unwrap();
+        assert_eq!(text, "<superblock/>");
+    }
+}
+
+//------------------------------------------
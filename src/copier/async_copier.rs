@@ -0,0 +1,313 @@
+use anyhow::{anyhow, Result};
+use io_uring::{opcode, types, IoUring};
+use std::fs::{File, OpenOptions};
+use std::os::fd::AsRawFd;
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::copier::*;
+use crate::io_engine::base::PAGE_SIZE;
+use crate::io_engine::buffer::Buffer;
+use crate::io_engine::is_page_aligned;
+
+#[cfg(test)]
+mod tests;
+
+//-------------------------------------
+
+// The number of extents that may be in flight at once.  Each one has a
+// slot in the buffer, which it keeps from the read until the write
+// completes.
+const QUEUE_DEPTH: usize = 32;
+
+// Runs of adjacent copies are joined into ios of up to this size
+const MAX_IO_SIZE: usize = 4 * 1024 * 1024;
+
+/// A copier that keeps many reads and writes in flight with io_uring.
+///
+/// Reads are issued in the order the ops are given, and so are writes;
+/// a write is held back until every earlier write has been issued.  So
+/// sort the ops as you would for the SyncCopier.  The source and
+/// destination blocks of a batch must not overlap.
+pub struct AsyncCopier {
+    block_size: usize,
+    slot_blocks: usize,
+    src: Arc<File>,
+    src_offset: u64,
+    dst: Arc<File>,
+    dst_offset: u64,
+    // Declared before the buffers, so the ring goes first
+    ring: IoUring,
+    slots: Vec<Buffer>,
+}
+
+// A run of ops where both the source and destination are adjacent
+struct Extent {
+    src: Block,
+    dst: Block,
+    len: usize,
+    // index of the first op
+    first: usize,
+    slot: usize,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum State {
+    Queued,
+    Reading,
+    Read(bool),
+    Writing,
+    Done,
+}
+
+fn mk_extents(ops: &[CopyOp], max_len: usize) -> Vec<Extent> {
+    let mut extents: Vec<Extent> = Vec::new();
+    for (i, op) in ops.iter().enumerate() {
+        if let Some(e) = extents.last_mut() {
+            if e.len < max_len && e.src + e.len as u64 == op.src && e.dst + e.len as u64 == op.dst {
+                e.len += 1;
+                continue;
+            }
+        }
+
+        extents.push(Extent {
+            src: op.src,
+            dst: op.dst,
+            len: 1,
+            first: i,
+            slot: 0,
+        });
+    }
+    extents
+}
+
+// user_data for the ring: the extent index, and whether it's the write
+fn tag(index: usize, write: bool) -> u64 {
+    ((index as u64) << 1) | write as u64
+}
+
+fn untag(user_data: u64) -> (usize, bool) {
+    ((user_data >> 1) as usize, user_data & 1 != 0)
+}
+
+impl AsyncCopier {
+    pub fn new(buffer_size: usize, block_size: usize, src: File, dst: File) -> Result<Self> {
+        Self::new_(buffer_size, block_size, Arc::new(src), Arc::new(dst))
+    }
+
+    // Copying regions within a file
+    pub fn in_file(buffer_size: usize, block_size: usize, in_out: File) -> Result<Self> {
+        let f = Arc::new(in_out);
+        Self::new_(buffer_size, block_size, f.clone(), f)
+    }
+
+    fn new_(buffer_size: usize, block_size: usize, src: Arc<File>, dst: Arc<File>) -> Result<Self> {
+        if block_size > buffer_size {
+            return Err(anyhow!("buffer size too small"));
+        }
+
+        let nr_blocks = buffer_size / block_size;
+        let nr_slots = std::cmp::min(QUEUE_DEPTH, nr_blocks);
+        let slot_blocks =
+            (nr_blocks / nr_slots).clamp(1, std::cmp::max(1, MAX_IO_SIZE / block_size));
+        let slots = (0..nr_slots)
+            .map(|_| Buffer::new(slot_blocks * block_size, PAGE_SIZE))
+            .collect();
+        let ring = IoUring::new(2 * nr_slots as u32)?;
+
+        Ok(Self {
+            block_size,
+            slot_blocks,
+            src,
+            src_offset: 0,
+            dst,
+            dst_offset: 0,
+            ring,
+            slots,
+        })
+    }
+
+    pub fn from_path<P: AsRef<Path>>(
+        buffer_size: usize,
+        block_size: usize,
+        src: P,
+        dst: P,
+    ) -> Result<Self> {
+        // must be a multiple of page size because we use O_DIRECT
+        if !is_page_aligned(block_size as u64) || !is_page_aligned(buffer_size as u64) {
+            return Err(anyhow!("block size must be page aligned"));
+        }
+
+        let src_file = OpenOptions::new()
+            .read(true)
+            .custom_flags(libc::O_EXCL | libc::O_DIRECT)
+            .open(src)?;
+
+        let dst_file = OpenOptions::new()
+            .read(false)
+            .write(true)
+            .custom_flags(libc::O_EXCL | libc::O_DIRECT)
+            .open(dst)?;
+
+        Self::new(buffer_size, block_size, src_file, dst_file)
+    }
+
+    pub fn src_offset(mut self, offset: u64) -> Result<Self> {
+        if !is_page_aligned(offset) {
+            return Err(anyhow!("offset must be page aligned"));
+        }
+        self.src_offset = offset;
+        Ok(self)
+    }
+
+    pub fn dest_offset(mut self, offset: u64) -> Result<Self> {
+        if !is_page_aligned(offset) {
+            return Err(anyhow!("offset must be page aligned"));
+        }
+        self.dst_offset = offset;
+        Ok(self)
+    }
+
+    fn push(&mut self, e: &Extent, index: usize, write: bool) -> Result<()> {
+        let len = e.len * self.block_size;
+        let buf = self.slots[e.slot].get_data()[..len].as_mut_ptr();
+        let op = if write {
+            let pos = e.dst * self.block_size as u64 + self.dst_offset;
+            opcode::Write::new(types::Fd(self.dst.as_raw_fd()), buf, len as u32)
+                .offset(pos)
+                .build()
+        } else {
+            let pos = e.src * self.block_size as u64 + self.src_offset;
+            opcode::Read::new(types::Fd(self.src.as_raw_fd()), buf, len as u32)
+                .offset(pos)
+                .build()
+        };
+
+        unsafe {
+            self.ring
+                .submission()
+                .push(&op.user_data(tag(index, write)))
+        }
+        .map_err(|_| anyhow!("submission queue is full"))
+    }
+
+    fn copy_extents(
+        &mut self,
+        ops: &[CopyOp],
+        progress: Arc<dyn CopyProgress + Sync + Send>,
+        in_flight: &mut usize,
+    ) -> Result<CopyStats> {
+        let mut stats = CopyStats::new(ops.len() as u64);
+        let mut extents = mk_extents(ops, self.slot_blocks);
+        let mut states = vec![State::Queued; extents.len()];
+        let mut free_slots: Vec<usize> = (0..self.slots.len()).rev().collect();
+        let mut next_read = 0;
+        let mut next_write = 0;
+
+        loop {
+            // Writes go out in order, as soon as the reads before them
+            // have landed.
+            while next_write < next_read {
+                let e = &extents[next_write];
+                match states[next_write] {
+                    State::Read(true) => {
+                        self.push(e, next_write, true)?;
+                        states[next_write] = State::Writing;
+                        *in_flight += 1;
+                    }
+                    State::Read(false) => {
+                        stats
+                            .read_errors
+                            .extend_from_slice(&ops[e.first..e.first + e.len]);
+                        free_slots.push(e.slot);
+                        states[next_write] = State::Done;
+                        progress.update(&stats);
+                    }
+                    _ => break,
+                }
+                next_write += 1;
+            }
+
+            while next_read < extents.len() {
+                let Some(slot) = free_slots.pop() else {
+                    break;
+                };
+                extents[next_read].slot = slot;
+                self.push(&extents[next_read], next_read, false)?;
+                states[next_read] = State::Reading;
+                *in_flight += 1;
+                next_read += 1;
+            }
+
+            if *in_flight == 0 {
+                break;
+            }
+
+            self.ring.submit_and_wait(1)?;
+            let completions: Vec<(u64, i32)> = self
+                .ring
+                .completion()
+                .map(|cqe| (cqe.user_data(), cqe.result()))
+                .collect();
+
+            for (user_data, result) in completions {
+                *in_flight -= 1;
+                let (index, write) = untag(user_data);
+                let e = &extents[index];
+
+                // a short read or write is as bad as an error
+                let ok = result >= 0 && result as usize == e.len * self.block_size;
+                if !write {
+                    states[index] = State::Read(ok);
+                    continue;
+                }
+
+                if ok {
+                    stats.nr_copied += e.len as u64;
+                } else {
+                    stats
+                        .write_errors
+                        .extend_from_slice(&ops[e.first..e.first + e.len]);
+                }
+                free_slots.push(e.slot);
+                states[index] = State::Done;
+                progress.update(&stats);
+            }
+        }
+
+        progress.inc_stats(&stats);
+        Ok(stats)
+    }
+
+    // The kernel may still be reading into, or writing from, the slots
+    // so wait for everything submitted before giving up on a copy.
+    fn drain(&mut self, mut in_flight: usize) {
+        while in_flight > 0 {
+            if let Err(e) = self.ring.submit_and_wait(1) {
+                if e.kind() == std::io::ErrorKind::Interrupted {
+                    continue;
+                }
+                break;
+            }
+            in_flight = in_flight.saturating_sub(self.ring.completion().count());
+        }
+    }
+}
+
+impl Copier for AsyncCopier {
+    fn copy(
+        &mut self,
+        ops: &[CopyOp],
+        progress: Arc<dyn CopyProgress + Sync + Send>,
+    ) -> Result<CopyStats> {
+        let mut in_flight = 0;
+        let r = self.copy_extents(ops, progress, &mut in_flight);
+        if r.is_err() {
+            self.drain(in_flight);
+        }
+        r
+    }
+}
+
+//-------------------------------------
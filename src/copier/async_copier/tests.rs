@@ -0,0 +1,137 @@
+use super::*;
+use rand::prelude::*;
+
+use crate::copier::test_utils::*;
+
+//------------------------------------------
+
+const SEED: u64 = 0x5a0c4d3e6f1b2a39;
+
+#[test]
+fn test_extents_join_adjacent_ops() {
+    let ops: Vec<CopyOp> = [(0, 10), (1, 11), (2, 12), (3, 20), (4, 21), (9, 22)]
+        .iter()
+        .map(|(src, dst)| CopyOp {
+            src: *src,
+            dst: *dst,
+        })
+        .collect();
+    let extents = mk_extents(&ops, 2);
+    let summary: Vec<(u64, u64, usize, usize)> = extents
+        .iter()
+        .map(|e| (e.src, e.dst, e.len, e.first))
+        .collect();
+    assert_eq!(
+        summary,
+        vec![(0, 10, 2, 0), (2, 12, 1, 2), (3, 20, 2, 3), (9, 22, 1, 5)]
+    );
+}
+
+#[test]
+fn test_mirroring() {
+    const NR_BLOCKS: u64 = 256;
+    let src = mk_dev(NR_BLOCKS, SEED);
+    let dst = mk_dev(NR_BLOCKS, 0);
+
+    let ops: Vec<CopyOp> = (0..NR_BLOCKS).map(|b| CopyOp { src: b, dst: b }).collect();
+    let mut copier = mk_file_copier(AsyncCopier::new, &src, dst.reopen().unwrap());
+    let stats = copy(&mut copier, &ops);
+    assert_eq!(stats.nr_copied, NR_BLOCKS);
+    assert!(stats.read_errors.is_empty());
+    assert!(stats.write_errors.is_empty());
+
+    for b in 0..NR_BLOCKS {
+        assert!(stamped_with(&dst, b, SEED, b));
+    }
+}
+
+#[test]
+fn test_copy_randomly() {
+    const NR_BLOCKS: u64 = 512;
+    let src = mk_dev(NR_BLOCKS, SEED);
+    let dst = mk_dev(NR_BLOCKS, 0);
+
+    let mut srcs: Vec<u64> = (0..NR_BLOCKS).collect();
+    let mut dsts: Vec<u64> = (0..NR_BLOCKS).collect();
+    srcs.shuffle(&mut rand::rng());
+    dsts.shuffle(&mut rand::rng());
+    let mut ops: Vec<CopyOp> = srcs
+        .into_iter()
+        .zip(dsts)
+        .take(300)
+        .map(|(src, dst)| CopyOp { src, dst })
+        .collect();
+    ops.sort_by_key(|op| op.dst);
+
+    let mut copier = mk_file_copier(AsyncCopier::new, &src, dst.reopen().unwrap());
+    let stats = copy(&mut copier, &ops);
+    assert_eq!(stats.nr_copied, ops.len() as u64);
+
+    for op in &ops {
+        assert!(stamped_with(&dst, op.dst, SEED, op.src));
+    }
+
+    // the copier can be reused
+    let stats = copy(&mut copier, &ops[..10]);
+    assert_eq!(stats.nr_copied, 10);
+}
+
+#[test]
+fn test_read_errors_are_reported() {
+    const NR_BLOCKS: u64 = 64;
+    let src = mk_dev(NR_BLOCKS, SEED);
+    let dst = mk_dev(NR_BLOCKS * 2, 0);
+
+    // the reads past the end of the source come up short
+    let ops: Vec<CopyOp> = (NR_BLOCKS - 4..NR_BLOCKS + 4)
+        .map(|b| CopyOp { src: b, dst: b })
+        .collect();
+    let mut copier = mk_file_copier(AsyncCopier::new, &src, dst.reopen().unwrap());
+    let stats = copy(&mut copier, &ops);
+    assert_eq!(stats.nr_copied + stats.read_errors.len() as u64, 8);
+    assert!(stats.read_errors.contains(&ops[7]));
+    assert!(stats.write_errors.is_empty());
+
+    // nothing is written for a failed read
+    for op in &stats.read_errors {
+        assert!(stamped_with(&dst, op.dst, 0, op.dst));
+    }
+}
+
+#[test]
+fn test_write_errors_are_reported() {
+    const NR_BLOCKS: u64 = 16;
+    let src = mk_dev(NR_BLOCKS, SEED);
+    let dst = mk_dev(NR_BLOCKS, 0);
+
+    // opened read only, so every write fails
+    let ro = OpenOptions::new().read(true).open(dst.path()).unwrap();
+    let ops: Vec<CopyOp> = (0..NR_BLOCKS).map(|b| CopyOp { src: b, dst: b }).collect();
+    let mut copier = mk_file_copier(AsyncCopier::new, &src, ro);
+    let stats = copy(&mut copier, &ops);
+    assert_eq!(stats.nr_copied, 0);
+    assert!(stats.read_errors.is_empty());
+    assert_eq!(stats.write_errors, ops);
+}
+
+#[test]
+fn test_in_file() {
+    const NR_BLOCKS: u64 = 128;
+    let dev = mk_dev(NR_BLOCKS, SEED);
+
+    let ops: Vec<CopyOp> = (64..NR_BLOCKS)
+        .map(|b| CopyOp {
+            src: b,
+            dst: b - 64,
+        })
+        .collect();
+    let mut copier = AsyncCopier::in_file(BUFFER_SIZE, BLOCK_SIZE, dev.reopen().unwrap()).unwrap();
+    let stats = copy(&mut copier, &ops);
+    assert_eq!(stats.nr_copied, 64);
+
+    for b in 0..64 {
+        assert!(stamped_with(&dev, b, SEED, b + 64));
+    }
+}
+
+//------------------------------------------
@@ -1,6 +1,8 @@
 use anyhow::Result;
 use std::sync::Arc;
 
+use crate::copier::throttle::CopyLimits;
+
 //-------------------------------------
 
 pub type Block = u64;
@@ -15,6 +17,11 @@ pub struct CopyOp {
 pub struct CopyStats {
     pub nr_blocks: Block,
     pub nr_copied: Block,
+    // Blocks that are counted as copied, but weren't written: the
+    // destination already held the data, or an earlier run copied them.
+    // Discards are only counted in nr_discarded.
+    pub nr_skipped: Block,
+    pub nr_discarded: Block,
     pub read_errors: Vec<CopyOp>,
     pub write_errors: Vec<CopyOp>,
 }
@@ -24,12 +31,34 @@ impl CopyStats {
         Self {
             nr_blocks,
             nr_copied: 0,
+            nr_skipped: 0,
+            nr_discarded: 0,
             read_errors: Vec::new(),
             write_errors: Vec::new(),
         }
     }
 }
 
+/// How the data gets moved.  The async copier keeps many ios in flight,
+/// which suits fast devices.  The compare copier reads the destination
+/// too, and only writes the blocks that differ, which suits re-running a
+/// copy that was interrupted.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum CopierType {
+    #[default]
+    Sync,
+    #[cfg(feature = "io_uring")]
+    Async,
+    Compare,
+}
+
+/// The copier the tools were asked for, and the limits it's run with
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct CopierOptions {
+    pub copier_type: CopierType,
+    pub limits: CopyLimits,
+}
+
 pub trait CopyProgress {
     /// This method is invoked during a copy batch for updating the progress bar
     /// more frequently, providing a smoother display of progress.
@@ -54,4 +83,14 @@ pub trait Copier {
     ) -> Result<CopyStats>;
 }
 
+impl<C: Copier + ?Sized> Copier for Box<C> {
+    fn copy(
+        &mut self,
+        ops: &[CopyOp],
+        progress: Arc<dyn CopyProgress + Sync + Send>,
+    ) -> Result<CopyStats> {
+        (**self).copy(ops, progress)
+    }
+}
+
 //-------------------------------------
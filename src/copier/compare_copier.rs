@@ -0,0 +1,288 @@
+use anyhow::{anyhow, Result};
+use fixedbitset::FixedBitSet;
+use std::fs::{File, OpenOptions};
+use std::os::unix::fs::{FileExt, OpenOptionsExt};
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::copier::*;
+use crate::file_utils::zero_range;
+use crate::io_engine::buffer::Buffer;
+use crate::io_engine::{is_page_aligned, PAGE_SIZE};
+
+#[cfg(test)]
+mod tests;
+
+//-------------------------------------
+
+/// A copier that reads the destination as well as the source, and leaves
+/// alone the blocks that already match.  Source blocks that are all
+/// zeroes are discarded at the destination, rather than written.
+///
+/// This is for re-running a copy that was interrupted, where most of the
+/// destination is already up to date; it saves writes at the cost of
+/// twice the reads.
+///
+/// With verify_only set nothing is written; blocks that don't match are
+/// reported as write errors instead, to check a copy that's been made.
+pub struct CompareCopier {
+    block_size: usize,
+    verify_only: bool,
+    buffer_blocks: usize,
+    src: Arc<File>,
+    src_offset: u64,
+    dst: Arc<File>,
+    dst_offset: u64,
+    src_buf: Buffer,
+    dst_buf: Buffer,
+}
+
+// A run of ops, from index begin to end, where the blocks on one side are
+// adjacent.
+struct Run {
+    begin: usize,
+    end: usize,
+    block: Block,
+}
+
+// Joins up the ops whose blocks, as picked out by key, follow on from the
+// op before.
+fn mk_runs<F: Fn(usize) -> Block>(indexes: &[usize], key: F) -> Vec<Run> {
+    let mut runs: Vec<Run> = Vec::new();
+    for &i in indexes {
+        if let Some(r) = runs.last_mut() {
+            if r.end == i && r.block + (r.end - r.begin) as u64 == key(i) {
+                r.end += 1;
+                continue;
+            }
+        }
+        runs.push(Run {
+            begin: i,
+            end: i + 1,
+            block: key(i),
+        });
+    }
+    runs
+}
+
+fn is_zero(data: &[u8]) -> bool {
+    data.iter().all(|b| *b == 0)
+}
+
+impl CompareCopier {
+    pub fn new(buffer_size: usize, block_size: usize, src: File, dst: File) -> Result<Self> {
+        Self::new_(buffer_size, block_size, Arc::new(src), Arc::new(dst))
+    }
+
+    // Copying regions within a file
+    pub fn in_file(buffer_size: usize, block_size: usize, in_out: File) -> Result<Self> {
+        let f = Arc::new(in_out);
+        Self::new_(buffer_size, block_size, f.clone(), f)
+    }
+
+    fn new_(buffer_size: usize, block_size: usize, src: Arc<File>, dst: Arc<File>) -> Result<Self> {
+        if block_size > buffer_size {
+            return Err(anyhow!("buffer size too small"));
+        }
+
+        let buffer_blocks = buffer_size / block_size;
+        Ok(Self {
+            block_size,
+            verify_only: false,
+            buffer_blocks,
+            src,
+            src_offset: 0,
+            dst,
+            dst_offset: 0,
+            src_buf: Buffer::new(buffer_blocks * block_size, PAGE_SIZE),
+            dst_buf: Buffer::new(buffer_blocks * block_size, PAGE_SIZE),
+        })
+    }
+
+    pub fn from_path<P: AsRef<Path>>(
+        buffer_size: usize,
+        block_size: usize,
+        src: P,
+        dst: P,
+    ) -> Result<Self> {
+        // must be a multiple of page size because we use O_DIRECT
+        if !is_page_aligned(block_size as u64) || !is_page_aligned(buffer_size as u64) {
+            return Err(anyhow!("block size must be page aligned"));
+        }
+
+        let src_file = OpenOptions::new()
+            .read(true)
+            .custom_flags(libc::O_EXCL | libc::O_DIRECT)
+            .open(src)?;
+
+        // the destination is read too
+        let dst_file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .custom_flags(libc::O_EXCL | libc::O_DIRECT)
+            .open(dst)?;
+
+        Self::new(buffer_size, block_size, src_file, dst_file)
+    }
+
+    pub fn src_offset(mut self, offset: u64) -> Result<Self> {
+        if !is_page_aligned(offset) {
+            return Err(anyhow!("offset must be page aligned"));
+        }
+        self.src_offset = offset;
+        Ok(self)
+    }
+
+    pub fn dest_offset(mut self, offset: u64) -> Result<Self> {
+        if !is_page_aligned(offset) {
+            return Err(anyhow!("offset must be page aligned"));
+        }
+        self.dst_offset = offset;
+        Ok(self)
+    }
+
+    pub fn verify_only(mut self, verify: bool) -> Self {
+        self.verify_only = verify;
+        self
+    }
+
+    // Reads the runs into the buffer, at the index of their first op.  A
+    // run that fails is read again a block at a time, so only the bad
+    // blocks are lost.  Returns the ops that were read.
+    fn read_runs(
+        &self,
+        file: &File,
+        offset: u64,
+        buf: &mut [u8],
+        runs: &[Run],
+        nr_ops: usize,
+    ) -> FixedBitSet {
+        let bs = self.block_size;
+        let mut success = FixedBitSet::with_capacity(nr_ops);
+        for r in runs {
+            let data = &mut buf[r.begin * bs..r.end * bs];
+            let pos = r.block * bs as u64 + offset;
+            if file.read_exact_at(data, pos).is_ok() {
+                success.insert_range(r.begin..r.end);
+                continue;
+            }
+
+            for (i, data) in data.chunks_mut(bs).enumerate() {
+                let pos = (r.block + i as u64) * bs as u64 + offset;
+                if file.read_exact_at(data, pos).is_ok() {
+                    success.insert(r.begin + i);
+                }
+            }
+        }
+        success
+    }
+
+    // Writes the runs from the buffer, falling back to a block at a time
+    // like read_runs().  Returns the ops that were written.
+    fn write_runs(&self, buf: &[u8], runs: &[Run], nr_ops: usize) -> FixedBitSet {
+        let bs = self.block_size;
+        let mut success = FixedBitSet::with_capacity(nr_ops);
+        for r in runs {
+            let data = &buf[r.begin * bs..r.end * bs];
+            let pos = r.block * bs as u64 + self.dst_offset;
+            if self.dst.write_all_at(data, pos).is_ok() {
+                success.insert_range(r.begin..r.end);
+                continue;
+            }
+
+            for (i, data) in data.chunks(bs).enumerate() {
+                let pos = (r.block + i as u64) * bs as u64 + self.dst_offset;
+                if self.dst.write_all_at(data, pos).is_ok() {
+                    success.insert(r.begin + i);
+                }
+            }
+        }
+        success
+    }
+
+    fn copy_chunk(&self, ops: &[CopyOp], stats: &mut CopyStats) {
+        let bs = self.block_size;
+        let src_buf = self.src_buf.get_data();
+        let dst_buf = self.dst_buf.get_data();
+        let all: Vec<usize> = (0..ops.len()).collect();
+
+        let runs = mk_runs(&all, |i| ops[i].src);
+        let src_ok = self.read_runs(&self.src, self.src_offset, src_buf, &runs, ops.len());
+
+        // A destination block that can't be read is simply written.
+        let runs = mk_runs(&all, |i| ops[i].dst);
+        let dst_ok = self.read_runs(&self.dst, self.dst_offset, dst_buf, &runs, ops.len());
+
+        let mut writes = Vec::new();
+        let mut zeroes = Vec::new();
+        for (i, op) in ops.iter().enumerate() {
+            if !src_ok.contains(i) {
+                stats.read_errors.push(*op);
+                continue;
+            }
+
+            let data = &src_buf[i * bs..(i + 1) * bs];
+            if dst_ok.contains(i) && data == &dst_buf[i * bs..(i + 1) * bs] {
+                if !self.verify_only {
+                    stats.nr_skipped += 1;
+                }
+                stats.nr_copied += 1;
+            } else if self.verify_only {
+                stats.write_errors.push(*op);
+            } else if is_zero(data) {
+                zeroes.push(i);
+            } else {
+                writes.push(i);
+            }
+        }
+
+        let runs = mk_runs(&writes, |i| ops[i].dst);
+        let written = self.write_runs(src_buf, &runs, ops.len());
+        for i in writes {
+            if written.contains(i) {
+                stats.nr_copied += 1;
+            } else {
+                stats.write_errors.push(ops[i]);
+            }
+        }
+
+        // The zeroes are joined up by destination alone, since there's
+        // nothing to write from the buffer.
+        let mut zero_runs: Vec<(Block, Vec<usize>)> = Vec::new();
+        for i in zeroes {
+            match zero_runs.last_mut() {
+                Some((b, members)) if *b + members.len() as u64 == ops[i].dst => members.push(i),
+                _ => zero_runs.push((ops[i].dst, vec![i])),
+            }
+        }
+        for (block, members) in zero_runs {
+            let len = members.len() as u64;
+            let pos = block * bs as u64 + self.dst_offset;
+            if zero_range(&self.dst, pos, len * bs as u64).is_ok() {
+                stats.nr_discarded += len;
+                stats.nr_copied += len;
+            } else {
+                stats.write_errors.extend(members.iter().map(|&i| ops[i]));
+            }
+        }
+    }
+}
+
+impl Copier for CompareCopier {
+    fn copy(
+        &mut self,
+        ops: &[CopyOp],
+        progress: Arc<dyn CopyProgress + Sync + Send>,
+    ) -> Result<CopyStats> {
+        let mut stats = CopyStats::new(ops.len() as u64);
+        for chunk in ops.chunks(self.buffer_blocks) {
+            self.copy_chunk(chunk, &mut stats);
+            progress.update(&stats);
+        }
+
+        progress.inc_stats(&stats);
+        Ok(stats)
+    }
+}
+
+//-------------------------------------
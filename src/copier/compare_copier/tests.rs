@@ -0,0 +1,192 @@
+use super::*;
+use std::os::unix::fs::FileExt;
+use tempfile::NamedTempFile;
+
+use crate::copier::test_utils::*;
+
+//------------------------------------------
+
+const SEED: u64 = 0x27d1c5e90a4b3f68;
+
+fn mirror(nr_blocks: u64) -> Vec<CopyOp> {
+    (0..nr_blocks).map(|b| CopyOp { src: b, dst: b }).collect()
+}
+
+#[test]
+fn test_runs_join_adjacent_blocks() {
+    let blocks = [10, 11, 12, 20, 21, 5];
+    let runs = mk_runs(&[0, 1, 2, 3, 4, 5], |i| blocks[i]);
+    let summary: Vec<(usize, usize, Block)> =
+        runs.iter().map(|r| (r.begin, r.end, r.block)).collect();
+    assert_eq!(summary, vec![(0, 3, 10), (3, 5, 20), (5, 6, 5)]);
+
+    // the ops must be adjacent in the buffer too
+    let runs = mk_runs(&[0, 2], |i| blocks[i]);
+    assert_eq!(runs.len(), 2);
+}
+
+#[test]
+fn test_identical_blocks_are_skipped() {
+    const NR_BLOCKS: u64 = 128;
+    let src = mk_dev(NR_BLOCKS, SEED);
+    let dst = mk_dev(NR_BLOCKS, SEED);
+
+    // opened read only, so any write would fail
+    let ro = OpenOptions::new().read(true).open(dst.path()).unwrap();
+    let mut copier = mk_file_copier(CompareCopier::new, &src, ro);
+    let stats = copy(&mut copier, &mirror(NR_BLOCKS));
+    assert_eq!(stats.nr_copied, NR_BLOCKS);
+    assert_eq!(stats.nr_skipped, NR_BLOCKS);
+    assert_eq!(stats.nr_discarded, 0);
+    assert!(stats.read_errors.is_empty());
+    assert!(stats.write_errors.is_empty());
+}
+
+#[test]
+fn test_changed_blocks_are_copied() {
+    const NR_BLOCKS: u64 = 128;
+    let src = mk_dev(NR_BLOCKS, SEED);
+    let dst = mk_dev(NR_BLOCKS, SEED);
+
+    let changed = [3, 4, 5, 6, 70, 127];
+    let mut stamper = Stamper::new(dst.reopen().unwrap(), 0, BLOCK_SIZE);
+    for b in changed {
+        stamper.visit(b).unwrap();
+    }
+
+    let mut copier = mk_file_copier(CompareCopier::new, &src, dst.reopen().unwrap());
+    let stats = copy(&mut copier, &mirror(NR_BLOCKS));
+    assert_eq!(stats.nr_copied, NR_BLOCKS);
+    assert_eq!(stats.nr_skipped, NR_BLOCKS - changed.len() as u64);
+    assert!(stats.write_errors.is_empty());
+
+    for b in 0..NR_BLOCKS {
+        assert!(stamped_with(&dst, b, SEED, b));
+    }
+}
+
+#[test]
+fn test_zero_blocks_are_discarded() {
+    const NR_BLOCKS: u64 = 64;
+    let src = mk_dev(NR_BLOCKS, SEED);
+    let dst = mk_dev(NR_BLOCKS, 0);
+
+    // zero a couple of runs in the source
+    let zeroes = [8, 9, 10, 11, 40, 63];
+    let zero = vec![0u8; BLOCK_SIZE];
+    for b in zeroes {
+        src.as_file()
+            .write_all_at(&zero, b * BLOCK_SIZE as u64)
+            .unwrap();
+    }
+
+    let mut copier = mk_file_copier(CompareCopier::new, &src, dst.reopen().unwrap());
+    let stats = copy(&mut copier, &mirror(NR_BLOCKS));
+    assert_eq!(stats.nr_copied, NR_BLOCKS);
+    assert_eq!(stats.nr_skipped, 0);
+    assert_eq!(stats.nr_discarded, zeroes.len() as u64);
+
+    for b in 0..NR_BLOCKS {
+        if zeroes.contains(&b) {
+            assert!(read_block(&dst, b).get_data().iter().all(|v| *v == 0));
+        } else {
+            assert!(stamped_with(&dst, b, SEED, b));
+        }
+    }
+
+    // Zeroes already at the destination are left alone
+    let stats = copy(&mut copier, &mirror(NR_BLOCKS));
+    assert_eq!(stats.nr_skipped, NR_BLOCKS);
+    assert_eq!(stats.nr_discarded, 0);
+}
+
+#[test]
+fn test_verify_only_reports_mismatches() {
+    const NR_BLOCKS: u64 = 64;
+    let src = mk_dev(NR_BLOCKS, SEED);
+    let dst = mk_dev(NR_BLOCKS, SEED);
+
+    let changed = [0, 31, 32, 63];
+    let mut stamper = Stamper::new(dst.reopen().unwrap(), 0, BLOCK_SIZE);
+    for b in changed {
+        stamper.visit(b).unwrap();
+    }
+
+    // opened read only, so any write would fail
+    let ro = OpenOptions::new().read(true).open(dst.path()).unwrap();
+    let mut copier = mk_file_copier(CompareCopier::new, &src, ro).verify_only(true);
+    let stats = copy(&mut copier, &mirror(NR_BLOCKS));
+    assert_eq!(stats.nr_copied, NR_BLOCKS - changed.len() as u64);
+    assert_eq!(stats.nr_skipped, 0);
+    let mismatched: Vec<Block> = stats.write_errors.iter().map(|op| op.dst).collect();
+    assert_eq!(mismatched, changed);
+    assert!(stats.read_errors.is_empty());
+
+    // and the destination is untouched
+    for b in changed {
+        assert!(stamped_with(&dst, b, 0, b));
+    }
+}
+
+#[test]
+fn test_read_errors_are_reported() {
+    const NR_BLOCKS: u64 = 64;
+    let src = mk_dev(NR_BLOCKS, SEED);
+    let dst = mk_dev(NR_BLOCKS * 2, 0);
+
+    // the reads past the end of the source fail
+    let ops: Vec<CopyOp> = (NR_BLOCKS - 4..NR_BLOCKS + 4)
+        .map(|b| CopyOp { src: b, dst: b })
+        .collect();
+    let mut copier = mk_file_copier(CompareCopier::new, &src, dst.reopen().unwrap());
+    let stats = copy(&mut copier, &ops);
+    assert_eq!(stats.nr_copied, 4);
+    assert_eq!(stats.read_errors, ops[4..]);
+    assert!(stats.write_errors.is_empty());
+
+    // nothing is written for a failed read
+    for op in &stats.read_errors {
+        assert!(stamped_with(&dst, op.dst, 0, op.dst));
+    }
+}
+
+#[test]
+fn test_unreadable_destination_is_written() {
+    const NR_BLOCKS: u64 = 32;
+    let src = mk_dev(NR_BLOCKS, SEED);
+    let dst = NamedTempFile::new().unwrap();
+
+    // the destination starts out empty, so none of it can be read
+    let mut copier = mk_file_copier(CompareCopier::new, &src, dst.reopen().unwrap());
+    let stats = copy(&mut copier, &mirror(NR_BLOCKS));
+    assert_eq!(stats.nr_copied, NR_BLOCKS);
+    assert_eq!(stats.nr_skipped, 0);
+
+    for b in 0..NR_BLOCKS {
+        assert!(stamped_with(&dst, b, SEED, b));
+    }
+}
+
+#[test]
+fn test_in_file() {
+    const NR_BLOCKS: u64 = 128;
+    let dev = mk_dev(NR_BLOCKS, SEED);
+
+    let ops: Vec<CopyOp> = (64..NR_BLOCKS)
+        .map(|b| CopyOp {
+            src: b,
+            dst: b - 64,
+        })
+        .collect();
+    let mut copier =
+        CompareCopier::in_file(BUFFER_SIZE, BLOCK_SIZE, dev.reopen().unwrap()).unwrap();
+    let stats = copy(&mut copier, &ops);
+    assert_eq!(stats.nr_copied, 64);
+    assert_eq!(stats.nr_skipped, 0);
+
+    for b in 0..64 {
+        assert!(stamped_with(&dev, b, SEED, b + 64));
+    }
+}
+
+//------------------------------------------
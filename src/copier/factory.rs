@@ -0,0 +1,107 @@
+use anyhow::Result;
+use std::fs::File;
+use std::path::Path;
+
+use crate::copier::*;
+use crate::io_engine::utils::VectoredBlockIo;
+
+//-------------------------------------
+
+/// Where a copier reads from and writes to.
+pub enum CopierDevs<'a> {
+    /// Opens the devices exclusively with O_DIRECT.  The offsets are in
+    /// bytes, and must be page aligned.
+    Paths {
+        src: &'a Path,
+        dst: &'a Path,
+        src_offset: u64,
+        dst_offset: u64,
+    },
+
+    /// Copies between two open files
+    Files { src: File, dst: File },
+
+    /// Moves blocks about within a single file
+    InFile(File),
+}
+
+/// Builds a copier of the given type.  The buffer and block sizes are in
+/// bytes.
+pub fn mk_copier(
+    copier_type: CopierType,
+    buffer_size: usize,
+    block_size: usize,
+    devs: CopierDevs,
+) -> Result<Box<dyn Copier + Send>> {
+    let copier: Box<dyn Copier + Send> = match (copier_type, devs) {
+        (
+            CopierType::Sync,
+            CopierDevs::Paths {
+                src,
+                dst,
+                src_offset,
+                dst_offset,
+            },
+        ) => Box::new(
+            SyncCopier::<VectoredBlockIo<File>>::from_path(buffer_size, block_size, src, dst)?
+                .src_offset(src_offset)?
+                .dest_offset(dst_offset)?,
+        ),
+        (CopierType::Sync, CopierDevs::Files { src, dst }) => {
+            let src: VectoredBlockIo<File> = src.into();
+            let dst: VectoredBlockIo<File> = dst.into();
+            Box::new(SyncCopier::new(buffer_size, block_size, src, dst)?)
+        }
+        (CopierType::Sync, CopierDevs::InFile(file)) => {
+            let vio: VectoredBlockIo<File> = file.into();
+            Box::new(SyncCopier::in_file(buffer_size, block_size, vio)?)
+        }
+
+        #[cfg(feature = "io_uring")]
+        (
+            CopierType::Async,
+            CopierDevs::Paths {
+                src,
+                dst,
+                src_offset,
+                dst_offset,
+            },
+        ) => Box::new(
+            AsyncCopier::from_path(buffer_size, block_size, src, dst)?
+                .src_offset(src_offset)?
+                .dest_offset(dst_offset)?,
+        ),
+        #[cfg(feature = "io_uring")]
+        (CopierType::Async, CopierDevs::Files { src, dst }) => {
+            Box::new(AsyncCopier::new(buffer_size, block_size, src, dst)?)
+        }
+        #[cfg(feature = "io_uring")]
+        (CopierType::Async, CopierDevs::InFile(file)) => {
+            Box::new(AsyncCopier::in_file(buffer_size, block_size, file)?)
+        }
+
+        (
+            CopierType::Compare,
+            CopierDevs::Paths {
+                src,
+                dst,
+                src_offset,
+                dst_offset,
+            },
+        ) => Box::new(
+            CompareCopier::from_path(buffer_size, block_size, src, dst)?
+                .src_offset(src_offset)?
+                .dest_offset(dst_offset)?,
+        ),
+        (CopierType::Compare, CopierDevs::Files { src, dst }) => {
+            Box::new(CompareCopier::new(buffer_size, block_size, src, dst)?)
+        }
+        (CopierType::Compare, CopierDevs::InFile(file)) => {
+            Box::new(CompareCopier::in_file(buffer_size, block_size, file)?)
+        }
+    };
+
+    Ok(copier)
+}
+
+//-------------------------------------
@@ -1,14 +1,28 @@
 pub mod base;
 pub mod batcher;
+pub mod compare_copier;
+pub mod factory;
 pub mod report;
 pub mod rescue_copier;
+pub mod rescue_map;
 pub mod sync_copier;
+pub mod throttle;
 pub mod wrapper;
 
 pub use crate::copier::base::*;
+pub use crate::copier::compare_copier::CompareCopier;
+pub use crate::copier::factory::{mk_copier, CopierDevs};
 pub use crate::copier::report::*;
 pub use crate::copier::rescue_copier::RescueCopier;
+pub use crate::copier::rescue_map::{MappedCopier, RescueMap};
 pub use crate::copier::sync_copier::SyncCopier;
+pub use crate::copier::throttle::{CopyLimits, IoPriority};
+
+#[cfg(feature = "io_uring")]
+pub mod async_copier;
+
+#[cfg(feature = "io_uring")]
+pub use crate::copier::async_copier::AsyncCopier;
 
 #[cfg(any(test, feature = "devtools"))]
 pub mod test_utils;
@@ -1,4 +1,5 @@
 use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 
 use crate::copier::{CopyProgress, CopyStats};
 use crate::report::Report;
@@ -27,36 +28,96 @@ struct AccumulatedStats {
 
     /// Number of write errors
     nr_write_errors: u64,
+
+    /// Number of copied blocks that didn't need writing
+    nr_skipped: u64,
+}
+
+// What the sub title last said, so it's only redrawn when there's news
+struct SubTitle {
+    when: Instant,
+    nr_errors: (u64, u64),
 }
 
+// The copy rate is refreshed this often
+const RATE_INTERVAL: Duration = Duration::from_secs(5);
+
 /// Struct that updates the progress bar in the reporter as the stats are
 /// updated by the copier threads.
 pub struct ProgressReporter {
     report: Arc<Report>,
+    block_size: u64,
+    start: Instant,
     inner: Mutex<AccumulatedStats>,
+    sub_title: Mutex<Option<SubTitle>>,
+}
+
+// eg, "52.4 MiB/s"
+fn format_rate(nr_bytes: u64, elapsed: Duration) -> String {
+    let secs = elapsed.as_secs_f64().max(0.001);
+    format!("{:.1} MiB/s", nr_bytes as f64 / secs / (1024.0 * 1024.0))
 }
 
 impl ProgressReporter {
     /// nr_blocks: total number of blocks to be copied
-    pub fn new(report: Arc<Report>, nr_blocks: u64) -> Self {
+    /// block_size: in bytes, for working out the copy rate
+    pub fn new(report: Arc<Report>, nr_blocks: u64, block_size: usize) -> Self {
         Self {
             report,
+            block_size: block_size as u64,
+            start: Instant::now(),
             inner: Mutex::new(AccumulatedStats {
                 nr_blocks,
                 nr_copied: 0,
                 nr_read_errors: 0,
                 nr_write_errors: 0,
+                nr_skipped: 0,
             }),
+            sub_title: Mutex::new(None),
         }
     }
 
-    fn update_error_logs(&self, nr_read_errors: u64, nr_write_errors: u64) {
+    /// The number of blocks so far that were up to date already, or
+    /// discarded rather than written.
+    pub fn nr_skipped(&self) -> u64 {
+        self.inner.lock().unwrap().nr_skipped
+    }
+
+    // Shows the effective copy rate, and any errors.  New errors are shown
+    // straight away, the rate only every so often.
+    fn update_sub_title(
+        &self,
+        nr_copied: u64,
+        nr_skipped: u64,
+        nr_read_errors: u64,
+        nr_write_errors: u64,
+    ) {
+        let mut last = self.sub_title.lock().unwrap();
+        let nr_errors = (nr_read_errors, nr_write_errors);
+        if let Some(last) = &*last {
+            if last.nr_errors == nr_errors && last.when.elapsed() < RATE_INTERVAL {
+                return;
+            }
+        }
+
+        let mut txt = format!(
+            "copying at {}",
+            format_rate(nr_copied * self.block_size, self.start.elapsed())
+        );
+        if nr_skipped > 0 {
+            txt += &format!(", {} writes avoided", nr_skipped);
+        }
         if nr_read_errors > 0 || nr_write_errors > 0 {
-            self.report.set_sub_title(&format!(
-                "read errors {}, write errors {}",
+            txt += &format!(
+                ", read errors {}, write errors {}",
                 nr_read_errors, nr_write_errors
-            ));
+            );
         }
+        self.report.set_sub_title(&txt);
+        *last = Some(SubTitle {
+            when: Instant::now(),
+            nr_errors,
+        });
     }
 
     fn update_progress(&self, nr_copied: u64, nr_blocks: u64) {
@@ -71,7 +132,9 @@ impl CopyProgress for ProgressReporter {
     fn update(&self, stats: &CopyStats) {
         let inner = self.inner.lock().unwrap();
 
-        self.update_error_logs(
+        self.update_sub_title(
+            inner.nr_copied + stats.nr_copied,
+            inner.nr_skipped + stats.nr_skipped + stats.nr_discarded,
             inner.nr_read_errors + stats.read_errors.len() as u64,
             inner.nr_write_errors + stats.write_errors.len() as u64,
         );
@@ -85,10 +148,30 @@ impl CopyProgress for ProgressReporter {
         inner.nr_copied += stats.nr_copied;
         inner.nr_read_errors += stats.read_errors.len() as u64;
         inner.nr_write_errors += stats.write_errors.len() as u64;
+        inner.nr_skipped += stats.nr_skipped + stats.nr_discarded;
 
-        self.update_error_logs(inner.nr_read_errors, inner.nr_write_errors);
+        self.update_sub_title(
+            inner.nr_copied,
+            inner.nr_skipped,
+            inner.nr_read_errors,
+            inner.nr_write_errors,
+        );
         self.update_progress(inner.nr_copied, inner.nr_blocks);
     }
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_rate() {
+        assert_eq!(
+            format_rate(100 * 1024 * 1024, Duration::from_secs(2)),
+            "50.0 MiB/s"
+        );
+        assert_eq!(format_rate(0, Duration::ZERO), "0.0 MiB/s");
+    }
+}
+
 //-----------------------------------------
@@ -15,8 +15,24 @@ mod tests;
 
 //-------------------------------------
 
+/// The granularity for one of a number of rescue passes.  Each pass
+/// halves it, as far as the block size allows, ending with a page at a
+/// time.
+pub fn pass_granularity(pass: u32, nr_passes: u32, block_size: usize) -> usize {
+    let mut granularity = PAGE_SIZE;
+    for _ in pass + 1..nr_passes {
+        if !block_size.is_multiple_of(granularity * 2) {
+            break;
+        }
+        granularity *= 2;
+    }
+    granularity
+}
+
 pub struct RescueCopier<T: FileExt> {
     block_size: usize,
+    granularity: usize,
+    reverse: bool,
     src: T,
     src_offset: u64,
     dst: T,
@@ -37,6 +53,8 @@ impl<T: FileExt> RescueCopier<T> {
 
         Ok(Self {
             block_size,
+            granularity: PAGE_SIZE,
+            reverse: false,
             src,
             src_offset: 0,
             dst,
@@ -81,10 +99,37 @@ impl<T: FileExt> RescueCopier<T> {
         Ok(self)
     }
 
-    fn do_read(src: &T, pos: u64, buffer: &mut [u8], read_success: &mut FixedBitSet) -> usize {
+    /// Copy in pieces of this many bytes, rather than a page at a time.
+    /// Larger pieces get through a damaged area more quickly, but lose
+    /// more data to each bad sector.
+    pub fn granularity(mut self, granularity: usize) -> Result<RescueCopier<T>> {
+        if !is_page_aligned(granularity as u64) || !self.block_size.is_multiple_of(granularity) {
+            return Err(anyhow!(
+                "granularity must be page aligned, and divide the block size"
+            ));
+        }
+        self.granularity = granularity;
+        self.read_success = FixedBitSet::with_capacity(self.block_size / granularity);
+        Ok(self)
+    }
+
+    /// Work through the ops backwards.  Approaching a damaged area from
+    /// the other side often gets more of it back.
+    pub fn reverse(mut self, reverse: bool) -> RescueCopier<T> {
+        self.reverse = reverse;
+        self
+    }
+
+    fn do_read(
+        src: &T,
+        pos: u64,
+        granularity: usize,
+        buffer: &mut [u8],
+        read_success: &mut FixedBitSet,
+    ) -> usize {
         let mut read_fails = 0;
-        for (i, chunk) in buffer.chunks_mut(PAGE_SIZE).enumerate() {
-            let p = pos + (i << PAGE_SHIFT) as u64;
+        for (i, chunk) in buffer.chunks_mut(granularity).enumerate() {
+            let p = pos + (i * granularity) as u64;
             if src.read_exact_at(chunk, p).is_ok() {
                 read_success.insert(i);
             } else {
@@ -94,11 +139,17 @@ impl<T: FileExt> RescueCopier<T> {
         read_fails
     }
 
-    fn do_write(dst: &T, pos: u64, buffer: &mut [u8], selected_pages: &FixedBitSet) -> u32 {
+    fn do_write(
+        dst: &T,
+        pos: u64,
+        granularity: usize,
+        buffer: &mut [u8],
+        selected_pages: &FixedBitSet,
+    ) -> u32 {
         let mut write_fails = 0;
         for i in selected_pages.ones() {
-            let offset = i << PAGE_SHIFT;
-            let page_buf = &buffer[offset..offset + PAGE_SIZE];
+            let offset = i * granularity;
+            let page_buf = &buffer[offset..offset + granularity];
             if dst.write_all_at(page_buf, pos + offset as u64).is_err() {
                 write_fails += 1;
             }
@@ -116,20 +167,36 @@ impl<T: FileExt + 'static> Copier for RescueCopier<T> {
         let mut stats = CopyStats::new(ops.len() as u64);
         let mut nr_copied = 0;
 
+        let ops: Box<dyn Iterator<Item = &CopyOp>> = if self.reverse {
+            Box::new(ops.iter().rev())
+        } else {
+            Box::new(ops.iter())
+        };
+
         // copy loop
         for op in ops {
             self.read_success.clear();
 
             let pos = self.src_offset + op.src * self.block_size as u64;
-            let read_fails =
-                Self::do_read(&self.src, pos, self.buf.get_data(), &mut self.read_success);
+            let read_fails = Self::do_read(
+                &self.src,
+                pos,
+                self.granularity,
+                self.buf.get_data(),
+                &mut self.read_success,
+            );
             if read_fails > 0 {
                 stats.read_errors.push(*op);
             }
 
             let pos = self.dst_offset + op.dst * self.block_size as u64;
-            let write_fails =
-                Self::do_write(&self.dst, pos, self.buf.get_data(), &self.read_success);
+            let write_fails = Self::do_write(
+                &self.dst,
+                pos,
+                self.granularity,
+                self.buf.get_data(),
+                &self.read_success,
+            );
             if write_fails > 0 {
                 stats.write_errors.push(*op);
                 continue;
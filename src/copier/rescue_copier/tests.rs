@@ -202,12 +202,19 @@ impl CopierTest {
     }
 
     fn copy(&self, ops: &[CopyOp]) -> Result<CopyStats> {
+        self.copy_with(ops, PAGE_SIZE, false)
+    }
+
+    fn copy_with(&self, ops: &[CopyOp], granularity: usize, reverse: bool) -> Result<CopyStats> {
         let mut copier = RescueCopier::<Ramdisk>::new(
             self.block_size,
             self.src.try_clone().unwrap(),
             self.dst.try_clone().unwrap(),
         )
-        .unwrap();
+        .unwrap()
+        .granularity(granularity)
+        .unwrap()
+        .reverse(reverse);
 
         let progress = Arc::new(IgnoreProgress {});
         copier.copy(ops, progress)
@@ -295,4 +302,73 @@ fn partial_copy_with_unreadable_and_unwritable_pages() -> Result<()> {
     t.verify(&ops)
 }
 
+#[test]
+fn coarse_granularity_loses_whole_pieces() -> Result<()> {
+    const NR_BLOCKS: u64 = 16;
+    let mut t = CopierTest::new(BLOCK_SIZE, NR_BLOCKS as u32, NR_BLOCKS as u32);
+    t.stamp_src_dev()?;
+    t.stamp_dst_dev()?;
+    t.invalidate_src_dev(65536..69632)?;
+
+    // the rest of the 16k piece around the bad page is lost too
+    t.faulty_src_pages.insert_range(16..20);
+
+    let ops: Vec<CopyOp> = (0..NR_BLOCKS).map(|b| CopyOp { src: b, dst: b }).collect();
+    let stats = t.copy_with(&ops, 16384, false).unwrap();
+    assert_eq!(stats.nr_copied, NR_BLOCKS - 1);
+    assert_eq!(stats.read_errors.len(), 1);
+    assert_eq!(stats.read_errors[0].src, 2);
+
+    t.verify(&ops)
+}
+
+#[test]
+fn reverse_copy() -> Result<()> {
+    const NR_BLOCKS: u64 = 16;
+    let mut t = CopierTest::new(BLOCK_SIZE, NR_BLOCKS as u32, NR_BLOCKS as u32);
+    t.stamp_src_dev()?;
+    t.stamp_dst_dev()?;
+    t.invalidate_src_dev(65536..69632)?;
+    t.invalidate_src_dev(327680..331776)?;
+
+    let ops: Vec<CopyOp> = (0..NR_BLOCKS).map(|b| CopyOp { src: b, dst: b }).collect();
+    let stats = t.copy_with(&ops, PAGE_SIZE, true).unwrap();
+    assert_eq!(stats.nr_copied, NR_BLOCKS - 2);
+
+    // the later block failed first
+    let failed: Vec<u64> = stats.read_errors.iter().map(|op| op.src).collect();
+    assert_eq!(failed, vec![10, 2]);
+
+    t.verify(&ops)
+}
+
+#[test]
+fn bad_granularity() {
+    let t = CopierTest::new(BLOCK_SIZE, 1, 1);
+    let copier = || {
+        RescueCopier::<Ramdisk>::new(
+            t.block_size,
+            t.src.try_clone().unwrap(),
+            t.dst.try_clone().unwrap(),
+        )
+        .unwrap()
+    };
+    assert!(copier().granularity(6144).is_err());
+    assert!(copier().granularity(65536).is_err());
+    assert!(copier().granularity(8192).is_ok());
+}
+
+#[test]
+fn granularity_halves_each_pass() {
+    let passes: Vec<usize> = (0..4).map(|p| pass_granularity(p, 4, 32768)).collect();
+    assert_eq!(passes, vec![32768, 16384, 8192, 4096]);
+
+    // a single pass works a page at a time, as the copier always has
+    assert_eq!(pass_granularity(0, 1, 32768), 4096);
+
+    // never larger than the block, and always dividing it
+    assert_eq!(pass_granularity(0, 8, 32768), 32768);
+    assert_eq!(pass_granularity(0, 8, 98304), 32768);
+}
+
 //------------------------------------------
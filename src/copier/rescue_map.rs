@@ -0,0 +1,298 @@
+use anyhow::{anyhow, Context, Result};
+use rangemap::RangeMap;
+use std::fs::{self, File};
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use crate::copier::*;
+
+#[cfg(test)]
+mod tests;
+
+//-------------------------------------
+
+// A rescue map records how the copy of each source block went, so a copy
+// from a failing device can be stopped and resumed, and the bad areas
+// retried.  It's a text file in the spirit of a ddrescue map:
+//
+//   # rescue map
+//   # source: cache superblock 7c3e91a0
+//   # block_size: 32768
+//   # nr_blocks: 4096
+//   0 1000 +
+//   1000 2 -
+//   1002 3094 ?
+//
+// Each line is a run of blocks: the first block, the number of blocks
+// and the state, which is one of:
+//
+//   ?  not tried yet
+//   +  copied
+//   -  failed, some of it may have been copied
+//
+// The source line identifies the state of the source when the map was
+// made, eg. by the checksum of its metadata.  A map is only any use while
+// the source is unchanged, since a block it says was copied may have been
+// written to since, so a map for another source is refused.
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BlockState {
+    Untried,
+    Good,
+    Bad,
+}
+
+impl BlockState {
+    fn to_char(self) -> char {
+        match self {
+            BlockState::Untried => '?',
+            BlockState::Good => '+',
+            BlockState::Bad => '-',
+        }
+    }
+
+    fn from_str(s: &str) -> Option<Self> {
+        match s {
+            "?" => Some(BlockState::Untried),
+            "+" => Some(BlockState::Good),
+            "-" => Some(BlockState::Bad),
+            _ => None,
+        }
+    }
+}
+
+pub struct RescueMap {
+    path: PathBuf,
+    source: String,
+    block_size: u64,
+    nr_blocks: u64,
+    states: RangeMap<u64, BlockState>,
+}
+
+fn parse_header(line: &str, key: &str) -> Option<u64> {
+    line.strip_prefix("# ")?
+        .strip_prefix(key)?
+        .strip_prefix(": ")?
+        .parse::<u64>()
+        .ok()
+}
+
+impl RescueMap {
+    /// Opens the map for a copy of nr_blocks blocks of the given size, in
+    /// bytes, from the given source.  A new map is started if the file
+    /// doesn't exist; if it does, it must be for a copy of the same shape
+    /// from the same source.
+    pub fn open(path: &Path, source: &str, block_size: usize, nr_blocks: u64) -> Result<Self> {
+        let mut map = RescueMap {
+            path: path.to_path_buf(),
+            source: source.to_string(),
+            block_size: block_size as u64,
+            nr_blocks,
+            states: RangeMap::new(),
+        };
+
+        if nr_blocks > 0 {
+            map.states.insert(0..nr_blocks, BlockState::Untried);
+        }
+
+        if path.exists() {
+            map.load()
+                .with_context(|| format!("couldn't load rescue map {}", path.display()))?;
+        }
+        Ok(map)
+    }
+
+    fn load(&mut self) -> Result<()> {
+        let input = BufReader::new(File::open(&self.path)?);
+        let mut source = None;
+        for (n, line) in input.lines().enumerate() {
+            let line = line?;
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            if line.starts_with('#') {
+                if let Some(s) = line.strip_prefix("# source: ") {
+                    source = Some(s.to_string());
+                } else if let Some(bs) = parse_header(line, "block_size") {
+                    if bs != self.block_size {
+                        return Err(anyhow!(
+                            "block size is {}, but the copy is of {} byte blocks",
+                            bs,
+                            self.block_size
+                        ));
+                    }
+                } else if let Some(nr) = parse_header(line, "nr_blocks") {
+                    if nr != self.nr_blocks {
+                        return Err(anyhow!(
+                            "map is for {} blocks, but the copy is of {}",
+                            nr,
+                            self.nr_blocks
+                        ));
+                    }
+                }
+                continue;
+            }
+
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let run = match fields[..] {
+                [begin, len, state] => begin
+                    .parse::<u64>()
+                    .ok()
+                    .zip(len.parse::<u64>().ok())
+                    .zip(BlockState::from_str(state)),
+                _ => None,
+            };
+            let ((begin, len), state) =
+                run.ok_or_else(|| anyhow!("line {}: couldn't parse '{}'", n + 1, line))?;
+            if len == 0 || begin + len > self.nr_blocks {
+                return Err(anyhow!("line {}: run is out of range", n + 1));
+            }
+            self.states.insert(begin..begin + len, state);
+        }
+
+        match source {
+            Some(s) if s == self.source => Ok(()),
+            Some(s) => Err(anyhow!(
+                "map is for {}, but the source is now {}; remove it to start again",
+                s,
+                self.source
+            )),
+            None => Err(anyhow!("map doesn't say which source it's for")),
+        }
+    }
+
+    /// Writes the map out.  The old map is replaced in one go, so a crash
+    /// leaves one or the other.
+    pub fn save(&self) -> Result<()> {
+        let mut tmp = self.path.clone().into_os_string();
+        tmp.push(".tmp");
+        let tmp = PathBuf::from(tmp);
+
+        {
+            let file = File::create(&tmp)?;
+            let mut out = BufWriter::new(&file);
+            writeln!(out, "# rescue map")?;
+            writeln!(out, "# source: {}", self.source)?;
+            writeln!(out, "# block_size: {}", self.block_size)?;
+            writeln!(out, "# nr_blocks: {}", self.nr_blocks)?;
+            for (r, state) in self.states.iter() {
+                writeln!(out, "{} {} {}", r.start, r.end - r.start, state.to_char())?;
+            }
+            out.flush()?;
+            drop(out);
+            file.sync_all()?;
+        }
+
+        fs::rename(&tmp, &self.path)
+            .with_context(|| format!("couldn't save rescue map {}", self.path.display()))?;
+        Ok(())
+    }
+
+    pub fn state(&self, b: Block) -> BlockState {
+        *self.states.get(&b).unwrap_or(&BlockState::Untried)
+    }
+
+    pub fn set(&mut self, b: Block, state: BlockState) {
+        if b < self.nr_blocks {
+            self.states.insert(b..b + 1, state);
+        }
+    }
+
+    /// The blocks in the given state, in order
+    pub fn blocks(&self, state: BlockState) -> Vec<Block> {
+        self.states
+            .iter()
+            .filter(|(_, s)| **s == state)
+            .flat_map(|(r, _)| r.clone())
+            .collect()
+    }
+
+    pub fn nr_blocks_in(&self, state: BlockState) -> u64 {
+        self.states
+            .iter()
+            .filter(|(_, s)| **s == state)
+            .map(|(r, _)| r.end - r.start)
+            .sum()
+    }
+}
+
+//-------------------------------------
+
+/// Wraps a copier, recording in the map how each block went, keyed by
+/// the source block.  Blocks the map says were copied already are left
+/// alone, which is how a copy resumes.  Blocks that failed before are
+/// reported as read errors without being tried, unless the copier is
+/// retrying them.
+pub struct MappedCopier<C> {
+    copier: C,
+    map: Arc<Mutex<RescueMap>>,
+    retry_bad: bool,
+}
+
+impl<C: Copier> MappedCopier<C> {
+    pub fn new(copier: C, map: Arc<Mutex<RescueMap>>) -> Self {
+        Self {
+            copier,
+            map,
+            retry_bad: false,
+        }
+    }
+
+    pub fn retry_bad(mut self, retry: bool) -> Self {
+        self.retry_bad = retry;
+        self
+    }
+}
+
+impl<C: Copier> Copier for MappedCopier<C> {
+    fn copy(
+        &mut self,
+        ops: &[CopyOp],
+        progress: Arc<dyn CopyProgress + Sync + Send>,
+    ) -> Result<CopyStats> {
+        let mut done = CopyStats::new(0);
+        let mut todo = Vec::with_capacity(ops.len());
+        {
+            let map = self.map.lock().unwrap();
+            for op in ops {
+                match map.state(op.src) {
+                    BlockState::Good => {
+                        done.nr_copied += 1;
+                        done.nr_skipped += 1;
+                    }
+                    BlockState::Bad if !self.retry_bad => done.read_errors.push(*op),
+                    _ => todo.push(*op),
+                }
+            }
+        }
+        progress.inc_stats(&done);
+
+        let mut stats = if todo.is_empty() {
+            CopyStats::new(0)
+        } else {
+            self.copier.copy(&todo, progress)?
+        };
+
+        {
+            let mut map = self.map.lock().unwrap();
+            for op in &todo {
+                map.set(op.src, BlockState::Good);
+            }
+            for op in stats.read_errors.iter().chain(stats.write_errors.iter()) {
+                map.set(op.src, BlockState::Bad);
+            }
+            map.save()?;
+        }
+
+        stats.nr_blocks = ops.len() as u64;
+        stats.nr_copied += done.nr_copied;
+        stats.nr_skipped += done.nr_skipped;
+        stats.read_errors.extend(done.read_errors);
+        Ok(stats)
+    }
+}
+
+//-------------------------------------
@@ -0,0 +1,175 @@
+use super::*;
+use tempfile::TempDir;
+
+//------------------------------------------
+
+const BLOCK_SIZE: usize = 32768;
+const SOURCE: &str = "test source 1";
+
+// Fails the reads of some source blocks, and notes what it was asked for
+struct FaultyCopier {
+    bad: Vec<Block>,
+    requested: Vec<Block>,
+}
+
+impl FaultyCopier {
+    fn new(bad: &[Block]) -> Self {
+        Self {
+            bad: bad.to_vec(),
+            requested: Vec::new(),
+        }
+    }
+}
+
+impl Copier for FaultyCopier {
+    fn copy(
+        &mut self,
+        ops: &[CopyOp],
+        _progress: Arc<dyn CopyProgress + Sync + Send>,
+    ) -> Result<CopyStats> {
+        let mut stats = CopyStats::new(ops.len() as u64);
+        for op in ops {
+            self.requested.push(op.src);
+            if self.bad.contains(&op.src) {
+                stats.read_errors.push(*op);
+            } else {
+                stats.nr_copied += 1;
+            }
+        }
+        Ok(stats)
+    }
+}
+
+fn mk_ops(blocks: std::ops::Range<u64>) -> Vec<CopyOp> {
+    blocks.map(|b| CopyOp { src: b, dst: b }).collect()
+}
+
+fn copy<C: Copier>(copier: &mut MappedCopier<C>, ops: &[CopyOp]) -> CopyStats {
+    copier.copy(ops, Arc::new(IgnoreProgress {})).unwrap()
+}
+
+#[test]
+fn test_new_map_is_untried() {
+    let dir = TempDir::new().unwrap();
+    let map = RescueMap::open(&dir.path().join("map"), SOURCE, BLOCK_SIZE, 100).unwrap();
+    assert_eq!(map.nr_blocks_in(BlockState::Untried), 100);
+    assert_eq!(map.state(42), BlockState::Untried);
+}
+
+#[test]
+fn test_save_and_load() {
+    let dir = TempDir::new().unwrap();
+    let path = dir.path().join("map");
+
+    let mut map = RescueMap::open(&path, SOURCE, BLOCK_SIZE, 100).unwrap();
+    for b in 0..50 {
+        map.set(b, BlockState::Good);
+    }
+    map.set(20, BlockState::Bad);
+    map.set(21, BlockState::Bad);
+    map.set(99, BlockState::Bad);
+    map.save().unwrap();
+
+    let text = std::fs::read_to_string(&path).unwrap();
+    let runs: Vec<&str> = text.lines().filter(|l| !l.starts_with('#')).collect();
+    assert_eq!(
+        runs,
+        vec!["0 20 +", "20 2 -", "22 28 +", "50 49 ?", "99 1 -"]
+    );
+
+    let map = RescueMap::open(&path, SOURCE, BLOCK_SIZE, 100).unwrap();
+    assert_eq!(map.blocks(BlockState::Bad), vec![20, 21, 99]);
+    assert_eq!(map.nr_blocks_in(BlockState::Good), 48);
+    assert_eq!(map.nr_blocks_in(BlockState::Untried), 49);
+}
+
+#[test]
+fn test_map_must_match_the_copy() {
+    let dir = TempDir::new().unwrap();
+    let path = dir.path().join("map");
+    RescueMap::open(&path, SOURCE, BLOCK_SIZE, 100)
+        .unwrap()
+        .save()
+        .unwrap();
+
+    assert!(RescueMap::open(&path, SOURCE, BLOCK_SIZE * 2, 100).is_err());
+    assert!(RescueMap::open(&path, SOURCE, BLOCK_SIZE, 101).is_err());
+}
+
+#[test]
+fn test_map_for_another_source_is_refused() {
+    let dir = TempDir::new().unwrap();
+    let path = dir.path().join("map");
+    let mut map = RescueMap::open(&path, SOURCE, BLOCK_SIZE, 100).unwrap();
+    map.set(0, BlockState::Good);
+    map.save().unwrap();
+
+    assert!(RescueMap::open(&path, "test source 2", BLOCK_SIZE, 100).is_err());
+
+    // nor is a map that doesn't say
+    std::fs::write(&path, "0 10 +\n").unwrap();
+    assert!(RescueMap::open(&path, SOURCE, BLOCK_SIZE, 100).is_err());
+}
+
+#[test]
+fn test_bad_lines_are_rejected() {
+    let dir = TempDir::new().unwrap();
+    let path = dir.path().join("map");
+    for text in ["0 10 x\n", "0 ten +\n", "0 0 +\n", "95 10 -\n", "0 10\n"] {
+        std::fs::write(&path, format!("# source: {}\n{}", SOURCE, text)).unwrap();
+        assert!(RescueMap::open(&path, SOURCE, BLOCK_SIZE, 100).is_err());
+    }
+}
+
+#[test]
+fn test_copier_records_results() {
+    let dir = TempDir::new().unwrap();
+    let path = dir.path().join("map");
+    let map = Arc::new(Mutex::new(
+        RescueMap::open(&path, SOURCE, BLOCK_SIZE, 100).unwrap(),
+    ));
+
+    let mut copier = MappedCopier::new(FaultyCopier::new(&[3, 7]), map.clone());
+    let stats = copy(&mut copier, &mk_ops(0..10));
+    assert_eq!(stats.nr_copied, 8);
+    assert_eq!(stats.read_errors.len(), 2);
+
+    // the map was saved as it went
+    let saved = RescueMap::open(&path, SOURCE, BLOCK_SIZE, 100).unwrap();
+    assert_eq!(saved.blocks(BlockState::Bad), vec![3, 7]);
+    assert_eq!(saved.nr_blocks_in(BlockState::Good), 8);
+}
+
+#[test]
+fn test_copier_resumes() {
+    let dir = TempDir::new().unwrap();
+    let path = dir.path().join("map");
+    let map = Arc::new(Mutex::new(
+        RescueMap::open(&path, SOURCE, BLOCK_SIZE, 100).unwrap(),
+    ));
+    let mut copier = MappedCopier::new(FaultyCopier::new(&[3]), map);
+    copy(&mut copier, &mk_ops(0..10));
+
+    // a later run only tries what's left, and reports the bad block
+    // without touching it
+    let map = Arc::new(Mutex::new(
+        RescueMap::open(&path, SOURCE, BLOCK_SIZE, 100).unwrap(),
+    ));
+    let mut copier = MappedCopier::new(FaultyCopier::new(&[]), map.clone());
+    let stats = copy(&mut copier, &mk_ops(0..20));
+    assert_eq!(copier.copier.requested, (10..20).collect::<Vec<u64>>());
+    assert_eq!(stats.nr_blocks, 20);
+    assert_eq!(stats.nr_copied, 19);
+    assert_eq!(stats.nr_skipped, 9);
+    assert_eq!(stats.read_errors, mk_ops(3..4));
+
+    // unless it's retrying them
+    let mut copier = MappedCopier::new(FaultyCopier::new(&[]), map.clone()).retry_bad(true);
+    let stats = copy(&mut copier, &mk_ops(0..20));
+    assert_eq!(copier.copier.requested, vec![3]);
+    assert_eq!(stats.nr_copied, 20);
+    assert!(stats.read_errors.is_empty());
+    assert_eq!(map.lock().unwrap().nr_blocks_in(BlockState::Good), 20);
+}
+
+//------------------------------------------
@@ -1,6 +1,13 @@
 use anyhow::Result;
 use std::os::unix::fs::FileExt;
 
+#[cfg(test)]
+use std::{fs::File, sync::Arc};
+#[cfg(test)]
+use tempfile::NamedTempFile;
+
+#[cfg(test)]
+use crate::copier::*;
 use crate::io_engine::base::PAGE_SIZE;
 use crate::io_engine::buffer::Buffer;
 use crate::random::Generator;
@@ -58,3 +65,55 @@ impl<T: FileExt> BlockVisitor for Stamper<T> {
 }
 
 //------------------------------------------
+
+// Helpers for the copiers that work on files
+
+#[cfg(test)]
+pub const BUFFER_SIZE: usize = 1048576; // 1 MiB
+#[cfg(test)]
+pub const BLOCK_SIZE: usize = 16384; // 16 KiB
+
+/// Creates a temporary device with each block stamped from the seed
+#[cfg(test)]
+pub fn mk_dev(nr_blocks: u64, seed: u64) -> NamedTempFile {
+    let f = NamedTempFile::new().unwrap();
+    f.as_file().set_len(nr_blocks * BLOCK_SIZE as u64).unwrap();
+    let mut stamper = Stamper::new(f.reopen().unwrap(), seed, BLOCK_SIZE);
+    visit_blocks(nr_blocks, &mut stamper).unwrap();
+    f
+}
+
+/// Builds a copier from the src device to dst, eg.
+/// `mk_file_copier(AsyncCopier::new, &src, dst)`
+#[cfg(test)]
+pub fn mk_file_copier<C>(
+    new: fn(usize, usize, File, File) -> Result<C>,
+    src: &NamedTempFile,
+    dst: File,
+) -> C {
+    new(BUFFER_SIZE, BLOCK_SIZE, src.reopen().unwrap(), dst).unwrap()
+}
+
+#[cfg(test)]
+pub fn read_block(dev: &NamedTempFile, block: u64) -> Buffer {
+    let buf = Buffer::new(BLOCK_SIZE, PAGE_SIZE);
+    dev.as_file()
+        .read_exact_at(buf.get_data(), block * BLOCK_SIZE as u64)
+        .unwrap();
+    buf
+}
+
+/// Checks the block was stamped with the seed for the given source block
+#[cfg(test)]
+pub fn stamped_with(dev: &NamedTempFile, block: u64, seed: u64, src: u64) -> bool {
+    Generator::new()
+        .verify_buffer(seed ^ src, read_block(dev, block).get_data())
+        .unwrap()
+}
+
+#[cfg(test)]
+pub fn copy<C: Copier>(copier: &mut C, ops: &[CopyOp]) -> CopyStats {
+    copier.copy(ops, Arc::new(IgnoreProgress {})).unwrap()
+}
+
+//------------------------------------------
@@ -0,0 +1,183 @@
+use anyhow::{anyhow, Result};
+use std::io;
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::copier::*;
+
+#[cfg(test)]
+mod tests;
+
+//-------------------------------------
+
+// Each block copied is a read and a write
+const IOS_PER_BLOCK: u64 = 2;
+
+// Batches are split so each piece takes about this long at the limit,
+// which keeps the pacing smooth.
+const SLICE_TIME: Duration = Duration::from_millis(100);
+
+// How far a copy that's been idle, eg. waiting for the metadata walk, may
+// run ahead of the limit before it's paced again.
+const MAX_BURST: Duration = Duration::from_secs(1);
+
+const IOPRIO_WHO_PROCESS: libc::c_int = 1;
+const IOPRIO_CLASS_SHIFT: u32 = 13;
+const IOPRIO_CLASS_RT: u32 = 1;
+const IOPRIO_CLASS_BE: u32 = 2;
+const IOPRIO_CLASS_IDLE: u32 = 3;
+const IOPRIO_DEFAULT_LEVEL: u8 = 4;
+
+//-------------------------------------
+
+/// The io priority class given to the copy, with the level within the
+/// class (0 is the highest).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum IoPriority {
+    RealTime(u8),
+    BestEffort(u8),
+    Idle,
+}
+
+impl IoPriority {
+    /// Parses idle, best-effort[:LEVEL] or realtime[:LEVEL]
+    pub fn parse(s: &str) -> Result<Self> {
+        let (class, level) = match s.split_once(':') {
+            Some((class, level)) => {
+                let level = level
+                    .parse::<u8>()
+                    .ok()
+                    .filter(|l| *l <= 7)
+                    .ok_or_else(|| anyhow!("io priority level must be between 0 and 7"))?;
+                (class, Some(level))
+            }
+            None => (s, None),
+        };
+
+        match (class, level) {
+            ("idle", None) => Ok(IoPriority::Idle),
+            ("best-effort", l) => Ok(IoPriority::BestEffort(l.unwrap_or(IOPRIO_DEFAULT_LEVEL))),
+            ("realtime", l) => Ok(IoPriority::RealTime(l.unwrap_or(IOPRIO_DEFAULT_LEVEL))),
+            _ => Err(anyhow!("unknown io priority '{}'", s)),
+        }
+    }
+
+    fn value(&self) -> u32 {
+        let (class, level) = match self {
+            IoPriority::RealTime(l) => (IOPRIO_CLASS_RT, *l),
+            IoPriority::BestEffort(l) => (IOPRIO_CLASS_BE, *l),
+            IoPriority::Idle => (IOPRIO_CLASS_IDLE, 0),
+        };
+        (class << IOPRIO_CLASS_SHIFT) | level as u32
+    }
+
+    /// Sets the priority of the calling thread.  Threads it goes on to
+    /// spawn inherit it.
+    pub fn apply(&self) -> io::Result<()> {
+        let r = unsafe {
+            libc::syscall(
+                libc::SYS_ioprio_set,
+                IOPRIO_WHO_PROCESS,
+                0,
+                self.value() as libc::c_int,
+            )
+        };
+        if r < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+}
+
+/// Limits on how hard a copy may drive the devices
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct CopyLimits {
+    pub max_bandwidth: Option<u64>, // bytes per second
+    pub max_iops: Option<u64>,
+    pub ioprio: Option<IoPriority>,
+}
+
+//-------------------------------------
+
+/// Paces the copies passed through it to stay within the limits.  Works
+/// with any Copier, since it only decides how many ops each copy call is
+/// given, and when.
+pub struct Throttle {
+    limits: CopyLimits,
+    block_size: u64,
+    start: Instant,
+    nr_blocks: u64,
+}
+
+impl Throttle {
+    pub fn new(limits: &CopyLimits, block_size: usize) -> Self {
+        Self {
+            limits: *limits,
+            block_size: block_size as u64,
+            start: Instant::now(),
+            nr_blocks: 0,
+        }
+    }
+
+    // The number of blocks to copy in one go
+    fn slice_len(&self) -> usize {
+        let slice = SLICE_TIME.as_secs_f64();
+        let mut len = usize::MAX;
+        if let Some(bw) = self.limits.max_bandwidth {
+            len = len.min((bw as f64 * slice / self.block_size as f64) as usize);
+        }
+        if let Some(iops) = self.limits.max_iops {
+            len = len.min((iops as f64 * slice / IOS_PER_BLOCK as f64) as usize);
+        }
+        len.max(1)
+    }
+
+    // How long the blocks copied so far should have taken
+    fn due(&self) -> Duration {
+        let mut due = 0.0f64;
+        if let Some(bw) = self.limits.max_bandwidth {
+            due = due.max((self.nr_blocks * self.block_size) as f64 / bw as f64);
+        }
+        if let Some(iops) = self.limits.max_iops {
+            due = due.max((self.nr_blocks * IOS_PER_BLOCK) as f64 / iops as f64);
+        }
+        Duration::from_secs_f64(due)
+    }
+
+    fn pace(&mut self, nr_blocks: u64) {
+        self.nr_blocks += nr_blocks;
+        let due = self.due();
+        let elapsed = self.start.elapsed();
+        if due > elapsed {
+            thread::sleep(due - elapsed);
+        } else if elapsed > due + MAX_BURST {
+            self.start += elapsed - due - MAX_BURST;
+        }
+    }
+
+    pub fn copy(
+        &mut self,
+        copier: &mut dyn Copier,
+        ops: &[CopyOp],
+        progress: Arc<dyn CopyProgress + Sync + Send>,
+    ) -> Result<CopyStats> {
+        if self.limits.max_bandwidth.is_none() && self.limits.max_iops.is_none() {
+            return copier.copy(ops, progress);
+        }
+
+        let mut stats = CopyStats::new(ops.len() as u64);
+        for slice in ops.chunks(self.slice_len()) {
+            let s = copier.copy(slice, progress.clone())?;
+            stats.nr_copied += s.nr_copied;
+            stats.nr_skipped += s.nr_skipped;
+            stats.nr_discarded += s.nr_discarded;
+            stats.read_errors.extend(s.read_errors);
+            stats.write_errors.extend(s.write_errors);
+            self.pace(slice.len() as u64);
+        }
+        Ok(stats)
+    }
+}
+
+//-------------------------------------
@@ -0,0 +1,121 @@
+use super::*;
+
+//------------------------------------------
+
+const BLOCK_SIZE: usize = 65536;
+
+// Copies nothing, but remembers how the ops were handed over
+#[derive(Default)]
+struct Recorder {
+    batches: Vec<usize>,
+}
+
+impl Copier for Recorder {
+    fn copy(
+        &mut self,
+        ops: &[CopyOp],
+        _progress: Arc<dyn CopyProgress + Sync + Send>,
+    ) -> Result<CopyStats> {
+        self.batches.push(ops.len());
+        let mut stats = CopyStats::new(ops.len() as u64);
+        stats.nr_copied = ops.len() as u64;
+        Ok(stats)
+    }
+}
+
+fn mk_ops(nr: u64) -> Vec<CopyOp> {
+    (0..nr).map(|b| CopyOp { src: b, dst: b }).collect()
+}
+
+#[test]
+fn test_parse_ioprio() {
+    assert_eq!(IoPriority::parse("idle").unwrap(), IoPriority::Idle);
+    assert_eq!(
+        IoPriority::parse("best-effort").unwrap(),
+        IoPriority::BestEffort(4)
+    );
+    assert_eq!(
+        IoPriority::parse("best-effort:7").unwrap(),
+        IoPriority::BestEffort(7)
+    );
+    assert_eq!(
+        IoPriority::parse("realtime:0").unwrap(),
+        IoPriority::RealTime(0)
+    );
+    assert!(IoPriority::parse("idle:3").is_err());
+    assert!(IoPriority::parse("best-effort:8").is_err());
+    assert!(IoPriority::parse("realtime:x").is_err());
+    assert!(IoPriority::parse("bulk").is_err());
+}
+
+#[test]
+fn test_ioprio_value() {
+    assert_eq!(IoPriority::Idle.value(), 3 << 13);
+    assert_eq!(IoPriority::BestEffort(7).value(), (2 << 13) | 7);
+    assert_eq!(IoPriority::RealTime(1).value(), (1 << 13) | 1);
+}
+
+#[test]
+fn test_slice_len() {
+    // 10 MiB/s of 64 KiB blocks is 160 blocks/s, or 16 per slice
+    let limits = CopyLimits {
+        max_bandwidth: Some(10 * 1024 * 1024),
+        ..Default::default()
+    };
+    assert_eq!(Throttle::new(&limits, BLOCK_SIZE).slice_len(), 16);
+
+    // each block is a read and a write
+    let limits = CopyLimits {
+        max_iops: Some(1000),
+        ..Default::default()
+    };
+    assert_eq!(Throttle::new(&limits, BLOCK_SIZE).slice_len(), 50);
+
+    // the tighter limit wins
+    let limits = CopyLimits {
+        max_bandwidth: Some(10 * 1024 * 1024),
+        max_iops: Some(100),
+        ..Default::default()
+    };
+    assert_eq!(Throttle::new(&limits, BLOCK_SIZE).slice_len(), 5);
+
+    // a slice always holds something
+    let limits = CopyLimits {
+        max_iops: Some(1),
+        ..Default::default()
+    };
+    assert_eq!(Throttle::new(&limits, BLOCK_SIZE).slice_len(), 1);
+}
+
+#[test]
+fn test_no_limits_passes_through() {
+    let mut throttle = Throttle::new(&CopyLimits::default(), BLOCK_SIZE);
+    let mut copier = Recorder::default();
+    let stats = throttle
+        .copy(&mut copier, &mk_ops(1000), Arc::new(IgnoreProgress {}))
+        .unwrap();
+    assert_eq!(stats.nr_copied, 1000);
+    assert_eq!(copier.batches, vec![1000]);
+}
+
+#[test]
+fn test_copy_is_paced() {
+    // 100 blocks/s
+    let limits = CopyLimits {
+        max_iops: Some(200),
+        ..Default::default()
+    };
+    let mut throttle = Throttle::new(&limits, BLOCK_SIZE);
+    let mut copier = Recorder::default();
+
+    let start = Instant::now();
+    let stats = throttle
+        .copy(&mut copier, &mk_ops(30), Arc::new(IgnoreProgress {}))
+        .unwrap();
+    assert!(start.elapsed() >= Duration::from_millis(250));
+    assert_eq!(stats.nr_blocks, 30);
+    assert_eq!(stats.nr_copied, 30);
+    assert_eq!(copier.batches, vec![10, 10, 10]);
+}
+
+//------------------------------------------
@@ -3,21 +3,45 @@ use std::sync::mpsc;
 use std::sync::Arc;
 use std::thread::{self, JoinHandle};
 
+use crate::copier::throttle::Throttle;
 use crate::copier::*;
 
 //---------------------------------------
 
 /*
  * A simple wrapper that runs Copier in a worker thread.
- * The copy process terminates on any read/write error.
+ * The copy process terminates on any read/write error, unless it's been
+ * told to keep going.
  */
 pub struct ThreadedCopier<T> {
     copier: T,
+    limits: CopyLimits,
+    block_size: usize,
+    keep_going: bool,
 }
 
 impl<T: Copier + Send + 'static> ThreadedCopier<T> {
     pub fn new(copier: T) -> ThreadedCopier<T> {
-        ThreadedCopier { copier }
+        ThreadedCopier {
+            copier,
+            limits: CopyLimits::default(),
+            block_size: 0,
+            keep_going: false,
+        }
+    }
+
+    /// Carry on past read and write errors.  For copiers that keep their
+    /// own record of them, such as a MappedCopier.
+    pub fn keep_going(mut self, keep_going: bool) -> ThreadedCopier<T> {
+        self.keep_going = keep_going;
+        self
+    }
+
+    /// Keep the copy within these limits.  The block size is in bytes.
+    pub fn limits(mut self, limits: &CopyLimits, block_size: usize) -> ThreadedCopier<T> {
+        self.limits = *limits;
+        self.block_size = block_size;
+        self
     }
 
     pub fn run(
@@ -25,19 +49,30 @@ impl<T: Copier + Send + 'static> ThreadedCopier<T> {
         rx: mpsc::Receiver<Vec<CopyOp>>,
         progress: Arc<dyn CopyProgress + Send + Sync>,
     ) -> JoinHandle<Result<()>> {
-        thread::spawn(move || Self::run_(rx, self.copier, progress))
+        thread::spawn(move || Self::run_(rx, self, progress))
     }
 
     fn run_(
         rx: mpsc::Receiver<Vec<CopyOp>>,
-        mut copier: T,
+        mut tc: ThreadedCopier<T>,
         progress: Arc<dyn CopyProgress + Send + Sync>,
     ) -> Result<()> {
+        if let Some(ioprio) = tc.limits.ioprio {
+            ioprio
+                .apply()
+                .map_err(|e| anyhow!("couldn't set the io priority: {}", e))?;
+        }
+
+        let mut throttle = Throttle::new(&tc.limits, tc.block_size);
         while let Ok(ops) = rx.recv() {
-            let stats = copier
-                .copy(&ops, progress.clone())
+            let stats = throttle
+                .copy(&mut tc.copier, &ops, progress.clone())
                 .map_err(|e| anyhow!("copy failed: {}", e))?;
 
+            if tc.keep_going {
+                continue;
+            }
+
             if !stats.read_errors.is_empty() {
                 return Err(anyhow!("read error in block {}", stats.read_errors[0].src));
             }
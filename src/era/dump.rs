@@ -2,12 +2,11 @@ use anyhow::{anyhow, Context, Result};
 use fixedbitset::FixedBitSet;
 use std::convert::TryFrom;
 use std::fs::File;
-use std::io::BufWriter;
-use std::io::Write;
 use std::path::Path;
 use std::sync::{Arc, Mutex};
 
 use crate::commands::engine::*;
+use crate::compression;
 use crate::dump_utils::{self, *};
 use crate::era::ir::{self, MetadataVisitor};
 use crate::era::superblock::*;
@@ -458,20 +457,22 @@ pub fn dump(opts: EraDumpOptions) -> anyhow::Result<()> {
     let ctx = mk_context(&opts)?;
     let sb = read_superblock(ctx.engine.as_ref(), SUPERBLOCK_LOCATION)?;
 
-    let writer: Box<dyn Write> = if opts.output.is_some() {
-        let f = File::create(opts.output.unwrap()).context(OutputError)?;
-        Box::new(BufWriter::new(f))
+    let mut writer = if let Some(path) = opts.output {
+        let f = File::create(path).context(OutputError)?;
+        compression::mk_writer(f, path).context(OutputError)?
     } else {
-        Box::new(BufWriter::new(std::io::stdout()))
+        compression::Writer::stdout()
     };
-    let mut out = xml::XmlWriter::new(writer, false);
+    let mut out = xml::XmlWriter::new(&mut writer, false);
 
     let writesets = get_writesets_ordered(ctx.engine.clone(), &sb, opts.repair)?;
     if opts.logical && !writesets.is_empty() {
-        dump_metadata_logical(ctx.engine, &mut out, &sb, opts.repair)
+        dump_metadata_logical(ctx.engine, &mut out, &sb, opts.repair)?;
     } else {
-        dump_metadata(ctx.engine, &mut out, &sb, opts.repair)
+        dump_metadata(ctx.engine, &mut out, &sb, opts.repair)?;
     }
+    drop(out);
+    writer.finish().context(OutputError)
 }
 
 //------------------------------------------
@@ -6,6 +6,7 @@ use std::path::Path;
 use std::sync::Arc;
 
 use crate::commands::engine::*;
+use crate::compression;
 use crate::era::ir::{self, MetadataVisitor, Visit};
 use crate::era::superblock::*;
 use crate::era::writeset::Writeset;
@@ -292,10 +293,15 @@ pub fn restore(opts: EraRestoreOptions) -> Result<()> {
     let ctx = mk_context(&opts)?;
 
     let sm = core_metadata_sm(ctx.engine.get_nr_blocks(), u32::MAX);
-    let mut w = WriteBatcher::new(ctx.engine.clone(), sm.clone(), ctx.engine.get_batch_size());
+    let mut w = WriteBatcher::new_pipelined(
+        ctx.engine.clone(),
+        sm.clone(),
+        ctx.engine.get_batch_size(),
+        PIPELINE_DEPTH,
+    );
 
     let mut restorer = Restorer::new(&mut w);
-    xml::read(input, &mut restorer)?;
+    xml::read(compression::mk_reader(input)?, &mut restorer)?;
 
     Ok(())
 }
@@ -12,7 +12,7 @@ use crate::io_engine::*;
 pub const SPACE_MAP_ROOT_SIZE: usize = 128;
 pub const SUPERBLOCK_LOCATION: u64 = 0;
 
-const MAGIC: u64 = 0o17660203573; // 0x7EC1077B in hex
+pub const MAGIC: u64 = 0o17660203573; // 0x7EC1077B in hex
 const UUID_SIZE: usize = 16;
 
 //------------------------------------------
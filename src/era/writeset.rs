@@ -1,5 +1,6 @@
 use byteorder::{LittleEndian, WriteBytesExt};
 use nom::{number::complete::*, IResult};
+use std::fmt;
 use std::io;
 
 use crate::pdata::unpack::*;
@@ -24,6 +25,12 @@ impl Unpack for Writeset {
     }
 }
 
+impl fmt::Display for Writeset {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "nr_bits = {}, root = {}", self.nr_bits, self.root)
+    }
+}
+
 impl Pack for Writeset {
     fn pack<W: WriteBytesExt>(&self, w: &mut W) -> io::Result<()> {
         w.write_u32::<LittleEndian>(self.nr_bits)?;
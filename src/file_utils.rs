@@ -2,9 +2,12 @@ use std::fs::{File, OpenOptions};
 use std::io;
 use std::io::{Seek, Write};
 use std::os::unix::ffi::OsStrExt;
+use std::os::unix::fs::FileExt;
 use std::os::unix::io::AsRawFd;
 use std::path::Path;
 
+use crate::io_engine::buffer::Buffer;
+use crate::io_engine::PAGE_SIZE;
 use crate::ioctl::{self, *};
 
 //---------------------------------------
@@ -102,4 +105,38 @@ pub fn create_sized_file(path: &Path, nr_bytes: u64) -> io::Result<std::fs::File
     Ok(file)
 }
 
+/// Makes a region read back as zeroes, giving the space back if the file
+/// or device can: a hole is punched in a file, a block device discards
+/// the region.  Otherwise zeroes are written.  The offset and length must
+/// be page aligned if the file was opened with O_DIRECT.
+pub fn zero_range(file: &File, offset: u64, len: u64) -> io::Result<()> {
+    let r = unsafe {
+        libc::fallocate(
+            file.as_raw_fd(),
+            libc::FALLOC_FL_PUNCH_HOLE | libc::FALLOC_FL_KEEP_SIZE,
+            offset as libc::off_t,
+            len as libc::off_t,
+        )
+    };
+    if r == 0 {
+        return Ok(());
+    }
+
+    let e = io::Error::last_os_error();
+    if e.raw_os_error() != Some(libc::EOPNOTSUPP) {
+        return Err(e);
+    }
+
+    const CHUNK_SIZE: u64 = 1024 * 1024;
+    let buf = Buffer::new(std::cmp::min(len, CHUNK_SIZE) as usize, PAGE_SIZE);
+    buf.get_data().fill(0);
+    let mut pos = 0;
+    while pos < len {
+        let n = std::cmp::min(len - pos, CHUNK_SIZE) as usize;
+        file.write_all_at(&buf.get_data()[..n], offset + pos)?;
+        pos += n as u64;
+    }
+    Ok(())
+}
+
 //---------------------------------------
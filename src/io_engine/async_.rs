@@ -6,10 +6,13 @@ use std::io::{self, Result};
 use std::os::fd::{AsRawFd, RawFd};
 use std::os::unix::fs::OpenOptionsExt;
 use std::path::Path;
+use std::sync::Arc;
+use std::time::Instant;
 use std::vec::Vec;
 
 use crate::io_engine::buffer_pool::{BufferPool, IOBlock};
 use crate::io_engine::ring_pool::*;
+use crate::io_engine::stats::IoStats;
 use crate::io_engine::*;
 
 //--------------------------------
@@ -21,6 +24,8 @@ const NR_RINGS: usize = 16;
 const QUEUE_DEPTH: u32 = 256;
 /// Maximum number of IO blocks that can be read in a single io_uring operation
 const MAX_BLOCKS_PER_READ: usize = 64;
+/// Maximum number of blocks that are written in a single io_uring operation
+const MAX_BLOCKS_PER_WRITE: usize = 64;
 /// Minimum block size in bytes (4KB)
 const MIN_BLOCK_SIZE: usize = 4 * 1024;
 /// Maximum block size in bytes (16MB)
@@ -45,6 +50,8 @@ struct IoData {
     iov: Vec<iovec>,
     /// The actual IO blocks being used for this operation
     blocks: Vec<IOBlock>,
+    /// When the operation was submitted, for the latency stats
+    submitted: Instant,
 }
 
 /// AsyncStreamReader provides asynchronous block-oriented reading capabilities using io_uring.
@@ -67,6 +74,8 @@ pub struct AsyncReader<'a> {
 
     /// The io_uring instance for async IO operations
     ring: &'a mut IoUring,
+
+    stats: &'a IoStats,
 }
 
 impl<'a> AsyncReader<'a> {
@@ -74,6 +83,7 @@ impl<'a> AsyncReader<'a> {
         fd: RawFd,
         io_blocks: &'a mut BufferPool,
         ring: &'a mut IoUring,
+        stats: &'a IoStats,
     ) -> io::Result<Self> {
         let block_size = io_blocks.get_block_size();
 
@@ -89,6 +99,7 @@ impl<'a> AsyncReader<'a> {
             fd,
             io_blocks,
             ring,
+            stats,
         })
     }
 
@@ -101,6 +112,7 @@ impl<'a> AsyncReader<'a> {
         let mut io_data = IoData {
             iov: Vec::with_capacity(MAX_BLOCKS_PER_READ),
             blocks: Vec::with_capacity(MAX_BLOCKS_PER_READ),
+            submitted: Instant::now(),
         };
 
         while blocks_this_read < MAX_BLOCKS_PER_READ as u64
@@ -152,6 +164,11 @@ impl<'a> AsyncReader<'a> {
     {
         self.ring.completion().for_each(|cqe| {
             let mut io_data = unsafe { Box::from_raw(cqe.user_data() as *mut IoData) };
+            self.stats.read_done(
+                cqe.result().max(0) as u64,
+                io_data.submitted.elapsed(),
+                cqe.result() >= 0,
+            );
             if cqe.result() < 0 {
                 for b in &io_data.blocks {
                     callback(b.loc, Err(io::Error::from_raw_os_error(-cqe.result())));
@@ -267,6 +284,7 @@ pub struct AsyncIoEngine {
     input: File,
     nr_blocks: u64,
     rings: RingPool,
+    stats: Arc<IoStats>,
 }
 
 unsafe impl Sync for AsyncIoEngine {}
@@ -292,9 +310,15 @@ impl AsyncIoEngine {
             input,
             nr_blocks,
             rings,
+            stats: Arc::new(IoStats::default()),
         })
     }
 
+    // Gather the io stats into a collection shared with the caller
+    pub fn with_stats(self, stats: Arc<IoStats>) -> Self {
+        Self { stats, ..self }
+    }
+
     pub fn new<P: AsRef<Path>>(path: P, writable: bool) -> Result<Self> {
         Self::new_with(path, writable, true)
     }
@@ -347,7 +371,13 @@ impl IoEngine for AsyncIoEngine {
         .offset(loc)
         .build();
 
+        let start = Instant::now();
         let ret = self.exec_op(&read_op)?;
+        self.stats.read_done(
+            ret.result().max(0) as u64,
+            start.elapsed(),
+            ret.result() >= 0,
+        );
 
         if ret.result() < 0 {
             return Err(io::Error::from_raw_os_error(-ret.result()));
@@ -370,6 +400,7 @@ impl IoEngine for AsyncIoEngine {
             let mut inflight = 0;
             let mut completed = 0;
             let mut block_data = Vec::with_capacity(blocks.len());
+            let mut submitted = vec![Instant::now(); blocks.len()];
 
             // Allocate blocks for all reads
             for &block_idx in blocks {
@@ -401,6 +432,7 @@ impl IoEngine for AsyncIoEngine {
                             break;
                         }
 
+                        submitted[idx] = Instant::now();
                         inflight += 1;
                     } else {
                         // This shouldn't happen, but just in case
@@ -414,6 +446,11 @@ impl IoEngine for AsyncIoEngine {
                 // Process completions
                 ring.completion().for_each(|cqe| {
                     let idx = cqe.user_data() as usize;
+                    self.stats.read_done(
+                        cqe.result().max(0) as u64,
+                        submitted[idx].elapsed(),
+                        cqe.result() >= 0,
+                    );
 
                     if cqe.result() < 0 {
                         // Error occurred
@@ -451,7 +488,7 @@ impl IoEngine for AsyncIoEngine {
         handler: &mut dyn ReadHandler,
     ) -> io::Result<()> {
         self.rings.with_ring(|ring| {
-            let mut reader = AsyncReader::new(self.get_fd(), io_block_pool, ring)?;
+            let mut reader = AsyncReader::new(self.get_fd(), io_block_pool, ring, &self.stats)?;
             reader.stream_blocks(blocks, handler)
         })
     }
@@ -468,7 +505,13 @@ impl IoEngine for AsyncIoEngine {
         .offset(loc)
         .build();
 
+        let start = Instant::now();
         let ret = self.exec_op(&write_op)?;
+        self.stats.write_done(
+            ret.result().max(0) as u64,
+            start.elapsed(),
+            ret.result() >= 0,
+        );
 
         if ret.result() < 0 {
             return Err(io::Error::from_raw_os_error(-ret.result()));
@@ -482,6 +525,37 @@ impl IoEngine for AsyncIoEngine {
     }
 
     fn write_many(&self, blocks: &[Block]) -> Result<Vec<Result<()>>> {
+        // Adjacent blocks are written with a single vectored op, so the
+        // runs need building, along with their iovecs, before anything is
+        // submitted.  The iovecs must stay put until the ops complete.
+        let mut order: Vec<usize> = (0..blocks.len()).collect();
+        order.sort_by_key(|&i| blocks[i].loc);
+
+        let mut runs: Vec<Vec<usize>> = Vec::new();
+        for i in order {
+            match runs.last_mut() {
+                Some(run)
+                    if run.len() < MAX_BLOCKS_PER_WRITE
+                        && blocks[*run.last().unwrap()].loc + 1 == blocks[i].loc =>
+                {
+                    run.push(i)
+                }
+                _ => runs.push(vec![i]),
+            }
+        }
+
+        let iovs: Vec<Vec<iovec>> = runs
+            .iter()
+            .map(|run| {
+                run.iter()
+                    .map(|&i| iovec {
+                        iov_base: unsafe { blocks[i].get_raw_ptr() } as *mut _,
+                        iov_len: BLOCK_SIZE,
+                    })
+                    .collect()
+            })
+            .collect();
+
         self.rings.with_ring(|ring| {
             let mut results = Vec::with_capacity(blocks.len());
             for _ in 0..blocks.len() {
@@ -490,20 +564,21 @@ impl IoEngine for AsyncIoEngine {
 
             let mut inflight = 0;
             let mut completed = 0;
+            let mut submitted = vec![Instant::now(); runs.len()];
 
-            // Process all blocks
-            while completed < blocks.len() {
+            // Process all runs, keeping at most QUEUE_DEPTH of them in flight
+            while completed < runs.len() {
                 // Submit as many operations as possible
-                while completed + inflight < blocks.len() && inflight < QUEUE_DEPTH as usize {
+                while completed + inflight < runs.len() && inflight < QUEUE_DEPTH as usize {
                     let idx = completed + inflight;
-                    let block = &blocks[idx];
-                    let offset = block.loc * BLOCK_SIZE as u64;
+                    let iov = &iovs[idx];
+                    let offset = blocks[runs[idx][0]].loc * BLOCK_SIZE as u64;
 
                     // Prepare write operation
-                    let write_op = opcode::Write::new(
+                    let write_op = opcode::Writev::new(
                         types::Fd(self.get_fd()),
-                        unsafe { block.get_raw_ptr() },
-                        BLOCK_SIZE as u32,
+                        iov.as_ptr(),
+                        iov.len() as u32,
                     )
                     .offset(offset)
                     .build()
@@ -515,6 +590,7 @@ impl IoEngine for AsyncIoEngine {
                         break;
                     }
 
+                    submitted[idx] = Instant::now();
                     inflight += 1;
                 }
 
@@ -524,16 +600,24 @@ impl IoEngine for AsyncIoEngine {
                 // Process completions
                 ring.completion().for_each(|cqe| {
                     let idx = cqe.user_data() as usize;
+                    let run = &runs[idx];
+                    self.stats.write_done(
+                        cqe.result().max(0) as u64,
+                        submitted[idx].elapsed(),
+                        cqe.result() >= 0,
+                    );
 
-                    if cqe.result() < 0 {
-                        // Error occurred
-                        results[idx] = Err(io::Error::from_raw_os_error(-cqe.result()));
-                    } else if cqe.result() as usize != BLOCK_SIZE {
-                        // Short write
-                        results[idx] = io_err("short write");
-                    } else {
-                        // Successful write
-                        results[idx] = Ok(());
+                    for &i in run {
+                        results[i] = if cqe.result() < 0 {
+                            // Error occurred
+                            Err(io::Error::from_raw_os_error(-cqe.result()))
+                        } else if cqe.result() as usize != run.len() * BLOCK_SIZE {
+                            // Short write
+                            io_err("short write")
+                        } else {
+                            // Successful write
+                            Ok(())
+                        };
                     }
 
                     inflight -= 1;
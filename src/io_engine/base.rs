@@ -101,6 +101,12 @@ pub trait IoEngine: Send + Sync {
     // The whole io could fail, or individual blocks
     fn write_many(&self, blocks: &[Block]) -> Result<Vec<Result<()>>>;
 
+    // Makes the completed writes durable.  Only engines with a write
+    // cache of their own need to do anything.
+    fn flush(&self) -> Result<()> {
+        Ok(())
+    }
+
     // FIXME: rename to stream_blocks?
     fn read_blocks(
         &self,
@@ -0,0 +1,259 @@
+use std::collections::{BTreeMap, HashMap};
+use std::io::{self, Result};
+use std::sync::{Arc, Mutex};
+
+use crate::checksum;
+use crate::io_engine::stats::IoStats;
+use crate::io_engine::*;
+
+#[cfg(test)]
+mod tests;
+
+//------------------------------------------
+
+// Keeps recently read blocks in memory, so tools that walk the same
+// nodes more than once only go to the device the first time.  Only
+// blocks with a good checksum are kept; a damaged block is always
+// reread, in case the device has something better to say the next time.
+// Writes go straight through to the inner engine.
+
+struct Entry {
+    data: Box<[u8]>,
+    tick: u64,
+}
+
+// A least recently used set of blocks.  'order' maps the tick of each
+// block's last use back to its location, so the oldest is the first.
+struct Lru {
+    capacity: usize,
+    tick: u64,
+    entries: HashMap<u64, Entry>,
+    order: BTreeMap<u64, u64>,
+}
+
+impl Lru {
+    fn new(capacity: usize) -> Self {
+        Lru {
+            capacity,
+            tick: 0,
+            entries: HashMap::new(),
+            order: BTreeMap::new(),
+        }
+    }
+
+    fn next_tick(&mut self) -> u64 {
+        self.tick += 1;
+        self.tick
+    }
+
+    fn get(&mut self, loc: u64) -> Option<Block> {
+        let tick = self.next_tick();
+        let e = self.entries.get_mut(&loc)?;
+        self.order.remove(&e.tick);
+        self.order.insert(tick, loc);
+        e.tick = tick;
+
+        let b = Block::new(loc);
+        b.get_data().copy_from_slice(&e.data);
+        Some(b)
+    }
+
+    fn insert(&mut self, loc: u64, data: &[u8]) {
+        if self.capacity == 0 {
+            return;
+        }
+
+        self.remove(loc);
+        while self.entries.len() >= self.capacity {
+            let (_, old) = self.order.pop_first().unwrap();
+            self.entries.remove(&old);
+        }
+
+        let tick = self.next_tick();
+        self.order.insert(tick, loc);
+        self.entries.insert(
+            loc,
+            Entry {
+                data: data.into(),
+                tick,
+            },
+        );
+    }
+
+    fn remove(&mut self, loc: u64) {
+        if let Some(e) = self.entries.remove(&loc) {
+            self.order.remove(&e.tick);
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.entries.len()
+    }
+}
+
+fn verified(data: &[u8]) -> bool {
+    checksum::metadata_block_type(data) != checksum::BT::UNKNOWN
+}
+
+//------------------------------------------
+
+pub struct BlockCacheIoEngine {
+    inner: Arc<dyn IoEngine>,
+    lru: Mutex<Lru>,
+    stats: Arc<IoStats>,
+}
+
+impl BlockCacheIoEngine {
+    // The capacity is in blocks
+    pub fn new(inner: Arc<dyn IoEngine>, capacity: usize, stats: Arc<IoStats>) -> Self {
+        BlockCacheIoEngine {
+            inner,
+            lru: Mutex::new(Lru::new(capacity)),
+            stats,
+        }
+    }
+
+    // The number of blocks currently held
+    pub fn nr_cached(&self) -> usize {
+        self.lru.lock().unwrap().len()
+    }
+
+    fn lookup(&self, loc: u64) -> Option<Block> {
+        let b = self.lru.lock().unwrap().get(loc);
+        if b.is_some() {
+            self.stats.cache_hit();
+        } else {
+            self.stats.cache_miss();
+        }
+        b
+    }
+
+    fn fill(&self, loc: u64, data: &[u8]) {
+        if verified(data) {
+            self.lru.lock().unwrap().insert(loc, data);
+        }
+    }
+
+    fn invalidate(&self, blocks: &[Block]) {
+        let mut lru = self.lru.lock().unwrap();
+        for b in blocks {
+            lru.remove(b.loc);
+        }
+    }
+}
+
+impl IoEngine for BlockCacheIoEngine {
+    fn get_nr_blocks(&self) -> u64 {
+        self.inner.get_nr_blocks()
+    }
+
+    fn get_batch_size(&self) -> usize {
+        self.inner.get_batch_size()
+    }
+
+    fn read(&self, loc: u64) -> Result<Block> {
+        if let Some(b) = self.lookup(loc) {
+            return Ok(b);
+        }
+
+        let b = self.inner.read(loc)?;
+        self.fill(loc, b.get_data());
+        Ok(b)
+    }
+
+    fn read_many(&self, blocks: &[u64]) -> Result<Vec<Result<Block>>> {
+        let mut results: Vec<Option<Result<Block>>> = Vec::with_capacity(blocks.len());
+        let mut misses = Vec::new();
+        for (i, loc) in blocks.iter().enumerate() {
+            let b = self.lookup(*loc);
+            if b.is_none() {
+                misses.push(i);
+            }
+            results.push(b.map(Ok));
+        }
+
+        if !misses.is_empty() {
+            let locs: Vec<u64> = misses.iter().map(|i| blocks[*i]).collect();
+            for (i, r) in misses.into_iter().zip(self.inner.read_many(&locs)?) {
+                if let Ok(b) = &r {
+                    self.fill(b.loc, b.get_data());
+                }
+                results[i] = Some(r);
+            }
+        }
+
+        Ok(results.into_iter().map(|r| r.unwrap()).collect())
+    }
+
+    fn write(&self, block: &Block) -> Result<()> {
+        self.invalidate(std::slice::from_ref(block));
+        self.inner.write(block)?;
+        self.fill(block.loc, block.get_data());
+        Ok(())
+    }
+
+    fn write_many(&self, blocks: &[Block]) -> Result<Vec<Result<()>>> {
+        self.invalidate(blocks);
+        let results = self.inner.write_many(blocks)?;
+        for (b, r) in blocks.iter().zip(results.iter()) {
+            if r.is_ok() {
+                self.fill(b.loc, b.get_data());
+            }
+        }
+        Ok(results)
+    }
+
+    fn flush(&self) -> Result<()> {
+        self.inner.flush()
+    }
+
+    // Cached blocks are handed over straight away, and only the misses
+    // are passed down.
+    fn read_blocks(
+        &self,
+        io_block_pool: &mut BufferPool,
+        blocks: &mut dyn Iterator<Item = u64>,
+        handler: &mut dyn ReadHandler,
+    ) -> io::Result<()> {
+        let mut misses = Vec::new();
+        for loc in blocks {
+            match self.lookup(loc) {
+                Some(b) => handler.handle(loc, Ok(b.get_data())),
+                None => misses.push(loc),
+            }
+        }
+
+        // Not every engine completes an empty read
+        if misses.is_empty() {
+            handler.complete();
+            return Ok(());
+        }
+
+        let mut handler = FillHandler {
+            engine: self,
+            inner: handler,
+        };
+        self.inner
+            .read_blocks(io_block_pool, &mut misses.into_iter(), &mut handler)
+    }
+}
+
+struct FillHandler<'a> {
+    engine: &'a BlockCacheIoEngine,
+    inner: &'a mut dyn ReadHandler,
+}
+
+impl ReadHandler for FillHandler<'_> {
+    fn handle(&mut self, loc: u64, data: io::Result<&[u8]>) {
+        if let Ok(data) = &data {
+            self.engine.fill(loc, data);
+        }
+        self.inner.handle(loc, data);
+    }
+
+    fn complete(&mut self) {
+        self.inner.complete();
+    }
+}
+
+//------------------------------------------
@@ -0,0 +1,136 @@
+use super::*;
+
+use crate::checksum::BT;
+use crate::io_engine::core::CoreIoEngine;
+
+//------------------------------------------
+
+const NR_BLOCKS: u64 = 64;
+
+// A node with a good checksum, stamped with the given value
+fn node(loc: u64, v: u8) -> Block {
+    let b = Block::zeroed(loc);
+    b.get_data()[16] = v;
+    checksum::write_checksum(b.get_data(), BT::NODE).unwrap();
+    b
+}
+
+fn mk_engine(capacity: usize) -> (Arc<CoreIoEngine>, BlockCacheIoEngine, Arc<IoStats>) {
+    let core = Arc::new(CoreIoEngine::new(NR_BLOCKS));
+    for loc in 0..NR_BLOCKS {
+        core.write(&node(loc, 0)).unwrap();
+    }
+    let stats = Arc::new(IoStats::default());
+    let cache = BlockCacheIoEngine::new(core.clone(), capacity, stats.clone());
+    (core, cache, stats)
+}
+
+fn value(engine: &dyn IoEngine, loc: u64) -> u8 {
+    engine.read(loc).unwrap().get_data()[16]
+}
+
+fn cache_line(stats: &IoStats) -> String {
+    stats
+        .summary()
+        .into_iter()
+        .find(|l| l.starts_with("cache:"))
+        .unwrap()
+}
+
+#[test]
+fn test_repeat_reads_hit() {
+    let (core, cache, stats) = mk_engine(16);
+    assert_eq!(value(&cache, 3), 0);
+
+    // change the device behind the cache's back
+    core.write(&node(3, 9)).unwrap();
+    assert_eq!(value(&cache, 3), 0);
+    assert_eq!(
+        cache_line(&stats),
+        "cache: 1 hits, 1 misses, 50.0% hit rate"
+    );
+}
+
+#[test]
+fn test_bad_checksums_are_not_kept() {
+    let (core, cache, _) = mk_engine(16);
+    core.write(&Block::zeroed(5)).unwrap();
+    cache.read(5).unwrap();
+    assert_eq!(cache.nr_cached(), 0);
+}
+
+#[test]
+fn test_least_recently_used_is_evicted() {
+    let (core, cache, _) = mk_engine(2);
+    cache.read(1).unwrap();
+    cache.read(2).unwrap();
+    cache.read(1).unwrap();
+    cache.read(3).unwrap();
+    assert_eq!(cache.nr_cached(), 2);
+
+    for loc in 1..4 {
+        core.write(&node(loc, 7)).unwrap();
+    }
+    assert_eq!(value(&cache, 1), 0);
+    assert_eq!(value(&cache, 3), 0);
+    assert_eq!(value(&cache, 2), 7);
+}
+
+#[test]
+fn test_writes_go_through() {
+    let (core, cache, _) = mk_engine(16);
+    cache.read(4).unwrap();
+    cache.write_many(&[node(4, 1), node(6, 2)]).unwrap();
+    assert_eq!(value(core.as_ref(), 4), 1);
+    assert_eq!(value(&cache, 4), 1);
+    assert_eq!(value(&cache, 6), 2);
+}
+
+#[test]
+fn test_read_many_mixes_hits_and_misses() {
+    let (core, cache, _) = mk_engine(16);
+    cache.read(2).unwrap();
+    core.write(&node(2, 5)).unwrap();
+    core.write(&node(3, 6)).unwrap();
+
+    let values: Vec<u8> = cache
+        .read_many(&[1, 2, 3])
+        .unwrap()
+        .into_iter()
+        .map(|r| r.unwrap().get_data()[16])
+        .collect();
+    assert_eq!(values, vec![0, 0, 6]);
+    assert_eq!(cache.nr_cached(), 3);
+}
+
+#[derive(Default)]
+struct Recorder {
+    values: Vec<(u64, u8)>,
+    completed: bool,
+}
+
+impl ReadHandler for Recorder {
+    fn handle(&mut self, loc: u64, data: io::Result<&[u8]>) {
+        self.values.push((loc, data.unwrap()[16]));
+    }
+
+    fn complete(&mut self) {
+        self.completed = true;
+    }
+}
+
+#[test]
+fn test_read_blocks_all_hits_completes() {
+    let (_, cache, _) = mk_engine(16);
+    cache.write_many(&[node(8, 1), node(9, 2)]).unwrap();
+
+    let mut pool = BufferPool::new(4, BLOCK_SIZE);
+    let mut recorder = Recorder::default();
+    cache
+        .read_blocks(&mut pool, &mut [8, 9].into_iter(), &mut recorder)
+        .unwrap();
+    assert_eq!(recorder.values, vec![(8, 1), (9, 2)]);
+    assert!(recorder.completed);
+}
+
+//------------------------------------------
@@ -0,0 +1,61 @@
+use std::ffi::OsStr;
+use std::io;
+use std::os::unix::fs::{FileTypeExt, MetadataExt};
+use std::path::Path;
+use udev::{Device, DeviceType};
+
+//------------------------------------------
+
+/// What the kernel tells us about the thing holding the metadata.  Used
+/// to pick an io engine that suits it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum DeviceInfo {
+    File,
+    Block {
+        rotational: bool,
+        // the number of requests the block layer will queue
+        queue_depth: u64,
+    },
+    // Neither a file nor a block device we could find in sysfs
+    Unknown,
+}
+
+fn parse_attr(v: Option<&OsStr>) -> Option<u64> {
+    v?.to_str()?.trim().parse::<u64>().ok()
+}
+
+// Partitions don't have a queue of their own, so look at the whole disk.
+fn queue_attr(dev: &Device, name: &str) -> Option<u64> {
+    let attr = format!("queue/{}", name);
+    if let Some(v) = parse_attr(dev.attribute_value(&attr)) {
+        return Some(v);
+    }
+
+    let disk = dev.parent_with_subsystem_devtype("block", "disk").ok()??;
+    parse_attr(disk.attribute_value(&attr))
+}
+
+pub fn probe(path: &Path) -> io::Result<DeviceInfo> {
+    let md = std::fs::metadata(path)?;
+    if md.is_file() {
+        return Ok(DeviceInfo::File);
+    }
+
+    if !md.file_type().is_block_device() {
+        return Ok(DeviceInfo::Unknown);
+    }
+
+    let dev = Device::from_devnum(DeviceType::Block, md.rdev())?;
+    match (
+        queue_attr(&dev, "rotational"),
+        queue_attr(&dev, "nr_requests"),
+    ) {
+        (Some(rotational), Some(queue_depth)) => Ok(DeviceInfo::Block {
+            rotational: rotational != 0,
+            queue_depth,
+        }),
+        _ => Ok(DeviceInfo::Unknown),
+    }
+}
+
+//------------------------------------------
@@ -0,0 +1,342 @@
+use anyhow::anyhow;
+use std::io::{self, Result};
+use std::ops::Range;
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use crate::io_engine::*;
+
+#[cfg(test)]
+mod tests;
+
+//------------------------------------------
+
+// Wraps another engine, failing selected ios so the error paths of the
+// tools can be exercised.  The faults are described by a spec of
+// colon separated settings, eg,
+//
+//    read=1,7-9:write-rate=0.01:flip=3:latency=5:seed=42
+//
+//    read=BLOCKS        reads of these blocks fail with EIO
+//    write=BLOCKS       writes of these blocks fail with EIO
+//    short=BLOCKS       reads of these blocks only fill part of the buffer
+//    flip=BLOCKS        reads of these blocks succeed, but with a bit flipped
+//    read-rate=P        the probability of any read failing
+//    write-rate=P       the probability of any write failing
+//    short-rate=P       the probability of any read coming back short
+//    flip-rate=P        the probability of any read having a bit flipped
+//    latency=MS         a delay added to every io
+//    seed=N             selects which blocks the probabilities pick
+//
+// Whether a block is picked depends only on the seed, the block and
+// the sort of fault, never on the order of the ios.  So a run can be
+// reproduced exactly, whichever engine or thread count is used, and a
+// failing block keeps failing just like a bad sector would.
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct BlockSet {
+    ranges: Vec<Range<u64>>,
+}
+
+impl BlockSet {
+    pub fn contains(&self, b: u64) -> bool {
+        self.ranges.iter().any(|r| r.contains(&b))
+    }
+
+    fn parse(text: &str) -> anyhow::Result<Self> {
+        let parse_u64 = |s: &str| {
+            s.parse::<u64>()
+                .map_err(|_| anyhow!("bad block number '{}'", s))
+        };
+
+        let mut ranges = Vec::new();
+        for item in text.split(',') {
+            match item.split_once('-') {
+                Some((b, e)) => {
+                    let (b, e) = (parse_u64(b)?, parse_u64(e)?);
+                    if b > e {
+                        return Err(anyhow!("bad block range '{}'", item));
+                    }
+                    ranges.push(b..(e + 1));
+                }
+                None => {
+                    let b = parse_u64(item)?;
+                    ranges.push(b..(b + 1));
+                }
+            }
+        }
+
+        Ok(BlockSet { ranges })
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Fault {
+    pub blocks: BlockSet,
+    pub rate: f64,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct FaultSpec {
+    pub read: Fault,
+    pub write: Fault,
+    pub short: Fault,
+    pub flip: Fault,
+    pub latency: Duration,
+    pub seed: u64,
+}
+
+impl FaultSpec {
+    pub fn parse(text: &str) -> anyhow::Result<Self> {
+        let mut spec = FaultSpec::default();
+
+        for setting in text.split(':').filter(|s| !s.is_empty()) {
+            let (key, value) = setting
+                .split_once('=')
+                .ok_or_else(|| anyhow!("expected KEY=VALUE, got '{}'", setting))?;
+
+            let parse_rate = |v: &str| match v.parse::<f64>() {
+                Ok(p) if (0.0..=1.0).contains(&p) => Ok(p),
+                _ => Err(anyhow!("bad probability '{}'", v)),
+            };
+            let parse_u64 = |v: &str| v.parse::<u64>().map_err(|_| anyhow!("bad number '{}'", v));
+
+            match key {
+                "read" => spec.read.blocks = BlockSet::parse(value)?,
+                "write" => spec.write.blocks = BlockSet::parse(value)?,
+                "short" => spec.short.blocks = BlockSet::parse(value)?,
+                "flip" => spec.flip.blocks = BlockSet::parse(value)?,
+                "read-rate" => spec.read.rate = parse_rate(value)?,
+                "write-rate" => spec.write.rate = parse_rate(value)?,
+                "short-rate" => spec.short.rate = parse_rate(value)?,
+                "flip-rate" => spec.flip.rate = parse_rate(value)?,
+                "latency" => spec.latency = Duration::from_millis(parse_u64(value)?),
+                "seed" => spec.seed = parse_u64(value)?,
+                _ => return Err(anyhow!("unknown fault setting '{}'", key)),
+            }
+        }
+
+        Ok(spec)
+    }
+}
+
+//------------------------------------------
+
+#[derive(Clone, Copy)]
+enum FaultKind {
+    Read = 1,
+    Write = 2,
+    Short = 3,
+    Flip = 4,
+}
+
+// splitmix64, good enough to spread the picks evenly.
+fn mix(mut x: u64) -> u64 {
+    x = x.wrapping_add(0x9e3779b97f4a7c15);
+    x = (x ^ (x >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94d049bb133111eb);
+    x ^ (x >> 31)
+}
+
+fn bad_read() -> io::Error {
+    io::Error::other("injected read failure")
+}
+
+fn bad_write() -> io::Error {
+    io::Error::other("injected write failure")
+}
+
+pub struct FaultyIoEngine {
+    inner: Arc<dyn IoEngine>,
+    spec: FaultSpec,
+}
+
+impl FaultyIoEngine {
+    pub fn new(inner: Arc<dyn IoEngine>, spec: FaultSpec) -> Self {
+        Self { inner, spec }
+    }
+
+    fn hash(&self, kind: FaultKind, b: u64) -> u64 {
+        mix(self.spec.seed ^ mix(b ^ ((kind as u64) << 56)))
+    }
+
+    fn picked(&self, kind: FaultKind, b: u64) -> bool {
+        let fault = match kind {
+            FaultKind::Read => &self.spec.read,
+            FaultKind::Write => &self.spec.write,
+            FaultKind::Short => &self.spec.short,
+            FaultKind::Flip => &self.spec.flip,
+        };
+
+        if fault.blocks.contains(b) {
+            return true;
+        }
+
+        // top 53 bits give a uniform value in [0, 1)
+        let p = (self.hash(kind, b) >> 11) as f64 / (1u64 << 53) as f64;
+        p < fault.rate
+    }
+
+    fn delay(&self) {
+        if !self.spec.latency.is_zero() {
+            thread::sleep(self.spec.latency);
+        }
+    }
+
+    fn flip_bit(&self, b: u64, data: &mut [u8]) {
+        let bit = (self.hash(FaultKind::Flip, b) >> 8) as usize % (data.len() * 8);
+        data[bit / 8] ^= 1 << (bit % 8);
+    }
+
+    // The device only delivered some of the sectors, the rest of the
+    // buffer is left zeroed.
+    fn truncate(&self, b: u64, data: &mut [u8]) {
+        let nr_sectors = data.len() >> SECTOR_SHIFT;
+        let kept = if nr_sectors > 1 {
+            1 + (self.hash(FaultKind::Short, b) >> 8) as usize % (nr_sectors - 1)
+        } else {
+            0
+        };
+        data[kept << SECTOR_SHIFT..].fill(0);
+    }
+
+    // Applies the read faults to a block that was read successfully
+    fn damage(&self, b: Block) -> Result<Block> {
+        if self.picked(FaultKind::Read, b.loc) {
+            Err(bad_read())
+        } else {
+            if self.picked(FaultKind::Short, b.loc) {
+                self.truncate(b.loc, b.get_data());
+            }
+            if self.picked(FaultKind::Flip, b.loc) {
+                self.flip_bit(b.loc, b.get_data());
+            }
+            Ok(b)
+        }
+    }
+}
+
+impl IoEngine for FaultyIoEngine {
+    fn get_nr_blocks(&self) -> u64 {
+        self.inner.get_nr_blocks()
+    }
+
+    fn get_batch_size(&self) -> usize {
+        self.inner.get_batch_size()
+    }
+
+    fn read(&self, b: u64) -> Result<Block> {
+        self.delay();
+        self.damage(self.inner.read(b)?)
+    }
+
+    fn read_many(&self, blocks: &[u64]) -> Result<Vec<Result<Block>>> {
+        self.delay();
+        let results = self.inner.read_many(blocks)?;
+        Ok(results
+            .into_iter()
+            .map(|r| r.and_then(|b| self.damage(b)))
+            .collect())
+    }
+
+    fn write(&self, block: &Block) -> Result<()> {
+        self.delay();
+        if self.picked(FaultKind::Write, block.loc) {
+            return Err(bad_write());
+        }
+        self.inner.write(block)
+    }
+
+    fn write_many(&self, blocks: &[Block]) -> Result<Vec<Result<()>>> {
+        self.delay();
+        let failed: Vec<bool> = blocks
+            .iter()
+            .map(|b| self.picked(FaultKind::Write, b.loc))
+            .collect();
+        if !failed.contains(&true) {
+            return self.inner.write_many(blocks);
+        }
+
+        // Only the blocks that survive reach the inner engine, so they
+        // have to be copied out.
+        let survivors: Vec<Block> = blocks
+            .iter()
+            .zip(&failed)
+            .filter(|(_, failed)| !**failed)
+            .map(|(b, _)| {
+                let copy = Block::new(b.loc);
+                copy.get_data().copy_from_slice(b.get_data());
+                copy
+            })
+            .collect();
+        let mut inner_results = self.inner.write_many(&survivors)?.into_iter();
+
+        Ok(failed
+            .iter()
+            .map(|failed| {
+                if *failed {
+                    Err(bad_write())
+                } else {
+                    inner_results.next().unwrap()
+                }
+            })
+            .collect())
+    }
+
+    fn flush(&self) -> Result<()> {
+        self.inner.flush()
+    }
+
+    fn read_blocks(
+        &self,
+        io_block_pool: &mut BufferPool,
+        blocks: &mut dyn Iterator<Item = u64>,
+        handler: &mut dyn ReadHandler,
+    ) -> io::Result<()> {
+        self.delay();
+        let mut handler = FaultyHandler {
+            engine: self,
+            inner: handler,
+            buf: Vec::new(),
+        };
+        self.inner.read_blocks(io_block_pool, blocks, &mut handler)
+    }
+}
+
+// Streamed blocks belong to the buffer pool, so any damage is applied
+// to a copy.
+struct FaultyHandler<'a> {
+    engine: &'a FaultyIoEngine,
+    inner: &'a mut dyn ReadHandler,
+    buf: Vec<u8>,
+}
+
+impl ReadHandler for FaultyHandler<'_> {
+    fn handle(&mut self, loc: u64, data: io::Result<&[u8]>) {
+        let engine = self.engine;
+        match data {
+            Ok(_) if engine.picked(FaultKind::Read, loc) => self.inner.handle(loc, Err(bad_read())),
+            Ok(data)
+                if engine.picked(FaultKind::Short, loc) || engine.picked(FaultKind::Flip, loc) =>
+            {
+                self.buf.clear();
+                self.buf.extend_from_slice(data);
+                if engine.picked(FaultKind::Short, loc) {
+                    engine.truncate(loc, &mut self.buf);
+                }
+                if engine.picked(FaultKind::Flip, loc) {
+                    engine.flip_bit(loc, &mut self.buf);
+                }
+                self.inner.handle(loc, Ok(&self.buf));
+            }
+            data => self.inner.handle(loc, data),
+        }
+    }
+
+    fn complete(&mut self) {
+        self.inner.complete();
+    }
+}
+
+//------------------------------------------
@@ -0,0 +1,109 @@
+use super::*;
+
+use crate::io_engine::core::CoreIoEngine;
+
+//------------------------------------------
+
+const NR_BLOCKS: u64 = 64;
+
+fn mk_engine(spec: &str) -> FaultyIoEngine {
+    let core = CoreIoEngine::new(NR_BLOCKS);
+    for loc in 0..NR_BLOCKS {
+        let b = Block::zeroed(loc);
+        b.get_data()[0..8].copy_from_slice(&loc.to_le_bytes());
+        core.write(&b).unwrap();
+    }
+    FaultyIoEngine::new(Arc::new(core), FaultSpec::parse(spec).unwrap())
+}
+
+fn stamp_ok(b: &Block) -> bool {
+    b.get_data()[0..8] == b.loc.to_le_bytes() && b.get_data()[8..].iter().all(|v| *v == 0)
+}
+
+#[test]
+fn test_parse_spec() {
+    let spec = FaultSpec::parse("read=1,7-9:write-rate=0.5:latency=5:seed=42").unwrap();
+    assert!(spec.read.blocks.contains(1));
+    assert!(!spec.read.blocks.contains(2));
+    assert!(spec.read.blocks.contains(9));
+    assert_eq!(spec.write.rate, 0.5);
+    assert_eq!(spec.latency, Duration::from_millis(5));
+    assert_eq!(spec.seed, 42);
+}
+
+#[test]
+fn test_parse_bad_spec() {
+    assert!(FaultSpec::parse("read=9-7").is_err());
+    assert!(FaultSpec::parse("read-rate=2").is_err());
+    assert!(FaultSpec::parse("bogus=1").is_err());
+    assert!(FaultSpec::parse("read").is_err());
+}
+
+#[test]
+fn test_read_faults() {
+    let engine = mk_engine("read=3:flip=5");
+    assert!(engine.read(3).is_err());
+    assert!(!stamp_ok(&engine.read(5).unwrap()));
+    assert!(stamp_ok(&engine.read(6).unwrap()));
+}
+
+#[test]
+fn test_short_read() {
+    let engine = mk_engine("short=4");
+    let b = Block::new(4);
+    b.get_data().fill(0xff);
+    engine.write(&b).unwrap();
+
+    // a whole number of sectors arrive, the rest of the buffer is zeroed
+    let b = engine.read(4).unwrap();
+    let data = b.get_data();
+    let kept = data.iter().take_while(|v| **v == 0xff).count();
+    assert!(kept > 0 && kept < BLOCK_SIZE);
+    assert_eq!(kept % (1 << SECTOR_SHIFT), 0);
+    assert!(data[kept..].iter().all(|v| *v == 0));
+}
+
+#[test]
+fn test_read_many_partial_failure() {
+    let engine = mk_engine("read=2,5");
+    let results = engine.read_many(&[1, 2, 3, 4, 5, 6]).unwrap();
+    let failed: Vec<bool> = results.iter().map(|r| r.is_err()).collect();
+    assert_eq!(failed, vec![false, true, false, false, true, false]);
+    for b in results.into_iter().flatten() {
+        assert!(stamp_ok(&b));
+    }
+}
+
+#[test]
+fn test_write_many_partial_failure() {
+    let engine = mk_engine("write=11");
+    let blocks: Vec<Block> = (10..13).map(Block::zeroed).collect();
+    let results = engine.write_many(&blocks).unwrap();
+    let failed: Vec<bool> = results.iter().map(|r| r.is_err()).collect();
+    assert_eq!(failed, vec![false, true, false]);
+
+    // the failed block must be left untouched
+    assert!(stamp_ok(&engine.read(11).unwrap()));
+    assert!(!stamp_ok(&engine.read(10).unwrap()));
+}
+
+#[test]
+fn test_rates_are_reproducible() {
+    let picks = |spec: &str| {
+        let engine = mk_engine(spec);
+        (0..NR_BLOCKS)
+            .map(|b| engine.read(b).is_err())
+            .collect::<Vec<bool>>()
+    };
+
+    let a = picks("read-rate=0.5:seed=1");
+    assert_eq!(a, picks("read-rate=0.5:seed=1"));
+    assert_ne!(a, picks("read-rate=0.5:seed=2"));
+
+    let nr_failed = a.iter().filter(|f| **f).count();
+    assert!(nr_failed > 0 && nr_failed < NR_BLOCKS as usize);
+    assert!(picks("read-rate=0").iter().all(|f| !f));
+    assert!(picks("read-rate=1").iter().all(|f| *f));
+}
+
+//------------------------------------------
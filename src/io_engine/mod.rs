@@ -1,14 +1,28 @@
 pub mod base;
+pub mod block_cache;
 pub mod buffer;
 pub mod buffer_pool;
+pub mod device_info;
+pub mod fault;
 pub mod gaps;
+pub mod nbd;
+pub mod overlay;
+pub mod pack;
 pub mod spindle;
+pub mod stats;
 pub mod sync;
+pub mod trace;
 pub mod utils;
 
 pub use crate::io_engine::base::*;
+pub use crate::io_engine::block_cache::BlockCacheIoEngine;
+pub use crate::io_engine::fault::FaultyIoEngine;
+pub use crate::io_engine::nbd::NbdIoEngine;
+pub use crate::io_engine::overlay::OverlayIoEngine;
+pub use crate::io_engine::pack::PackIoEngine;
 pub use crate::io_engine::spindle::SpindleIoEngine;
 pub use crate::io_engine::sync::SyncIoEngine;
+pub use crate::io_engine::trace::RecordingIoEngine;
 
 #[cfg(feature = "io_uring")]
 pub mod async_;
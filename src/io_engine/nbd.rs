@@ -0,0 +1,509 @@
+use std::collections::HashMap;
+use std::io::{self, BufReader, BufWriter, Read, Result, Write};
+use std::net::TcpStream;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+use crate::io_engine::stats::IoStats;
+use crate::io_engine::*;
+
+#[cfg(any(test, feature = "devtools"))]
+pub mod server;
+
+#[cfg(test)]
+mod tests;
+
+//------------------------------------------
+
+// A client for the network block device protocol, so metadata exported
+// from another machine (eg, with qemu-nbd or nbdkit) can be read in
+// place.  Only the fixed newstyle handshake with NBD_OPT_EXPORT_NAME,
+// and simple replies, are used; every server supports those.
+//
+// Exports are given as nbd://host[:port][/export], with IPv6 addresses
+// in brackets.
+
+pub const NBD_DEFAULT_PORT: u16 = 10809;
+
+pub const NBD_MAGIC: u64 = 0x4e42444d41474943; // "NBDMAGIC"
+pub const NBD_IHAVEOPT: u64 = 0x49484156454f5054; // "IHAVEOPT"
+pub const NBD_REQUEST_MAGIC: u32 = 0x25609513;
+pub const NBD_SIMPLE_REPLY_MAGIC: u32 = 0x67446698;
+
+// handshake flags
+pub const NBD_FLAG_FIXED_NEWSTYLE: u16 = 1 << 0;
+pub const NBD_FLAG_NO_ZEROES: u16 = 1 << 1;
+
+// transmission flags
+pub const NBD_FLAG_HAS_FLAGS: u16 = 1 << 0;
+pub const NBD_FLAG_READ_ONLY: u16 = 1 << 1;
+pub const NBD_FLAG_SEND_FLUSH: u16 = 1 << 2;
+
+pub const NBD_OPT_EXPORT_NAME: u32 = 1;
+
+pub const NBD_CMD_READ: u16 = 0;
+pub const NBD_CMD_WRITE: u16 = 1;
+pub const NBD_CMD_DISC: u16 = 2;
+pub const NBD_CMD_FLUSH: u16 = 3;
+
+// Requests are kept to this many blocks, and no more than this many are
+// in flight at once.
+const MAX_BLOCKS_PER_REQUEST: usize = 64;
+const MAX_IN_FLIGHT: usize = 16;
+
+fn nbd_err<T>(msg: String) -> Result<T> {
+    Err(io::Error::other(msg))
+}
+
+//------------------------------------------
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct NbdUrl {
+    pub host: String,
+    pub port: u16,
+    pub export: String,
+}
+
+pub fn is_nbd_url(path: &Path) -> bool {
+    path.to_str().is_some_and(|s| s.starts_with("nbd://"))
+}
+
+pub fn parse_nbd_url(url: &str) -> Result<NbdUrl> {
+    let bad = || {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("bad nbd url '{}'", url),
+        )
+    };
+
+    let rest = url.strip_prefix("nbd://").ok_or_else(bad)?;
+    let (addr, export) = rest.split_once('/').unwrap_or((rest, ""));
+    let (host, port) = match addr.strip_prefix('[') {
+        Some(v6) => {
+            let (host, rest) = v6.split_once(']').ok_or_else(bad)?;
+            match rest {
+                "" => (host, None),
+                _ => (host, Some(rest.strip_prefix(':').ok_or_else(bad)?)),
+            }
+        }
+        None => match addr.rsplit_once(':') {
+            Some((host, port)) => (host, Some(port)),
+            None => (addr, None),
+        },
+    };
+    let port = match port {
+        Some(port) => port.parse::<u16>().map_err(|_| bad())?,
+        None => NBD_DEFAULT_PORT,
+    };
+    if host.is_empty() {
+        return Err(bad());
+    }
+
+    Ok(NbdUrl {
+        host: host.to_string(),
+        port,
+        export: export.to_string(),
+    })
+}
+
+//------------------------------------------
+
+struct Request {
+    kind: u16,
+    handle: u64,
+    offset: u64,
+    len: u32,
+}
+
+impl Request {
+    fn send<W: Write>(&self, w: &mut W, data: Option<&[u8]>) -> Result<()> {
+        let mut buf = [0u8; 28];
+        buf[0..4].copy_from_slice(&NBD_REQUEST_MAGIC.to_be_bytes());
+        buf[4..6].copy_from_slice(&0u16.to_be_bytes());
+        buf[6..8].copy_from_slice(&self.kind.to_be_bytes());
+        buf[8..16].copy_from_slice(&self.handle.to_be_bytes());
+        buf[16..24].copy_from_slice(&self.offset.to_be_bytes());
+        buf[24..28].copy_from_slice(&self.len.to_be_bytes());
+        w.write_all(&buf)?;
+        if let Some(data) = data {
+            w.write_all(data)?;
+        }
+        Ok(())
+    }
+}
+
+// Returns the handle and the error code of a simple reply.  Any data
+// that follows is left for the caller.
+fn recv_reply<R: Read>(r: &mut R) -> Result<(u64, u32)> {
+    let mut buf = [0u8; 16];
+    r.read_exact(&mut buf)?;
+    let magic = u32::from_be_bytes(buf[0..4].try_into().unwrap());
+    if magic != NBD_SIMPLE_REPLY_MAGIC {
+        return nbd_err(format!("bad nbd reply magic {:#x}", magic));
+    }
+    let error = u32::from_be_bytes(buf[4..8].try_into().unwrap());
+    let handle = u64::from_be_bytes(buf[8..16].try_into().unwrap());
+    Ok((handle, error))
+}
+
+fn read_u16<R: Read>(r: &mut R) -> Result<u16> {
+    let mut buf = [0u8; 2];
+    r.read_exact(&mut buf)?;
+    Ok(u16::from_be_bytes(buf))
+}
+
+fn read_u64<R: Read>(r: &mut R) -> Result<u64> {
+    let mut buf = [0u8; 8];
+    r.read_exact(&mut buf)?;
+    Ok(u64::from_be_bytes(buf))
+}
+
+//------------------------------------------
+
+struct Connection {
+    reader: BufReader<TcpStream>,
+    writer: BufWriter<TcpStream>,
+    next_handle: u64,
+}
+
+impl Connection {
+    // Returns the connection, the size of the export and its transmission
+    // flags.
+    fn open(url: &NbdUrl) -> Result<(Self, u64, u16)> {
+        let stream = TcpStream::connect((url.host.as_str(), url.port))?;
+        stream.set_nodelay(true)?;
+        let mut reader = BufReader::new(stream.try_clone()?);
+        let mut writer = BufWriter::new(stream);
+
+        if read_u64(&mut reader)? != NBD_MAGIC || read_u64(&mut reader)? != NBD_IHAVEOPT {
+            return nbd_err(format!(
+                "{}:{} is not a newstyle nbd server",
+                url.host, url.port
+            ));
+        }
+        let server_flags = read_u16(&mut reader)?;
+        if server_flags & NBD_FLAG_FIXED_NEWSTYLE == 0 {
+            return nbd_err("the nbd server doesn't support fixed newstyle".to_string());
+        }
+        let no_zeroes = server_flags & NBD_FLAG_NO_ZEROES != 0;
+
+        let client_flags = (server_flags & (NBD_FLAG_FIXED_NEWSTYLE | NBD_FLAG_NO_ZEROES)) as u32;
+        writer.write_all(&client_flags.to_be_bytes())?;
+        writer.write_all(&NBD_IHAVEOPT.to_be_bytes())?;
+        writer.write_all(&NBD_OPT_EXPORT_NAME.to_be_bytes())?;
+        writer.write_all(&(url.export.len() as u32).to_be_bytes())?;
+        writer.write_all(url.export.as_bytes())?;
+        writer.flush()?;
+
+        // The server just drops the connection if it doesn't know the export
+        let size = read_u64(&mut reader).map_err(|_| {
+            io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("the nbd server has no export '{}'", url.export),
+            )
+        })?;
+        let flags = read_u16(&mut reader)?;
+        if !no_zeroes {
+            let mut zeroes = [0u8; 124];
+            reader.read_exact(&mut zeroes)?;
+        }
+
+        Ok((
+            Connection {
+                reader,
+                writer,
+                next_handle: 0,
+            },
+            size,
+            flags,
+        ))
+    }
+
+    fn handle(&mut self) -> u64 {
+        self.next_handle += 1;
+        self.next_handle
+    }
+
+    // Reads runs of adjacent blocks, keeping a window of requests in
+    // flight.  Each run is given as (first block, nr blocks).  The
+    // callback sees each run's data, or the error the server returned.
+    fn read_runs<F>(&mut self, runs: &[(u64, usize)], stats: &IoStats, mut f: F) -> Result<()>
+    where
+        F: FnMut(usize, Result<&[u8]>),
+    {
+        let mut in_flight: HashMap<u64, (usize, Instant)> = HashMap::new();
+        let mut sent = 0;
+        let mut buf = Vec::new();
+
+        while sent < runs.len() || !in_flight.is_empty() {
+            while sent < runs.len() && in_flight.len() < MAX_IN_FLIGHT {
+                let (begin, len) = runs[sent];
+                let handle = self.handle();
+                Request {
+                    kind: NBD_CMD_READ,
+                    handle,
+                    offset: begin * BLOCK_SIZE as u64,
+                    len: (len * BLOCK_SIZE) as u32,
+                }
+                .send(&mut self.writer, None)?;
+                in_flight.insert(handle, (sent, Instant::now()));
+                sent += 1;
+            }
+            self.writer.flush()?;
+
+            let (handle, error) = recv_reply(&mut self.reader)?;
+            let (index, start) = in_flight
+                .remove(&handle)
+                .ok_or_else(|| io::Error::other("nbd reply for an unknown request"))?;
+            let nr_bytes = runs[index].1 * BLOCK_SIZE;
+            if error != 0 {
+                stats.read_done(nr_bytes as u64, start.elapsed(), false);
+                f(index, Err(io::Error::from_raw_os_error(error as i32)));
+            } else {
+                buf.resize(nr_bytes, 0);
+                self.reader.read_exact(&mut buf)?;
+                stats.read_done(nr_bytes as u64, start.elapsed(), true);
+                f(index, Ok(&buf));
+            }
+        }
+
+        Ok(())
+    }
+
+    fn write_blocks(&mut self, blocks: &[Block], stats: &IoStats) -> Result<Vec<Result<()>>> {
+        let mut results: Vec<Result<()>> = Vec::with_capacity(blocks.len());
+        let mut in_flight: HashMap<u64, (usize, Instant)> = HashMap::new();
+        for (i, b) in blocks.iter().enumerate() {
+            let handle = self.handle();
+            Request {
+                kind: NBD_CMD_WRITE,
+                handle,
+                offset: b.loc * BLOCK_SIZE as u64,
+                len: BLOCK_SIZE as u32,
+            }
+            .send(&mut self.writer, Some(b.get_data()))?;
+            in_flight.insert(handle, (i, Instant::now()));
+            results.push(Ok(()));
+
+            if in_flight.len() == MAX_IN_FLIGHT || i + 1 == blocks.len() {
+                self.writer.flush()?;
+                while !in_flight.is_empty() {
+                    let (handle, error) = recv_reply(&mut self.reader)?;
+                    let (index, start) = in_flight
+                        .remove(&handle)
+                        .ok_or_else(|| io::Error::other("nbd reply for an unknown request"))?;
+                    stats.write_done(BLOCK_SIZE as u64, start.elapsed(), error == 0);
+                    if error != 0 {
+                        results[index] = Err(io::Error::from_raw_os_error(error as i32));
+                    }
+                }
+            }
+        }
+        Ok(results)
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        let handle = self.handle();
+        Request {
+            kind: NBD_CMD_FLUSH,
+            handle,
+            offset: 0,
+            len: 0,
+        }
+        .send(&mut self.writer, None)?;
+        self.writer.flush()?;
+
+        let (reply, error) = recv_reply(&mut self.reader)?;
+        if reply != handle {
+            return nbd_err("nbd reply for an unknown request".to_string());
+        }
+        if error != 0 {
+            return Err(io::Error::from_raw_os_error(error as i32));
+        }
+        Ok(())
+    }
+
+    fn disconnect(&mut self) -> Result<()> {
+        let handle = self.handle();
+        Request {
+            kind: NBD_CMD_DISC,
+            handle,
+            offset: 0,
+            len: 0,
+        }
+        .send(&mut self.writer, None)?;
+        self.writer.flush()
+    }
+}
+
+//------------------------------------------
+
+pub struct NbdIoEngine {
+    conn: Mutex<Connection>,
+    nr_blocks: u64,
+    writable: bool,
+    can_flush: bool,
+    stats: Arc<IoStats>,
+}
+
+impl NbdIoEngine {
+    pub fn new(url: &str, writable: bool) -> Result<Self> {
+        let url = parse_nbd_url(url)?;
+        let (conn, size, flags) = Connection::open(&url)?;
+        if writable && flags & NBD_FLAG_READ_ONLY != 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::PermissionDenied,
+                format!("the nbd export '{}' is read only", url.export),
+            ));
+        }
+
+        Ok(NbdIoEngine {
+            conn: Mutex::new(conn),
+            nr_blocks: size / BLOCK_SIZE as u64,
+            writable,
+            can_flush: flags & NBD_FLAG_SEND_FLUSH != 0,
+            stats: Arc::new(IoStats::default()),
+        })
+    }
+
+    // Gather the io stats into a collection shared with the caller
+    pub fn with_stats(mut self, stats: Arc<IoStats>) -> Self {
+        self.stats = stats;
+        self
+    }
+
+    fn check_writable(&self) -> Result<()> {
+        if !self.writable {
+            return Err(io::Error::new(
+                io::ErrorKind::PermissionDenied,
+                "the nbd export was opened read only",
+            ));
+        }
+        Ok(())
+    }
+}
+
+impl Drop for NbdIoEngine {
+    fn drop(&mut self) {
+        // The server may be caching our writes
+        let _ = self.flush();
+        let _ = self.conn.lock().unwrap().disconnect();
+    }
+}
+
+// Groups the blocks into runs of adjacent locations, keeping their
+// original order.
+fn find_runs(blocks: &[u64]) -> Vec<(u64, usize)> {
+    let mut runs: Vec<(u64, usize)> = Vec::new();
+    for &b in blocks {
+        match runs.last_mut() {
+            Some((begin, len)) if *begin + *len as u64 == b && *len < MAX_BLOCKS_PER_REQUEST => {
+                *len += 1
+            }
+            _ => runs.push((b, 1)),
+        }
+    }
+    runs
+}
+
+impl NbdIoEngine {
+    // Reads the blocks, in runs, calling back with the position of each
+    // block in the list along with its data.  If a run fails, its blocks
+    // are reread one at a time, so only the bad ones are reported.
+    fn read_blocks_<F>(&self, blocks: &[u64], mut f: F) -> Result<()>
+    where
+        F: FnMut(usize, Result<&[u8]>),
+    {
+        let runs = find_runs(blocks);
+        let mut firsts = Vec::with_capacity(runs.len());
+        let mut pos = 0;
+        for (_, len) in &runs {
+            firsts.push(pos);
+            pos += len;
+        }
+
+        let mut conn = self.conn.lock().unwrap();
+        let mut retries = Vec::new();
+        conn.read_runs(&runs, &self.stats, |index, data| {
+            let len = runs[index].1;
+            match data {
+                Ok(data) => {
+                    for i in 0..len {
+                        f(
+                            firsts[index] + i,
+                            Ok(&data[i * BLOCK_SIZE..(i + 1) * BLOCK_SIZE]),
+                        );
+                    }
+                }
+                Err(e) if len == 1 => f(firsts[index], Err(e)),
+                Err(_) => retries.extend(firsts[index]..firsts[index] + len),
+            }
+        })?;
+
+        let singles: Vec<(u64, usize)> = retries.iter().map(|i| (blocks[*i], 1)).collect();
+        conn.read_runs(&singles, &self.stats, |index, data| f(retries[index], data))
+    }
+}
+
+impl IoEngine for NbdIoEngine {
+    fn get_nr_blocks(&self) -> u64 {
+        self.nr_blocks
+    }
+
+    fn get_batch_size(&self) -> usize {
+        MAX_BLOCKS_PER_REQUEST * MAX_IN_FLIGHT
+    }
+
+    fn read(&self, loc: u64) -> Result<Block> {
+        self.read_many(&[loc])?.pop().unwrap()
+    }
+
+    fn read_many(&self, blocks: &[u64]) -> Result<Vec<Result<Block>>> {
+        let mut results: Vec<Option<Result<Block>>> = Vec::with_capacity(blocks.len());
+        results.resize_with(blocks.len(), || None);
+
+        self.read_blocks_(blocks, |i, data| {
+            results[i] = Some(data.map(|data| {
+                let b = Block::new(blocks[i]);
+                b.get_data().copy_from_slice(data);
+                b
+            }));
+        })?;
+
+        Ok(results.into_iter().map(|r| r.unwrap()).collect())
+    }
+
+    fn write(&self, block: &Block) -> Result<()> {
+        self.write_many(std::slice::from_ref(block))?.pop().unwrap()
+    }
+
+    fn write_many(&self, blocks: &[Block]) -> Result<Vec<Result<()>>> {
+        self.check_writable()?;
+        self.conn.lock().unwrap().write_blocks(blocks, &self.stats)
+    }
+
+    // Servers that don't advertise flush write straight through
+    fn flush(&self) -> Result<()> {
+        if !self.writable || !self.can_flush {
+            return Ok(());
+        }
+        self.conn.lock().unwrap().flush()
+    }
+
+    fn read_blocks(
+        &self,
+        _io_block_pool: &mut BufferPool,
+        blocks: &mut dyn Iterator<Item = u64>,
+        handler: &mut dyn ReadHandler,
+    ) -> io::Result<()> {
+        let blocks: Vec<u64> = blocks.collect();
+        for chunk in blocks.chunks(self.get_batch_size()) {
+            self.read_blocks_(chunk, |i, data| handler.handle(chunk[i], data))?;
+        }
+        handler.complete();
+        Ok(())
+    }
+}
+
+//------------------------------------------
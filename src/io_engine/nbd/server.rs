@@ -0,0 +1,209 @@
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufReader, BufWriter, Read, Result, Write};
+use std::net::{TcpListener, TcpStream};
+use std::os::unix::fs::FileExt;
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::thread;
+
+use crate::io_engine::nbd::*;
+
+//------------------------------------------
+
+// A minimal nbd server, exporting a single file on the loopback
+// interface.  It stands in for qemu-nbd or nbdkit in the tests.  Each
+// connection is served on a thread of its own, and the server runs until
+// the process exits.
+
+const NBD_OPT_REPLY_MAGIC: u64 = 0x3e889045565a9;
+const NBD_REP_ERR_UNSUP: u32 = (1 << 31) + 1;
+
+const EPERM: u32 = 1;
+const EIO: u32 = 5;
+const EINVAL: u32 = 22;
+
+#[derive(Clone)]
+struct Export {
+    file: Arc<File>,
+    name: String,
+    size: u64,
+    read_only: bool,
+    nr_requests: Arc<AtomicU64>,
+}
+
+pub struct NbdServer {
+    port: u16,
+    export: Export,
+}
+
+impl NbdServer {
+    pub fn start(path: &Path, name: &str, read_only: bool) -> Result<Self> {
+        let file = OpenOptions::new().read(true).write(!read_only).open(path)?;
+        let size = file.metadata()?.len();
+        let export = Export {
+            file: Arc::new(file),
+            name: name.to_string(),
+            size,
+            read_only,
+            nr_requests: Arc::new(AtomicU64::new(0)),
+        };
+
+        let listener = TcpListener::bind("127.0.0.1:0")?;
+        let port = listener.local_addr()?.port();
+        let e = export.clone();
+        thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let e = e.clone();
+                thread::spawn(move || {
+                    let _ = serve(stream, &e);
+                });
+            }
+        });
+
+        Ok(NbdServer { port, export })
+    }
+
+    pub fn url(&self) -> String {
+        format!("nbd://127.0.0.1:{}/{}", self.port, self.export.name)
+    }
+
+    // The number of read, write and flush requests served so far
+    pub fn nr_requests(&self) -> u64 {
+        self.export.nr_requests.load(Ordering::SeqCst)
+    }
+}
+
+//------------------------------------------
+
+fn read_u32<R: Read>(r: &mut R) -> Result<u32> {
+    let mut buf = [0u8; 4];
+    r.read_exact(&mut buf)?;
+    Ok(u32::from_be_bytes(buf))
+}
+
+fn read_u64<R: Read>(r: &mut R) -> Result<u64> {
+    let mut buf = [0u8; 8];
+    r.read_exact(&mut buf)?;
+    Ok(u64::from_be_bytes(buf))
+}
+
+fn send_reply<W: Write>(w: &mut W, handle: u64, error: u32, data: Option<&[u8]>) -> Result<()> {
+    w.write_all(&NBD_SIMPLE_REPLY_MAGIC.to_be_bytes())?;
+    w.write_all(&error.to_be_bytes())?;
+    w.write_all(&handle.to_be_bytes())?;
+    if let Some(data) = data {
+        w.write_all(data)?;
+    }
+    Ok(())
+}
+
+// Returns false if the client asked for an export we don't have
+fn handshake<R: Read, W: Write>(r: &mut R, w: &mut W, e: &Export) -> Result<bool> {
+    w.write_all(&NBD_MAGIC.to_be_bytes())?;
+    w.write_all(&NBD_IHAVEOPT.to_be_bytes())?;
+    w.write_all(&(NBD_FLAG_FIXED_NEWSTYLE | NBD_FLAG_NO_ZEROES).to_be_bytes())?;
+    w.flush()?;
+
+    let client_flags = read_u32(r)?;
+    loop {
+        if read_u64(r)? != NBD_IHAVEOPT {
+            return Err(io::Error::other("bad option magic"));
+        }
+        let opt = read_u32(r)?;
+        let len = read_u32(r)?;
+        let mut data = vec![0; len as usize];
+        r.read_exact(&mut data)?;
+
+        if opt != NBD_OPT_EXPORT_NAME {
+            w.write_all(&NBD_OPT_REPLY_MAGIC.to_be_bytes())?;
+            w.write_all(&opt.to_be_bytes())?;
+            w.write_all(&NBD_REP_ERR_UNSUP.to_be_bytes())?;
+            w.write_all(&0u32.to_be_bytes())?;
+            w.flush()?;
+            continue;
+        }
+
+        if data != e.name.as_bytes() {
+            return Ok(false);
+        }
+
+        let mut flags = NBD_FLAG_HAS_FLAGS;
+        if e.read_only {
+            flags |= NBD_FLAG_READ_ONLY;
+        } else {
+            flags |= NBD_FLAG_SEND_FLUSH;
+        }
+        w.write_all(&e.size.to_be_bytes())?;
+        w.write_all(&flags.to_be_bytes())?;
+        if client_flags & NBD_FLAG_NO_ZEROES as u32 == 0 {
+            w.write_all(&[0u8; 124])?;
+        }
+        w.flush()?;
+        return Ok(true);
+    }
+}
+
+fn serve(stream: TcpStream, e: &Export) -> Result<()> {
+    let mut r = BufReader::new(stream.try_clone()?);
+    let mut w = BufWriter::new(stream);
+
+    if !handshake(&mut r, &mut w, e)? {
+        return Ok(());
+    }
+
+    let mut data = Vec::new();
+    loop {
+        let mut req = [0u8; 28];
+        r.read_exact(&mut req)?;
+        if u32::from_be_bytes(req[0..4].try_into().unwrap()) != NBD_REQUEST_MAGIC {
+            return Err(io::Error::other("bad request magic"));
+        }
+        let kind = u16::from_be_bytes(req[6..8].try_into().unwrap());
+        let handle = u64::from_be_bytes(req[8..16].try_into().unwrap());
+        let offset = u64::from_be_bytes(req[16..24].try_into().unwrap());
+        let len = u32::from_be_bytes(req[24..28].try_into().unwrap()) as usize;
+        let in_range = offset + len as u64 <= e.size;
+
+        if kind == NBD_CMD_DISC {
+            return Ok(());
+        }
+        e.nr_requests.fetch_add(1, Ordering::SeqCst);
+
+        match kind {
+            NBD_CMD_READ if in_range => {
+                data.resize(len, 0);
+                match e.file.read_exact_at(&mut data, offset) {
+                    Ok(()) => send_reply(&mut w, handle, 0, Some(&data))?,
+                    Err(_) => send_reply(&mut w, handle, EIO, None)?,
+                }
+            }
+            NBD_CMD_WRITE => {
+                data.resize(len, 0);
+                r.read_exact(&mut data)?;
+                let error = if e.read_only {
+                    EPERM
+                } else if !in_range {
+                    EINVAL
+                } else if e.file.write_all_at(&data, offset).is_err() {
+                    EIO
+                } else {
+                    0
+                };
+                send_reply(&mut w, handle, error, None)?;
+            }
+            NBD_CMD_FLUSH => {
+                let error = if e.file.sync_all().is_ok() { 0 } else { EIO };
+                send_reply(&mut w, handle, error, None)?;
+            }
+            _ => send_reply(&mut w, handle, EINVAL, None)?,
+        }
+
+        // Keep writing replies while the client has requests queued
+        if r.buffer().is_empty() {
+            w.flush()?;
+        }
+    }
+}
+
+//------------------------------------------
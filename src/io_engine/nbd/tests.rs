@@ -0,0 +1,203 @@
+use super::server::NbdServer;
+use super::*;
+
+use std::fs::OpenOptions;
+use std::io::Write;
+use tempfile::NamedTempFile;
+
+//------------------------------------------
+
+const NR_BLOCKS: u64 = 32;
+
+// A file where every byte of block n is n
+fn mk_export() -> NamedTempFile {
+    let mut f = NamedTempFile::new().unwrap();
+    for loc in 0..NR_BLOCKS {
+        f.write_all(&[loc as u8; BLOCK_SIZE]).unwrap();
+    }
+    f.flush().unwrap();
+    f
+}
+
+fn stamp(engine: &dyn IoEngine, loc: u64) -> u8 {
+    let b = engine.read(loc).unwrap();
+    let data = b.get_data();
+    assert!(data.iter().all(|v| *v == data[0]));
+    data[0]
+}
+
+#[test]
+fn test_parse_url() {
+    assert_eq!(
+        parse_nbd_url("nbd://server:1234/pool-meta").unwrap(),
+        NbdUrl {
+            host: "server".to_string(),
+            port: 1234,
+            export: "pool-meta".to_string(),
+        }
+    );
+    assert_eq!(
+        parse_nbd_url("nbd://server").unwrap(),
+        NbdUrl {
+            host: "server".to_string(),
+            port: NBD_DEFAULT_PORT,
+            export: "".to_string(),
+        }
+    );
+    assert_eq!(
+        parse_nbd_url("nbd://[::1]:1234/pool-meta").unwrap(),
+        NbdUrl {
+            host: "::1".to_string(),
+            port: 1234,
+            export: "pool-meta".to_string(),
+        }
+    );
+    assert_eq!(
+        parse_nbd_url("nbd://[fe80::1]").unwrap(),
+        NbdUrl {
+            host: "fe80::1".to_string(),
+            port: NBD_DEFAULT_PORT,
+            export: "".to_string(),
+        }
+    );
+    assert!(parse_nbd_url("nbd://[::1/meta").is_err());
+    assert!(parse_nbd_url("nbd://[::1]1234/meta").is_err());
+    assert!(parse_nbd_url("nbd://[]:1234/meta").is_err());
+    assert!(parse_nbd_url("nbd://:1234/meta").is_err());
+    assert!(parse_nbd_url("nbd://server:port/meta").is_err());
+    assert!(parse_nbd_url("/dev/sda").is_err());
+}
+
+#[test]
+fn test_read() {
+    let f = mk_export();
+    let server = NbdServer::start(f.path(), "meta", true).unwrap();
+    let engine = NbdIoEngine::new(&server.url(), false).unwrap();
+    assert_eq!(engine.get_nr_blocks(), NR_BLOCKS);
+    assert_eq!(stamp(&engine, 0), 0);
+    assert_eq!(stamp(&engine, 17), 17);
+}
+
+#[test]
+fn test_read_many_is_batched() {
+    let f = mk_export();
+    let server = NbdServer::start(f.path(), "meta", true).unwrap();
+    let engine = NbdIoEngine::new(&server.url(), false).unwrap();
+
+    let blocks = [4, 5, 6, 7, 20, 21, 2];
+    let stamps: Vec<u8> = engine
+        .read_many(&blocks)
+        .unwrap()
+        .into_iter()
+        .map(|r| r.unwrap().get_data()[0])
+        .collect();
+    assert_eq!(stamps, vec![4, 5, 6, 7, 20, 21, 2]);
+    assert_eq!(server.nr_requests(), 3);
+}
+
+#[test]
+fn test_read_past_the_end_fails() {
+    let f = mk_export();
+    let server = NbdServer::start(f.path(), "meta", true).unwrap();
+    let engine = NbdIoEngine::new(&server.url(), false).unwrap();
+
+    let results = engine.read_many(&[NR_BLOCKS - 1, NR_BLOCKS]).unwrap();
+    assert!(results[0].is_ok());
+    assert!(results[1].is_err());
+
+    // the connection is still usable
+    assert_eq!(stamp(&engine, 3), 3);
+}
+
+#[test]
+fn test_unknown_export() {
+    let f = mk_export();
+    let server = NbdServer::start(f.path(), "meta", true).unwrap();
+    let url = server.url().replace("/meta", "/data");
+    assert!(NbdIoEngine::new(&url, false).is_err());
+}
+
+#[test]
+fn test_read_only_by_default() {
+    let f = mk_export();
+    let server = NbdServer::start(f.path(), "meta", false).unwrap();
+    let engine = NbdIoEngine::new(&server.url(), false).unwrap();
+    assert!(engine.write(&Block::zeroed(1)).is_err());
+    assert_eq!(server.nr_requests(), 0);
+}
+
+#[test]
+fn test_read_only_export_refuses_writable_open() {
+    let f = mk_export();
+    let server = NbdServer::start(f.path(), "meta", true).unwrap();
+    assert!(NbdIoEngine::new(&server.url(), true).is_err());
+}
+
+#[test]
+fn test_write() {
+    let f = mk_export();
+    let server = NbdServer::start(f.path(), "meta", false).unwrap();
+    let engine = NbdIoEngine::new(&server.url(), true).unwrap();
+
+    let results = engine
+        .write_many(&[Block::zeroed(3), Block::zeroed(9)])
+        .unwrap();
+    assert!(results.iter().all(|r| r.is_ok()));
+    assert_eq!(stamp(&engine, 3), 0);
+    assert_eq!(stamp(&engine, 9), 0);
+    assert_eq!(stamp(&engine, 10), 10);
+
+    // the file underneath has changed too
+    let file = OpenOptions::new().read(true).open(f.path()).unwrap();
+    let mut buf = [1u8; BLOCK_SIZE];
+    std::os::unix::fs::FileExt::read_exact_at(&file, &mut buf, 9 * BLOCK_SIZE as u64).unwrap();
+    assert!(buf.iter().all(|v| *v == 0));
+}
+
+#[test]
+fn test_flush() {
+    let f = mk_export();
+    let server = NbdServer::start(f.path(), "meta", false).unwrap();
+    let engine = NbdIoEngine::new(&server.url(), true).unwrap();
+    engine.write(&Block::zeroed(3)).unwrap();
+    engine.flush().unwrap();
+    assert_eq!(server.nr_requests(), 2);
+
+    // and again before disconnecting
+    drop(engine);
+    assert_eq!(server.nr_requests(), 3);
+}
+
+#[derive(Default)]
+struct Recorder {
+    stamps: Vec<(u64, u8)>,
+    completed: bool,
+}
+
+impl ReadHandler for Recorder {
+    fn handle(&mut self, loc: u64, data: io::Result<&[u8]>) {
+        self.stamps.push((loc, data.unwrap()[0]));
+    }
+
+    fn complete(&mut self) {
+        self.completed = true;
+    }
+}
+
+#[test]
+fn test_read_blocks() {
+    let f = mk_export();
+    let server = NbdServer::start(f.path(), "meta", true).unwrap();
+    let engine = NbdIoEngine::new(&server.url(), false).unwrap();
+
+    let mut pool = BufferPool::new(4, BLOCK_SIZE);
+    let mut recorder = Recorder::default();
+    engine
+        .read_blocks(&mut pool, &mut [8, 9, 1].into_iter(), &mut recorder)
+        .unwrap();
+    assert_eq!(recorder.stamps, vec![(8, 8), (9, 9), (1, 1)]);
+    assert!(recorder.completed);
+    assert_eq!(server.nr_requests(), 2);
+}
+
+//------------------------------------------
@@ -0,0 +1,357 @@
+use std::fs::{File, OpenOptions};
+use std::io::{self, Result};
+use std::os::unix::fs::FileExt;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+use crate::io_engine::*;
+
+#[cfg(test)]
+mod tests;
+
+//------------------------------------------
+
+// Writes go to a side file, leaving the device untouched, and reads of
+// blocks that have been written are served from there.  The side file
+// is sparse and laid out as:
+//
+//    block 0                  header: magic, version, nr device blocks,
+//                             checksum of the device superblock
+//    blocks 1..1+B            bitmap of the device blocks held
+//    block 1+B+loc            the data for device block loc
+//
+// The data is synced before the bits for newly held blocks are set, so a
+// crash never leaves the overlay claiming a block it doesn't hold.  An
+// existing side file is reopened, so a series of tools can be run against
+// the same overlay.  But only while the device is as it was when the
+// overlay was made; once the superblock on the device has changed the
+// held blocks belong to a different state of the metadata.
+
+const OVERLAY_MAGIC: u64 = 0x79616c7265766f; // "overlay"
+const OVERLAY_VERSION: u32 = 1;
+
+const BITS_PER_BLOCK: u64 = BLOCK_SIZE as u64 * 8;
+const WORDS_PER_BLOCK: usize = BLOCK_SIZE / 8;
+
+fn bad_overlay(msg: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+struct OverlayHeader {
+    nr_blocks: u64,
+    sb_csum: u32,
+}
+
+fn read_header(file: &File) -> Result<OverlayHeader> {
+    let mut header = [0u8; 24];
+    file.read_exact_at(&mut header, 0)
+        .map_err(|_| bad_overlay("not an overlay file".to_string()))?;
+
+    let magic = u64::from_le_bytes(header[0..8].try_into().unwrap());
+    let version = u32::from_le_bytes(header[8..12].try_into().unwrap());
+    let nr_blocks = u64::from_le_bytes(header[12..20].try_into().unwrap());
+    let sb_csum = u32::from_le_bytes(header[20..24].try_into().unwrap());
+    if magic != OVERLAY_MAGIC {
+        return Err(bad_overlay("not an overlay file".to_string()));
+    }
+    if version != OVERLAY_VERSION {
+        return Err(bad_overlay(format!(
+            "unsupported overlay version {}",
+            version
+        )));
+    }
+    Ok(OverlayHeader { nr_blocks, sb_csum })
+}
+
+fn write_header(file: &File, header: &OverlayHeader) -> Result<()> {
+    let mut buf = vec![0u8; BLOCK_SIZE];
+    buf[0..8].copy_from_slice(&OVERLAY_MAGIC.to_le_bytes());
+    buf[8..12].copy_from_slice(&OVERLAY_VERSION.to_le_bytes());
+    buf[12..20].copy_from_slice(&header.nr_blocks.to_le_bytes());
+    buf[20..24].copy_from_slice(&header.sb_csum.to_le_bytes());
+    file.write_all_at(&buf, 0)
+}
+
+// The thin, cache and era superblocks all live in block 0, and every
+// commit of new metadata rewrites them.
+fn superblock_csum(dev: &dyn IoEngine) -> Result<u32> {
+    if dev.get_nr_blocks() == 0 {
+        return Ok(0);
+    }
+    Ok(crc32c::crc32c(dev.read(0)?.get_data()))
+}
+
+fn nr_bitmap_blocks(nr_blocks: u64) -> u64 {
+    nr_blocks.div_ceil(BITS_PER_BLOCK)
+}
+
+// Checks the side file really is an overlay, returning the number of
+// device blocks it was made for.
+pub fn check_overlay<P: AsRef<Path>>(path: P) -> Result<u64> {
+    Ok(read_header(&File::open(path)?)?.nr_blocks)
+}
+
+//------------------------------------------
+
+pub struct OverlayIoEngine {
+    inner: Arc<dyn IoEngine>,
+    file: File,
+    nr_bitmap_blocks: u64,
+    bitmap: Mutex<Vec<u64>>,
+}
+
+impl OverlayIoEngine {
+    pub fn new<P: AsRef<Path>>(inner: Arc<dyn IoEngine>, path: P) -> Result<Self> {
+        let nr_blocks = inner.get_nr_blocks();
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(path)?;
+
+        // the device itself is never written through the overlay, so its
+        // superblock is still the one the overlay was made against
+        let sb_csum = superblock_csum(inner.as_ref())?;
+        if file.metadata()?.len() == 0 {
+            write_header(&file, &OverlayHeader { nr_blocks, sb_csum })?;
+        } else {
+            let header = read_header(&file)?;
+            if header.nr_blocks != nr_blocks {
+                return Err(bad_overlay(format!(
+                    "the overlay was made for a device of {} blocks, but this one has {}",
+                    header.nr_blocks, nr_blocks
+                )));
+            }
+            if header.sb_csum != sb_csum {
+                return Err(bad_overlay(
+                    "the superblock on the device has changed since the overlay was made"
+                        .to_string(),
+                ));
+            }
+        }
+
+        let nr_bitmap_blocks = nr_bitmap_blocks(nr_blocks);
+        let mut bitmap = vec![0u64; nr_bitmap_blocks as usize * WORDS_PER_BLOCK];
+        let mut buf = vec![0u8; BLOCK_SIZE];
+        for (i, words) in bitmap.chunks_mut(WORDS_PER_BLOCK).enumerate() {
+            // the side file may stop short of the bitmap, which reads as zeroes
+            let len = file.read_at(&mut buf, (1 + i as u64) * BLOCK_SIZE as u64)?;
+            buf[len..].fill(0);
+            for (w, bytes) in words.iter_mut().zip(buf.chunks(8)) {
+                *w = u64::from_le_bytes(bytes.try_into().unwrap());
+            }
+        }
+
+        Ok(Self {
+            inner,
+            file,
+            nr_bitmap_blocks,
+            bitmap: Mutex::new(bitmap),
+        })
+    }
+
+    fn data_offset(&self, loc: u64) -> u64 {
+        (1 + self.nr_bitmap_blocks + loc) * BLOCK_SIZE as u64
+    }
+
+    fn held(&self, loc: u64) -> bool {
+        let bitmap = self.bitmap.lock().unwrap();
+        bitmap
+            .get((loc / 64) as usize)
+            .is_some_and(|w| w & (1 << (loc % 64)) != 0)
+    }
+
+    // The device blocks that have been written to the overlay
+    pub fn held_blocks(&self) -> Vec<u64> {
+        let bitmap = self.bitmap.lock().unwrap();
+        let mut blocks = Vec::new();
+        for (i, w) in bitmap.iter().enumerate() {
+            let mut w = *w;
+            while w != 0 {
+                let bit = w.trailing_zeros() as u64;
+                blocks.push(i as u64 * 64 + bit);
+                w &= w - 1;
+            }
+        }
+        blocks
+    }
+
+    fn read_held(&self, loc: u64) -> Result<Block> {
+        let b = Block::new(loc);
+        self.file
+            .read_exact_at(b.get_data(), self.data_offset(loc))?;
+        Ok(b)
+    }
+
+    fn write_data(&self, block: &Block) -> Result<()> {
+        if block.loc >= self.inner.get_nr_blocks() {
+            return Err(io::Error::from(io::ErrorKind::InvalidInput));
+        }
+        self.file
+            .write_all_at(block.get_data(), self.data_offset(block.loc))
+    }
+
+    // Records blocks whose data has been written as held
+    fn mark_held(&self, locs: &[u64]) -> Result<()> {
+        if locs.is_empty() {
+            return Ok(());
+        }
+        self.file.sync_data()?;
+
+        let mut bitmap = self.bitmap.lock().unwrap();
+        let mut changed = Vec::new();
+        for loc in locs {
+            let word = (loc / 64) as usize;
+            bitmap[word] |= 1 << (loc % 64);
+            changed.push(word / WORDS_PER_BLOCK);
+        }
+        changed.sort_unstable();
+        changed.dedup();
+
+        for index in changed {
+            let words = &bitmap[index * WORDS_PER_BLOCK..(index + 1) * WORDS_PER_BLOCK];
+            let bytes: Vec<u8> = words.iter().flat_map(|w| w.to_le_bytes()).collect();
+            self.file
+                .write_all_at(&bytes, (1 + index as u64) * BLOCK_SIZE as u64)?;
+        }
+        Ok(())
+    }
+}
+
+impl Drop for OverlayIoEngine {
+    fn drop(&mut self) {
+        let _ = self.file.sync_all();
+    }
+}
+
+impl IoEngine for OverlayIoEngine {
+    fn get_nr_blocks(&self) -> u64 {
+        self.inner.get_nr_blocks()
+    }
+
+    fn get_batch_size(&self) -> usize {
+        self.inner.get_batch_size()
+    }
+
+    fn read(&self, b: u64) -> Result<Block> {
+        if self.held(b) {
+            self.read_held(b)
+        } else {
+            self.inner.read(b)
+        }
+    }
+
+    fn read_many(&self, blocks: &[u64]) -> Result<Vec<Result<Block>>> {
+        let held: Vec<bool> = blocks.iter().map(|b| self.held(*b)).collect();
+        if !held.contains(&true) {
+            return self.inner.read_many(blocks);
+        }
+
+        let missing: Vec<u64> = blocks
+            .iter()
+            .zip(&held)
+            .filter(|(_, held)| !**held)
+            .map(|(b, _)| *b)
+            .collect();
+        let mut inner_results = self.inner.read_many(&missing)?.into_iter();
+
+        Ok(blocks
+            .iter()
+            .zip(&held)
+            .map(|(b, held)| {
+                if *held {
+                    self.read_held(*b)
+                } else {
+                    inner_results.next().unwrap()
+                }
+            })
+            .collect())
+    }
+
+    fn write(&self, block: &Block) -> Result<()> {
+        self.write_data(block)?;
+        if !self.held(block.loc) {
+            self.mark_held(&[block.loc])?;
+        }
+        Ok(())
+    }
+
+    fn write_many(&self, blocks: &[Block]) -> Result<Vec<Result<()>>> {
+        let mut results = Vec::with_capacity(blocks.len());
+        let mut fresh = Vec::new();
+        for b in blocks {
+            let r = self.write_data(b);
+            if r.is_ok() && !self.held(b.loc) {
+                fresh.push(b.loc);
+            }
+            results.push(r);
+        }
+        self.mark_held(&fresh)?;
+        Ok(results)
+    }
+
+    fn flush(&self) -> Result<()> {
+        self.file.sync_data()
+    }
+
+    // The inner engine still reads the held blocks, but the handler is
+    // given the overlay's copy instead.
+    fn read_blocks(
+        &self,
+        io_block_pool: &mut BufferPool,
+        blocks: &mut dyn Iterator<Item = u64>,
+        handler: &mut dyn ReadHandler,
+    ) -> io::Result<()> {
+        let mut handler = OverlayHandler {
+            engine: self,
+            inner: handler,
+        };
+        self.inner.read_blocks(io_block_pool, blocks, &mut handler)
+    }
+}
+
+struct OverlayHandler<'a> {
+    engine: &'a OverlayIoEngine,
+    inner: &'a mut dyn ReadHandler,
+}
+
+impl ReadHandler for OverlayHandler<'_> {
+    fn handle(&mut self, loc: u64, data: io::Result<&[u8]>) {
+        if self.engine.held(loc) {
+            match self.engine.read_held(loc) {
+                Ok(b) => self.inner.handle(loc, Ok(b.get_data())),
+                Err(e) => self.inner.handle(loc, Err(e)),
+            }
+        } else {
+            self.inner.handle(loc, data);
+        }
+    }
+
+    fn complete(&mut self) {
+        self.inner.complete();
+    }
+}
+
+//------------------------------------------
+
+// Copies the blocks held by an overlay onto the device it was made for,
+// returning how many were written.
+pub fn commit_overlay<P: AsRef<Path>>(dev: Arc<dyn IoEngine>, path: P) -> Result<u64> {
+    let overlay = OverlayIoEngine::new(dev.clone(), path)?;
+    let held = overlay.held_blocks();
+
+    for chunk in held.chunks(dev.get_batch_size().max(1)) {
+        let blocks = chunk
+            .iter()
+            .map(|b| overlay.read_held(*b))
+            .collect::<Result<Vec<Block>>>()?;
+        for r in dev.write_many(&blocks)? {
+            r?;
+        }
+    }
+
+    Ok(held.len() as u64)
+}
+
+//------------------------------------------
@@ -0,0 +1,134 @@
+use super::*;
+
+use crate::io_engine::core::CoreIoEngine;
+
+//------------------------------------------
+
+const NR_BLOCKS: u64 = 64;
+
+fn stamped(loc: u64, v: u8) -> Block {
+    let b = Block::zeroed(loc);
+    b.get_data().fill(v);
+    b
+}
+
+// The core engine's memory starts out uninitialised
+fn mk_core(nr_blocks: u64) -> Arc<CoreIoEngine> {
+    let core = CoreIoEngine::new(nr_blocks);
+    for loc in 0..nr_blocks {
+        core.write(&Block::zeroed(loc)).unwrap();
+    }
+    Arc::new(core)
+}
+
+fn value(engine: &dyn IoEngine, loc: u64) -> u8 {
+    engine.read(loc).unwrap().get_data()[0]
+}
+
+#[test]
+fn test_writes_go_to_overlay() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("overlay");
+    let core = mk_core(NR_BLOCKS);
+    core.write(&stamped(3, 1)).unwrap();
+
+    let overlay = OverlayIoEngine::new(core.clone(), &path).unwrap();
+    overlay.write(&stamped(3, 2)).unwrap();
+    overlay.write_many(&[stamped(5, 3), stamped(6, 4)]).unwrap();
+
+    assert_eq!(value(&overlay, 3), 2);
+    assert_eq!(value(core.as_ref(), 3), 1);
+    assert_eq!(value(core.as_ref(), 5), 0);
+
+    let results = overlay.read_many(&[2, 3, 4, 5, 6]).unwrap();
+    let values: Vec<u8> = results
+        .into_iter()
+        .map(|r| r.unwrap().get_data()[0])
+        .collect();
+    assert_eq!(values, vec![0, 2, 0, 3, 4]);
+    assert_eq!(overlay.held_blocks(), vec![3, 5, 6]);
+}
+
+#[test]
+fn test_reopen() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("overlay");
+    let core = mk_core(NR_BLOCKS);
+    {
+        let overlay = OverlayIoEngine::new(core.clone(), &path).unwrap();
+        overlay.write(&stamped(63, 7)).unwrap();
+    }
+
+    assert_eq!(check_overlay(&path).unwrap(), NR_BLOCKS);
+    let overlay = OverlayIoEngine::new(core.clone(), &path).unwrap();
+    assert_eq!(overlay.held_blocks(), vec![63]);
+    assert_eq!(value(&overlay, 63), 7);
+}
+
+#[test]
+fn test_size_mismatch() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("overlay");
+    OverlayIoEngine::new(mk_core(NR_BLOCKS), &path).unwrap();
+    assert!(OverlayIoEngine::new(mk_core(NR_BLOCKS + 1), &path).is_err());
+}
+
+#[test]
+fn test_device_changed() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("overlay");
+    let core = mk_core(NR_BLOCKS);
+    {
+        let overlay = OverlayIoEngine::new(core.clone(), &path).unwrap();
+        overlay.write(&stamped(1, 5)).unwrap();
+    }
+
+    // a new transaction committed to the device behind the overlay's back
+    core.write(&stamped(0, 9)).unwrap();
+    assert!(OverlayIoEngine::new(core.clone(), &path).is_err());
+    assert!(commit_overlay(core.clone(), &path).is_err());
+    assert_eq!(value(core.as_ref(), 1), 0);
+}
+
+#[test]
+fn test_not_an_overlay() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("overlay");
+    std::fs::write(&path, vec![1u8; BLOCK_SIZE]).unwrap();
+    assert!(check_overlay(&path).is_err());
+    assert!(OverlayIoEngine::new(mk_core(NR_BLOCKS), &path).is_err());
+}
+
+#[test]
+fn test_write_past_end() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("overlay");
+    let overlay = OverlayIoEngine::new(mk_core(NR_BLOCKS), &path).unwrap();
+    assert!(overlay.write(&stamped(NR_BLOCKS, 1)).is_err());
+}
+
+#[test]
+fn test_commit() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("overlay");
+    let core = mk_core(NR_BLOCKS);
+    {
+        let overlay = OverlayIoEngine::new(core.clone(), &path).unwrap();
+        overlay.write(&stamped(1, 5)).unwrap();
+        overlay.write(&stamped(40, 6)).unwrap();
+    }
+
+    // the overlay's own copy of the superblock doesn't count as a change
+    {
+        let overlay = OverlayIoEngine::new(core.clone(), &path).unwrap();
+        overlay.write(&stamped(0, 4)).unwrap();
+    }
+
+    assert_eq!(commit_overlay(core.clone(), &path).unwrap(), 3);
+    assert_eq!(value(core.as_ref(), 0), 4);
+    assert_eq!(value(core.as_ref(), 1), 5);
+    assert_eq!(value(core.as_ref(), 40), 6);
+    assert_eq!(value(core.as_ref(), 2), 0);
+}
+
+//------------------------------------------
@@ -0,0 +1,129 @@
+use std::collections::{HashMap, VecDeque};
+use std::fs::File;
+use std::io::{self, Result};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+use crate::io_engine::*;
+use crate::pack::toplevel::{read_chunk, read_pack_index};
+
+//------------------------------------------
+
+// Serves blocks straight out of a pack file.  The chunk holding a
+// block is decompressed on demand, and the most recently used chunks
+// are kept decompressed since btree walks tend to visit neighbouring
+// blocks.  Blocks that are not in the pack read as zeroes, just as they
+// would after thin_metadata_unpack.
+
+const NR_CACHED_CHUNKS: usize = 16;
+
+type Chunk = Arc<HashMap<u64, Vec<u8>>>;
+
+pub struct PackIoEngine {
+    nr_blocks: u64,
+    input: File,
+    chunks: HashMap<u64, u64>,
+    cache: Mutex<VecDeque<(u64, Chunk)>>,
+}
+
+impl PackIoEngine {
+    pub fn new<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let mut input = File::open(path)?;
+        let index = read_pack_index(&mut input).map_err(io::Error::other)?;
+
+        Ok(Self {
+            nr_blocks: index.nr_blocks,
+            input,
+            chunks: index.chunks,
+            cache: Mutex::new(VecDeque::with_capacity(NR_CACHED_CHUNKS)),
+        })
+    }
+
+    fn get_chunk(&self, offset: u64) -> Result<Chunk> {
+        {
+            let mut cache = self.cache.lock().unwrap();
+            if let Some(pos) = cache.iter().position(|(o, _)| *o == offset) {
+                let entry = cache.remove(pos).unwrap();
+                let chunk = entry.1.clone();
+                cache.push_front(entry);
+                return Ok(chunk);
+            }
+        }
+
+        let blocks = read_chunk(&self.input, offset).map_err(io::Error::other)?;
+        let chunk: Chunk = Arc::new(blocks.into_iter().collect());
+
+        let mut cache = self.cache.lock().unwrap();
+        cache.push_front((offset, chunk.clone()));
+        cache.truncate(NR_CACHED_CHUNKS);
+
+        Ok(chunk)
+    }
+
+    fn read_only() -> io::Error {
+        io::Error::new(io::ErrorKind::PermissionDenied, "pack files are read only")
+    }
+}
+
+impl IoEngine for PackIoEngine {
+    fn get_nr_blocks(&self) -> u64 {
+        self.nr_blocks
+    }
+
+    fn get_batch_size(&self) -> usize {
+        32
+    }
+
+    fn read(&self, loc: u64) -> Result<Block> {
+        if loc >= self.nr_blocks {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("block {} is beyond the end of the pack", loc),
+            ));
+        }
+
+        match self.chunks.get(&loc) {
+            Some(offset) => {
+                let chunk = self.get_chunk(*offset)?;
+                let data = chunk
+                    .get(&loc)
+                    .ok_or_else(|| io::Error::other("pack index is inconsistent"))?;
+                let b = Block::new(loc);
+                b.get_data().copy_from_slice(data);
+                Ok(b)
+            }
+            None => Ok(Block::zeroed(loc)),
+        }
+    }
+
+    fn read_many(&self, blocks: &[u64]) -> Result<Vec<Result<Block>>> {
+        Ok(blocks.iter().map(|b| self.read(*b)).collect())
+    }
+
+    fn write(&self, _block: &Block) -> Result<()> {
+        Err(Self::read_only())
+    }
+
+    fn write_many(&self, _blocks: &[Block]) -> Result<Vec<Result<()>>> {
+        Err(Self::read_only())
+    }
+
+    fn read_blocks(
+        &self,
+        _io_block_pool: &mut BufferPool,
+        blocks: &mut dyn Iterator<Item = u64>,
+        handler: &mut dyn ReadHandler,
+    ) -> io::Result<()> {
+        for loc in blocks {
+            match self.read(loc) {
+                Ok(b) => handler.handle(loc, Ok(b.get_data())),
+                Err(e) => handler.handle(loc, Err(e)),
+            }
+        }
+
+        handler.complete();
+        Ok(())
+    }
+}
+
+//------------------------------------------
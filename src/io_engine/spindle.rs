@@ -8,11 +8,12 @@ use std::os::unix::fs::OpenOptionsExt;
 use std::os::unix::prelude::FileExt;
 use std::path::Path;
 use std::sync::mpsc;
-use std::sync::RwLock;
+use std::sync::{Arc, RwLock};
 use std::thread;
 
 use crate::checksum::*;
 use crate::io_engine::buffer::*;
+use crate::io_engine::stats::IoStats;
 use crate::io_engine::*;
 use crate::pack::node_encode::*;
 use crate::run_iter::*;
@@ -54,11 +55,9 @@ fn unpack_block(z: &[u8], loc: u64) -> Result<Block> {
     Ok(b)
 }
 
-fn pack_chunk(
-    first_block: u64,
-    chunk: &[u8],
-    compressed: &mut BTreeMap<u32, Vec<u8>>,
-) -> Result<u64> {
+// Damaged blocks that can't be packed are left out, and read from the
+// device when asked for.
+fn pack_chunk(first_block: u64, chunk: &[u8], compressed: &mut BTreeMap<u32, Vec<u8>>) -> u64 {
     let mut total_packed = 0u64;
 
     for b in 0..(chunk.len() / BLOCK_SIZE) {
@@ -69,14 +68,14 @@ fn pack_chunk(
         let kind = metadata_block_type(data);
         if kind != BT::UNKNOWN {
             let mut packed = Vec::with_capacity(64);
-            pack_block(&mut packed, kind, data)?;
-            total_packed += packed.len() as u64;
-
-            compressed.insert(block as u32, packed);
+            if pack_block(&mut packed, kind, data).is_ok() {
+                total_packed += packed.len() as u64;
+                compressed.insert(block as u32, packed);
+            }
         }
     }
 
-    Ok(total_packed)
+    total_packed
 }
 
 fn packer_thread(
@@ -86,7 +85,7 @@ fn packer_thread(
     let mut compressed = BTreeMap::new();
     while let Ok((first_block, buffer)) = rx.recv() {
         let chunk = buffer.get_data();
-        pack_chunk(first_block, chunk, &mut compressed).expect("pack chunk failed");
+        pack_chunk(first_block, chunk, &mut compressed);
     }
 
     result_tx
@@ -100,10 +99,16 @@ struct SpindleIoEngine_ {
     nr_blocks: u64,
     compressed: BTreeMap<u32, Vec<u8>>,
     input: File,
+    stats: Arc<IoStats>,
 }
 
 impl SpindleIoEngine_ {
-    pub fn new<P: AsRef<Path>>(path: P, blocks: RoaringBitmap, excl: bool) -> Result<Self> {
+    pub fn new<P: AsRef<Path>>(
+        path: P,
+        blocks: RoaringBitmap,
+        excl: bool,
+        stats: Arc<IoStats>,
+    ) -> Result<Self> {
         let nr_blocks = get_nr_blocks(path.as_ref())?;
         let mut input = OpenOptions::new()
             .read(true)
@@ -128,7 +133,8 @@ impl SpindleIoEngine_ {
                 while !range.is_empty() {
                     let len = std::cmp::min(range.len(), 16 * 1024); // Max 64M buffer
                     let buffer = Buffer::new(len * BLOCK_SIZE, 4096);
-                    input.read_exact(buffer.get_data())?;
+                    stats.time_read(len, || input.read_exact(buffer.get_data()))?;
+                    stats.prefetched(len as u64);
                     tx.send((range.start as u64, buffer))?;
                     range.start += len as u32;
                 }
@@ -143,24 +149,31 @@ impl SpindleIoEngine_ {
             nr_blocks,
             compressed,
             input,
+            stats,
         })
     }
 
     fn read_(&self, loc: u64) -> io::Result<Block> {
         if let Some(z) = self.compressed.get(&(loc as u32)) {
+            self.stats.cache_hit();
             unpack_block(z, loc).map_err(|_| io::Error::other("unpack failed"))
         } else {
+            self.stats.cache_miss();
             let b = Block::new(loc);
-            self.input
-                .read_exact_at(b.get_data(), loc * BLOCK_SIZE as u64)?;
+            self.stats.time_read(1, || {
+                self.input
+                    .read_exact_at(b.get_data(), loc * BLOCK_SIZE as u64)
+            })?;
             Ok(b)
         }
     }
 
     fn write_(&mut self, b: &Block) -> io::Result<()> {
         self.compressed.remove(&(b.loc as u32));
-        self.input
-            .write_all_at(b.get_data(), b.loc * BLOCK_SIZE as u64)
+        self.stats.time_write(1, || {
+            self.input
+                .write_all_at(b.get_data(), b.loc * BLOCK_SIZE as u64)
+        })
     }
 }
 
@@ -172,8 +185,19 @@ pub struct SpindleIoEngine {
 
 impl SpindleIoEngine {
     pub fn new<P: AsRef<Path>>(path: P, blocks: RoaringBitmap, excl: bool) -> Result<Self> {
+        Self::new_with_stats(path, blocks, excl, Arc::new(IoStats::default()))
+    }
+
+    // The blocks are read in as the engine is built, so the stats have
+    // to be handed over up front.
+    pub fn new_with_stats<P: AsRef<Path>>(
+        path: P,
+        blocks: RoaringBitmap,
+        excl: bool,
+        stats: Arc<IoStats>,
+    ) -> Result<Self> {
         Ok(Self {
-            inner: RwLock::new(SpindleIoEngine_::new(path, blocks, excl)?),
+            inner: RwLock::new(SpindleIoEngine_::new(path, blocks, excl, stats)?),
         })
     }
 }
@@ -217,13 +241,23 @@ impl IoEngine for SpindleIoEngine {
         Ok(bs)
     }
 
+    // The metadata is already in memory, so there's nothing to gain from
+    // the pool.
     fn read_blocks(
         &self,
         _io_block_pool: &mut BufferPool,
-        _blocks: &mut dyn Iterator<Item = u64>,
-        _handler: &mut dyn ReadHandler,
+        blocks: &mut dyn Iterator<Item = u64>,
+        handler: &mut dyn ReadHandler,
     ) -> io::Result<()> {
-        todo!();
+        let inner = self.inner.read().unwrap();
+        for loc in blocks {
+            match inner.read_(loc) {
+                Ok(b) => handler.handle(loc, Ok(b.get_data())),
+                Err(e) => handler.handle(loc, Err(e)),
+            }
+        }
+        handler.complete();
+        Ok(())
     }
 }
 
@@ -0,0 +1,198 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+use crate::io_engine::BLOCK_SIZE;
+
+#[cfg(test)]
+mod tests;
+
+//------------------------------------------
+
+// Latencies are kept in power of two buckets of microseconds, so the
+// percentiles are only accurate to within a factor of two.  That's
+// plenty to tell a device that's struggling from one that's idle.
+const NR_BUCKETS: usize = 32;
+
+#[derive(Default)]
+struct Latencies {
+    buckets: [AtomicU64; NR_BUCKETS],
+    max_us: AtomicU64,
+}
+
+impl Latencies {
+    fn record(&self, latency: Duration) {
+        let us = latency.as_micros() as u64;
+        let bucket = std::cmp::min((u64::BITS - us.leading_zeros()) as usize, NR_BUCKETS - 1);
+        self.buckets[bucket].fetch_add(1, Ordering::Relaxed);
+        self.max_us.fetch_max(us, Ordering::Relaxed);
+    }
+
+    // The upper bound of the bucket holding the given percentile
+    fn percentile(&self, p: u64) -> Option<u64> {
+        let counts: Vec<u64> = self
+            .buckets
+            .iter()
+            .map(|c| c.load(Ordering::Relaxed))
+            .collect();
+        let total: u64 = counts.iter().sum();
+        if total == 0 {
+            return None;
+        }
+
+        let wanted = (total * p).div_ceil(100);
+        let mut seen = 0;
+        for (bucket, count) in counts.iter().enumerate() {
+            seen += count;
+            if seen >= wanted {
+                let bound = (1u64 << bucket).saturating_sub(1);
+                return Some(std::cmp::min(bound, self.max_us.load(Ordering::Relaxed)));
+            }
+        }
+        unreachable!()
+    }
+
+    fn summary(&self) -> String {
+        match (
+            self.percentile(50),
+            self.percentile(90),
+            self.percentile(99),
+        ) {
+            (Some(p50), Some(p90), Some(p99)) => format!(
+                "p50 {}us, p90 {}us, p99 {}us, max {}us",
+                p50,
+                p90,
+                p99,
+                self.max_us.load(Ordering::Relaxed)
+            ),
+            _ => "-".to_string(),
+        }
+    }
+}
+
+//------------------------------------------
+
+#[derive(Default)]
+struct Counts {
+    nr_ios: AtomicU64,
+    nr_bytes: AtomicU64,
+    nr_errors: AtomicU64,
+    max_io: AtomicU64,
+    latency: Latencies,
+}
+
+impl Counts {
+    fn record(&self, nr_bytes: u64, latency: Duration, ok: bool) {
+        self.nr_ios.fetch_add(1, Ordering::Relaxed);
+        if ok {
+            self.nr_bytes.fetch_add(nr_bytes, Ordering::Relaxed);
+        } else {
+            self.nr_errors.fetch_add(1, Ordering::Relaxed);
+        }
+        self.max_io.fetch_max(nr_bytes, Ordering::Relaxed);
+        self.latency.record(latency);
+    }
+
+    fn summary(&self, what: &str) -> Vec<String> {
+        let nr_ios = self.nr_ios.load(Ordering::Relaxed);
+        let nr_bytes = self.nr_bytes.load(Ordering::Relaxed);
+        let mean_io = nr_bytes.checked_div(nr_ios).unwrap_or(0);
+        vec![
+            format!(
+                "{}: {} ios, {} bytes, {} errors",
+                what,
+                nr_ios,
+                nr_bytes,
+                self.nr_errors.load(Ordering::Relaxed)
+            ),
+            format!(
+                "{} size: mean {} bytes, max {} bytes",
+                what,
+                mean_io,
+                self.max_io.load(Ordering::Relaxed)
+            ),
+            format!("{} latency: {}", what, self.latency.summary()),
+        ]
+    }
+}
+
+//------------------------------------------
+
+// Gathered by the engines as they talk to the device, and shared with
+// the tool so it can print them at exit.  An io is a single request to
+// the kernel, so a vectored read of 16 blocks counts once.
+#[derive(Default)]
+pub struct IoStats {
+    reads: Counts,
+    writes: Counts,
+    nr_cache_hits: AtomicU64,
+    nr_cache_misses: AtomicU64,
+    nr_prefetched: AtomicU64,
+}
+
+impl IoStats {
+    pub fn read_done(&self, nr_bytes: u64, latency: Duration, ok: bool) {
+        self.reads.record(nr_bytes, latency, ok);
+    }
+
+    pub fn write_done(&self, nr_bytes: u64, latency: Duration, ok: bool) {
+        self.writes.record(nr_bytes, latency, ok);
+    }
+
+    pub fn time_read<T, E, F>(&self, nr_blocks: usize, f: F) -> Result<T, E>
+    where
+        F: FnOnce() -> Result<T, E>,
+    {
+        let start = Instant::now();
+        let r = f();
+        self.read_done((nr_blocks * BLOCK_SIZE) as u64, start.elapsed(), r.is_ok());
+        r
+    }
+
+    pub fn time_write<T, E, F>(&self, nr_blocks: usize, f: F) -> Result<T, E>
+    where
+        F: FnOnce() -> Result<T, E>,
+    {
+        let start = Instant::now();
+        let r = f();
+        self.write_done((nr_blocks * BLOCK_SIZE) as u64, start.elapsed(), r.is_ok());
+        r
+    }
+
+    pub fn cache_hit(&self) {
+        self.nr_cache_hits.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn cache_miss(&self) {
+        self.nr_cache_misses.fetch_add(1, Ordering::Relaxed);
+    }
+
+    // Blocks read ahead of being asked for
+    pub fn prefetched(&self, nr_blocks: u64) {
+        self.nr_prefetched.fetch_add(nr_blocks, Ordering::Relaxed);
+    }
+
+    pub fn summary(&self) -> Vec<String> {
+        let mut lines = self.reads.summary("reads");
+        lines.extend(self.writes.summary("writes"));
+
+        let hits = self.nr_cache_hits.load(Ordering::Relaxed);
+        let misses = self.nr_cache_misses.load(Ordering::Relaxed);
+        if hits + misses > 0 {
+            lines.push(format!(
+                "cache: {} hits, {} misses, {:.1}% hit rate",
+                hits,
+                misses,
+                hits as f64 * 100.0 / (hits + misses) as f64
+            ));
+        }
+
+        let prefetched = self.nr_prefetched.load(Ordering::Relaxed);
+        if prefetched > 0 {
+            lines.push(format!("prefetched: {} blocks", prefetched));
+        }
+
+        lines
+    }
+}
+
+//------------------------------------------
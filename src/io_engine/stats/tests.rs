@@ -0,0 +1,72 @@
+use super::*;
+
+use std::io;
+
+//------------------------------------------
+
+fn us(n: u64) -> Duration {
+    Duration::from_micros(n)
+}
+
+#[test]
+fn test_no_ios() {
+    let stats = IoStats::default();
+    let lines = stats.summary();
+    assert_eq!(lines[0], "reads: 0 ios, 0 bytes, 0 errors");
+    assert_eq!(lines[2], "reads latency: -");
+    assert!(!lines.iter().any(|l| l.starts_with("cache")));
+}
+
+#[test]
+fn test_counts() {
+    let stats = IoStats::default();
+    stats.read_done(4096, us(10), true);
+    stats.read_done(8192, us(10), true);
+    stats.read_done(4096, us(10), false);
+    stats.write_done(4096, us(10), true);
+
+    let lines = stats.summary();
+    assert_eq!(lines[0], "reads: 3 ios, 12288 bytes, 1 errors");
+    assert_eq!(lines[1], "reads size: mean 4096 bytes, max 8192 bytes");
+    assert_eq!(lines[3], "writes: 1 ios, 4096 bytes, 0 errors");
+}
+
+#[test]
+fn test_percentiles() {
+    let latencies = Latencies::default();
+    for _ in 0..90 {
+        latencies.record(us(100));
+    }
+    for _ in 0..10 {
+        latencies.record(us(5000));
+    }
+
+    // 100us lands in the 64..127 bucket
+    assert_eq!(latencies.percentile(50), Some(127));
+    assert_eq!(latencies.percentile(90), Some(127));
+    // and the bound is never above the max
+    assert_eq!(latencies.percentile(99), Some(5000));
+}
+
+#[test]
+fn test_time_read() {
+    let stats = IoStats::default();
+    let r: io::Result<()> = stats.time_read(2, || Err(io::Error::other("bang")));
+    assert!(r.is_err());
+    assert_eq!(stats.summary()[0], "reads: 1 ios, 0 bytes, 1 errors");
+}
+
+#[test]
+fn test_cache_hits() {
+    let stats = IoStats::default();
+    stats.cache_hit();
+    stats.cache_hit();
+    stats.cache_hit();
+    stats.cache_miss();
+    stats.prefetched(20);
+    let lines = stats.summary();
+    assert!(lines.contains(&"cache: 3 hits, 1 misses, 75.0% hit rate".to_string()));
+    assert!(lines.contains(&"prefetched: 20 blocks".to_string()));
+}
+
+//------------------------------------------
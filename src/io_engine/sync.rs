@@ -3,8 +3,10 @@ use std::fs::OpenOptions;
 use std::io::{self, Result};
 use std::os::unix::fs::{FileExt, OpenOptionsExt};
 use std::path::Path;
+use std::sync::Arc;
 
 use crate::io_engine::gaps::*;
+use crate::io_engine::stats::IoStats;
 use crate::io_engine::utils::*;
 use crate::io_engine::*;
 
@@ -25,10 +27,15 @@ const MAX_IO_BLOCKS: usize = 64;
 struct SyncReader<'a> {
     reader: VectoredBlockIo<&'a File>,
     io_blocks: &'a mut BufferPool,
+    stats: &'a IoStats,
 }
 
 impl<'a> SyncReader<'a> {
-    pub fn new(file: &'a File, io_blocks: &'a mut BufferPool) -> io::Result<Self> {
+    pub fn new(
+        file: &'a File,
+        io_blocks: &'a mut BufferPool,
+        stats: &'a IoStats,
+    ) -> io::Result<Self> {
         let block_size = io_blocks.get_block_size();
 
         // Validate input parameters
@@ -44,7 +51,11 @@ impl<'a> SyncReader<'a> {
 
         let reader = VectoredBlockIo::with_partial(file);
 
-        Ok(Self { reader, io_blocks })
+        Ok(Self {
+            reader,
+            io_blocks,
+            stats,
+        })
     }
 
     fn read_io_blocks_<F>(&mut self, block_indices: &[u64], mut callback: F) -> io::Result<()>
@@ -75,10 +86,16 @@ impl<'a> SyncReader<'a> {
         let mut buf_refs: Vec<&mut [u8]> = bufs.iter_mut().map(|b| &mut b[..]).collect();
 
         // Read the blocks
+        let start = std::time::Instant::now();
         let results = self
             .reader
-            .read_blocks(&mut buf_refs, block_indices[0] * block_size as u64)
-            .map_err(io::Error::other)?;
+            .read_blocks(&mut buf_refs, block_indices[0] * block_size as u64);
+        self.stats.read_done(
+            (buf_refs.len() * block_size) as u64,
+            start.elapsed(),
+            results.is_ok(),
+        );
+        let results = results.map_err(io::Error::other)?;
 
         // Process results and invoke callback
         for (i, result) in results.into_iter().enumerate() {
@@ -145,6 +162,7 @@ impl<'a> SyncReader<'a> {
 pub struct SyncIoEngine {
     nr_blocks: u64,
     file: File,
+    stats: Arc<IoStats>,
 }
 
 impl SyncIoEngine {
@@ -170,7 +188,16 @@ impl SyncIoEngine {
         let nr_blocks = get_nr_blocks(path.as_ref())?; // check file mode before opening it
         let file = SyncIoEngine::open_file(path.as_ref(), writable, excl)?;
 
-        Ok(SyncIoEngine { nr_blocks, file })
+        Ok(SyncIoEngine {
+            nr_blocks,
+            file,
+            stats: Arc::new(IoStats::default()),
+        })
+    }
+
+    // Gather the io stats into a collection shared with the caller
+    pub fn with_stats(self, stats: Arc<IoStats>) -> Self {
+        Self { stats, ..self }
     }
 
     fn bad_read<T>() -> Result<T> {
@@ -183,6 +210,7 @@ impl SyncIoEngine {
 
     fn read_many_<T: VectoredIo>(
         vio: VectoredBlockIo<T>,
+        stats: &IoStats,
         blocks: &[u64],
     ) -> Result<Vec<Result<Block>>> {
         const GAP_THRESHOLD: u64 = 8;
@@ -242,7 +270,10 @@ impl SyncIoEngine {
             assert!(first.is_some());
 
             // Issue io
-            let run_results = vio.read_blocks(&mut buffers[..], first.unwrap() * BLOCK_SIZE as u64);
+            let nr_blocks = buffers.len();
+            let run_results = stats.time_read(nr_blocks, || {
+                vio.read_blocks(&mut buffers[..], first.unwrap() * BLOCK_SIZE as u64)
+            });
 
             if let Ok(run_results) = run_results {
                 // select results
@@ -291,6 +322,7 @@ impl SyncIoEngine {
 
     fn write_many_<T: VectoredIo>(
         vio: VectoredBlockIo<T>,
+        stats: &IoStats,
         blocks: &[Block],
     ) -> Result<Vec<Result<()>>> {
         if blocks.is_empty() {
@@ -309,7 +341,9 @@ impl SyncIoEngine {
                 .iter()
                 .map(|b| b.as_ref())
                 .collect();
-            let run_results = vio.write_blocks(&buffers, batch_start * BLOCK_SIZE as u64);
+            let run_results = stats.time_write(batch_size, || {
+                vio.write_blocks(&buffers, batch_start * BLOCK_SIZE as u64)
+            });
             issued += batch_size;
 
             if let Ok(run_results) = run_results {
@@ -339,23 +373,27 @@ impl IoEngine for SyncIoEngine {
 
     fn read(&self, loc: u64) -> Result<Block> {
         let b = Block::new(loc);
-        self.file
-            .read_exact_at(b.get_data(), b.loc * BLOCK_SIZE as u64)?;
+        self.stats.time_read(1, || {
+            self.file
+                .read_exact_at(b.get_data(), b.loc * BLOCK_SIZE as u64)
+        })?;
         Ok(b)
     }
 
     fn read_many(&self, blocks: &[u64]) -> Result<Vec<Result<Block>>> {
-        Self::read_many_((&self.file).into(), blocks)
+        Self::read_many_((&self.file).into(), &self.stats, blocks)
     }
 
     fn write(&self, b: &Block) -> Result<()> {
-        self.file
-            .write_all_at(b.get_data(), b.loc * BLOCK_SIZE as u64)?;
+        self.stats.time_write(1, || {
+            self.file
+                .write_all_at(b.get_data(), b.loc * BLOCK_SIZE as u64)
+        })?;
         Ok(())
     }
 
     fn write_many(&self, blocks: &[Block]) -> Result<Vec<Result<()>>> {
-        Self::write_many_((&self.file).into(), blocks)
+        Self::write_many_((&self.file).into(), &self.stats, blocks)
     }
 
     fn read_blocks(
@@ -364,7 +402,7 @@ impl IoEngine for SyncIoEngine {
         blocks: &mut dyn Iterator<Item = u64>,
         handler: &mut dyn ReadHandler,
     ) -> io::Result<()> {
-        let mut reader = SyncReader::new(&self.file, io_block_pool)?;
+        let mut reader = SyncReader::new(&self.file, io_block_pool, &self.stats)?;
         reader.stream_blocks(blocks, handler)
     }
 }
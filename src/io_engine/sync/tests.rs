@@ -71,7 +71,7 @@ fn test_write_many(blocks: &[Block]) -> io::Result<()> {
         })
         .returning(|bufs, _| Ok(bufs.iter().map(|buf| buf.iov_len).sum()));
 
-    let results = SyncIoEngine::write_many_(v.into(), blocks)?;
+    let results = SyncIoEngine::write_many_(v.into(), &IoStats::default(), blocks)?;
     assert_eq!(results.len(), blocks.len());
 
     Ok(())
@@ -0,0 +1,286 @@
+use anyhow::{anyhow, Result};
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::io_engine::*;
+
+#[cfg(test)]
+mod tests;
+
+//------------------------------------------
+
+// Traces are text, one block per line, so they can be picked apart with
+// the usual tools:
+//
+//    <time_us> <thread> <batch> <r|w> <block>
+//
+// The time is when the io was issued, relative to the engine being
+// opened.  Every call into the engine is a batch, so the blocks of a
+// read_many() share a batch number.
+
+const TRACE_HEADER: &str = "# time_us thread batch op block";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TraceOp {
+    Read,
+    Write,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TraceRecord {
+    pub time_us: u64,
+    pub thread: u64,
+    pub batch: u64,
+    pub op: TraceOp,
+    pub block: u64,
+}
+
+// std's ThreadId can't be turned into a number, so threads are numbered
+// in the order they first do io.
+static NEXT_THREAD: AtomicU64 = AtomicU64::new(0);
+
+thread_local! {
+    static THREAD: u64 = NEXT_THREAD.fetch_add(1, Ordering::Relaxed);
+}
+
+//------------------------------------------
+
+// Passes everything through to another engine, logging each block.
+pub struct RecordingIoEngine {
+    inner: Arc<dyn IoEngine>,
+    out: Mutex<BufWriter<File>>,
+    start: Instant,
+    nr_batches: AtomicU64,
+}
+
+impl RecordingIoEngine {
+    pub fn new<P: AsRef<Path>>(inner: Arc<dyn IoEngine>, trace: P) -> io::Result<Self> {
+        let mut out = BufWriter::new(File::create(trace)?);
+        writeln!(out, "{}", TRACE_HEADER)?;
+
+        Ok(Self {
+            inner,
+            out: Mutex::new(out),
+            start: Instant::now(),
+            nr_batches: AtomicU64::new(0),
+        })
+    }
+
+    fn new_batch(&self) -> u64 {
+        self.nr_batches.fetch_add(1, Ordering::Relaxed)
+    }
+
+    // A trace that can't be written isn't worth failing the tool for,
+    // so errors are dropped.
+    fn log<I: Iterator<Item = u64>>(&self, batch: u64, op: TraceOp, blocks: I) {
+        let time_us = self.start.elapsed().as_micros() as u64;
+        let thread = THREAD.with(|t| *t);
+        let op = match op {
+            TraceOp::Read => 'r',
+            TraceOp::Write => 'w',
+        };
+
+        let mut out = self.out.lock().unwrap();
+        for b in blocks {
+            let _ = writeln!(out, "{} {} {} {} {}", time_us, thread, batch, op, b);
+        }
+    }
+}
+
+impl Drop for RecordingIoEngine {
+    fn drop(&mut self) {
+        let _ = self.out.lock().unwrap().flush();
+    }
+}
+
+impl IoEngine for RecordingIoEngine {
+    fn get_nr_blocks(&self) -> u64 {
+        self.inner.get_nr_blocks()
+    }
+
+    fn get_batch_size(&self) -> usize {
+        self.inner.get_batch_size()
+    }
+
+    fn read(&self, b: u64) -> io::Result<Block> {
+        self.log(self.new_batch(), TraceOp::Read, std::iter::once(b));
+        self.inner.read(b)
+    }
+
+    fn read_many(&self, blocks: &[u64]) -> io::Result<Vec<io::Result<Block>>> {
+        self.log(self.new_batch(), TraceOp::Read, blocks.iter().copied());
+        self.inner.read_many(blocks)
+    }
+
+    fn write(&self, block: &Block) -> io::Result<()> {
+        self.log(self.new_batch(), TraceOp::Write, std::iter::once(block.loc));
+        self.inner.write(block)
+    }
+
+    fn write_many(&self, blocks: &[Block]) -> io::Result<Vec<io::Result<()>>> {
+        self.log(
+            self.new_batch(),
+            TraceOp::Write,
+            blocks.iter().map(|b| b.loc),
+        );
+        self.inner.write_many(blocks)
+    }
+
+    fn flush(&self) -> io::Result<()> {
+        self.inner.flush()
+    }
+
+    // Streamed blocks are logged as the inner engine asks for them.
+    fn read_blocks(
+        &self,
+        io_block_pool: &mut BufferPool,
+        blocks: &mut dyn Iterator<Item = u64>,
+        handler: &mut dyn ReadHandler,
+    ) -> io::Result<()> {
+        let batch = self.new_batch();
+        let mut blocks = blocks.inspect(|b| self.log(batch, TraceOp::Read, std::iter::once(*b)));
+        self.inner.read_blocks(io_block_pool, &mut blocks, handler)
+    }
+}
+
+//------------------------------------------
+
+fn parse_record(line: &str) -> Option<TraceRecord> {
+    let mut fields = line.split_whitespace();
+    let mut next_u64 = || fields.next()?.parse::<u64>().ok();
+    let time_us = next_u64()?;
+    let thread = next_u64()?;
+    let batch = next_u64()?;
+    let op = match fields.next()? {
+        "r" => TraceOp::Read,
+        "w" => TraceOp::Write,
+        _ => return None,
+    };
+    let block = fields.next()?.parse::<u64>().ok()?;
+    if fields.next().is_some() {
+        return None;
+    }
+
+    Some(TraceRecord {
+        time_us,
+        thread,
+        batch,
+        op,
+        block,
+    })
+}
+
+pub fn read_trace<P: AsRef<Path>>(path: P) -> Result<Vec<TraceRecord>> {
+    let input = BufReader::new(File::open(path)?);
+    let mut trace = Vec::new();
+    for (n, line) in input.lines().enumerate() {
+        let line = line?;
+        if line.starts_with('#') || line.trim().is_empty() {
+            continue;
+        }
+        let record =
+            parse_record(&line).ok_or_else(|| anyhow!("bad trace record at line {}", n + 1))?;
+        trace.push(record);
+    }
+    Ok(trace)
+}
+
+//------------------------------------------
+
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct TraceStats {
+    pub nr_reads: u64,
+    pub nr_writes: u64,
+    pub nr_batches: u64,
+    pub nr_threads: u64,
+
+    // Any io that doesn't follow on from the one before is a seek, and
+    // the distance is how many blocks the head had to travel.
+    pub nr_seeks: u64,
+    pub seek_distance: u64,
+    pub duration: Duration,
+}
+
+pub fn trace_stats(trace: &[TraceRecord]) -> TraceStats {
+    let mut stats = TraceStats::default();
+    let mut batches = std::collections::BTreeSet::new();
+    let mut threads = std::collections::BTreeSet::new();
+    let mut prev: Option<u64> = None;
+
+    for r in trace {
+        match r.op {
+            TraceOp::Read => stats.nr_reads += 1,
+            TraceOp::Write => stats.nr_writes += 1,
+        }
+        batches.insert(r.batch);
+        threads.insert(r.thread);
+
+        if let Some(prev) = prev {
+            if r.block != prev + 1 {
+                stats.nr_seeks += 1;
+                stats.seek_distance += r.block.abs_diff(prev + 1);
+            }
+        }
+        prev = Some(r.block);
+    }
+
+    stats.nr_batches = batches.len() as u64;
+    stats.nr_threads = threads.len() as u64;
+    if let (Some(first), Some(last)) = (trace.first(), trace.last()) {
+        stats.duration = Duration::from_micros(last.time_us.saturating_sub(first.time_us));
+    }
+
+    stats
+}
+
+//------------------------------------------
+
+#[derive(Debug, Default)]
+pub struct ReplayStats {
+    pub nr_ios: u64,
+    pub nr_errors: u64,
+    pub elapsed: Duration,
+}
+
+// Re-issues the batches of a trace, in order and from a single thread.
+// Traces don't hold the data, so writes are only replayed if asked for,
+// and then with zeroes.
+pub fn replay(
+    engine: &dyn IoEngine,
+    trace: &[TraceRecord],
+    replay_writes: bool,
+) -> Result<ReplayStats> {
+    let mut stats = ReplayStats::default();
+    let start = Instant::now();
+
+    for batch in trace.chunk_by(|a, b| a.batch == b.batch && a.op == b.op) {
+        let blocks: Vec<u64> = batch.iter().map(|r| r.block).collect();
+        let nr_errors = match batch[0].op {
+            TraceOp::Read if blocks.len() == 1 => engine.read(blocks[0]).is_err() as usize,
+            TraceOp::Read => match engine.read_many(&blocks) {
+                Ok(results) => results.iter().filter(|r| r.is_err()).count(),
+                Err(_) => blocks.len(),
+            },
+            TraceOp::Write if !replay_writes => continue,
+            TraceOp::Write => {
+                let bs: Vec<Block> = blocks.iter().map(|b| Block::zeroed(*b)).collect();
+                match engine.write_many(&bs) {
+                    Ok(results) => results.iter().filter(|r| r.is_err()).count(),
+                    Err(_) => blocks.len(),
+                }
+            }
+        };
+
+        stats.nr_ios += blocks.len() as u64;
+        stats.nr_errors += nr_errors as u64;
+    }
+
+    stats.elapsed = start.elapsed();
+    Ok(stats)
+}
+
+//------------------------------------------
@@ -0,0 +1,95 @@
+use super::*;
+
+use crate::io_engine::core::CoreIoEngine;
+
+//------------------------------------------
+
+const NR_BLOCKS: u64 = 64;
+
+fn record<F: FnOnce(&dyn IoEngine)>(f: F) -> Vec<TraceRecord> {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("trace");
+    {
+        let core = Arc::new(CoreIoEngine::new(NR_BLOCKS));
+        let engine = RecordingIoEngine::new(core, &path).unwrap();
+        f(&engine);
+    }
+    read_trace(&path).unwrap()
+}
+
+fn blocks(trace: &[TraceRecord]) -> Vec<(TraceOp, u64)> {
+    trace.iter().map(|r| (r.op, r.block)).collect()
+}
+
+#[test]
+fn test_records_every_block() {
+    let trace = record(|engine| {
+        engine.read(3).unwrap();
+        engine.read_many(&[4, 5, 9]).unwrap();
+        engine.write(&Block::zeroed(7)).unwrap();
+    });
+
+    assert_eq!(
+        blocks(&trace),
+        vec![
+            (TraceOp::Read, 3),
+            (TraceOp::Read, 4),
+            (TraceOp::Read, 5),
+            (TraceOp::Read, 9),
+            (TraceOp::Write, 7),
+        ]
+    );
+
+    // the blocks of a read_many share a batch
+    let batches: Vec<u64> = trace.iter().map(|r| r.batch).collect();
+    assert_eq!(batches, vec![0, 1, 1, 1, 2]);
+}
+
+#[test]
+fn test_parse_bad_record() {
+    assert!(parse_record("0 0 0 r 1").is_some());
+    assert!(parse_record("0 0 0 x 1").is_none());
+    assert!(parse_record("0 0 r 1").is_none());
+    assert!(parse_record("0 0 0 r 1 2").is_none());
+}
+
+#[test]
+fn test_stats() {
+    let trace = record(|engine| {
+        engine.read_many(&[0, 1, 2]).unwrap();
+        engine.read(10).unwrap();
+        engine.read(4).unwrap();
+        engine.write(&Block::zeroed(5)).unwrap();
+    });
+
+    let stats = trace_stats(&trace);
+    assert_eq!(stats.nr_reads, 5);
+    assert_eq!(stats.nr_writes, 1);
+    assert_eq!(stats.nr_batches, 4);
+    assert_eq!(stats.nr_threads, 1);
+    assert_eq!(stats.nr_seeks, 2);
+    assert_eq!(stats.seek_distance, 7 + 7);
+}
+
+#[test]
+fn test_replay() {
+    let trace = record(|engine| {
+        engine.read_many(&[0, 1, 2]).unwrap();
+        engine.write(&Block::zeroed(5)).unwrap();
+    });
+
+    let core = CoreIoEngine::new(NR_BLOCKS);
+    let stats = replay(&core, &trace, false).unwrap();
+    assert_eq!(stats.nr_ios, 3);
+    assert_eq!(stats.nr_errors, 0);
+
+    let stats = replay(&core, &trace, true).unwrap();
+    assert_eq!(stats.nr_ios, 4);
+
+    // blocks past the end of the device fail
+    let small = CoreIoEngine::new(2);
+    let stats = replay(&small, &trace, true).unwrap();
+    assert_eq!(stats.nr_errors, 2);
+}
+
+//------------------------------------------
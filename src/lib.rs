@@ -7,6 +7,7 @@ extern crate quickcheck_macros;
 pub mod cache;
 pub mod checksum;
 pub mod commands;
+pub mod compression;
 pub mod copier;
 pub mod dump_utils;
 pub mod era;
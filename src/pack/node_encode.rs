@@ -104,16 +104,145 @@ pub fn pack_superblock<W: Write>(w: &mut W, bytes: &[u8]) -> PResult<()> {
     io_to_pr(pack_literal(w, bytes))
 }
 
+// Space map bitmaps hold 2 bit reference counts, so runs of free or
+// singly referenced blocks become runs of identical words.
 pub fn pack_bitmap<W: Write>(w: &mut W, bytes: &[u8]) -> PResult<()> {
-    io_to_pr(pack_literal(w, bytes))
+    let (i, hdr) = nom_to_pr(take(16usize)(bytes))?;
+    let (tail, words) = nom_to_pr(run64(i, i.len() / 8))?;
+
+    io_to_pr(pack_literal(w, hdr))?;
+    io_to_pr(pack_u64s(w, &words))?;
+    if !tail.is_empty() {
+        io_to_pr(pack_literal(w, tail))?;
+    }
+
+    Ok(())
 }
 
 pub fn pack_index<W: Write>(w: &mut W, bytes: &[u8]) -> PResult<()> {
     io_to_pr(pack_literal(w, bytes))
 }
 
+struct ArraySummary {
+    max_entries: usize,
+    value_size: usize,
+}
+
+fn summarise_array(data: &[u8]) -> IResult<&[u8], ArraySummary> {
+    let (i, _csum) = le_u32(data)?;
+    let (i, max_entries) = le_u32(i)?;
+    let (i, _nr_entries) = le_u32(i)?;
+    let (i, value_size) = le_u32(i)?;
+    let (i, _blocknr) = le_u64(i)?;
+    Ok((
+        i,
+        ArraySummary {
+            max_entries: max_entries as usize,
+            value_size: value_size as usize,
+        },
+    ))
+}
+
+// Array blocks hold the cache mappings (u64), the policy hints and
+// era entries (u32), and the words of the dirty, discard and era
+// writeset bitsets (u64).  The values are packed as a run of u64s,
+// pairing up u32 values, which collapses unmapped entries, clean
+// bitset words and repeated eras.  Any other value size is stored
+// verbatim.
 pub fn pack_array<W: Write>(w: &mut W, bytes: &[u8]) -> PResult<()> {
-    io_to_pr(pack_literal(w, bytes))
+    let (_, info) = nom_to_pr(summarise_array(bytes))?;
+    let (i, hdr) = nom_to_pr(take(24usize)(bytes))?;
+
+    let values_len = info.max_entries * info.value_size;
+    if (info.value_size != 4 && info.value_size != 8) || values_len % 8 != 0 || values_len > i.len()
+    {
+        return io_to_pr(pack_literal(w, bytes));
+    }
+
+    let (tail, words) = nom_to_pr(run64(i, values_len / 8))?;
+
+    io_to_pr(pack_literal(w, hdr))?;
+    io_to_pr(pack_u64s(w, &words))?;
+    if !tail.is_empty() {
+        io_to_pr(pack_literal(w, tail))?;
+    }
+
+    Ok(())
+}
+
+//-------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use byteorder::{LittleEndian, WriteBytesExt};
+    use std::io::Cursor;
+
+    fn check_round_trip<F>(bytes: &[u8], pack: F)
+    where
+        F: Fn(&mut Vec<u8>, &[u8]) -> PResult<()>,
+    {
+        let mut packed = Vec::new();
+        pack(&mut packed, bytes).unwrap();
+        assert!(packed.len() < bytes.len());
+
+        let unpacked = unpack(&mut Cursor::new(&packed), bytes.len()).unwrap();
+        assert_eq!(bytes, &unpacked[..]);
+    }
+
+    fn mk_array_block(max_entries: u32, value_size: u32, values: &[u8]) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(4096);
+        bytes.write_u32::<LittleEndian>(0xdeadbeef).unwrap();
+        bytes.write_u32::<LittleEndian>(max_entries).unwrap();
+        bytes.write_u32::<LittleEndian>(max_entries / 2).unwrap();
+        bytes.write_u32::<LittleEndian>(value_size).unwrap();
+        bytes.write_u64::<LittleEndian>(1234).unwrap();
+        bytes.extend_from_slice(values);
+        bytes.resize(4096, 0);
+        bytes
+    }
+
+    #[test]
+    fn test_pack_mapping_array() {
+        // consecutive origin blocks, with the valid flag set
+        let mut values = Vec::new();
+        for i in 0..254u64 {
+            values
+                .write_u64::<LittleEndian>(((i + 100) << 16) | 1)
+                .unwrap();
+        }
+        check_round_trip(&mk_array_block(509, 8, &values), pack_array);
+    }
+
+    #[test]
+    fn test_pack_hint_array() {
+        let mut values = Vec::new();
+        for i in 0..1018u32 {
+            values.write_u32::<LittleEndian>(i / 100).unwrap();
+        }
+        check_round_trip(&mk_array_block(1018, 4, &values), pack_array);
+    }
+
+    #[test]
+    fn test_pack_odd_sized_array() {
+        let values = vec![7u8; 2000];
+        let bytes = mk_array_block(678, 6, &values);
+        let mut packed = Vec::new();
+        pack_array(&mut packed, &bytes).unwrap();
+        let unpacked = unpack(&mut Cursor::new(&packed), bytes.len()).unwrap();
+        assert_eq!(bytes, unpacked);
+    }
+
+    #[test]
+    fn test_pack_bitmap() {
+        let mut bytes = vec![0u8; 4096];
+        bytes[0..4].copy_from_slice(&0xdeadbeefu32.to_le_bytes());
+        bytes[8..16].copy_from_slice(&5u64.to_le_bytes());
+        for b in &mut bytes[16..1000] {
+            *b = 0x55;
+        }
+        check_round_trip(&bytes, pack_bitmap);
+    }
 }
 
 //-------------------------------------
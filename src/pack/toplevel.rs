@@ -3,14 +3,15 @@ use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
 
 use flate2::{read::ZlibDecoder, write::ZlibEncoder, Compression};
 
+use std::collections::{HashMap, HashSet};
 use std::os::unix::fs::{FileExt, OpenOptionsExt};
 use std::{
-    fs::OpenOptions,
+    fs::{File, OpenOptions},
     io,
     io::prelude::*,
     io::Write,
     ops::DerefMut,
-    path::Path,
+    path::{Path, PathBuf},
     sync::{Arc, Mutex},
     thread::spawn,
 };
@@ -20,12 +21,39 @@ use std::sync::mpsc::{sync_channel, Receiver};
 
 use crate::checksum::*;
 use crate::file_utils;
+use crate::io_engine::stats::IoStats;
 use crate::pack::node_encode::*;
 
 const BLOCK_SIZE: u64 = 4096;
 const MAGIC: u64 = 0xa537a0aa6309ef77;
 const PACK_VERSION: u64 = 3;
 
+// Version 4 extends the header with a flags word and the checksum of
+// the base pack.  Incremental packs are written as version 4.
+const PACK_VERSION_EXT: u64 = 4;
+const FLAG_INCREMENTAL: u64 = 1;
+
+// Version 5 adds the offset of a block index, which follows the chunks,
+// so blocks can be read without unpacking the whole pack.  Full packs
+// are written as version 5.  Versions 3 and 4 are still read.
+const PACK_VERSION_INDEXED: u64 = 5;
+
+// Byte offset of the index_offset field within a version 5 header
+const INDEX_OFFSET_POS: u64 = 48;
+
+// Incremental packs record blocks that are no longer metadata by
+// setting the top bit of the block number.  No block data follows.
+const DISCARD_BIT: u64 = 1 << 63;
+
+// The image the base chain unpacks to.  It's held in an unlinked file,
+// so unchanged blocks can be compared byte for byte without keeping the
+// whole base in memory.
+struct BaseImage {
+    file: File,
+    // the blocks that are metadata in the base
+    blocks: HashSet<u64>,
+}
+
 fn shuffle<T>(v: &mut [T]) {
     let mut rng = rand::rng();
     v.shuffle(&mut rng);
@@ -63,10 +91,34 @@ fn mk_chunk_vecs(nr_blocks: u64, nr_jobs: u64) -> Vec<Vec<(u64, u64)>> {
 }
 
 pub fn pack(input_file: &Path, output_file: &Path) -> Result<()> {
+    pack_incremental(input_file, output_file, &[], Arc::default())
+}
+
+/// Packs only the blocks that differ from the image held in the chain
+/// of base packs.  An empty chain produces a full pack.  The reads of
+/// the metadata are recorded in the stats.
+pub fn pack_incremental(
+    input_file: &Path,
+    output_file: &Path,
+    base_files: &[&Path],
+    stats: Arc<IoStats>,
+) -> Result<()> {
     let nr_blocks = get_nr_blocks(input_file)?;
     let nr_jobs = std::cmp::max(1, std::cmp::min(num_cpus::get() as u64, nr_blocks / 128));
     let chunk_vecs = mk_chunk_vecs(nr_blocks, nr_jobs);
 
+    let (header, base) = if base_files.is_empty() {
+        (PackHeader::full(nr_blocks), None)
+    } else {
+        let chain = resolve_chain(None, base_files)?;
+        let header = PackHeader::incremental(nr_blocks, chain.last().unwrap().csum);
+        let dir = match output_file.parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => dir,
+            _ => Path::new("."),
+        };
+        (header, Some(Arc::new(replay_chain(&chain, dir)?)))
+    };
+
     let input = OpenOptions::new()
         .read(true)
         .write(false)
@@ -80,7 +132,7 @@ pub fn pack(input_file: &Path, output_file: &Path) -> Result<()> {
         .truncate(true)
         .open(output_file)?;
 
-    write_header(&output, nr_blocks).context("unable to write pack file header")?;
+    write_header(&output, &header).context("unable to write pack file header")?;
 
     let sync_input = Arc::new(Mutex::new(input));
     let sync_output = Arc::new(Mutex::new(output));
@@ -90,11 +142,38 @@ pub fn pack(input_file: &Path, output_file: &Path) -> Result<()> {
         let sync_input = Arc::clone(&sync_input);
         let sync_output = Arc::clone(&sync_output);
         let chunks = chunk_vecs[job as usize].clone();
-        threads.push(spawn(move || crunch(sync_input, sync_output, chunks)));
+        let base = base.clone();
+        let stats = stats.clone();
+        threads.push(spawn(move || {
+            crunch(sync_input, sync_output, chunks, base, &stats)
+        }));
     }
 
+    let mut present = HashSet::new();
+    let mut index = Vec::new();
     for t in threads {
-        t.join().unwrap()?;
+        let crunched = t.join().unwrap()?;
+        present.extend(crunched.present);
+        index.extend(crunched.index);
+    }
+
+    if let Some(base) = base {
+        let mut discards: Vec<u64> = base
+            .blocks
+            .iter()
+            .filter(|b| **b < nr_blocks && !present.contains(*b))
+            .copied()
+            .collect();
+        discards.sort_unstable();
+        write_discards(sync_output.lock().unwrap().deref_mut(), &discards)?;
+    }
+
+    if header.version == PACK_VERSION_INDEXED {
+        let mut output = sync_output.lock().unwrap();
+        index.sort_unstable();
+        let offset = output.stream_position()?;
+        write_index(output.deref_mut(), &index).context("unable to write pack file index")?;
+        output.write_all_at(&offset.to_le_bytes(), INDEX_OFFSET_POS)?;
     }
 
     sync_output.lock().unwrap().sync_all()?;
@@ -102,18 +181,53 @@ pub fn pack(input_file: &Path, output_file: &Path) -> Result<()> {
     Ok(())
 }
 
-fn crunch<R, W>(input: Arc<Mutex<R>>, output: Arc<Mutex<W>>, ranges: Vec<(u64, u64)>) -> Result<()>
+struct Crunched {
+    // blocks that are still metadata and were present in the base image
+    present: Vec<u64>,
+    // (block, chunk offset) of every block written
+    index: Vec<(u64, u64)>,
+}
+
+fn write_chunk<W>(
+    output: &Mutex<W>,
+    compressed: &[u8],
+    blocks: &mut Vec<u64>,
+    index: &mut Vec<(u64, u64)>,
+) -> io::Result<()>
+where
+    W: Write + Seek,
+{
+    let mut output = output.lock().unwrap();
+    let offset = output.stream_position()?;
+    output.write_u64::<LittleEndian>(compressed.len() as u64)?;
+    output.write_all(compressed)?;
+    index.extend(blocks.drain(..).map(|b| (b, offset)));
+    Ok(())
+}
+
+fn crunch<R, W>(
+    input: Arc<Mutex<R>>,
+    output: Arc<Mutex<W>>,
+    ranges: Vec<(u64, u64)>,
+    base: Option<Arc<BaseImage>>,
+    stats: &IoStats,
+) -> Result<Crunched>
 where
     R: Read + Seek + FileExt,
-    W: Write,
+    W: Write + Seek,
 {
-    let mut written = 0u64;
+    let mut present = Vec::new();
+    let mut index = Vec::new();
+    let mut blocks = Vec::with_capacity(1024);
+    let mut base_block = vec![0; BLOCK_SIZE as usize];
     let mut z = ZlibEncoder::new(Vec::new(), Compression::default());
     for (lo, hi) in ranges {
         // We read multiple blocks at once to reduce contention
         // on input.
         let mut input = input.lock().unwrap();
-        let big_data = read_blocks(input.deref_mut(), lo, hi - lo)?;
+        let big_data = stats.time_read((hi - lo) as usize, || {
+            read_blocks(input.deref_mut(), lo, hi - lo)
+        })?;
         drop(input);
 
         for b in lo..hi {
@@ -121,45 +235,148 @@ where
             let data = &big_data[block_start..(block_start + BLOCK_SIZE as usize)];
             let kind = metadata_block_type(data);
             if kind != BT::UNKNOWN {
+                if let Some(base) = base.as_ref().filter(|base| base.blocks.contains(&b)) {
+                    present.push(b);
+                    base.file.read_exact_at(&mut base_block, b * BLOCK_SIZE)?;
+                    if base_block == data {
+                        continue;
+                    }
+                }
+
                 z.write_u64::<LittleEndian>(b)?;
                 pack_block(&mut z, kind, data)?;
 
-                written += 1;
-                if written == 1024 {
+                blocks.push(b);
+                if blocks.len() == 1024 {
                     let compressed = z.reset(Vec::new())?;
-
-                    let mut output = output.lock().unwrap();
-                    output.write_u64::<LittleEndian>(compressed.len() as u64)?;
-                    output.write_all(&compressed)?;
-                    written = 0;
+                    write_chunk(&output, &compressed, &mut blocks, &mut index)?;
                 }
             }
         }
     }
 
-    if written > 0 {
+    if !blocks.is_empty() {
         let compressed = z.finish()?;
-        let mut output = output.lock().unwrap();
-        output.write_u64::<LittleEndian>(compressed.len() as u64)?;
-        output.write_all(&compressed)?;
+        write_chunk(&output, &compressed, &mut blocks, &mut index)?;
     }
 
+    Ok(Crunched { present, index })
+}
+
+fn write_discards<W: Write>(w: &mut W, blocks: &[u64]) -> Result<()> {
+    for chunk in blocks.chunks(4096) {
+        let mut z = ZlibEncoder::new(Vec::new(), Compression::default());
+        for b in chunk {
+            z.write_u64::<LittleEndian>(b | DISCARD_BIT)?;
+        }
+
+        let compressed = z.finish()?;
+        w.write_u64::<LittleEndian>(compressed.len() as u64)?;
+        w.write_all(&compressed)?;
+    }
+
+    Ok(())
+}
+
+// The index is a compressed list of (block, chunk offset) pairs,
+// sorted by block.
+fn write_index<W: Write>(w: &mut W, index: &[(u64, u64)]) -> Result<()> {
+    let mut z = ZlibEncoder::new(Vec::new(), Compression::default());
+    for (b, offset) in index {
+        z.write_u64::<LittleEndian>(*b)?;
+        z.write_u64::<LittleEndian>(*offset)?;
+    }
+
+    let compressed = z.finish()?;
+    w.write_u64::<LittleEndian>(compressed.len() as u64)?;
+    w.write_all(&compressed)?;
     Ok(())
 }
 
-fn write_header<W>(mut w: W, nr_blocks: u64) -> io::Result<()>
+// Reads a length prefixed byte string
+fn read_sized_at(input: &File, offset: u64) -> io::Result<Vec<u8>> {
+    let mut len = [0u8; 8];
+    input.read_exact_at(&mut len, offset)?;
+    let mut bytes = vec![0; u64::from_le_bytes(len) as usize];
+    input.read_exact_at(&mut bytes, offset + 8)?;
+    Ok(bytes)
+}
+
+fn read_index_at(input: &File, offset: u64) -> Result<HashMap<u64, u64>> {
+    let bytes = read_sized_at(input, offset)?;
+    let mut index = HashMap::new();
+    let mut z = ZlibDecoder::new(&bytes[0..]);
+    while let Ok(b) = z.read_u64::<LittleEndian>() {
+        let chunk_offset = z.read_u64::<LittleEndian>()?;
+        index.insert(b, chunk_offset);
+    }
+    Ok(index)
+}
+
+//------------------------------------------
+
+#[derive(Clone, Debug)]
+struct PackHeader {
+    version: u64,
+    nr_blocks: u64,
+    flags: u64,
+    base_csum: u64,
+    index_offset: u64,
+}
+
+impl PackHeader {
+    fn full(nr_blocks: u64) -> Self {
+        PackHeader {
+            version: PACK_VERSION_INDEXED,
+            nr_blocks,
+            flags: 0,
+            base_csum: 0,
+            index_offset: 0,
+        }
+    }
+
+    fn incremental(nr_blocks: u64, base_csum: u64) -> Self {
+        PackHeader {
+            version: PACK_VERSION_EXT,
+            nr_blocks,
+            flags: FLAG_INCREMENTAL,
+            base_csum,
+            index_offset: 0,
+        }
+    }
+
+    fn is_incremental(&self) -> bool {
+        self.flags & FLAG_INCREMENTAL != 0
+    }
+
+    // The index offset is filled in once all the chunks are written
+    fn is_indexed(&self) -> bool {
+        self.version == PACK_VERSION_INDEXED && self.index_offset != 0
+    }
+}
+
+fn write_header<W>(mut w: W, header: &PackHeader) -> io::Result<()>
 where
     W: byteorder::WriteBytesExt,
 {
     w.write_u64::<LittleEndian>(MAGIC)?;
-    w.write_u64::<LittleEndian>(PACK_VERSION)?;
+    w.write_u64::<LittleEndian>(header.version)?;
     w.write_u64::<LittleEndian>(4096)?;
-    w.write_u64::<LittleEndian>(nr_blocks)?;
+    w.write_u64::<LittleEndian>(header.nr_blocks)?;
+
+    if header.version >= PACK_VERSION_EXT {
+        w.write_u64::<LittleEndian>(header.flags)?;
+        w.write_u64::<LittleEndian>(header.base_csum)?;
+    }
+
+    if header.version == PACK_VERSION_INDEXED {
+        w.write_u64::<LittleEndian>(header.index_offset)?;
+    }
 
     Ok(())
 }
 
-fn read_header<R>(mut r: R) -> io::Result<u64>
+fn read_header<R>(mut r: R) -> io::Result<PackHeader>
 where
     R: byteorder::ReadBytesExt,
 {
@@ -172,10 +389,10 @@ where
     }
 
     let version = r.read_u64::<LittleEndian>()?;
-    if version != PACK_VERSION {
+    if !(PACK_VERSION..=PACK_VERSION_INDEXED).contains(&version) {
         return Err(io::Error::new(
             io::ErrorKind::InvalidData,
-            format!("unsupported pack file version ({}).", PACK_VERSION),
+            format!("unsupported pack file version ({}).", version),
         ));
     }
 
@@ -187,7 +404,239 @@ where
         ));
     }
 
-    r.read_u64::<LittleEndian>()
+    let nr_blocks = r.read_u64::<LittleEndian>()?;
+    if version == PACK_VERSION {
+        return Ok(PackHeader {
+            version,
+            nr_blocks,
+            flags: 0,
+            base_csum: 0,
+            index_offset: 0,
+        });
+    }
+
+    let flags = r.read_u64::<LittleEndian>()?;
+    let base_csum = r.read_u64::<LittleEndian>()?;
+    let index_offset = if version == PACK_VERSION_INDEXED {
+        r.read_u64::<LittleEndian>()?
+    } else {
+        0
+    };
+    Ok(PackHeader {
+        version,
+        nr_blocks,
+        flags,
+        base_csum,
+        index_offset,
+    })
+}
+
+// Passes the offset and compressed bytes of each chunk to the visitor.
+// The input must be positioned just after the header.
+fn read_chunks<F>(input: &mut File, header: &PackHeader, mut visit: F) -> Result<()>
+where
+    F: FnMut(u64, Vec<u8>) -> Result<()>,
+{
+    // the index follows the chunks
+    let end = if header.is_indexed() {
+        header.index_offset
+    } else {
+        u64::MAX
+    };
+
+    loop {
+        let offset = input.stream_position()?;
+        if offset >= end {
+            break;
+        }
+
+        let len = match input.read_u64::<LittleEndian>() {
+            Ok(len) => len,
+            Err(_) => break,
+        };
+        let mut bytes = vec![0; len as usize];
+        input.read_exact(&mut bytes)?;
+        visit(offset, bytes)?;
+    }
+
+    Ok(())
+}
+
+//------------------------------------------
+
+struct PackInfo {
+    path: PathBuf,
+    header: PackHeader,
+    csum: u64,
+}
+
+// Incremental packs refer to their base by the crc32c of the whole
+// base pack file.
+fn pack_checksum(path: &Path) -> io::Result<u64> {
+    let mut input = File::open(path)?;
+    let mut buf = vec![0; 1 << 20];
+    let mut csum = 0;
+    loop {
+        let n = input.read(&mut buf)?;
+        if n == 0 {
+            break;
+        }
+        csum = crc32c::crc32c_append(csum, &buf[..n]);
+    }
+    Ok(csum as u64)
+}
+
+fn read_pack_info(path: &Path) -> Result<PackInfo> {
+    let header = read_header(File::open(path)?)
+        .with_context(|| format!("unable to read pack file '{}'", path.display()))?;
+    let csum = pack_checksum(path)?;
+    Ok(PackInfo {
+        path: path.to_path_buf(),
+        header,
+        csum,
+    })
+}
+
+// Orders the packs from the full pack to the tip.  If no tip is given
+// it is the only base pack that no other pack refers to.
+fn resolve_chain(tip: Option<PackInfo>, base_files: &[&Path]) -> Result<Vec<PackInfo>> {
+    let mut pool = base_files
+        .iter()
+        .map(|path| read_pack_info(path))
+        .collect::<Result<Vec<_>>>()?;
+
+    let tip = match tip {
+        Some(tip) => tip,
+        None => {
+            let is_referenced = |csum: u64| {
+                pool.iter()
+                    .any(|p| p.header.is_incremental() && p.header.base_csum == csum)
+            };
+            let tips: Vec<usize> = (0..pool.len())
+                .filter(|i| !is_referenced(pool[*i].csum))
+                .collect();
+            if tips.len() != 1 {
+                return Err(anyhow!("the base packs do not form a single chain"));
+            }
+            pool.swap_remove(tips[0])
+        }
+    };
+
+    let mut chain = vec![tip];
+    loop {
+        let last = chain.last().unwrap();
+        if !last.header.is_incremental() {
+            break;
+        }
+
+        let base_csum = last.header.base_csum;
+        let index = pool
+            .iter()
+            .position(|p| p.csum == base_csum)
+            .ok_or_else(|| {
+                anyhow!(
+                    "base pack of '{}' (checksum {:08x}) was not given",
+                    last.path.display(),
+                    base_csum
+                )
+            })?;
+        chain.push(pool.swap_remove(index));
+    }
+
+    if let Some(p) = pool.first() {
+        return Err(anyhow!(
+            "'{}' is not part of the pack chain",
+            p.path.display()
+        ));
+    }
+
+    chain.reverse();
+    Ok(chain)
+}
+
+// Decodes a compressed chunk, passing each block to the visitor.
+// Discarded blocks are passed as None.
+fn decode_chunk<F>(bytes: &[u8], mut visit: F) -> io::Result<()>
+where
+    F: FnMut(u64, Option<Vec<u8>>) -> io::Result<()>,
+{
+    let mut z = ZlibDecoder::new(bytes);
+
+    while let Ok(b) = z.read_u64::<LittleEndian>() {
+        if b & DISCARD_BIT != 0 {
+            visit(b & !DISCARD_BIT, None)?;
+            continue;
+        }
+
+        let block = crate::pack::vm::unpack(&mut z, BLOCK_SIZE as usize)?;
+        assert!(metadata_block_type(&block[0..]) != BT::UNKNOWN);
+        visit(b, Some(block))?;
+    }
+
+    Ok(())
+}
+
+// Unpacks the chain into a temporary file in the given directory.
+fn replay_chain(chain: &[PackInfo], dir: &Path) -> Result<BaseImage> {
+    let file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .custom_flags(libc::O_TMPFILE)
+        .open(dir)
+        .with_context(|| format!("unable to create a temporary file in {}", dir.display()))?;
+    let mut blocks = HashSet::new();
+
+    for info in chain {
+        let mut input = File::open(&info.path)?;
+        read_header(&mut input)?;
+
+        read_chunks(&mut input, &info.header, |_, bytes| {
+            decode_chunk(&bytes, |b, block| {
+                match block {
+                    Some(block) => {
+                        file.write_all_at(&block, b * BLOCK_SIZE)?;
+                        blocks.insert(b);
+                    }
+                    None => {
+                        blocks.remove(&b);
+                    }
+                }
+                Ok(())
+            })?;
+            Ok(())
+        })?;
+
+        blocks.retain(|b| *b < info.header.nr_blocks);
+    }
+
+    Ok(BaseImage { file, blocks })
+}
+
+//------------------------------------------
+
+/// Ensures the metadata starts with the given kind of superblock, so
+/// the cache and era tools don't silently pack the wrong device.
+pub fn check_superblock(input_file: &Path, kind: BT) -> Result<()> {
+    let input = File::open(input_file)?;
+    let mut buf = vec![0; BLOCK_SIZE as usize];
+    input.read_exact_at(&mut buf, 0)?;
+
+    match metadata_block_type(&buf) {
+        BT::UNKNOWN => Err(anyhow!("bad checksum in superblock")),
+        found if found == kind => Ok(()),
+        _ => Err(anyhow!(
+            "input does not contain {} metadata",
+            superblock_name(&kind)
+        )),
+    }
+}
+
+fn superblock_name(kind: &BT) -> &'static str {
+    match kind {
+        BT::CACHE_SUPERBLOCK => "cache",
+        BT::ERA_SUPERBLOCK => "era",
+        _ => "thin",
+    }
 }
 
 fn get_nr_blocks(path: &Path) -> io::Result<u64> {
@@ -219,71 +668,58 @@ fn pack_block<W: Write>(w: &mut W, kind: BT, buf: &[u8]) -> Result<()> {
     Ok(())
 }
 
-fn write_zero_block<W>(w: &mut W, b: u64) -> io::Result<()>
+fn write_zero_block<W>(w: &mut W, b: u64, stats: &IoStats) -> io::Result<()>
 where
     W: Write + Seek + FileExt,
 {
     let zeroes: Vec<u8> = vec![0; BLOCK_SIZE as usize];
-    w.write_all_at(&zeroes, b * BLOCK_SIZE)?;
+    stats.time_write(1, || w.write_all_at(&zeroes, b * BLOCK_SIZE))?;
     Ok(())
 }
 
-fn write_blocks<W>(w: &Arc<Mutex<W>>, blocks: &mut Vec<(u64, Vec<u8>)>) -> io::Result<()>
+fn write_blocks<W>(
+    w: &Arc<Mutex<W>>,
+    blocks: &mut Vec<(u64, Vec<u8>)>,
+    stats: &IoStats,
+) -> io::Result<()>
 where
     W: Write + Seek + FileExt,
 {
     let w = w.lock().unwrap();
     while let Some((b, block)) = blocks.pop() {
-        w.write_all_at(&block, b * BLOCK_SIZE)?;
+        stats.time_write(1, || w.write_all_at(&block, b * BLOCK_SIZE))?;
     }
     Ok(())
 }
 
-fn decode_worker<W>(rx: Receiver<Vec<u8>>, w: Arc<Mutex<W>>) -> io::Result<()>
+fn decode_worker<W>(rx: Receiver<Vec<u8>>, w: Arc<Mutex<W>>, stats: &IoStats) -> io::Result<()>
 where
     W: Write + Seek + FileExt,
 {
     let mut blocks = Vec::new();
 
     while let Ok(bytes) = rx.recv() {
-        let mut z = ZlibDecoder::new(&bytes[0..]);
-
-        while let Ok(b) = z.read_u64::<LittleEndian>() {
-            let block = crate::pack::vm::unpack(&mut z, BLOCK_SIZE as usize).unwrap();
-            assert!(metadata_block_type(&block[0..]) != BT::UNKNOWN);
+        decode_chunk(&bytes, |b, block| {
+            let block = block.unwrap_or_else(|| vec![0; BLOCK_SIZE as usize]);
             blocks.push((b, block));
 
             if blocks.len() >= 32 {
-                write_blocks(&w, &mut blocks)?;
+                write_blocks(&w, &mut blocks, stats)?;
             }
-        }
+            Ok(())
+        })?;
     }
 
-    write_blocks(&w, &mut blocks)?;
+    write_blocks(&w, &mut blocks, stats)?;
     Ok(())
 }
 
-pub fn unpack(input_file: &Path, output_file: &Path) -> Result<()> {
-    let mut input = OpenOptions::new()
-        .read(true)
-        .write(false)
-        .open(input_file)?;
-
-    let nr_blocks = read_header(&input)?;
-
-    let mut output = OpenOptions::new()
-        .read(false)
-        .write(true)
-        .create(true)
-        .truncate(true)
-        .open(output_file)?;
-
-    // zero the last block to size the file
-    write_zero_block(&mut output, nr_blocks - 1)?;
-
-    // Run until we hit the end
-    let output = Arc::new(Mutex::new(output));
-
+fn unpack_chunks(
+    input: &mut File,
+    header: &PackHeader,
+    output: &Arc<Mutex<File>>,
+    stats: &Arc<IoStats>,
+) -> Result<()> {
     // kick off the workers
     let nr_jobs = num_cpus::get();
     let mut senders = Vec::new();
@@ -291,19 +727,19 @@ pub fn unpack(input_file: &Path, output_file: &Path) -> Result<()> {
 
     for _ in 0..nr_jobs {
         let (tx, rx) = sync_channel(1);
-        let output = Arc::clone(&output);
+        let output = Arc::clone(output);
+        let stats = Arc::clone(stats);
         senders.push(tx);
-        threads.push(spawn(move || decode_worker(rx, output)));
+        threads.push(spawn(move || decode_worker(rx, output, &stats)));
     }
 
     // Read z compressed chunk, and hand to worker thread.
     let mut next_worker = 0;
-    while let Ok(len) = input.read_u64::<LittleEndian>() {
-        let mut bytes = vec![0; len as usize];
-        input.read_exact(&mut bytes)?;
+    read_chunks(input, header, |_, bytes| {
         senders[next_worker].send(bytes).unwrap();
         next_worker = (next_worker + 1) % nr_jobs;
-    }
+        Ok(())
+    })?;
 
     for s in senders {
         drop(s);
@@ -313,7 +749,171 @@ pub fn unpack(input_file: &Path, output_file: &Path) -> Result<()> {
         t.join().unwrap()?;
     }
 
+    Ok(())
+}
+
+pub fn unpack(input_file: &Path, output_file: &Path) -> Result<()> {
+    unpack_incremental(input_file, output_file, &[], Arc::default())
+}
+
+/// Unpacks a pack file.  If it is an incremental pack, the chain of
+/// base packs it was built upon must be given, in any order.  The writes
+/// of the metadata are recorded in the stats.
+pub fn unpack_incremental(
+    input_file: &Path,
+    output_file: &Path,
+    base_files: &[&Path],
+    stats: Arc<IoStats>,
+) -> Result<()> {
+    let header = read_header(File::open(input_file)?)?;
+    let chain = if base_files.is_empty() && !header.is_incremental() {
+        vec![PackInfo {
+            path: input_file.to_path_buf(),
+            header,
+            csum: 0,
+        }]
+    } else {
+        resolve_chain(Some(read_pack_info(input_file)?), base_files)?
+    };
+
+    let output = OpenOptions::new()
+        .read(false)
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(output_file)?;
+    let is_file = output.metadata()?.file_type().is_file();
+
+    // Run until we hit the end
+    let output = Arc::new(Mutex::new(output));
+
+    let mut nr_blocks = 0;
+    for info in chain {
+        let mut input = OpenOptions::new()
+            .read(true)
+            .write(false)
+            .open(&info.path)?;
+        read_header(&mut input)?;
+
+        {
+            let mut output = output.lock().unwrap();
+            if info.header.nr_blocks > nr_blocks {
+                // zero the last block to size the file
+                write_zero_block(output.deref_mut(), info.header.nr_blocks - 1, &stats)?;
+            } else if info.header.nr_blocks < nr_blocks && is_file {
+                output.set_len(info.header.nr_blocks * BLOCK_SIZE)?;
+            }
+        }
+        nr_blocks = info.header.nr_blocks;
+
+        unpack_chunks(&mut input, &info.header, &output, &stats)?;
+    }
+
     output.lock().unwrap().sync_all()?;
 
     Ok(())
 }
+
+//------------------------------------------
+
+/// Checks for the pack file magic.  Only regular files are considered.
+pub fn is_pack_file(path: &Path) -> bool {
+    if !matches!(file_utils::is_file(path), Ok(true)) {
+        return false;
+    }
+
+    File::open(path)
+        .and_then(|mut f| f.read_u64::<LittleEndian>())
+        .is_ok_and(|magic| magic == MAGIC)
+}
+
+/// Locates the blocks of a full pack so they can be read on demand.
+pub struct PackIndex {
+    pub nr_blocks: u64,
+
+    /// Maps block number -> offset of the chunk holding it
+    pub chunks: HashMap<u64, u64>,
+}
+
+/// Reads the index of a full pack.  Packs without an index are scanned
+/// instead, which means decompressing every chunk.
+pub fn read_pack_index(input: &mut File) -> Result<PackIndex> {
+    input.rewind()?;
+    let header = read_header(&mut *input)?;
+    if header.is_incremental() {
+        return Err(anyhow!(
+            "incremental packs can only be used by unpacking them with their base packs"
+        ));
+    }
+
+    let chunks = if header.is_indexed() {
+        read_index_at(input, header.index_offset)?
+    } else {
+        let mut chunks = HashMap::new();
+        read_chunks(input, &header, |offset, bytes| {
+            decode_chunk(&bytes, |b, _| {
+                chunks.insert(b, offset);
+                Ok(())
+            })?;
+            Ok(())
+        })?;
+        chunks
+    };
+
+    Ok(PackIndex {
+        nr_blocks: header.nr_blocks,
+        chunks,
+    })
+}
+
+/// Decompresses the chunk at the given offset, returning its blocks.
+pub fn read_chunk(input: &File, offset: u64) -> Result<Vec<(u64, Vec<u8>)>> {
+    let bytes = read_sized_at(input, offset)?;
+    let mut blocks = Vec::new();
+    decode_chunk(&bytes, |b, block| {
+        if let Some(block) = block {
+            blocks.push((b, block));
+        }
+        Ok(())
+    })?;
+    Ok(blocks)
+}
+
+//------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    fn round_trip(header: &PackHeader) -> (usize, PackHeader) {
+        let mut bytes = Vec::new();
+        write_header(&mut bytes, header).unwrap();
+        (bytes.len(), read_header(Cursor::new(&bytes)).unwrap())
+    }
+
+    #[test]
+    fn incremental_header_has_no_index_offset() {
+        // as written before the index was added
+        let (len, header) = round_trip(&PackHeader::incremental(100, 0x1234));
+        assert_eq!(len, 48);
+        assert_eq!(header.version, PACK_VERSION_EXT);
+        assert_eq!(header.base_csum, 0x1234);
+        assert!(header.is_incremental());
+        assert!(!header.is_indexed());
+    }
+
+    #[test]
+    fn full_header_has_index_offset() {
+        let mut full = PackHeader::full(100);
+        full.index_offset = 4096;
+        let (len, header) = round_trip(&full);
+        assert_eq!(len as u64, INDEX_OFFSET_POS + 8);
+        assert_eq!(header.version, PACK_VERSION_INDEXED);
+        assert!(!header.is_incremental());
+        assert!(header.is_indexed());
+        assert_eq!(header.index_offset, 4096);
+    }
+}
+
+//------------------------------------------
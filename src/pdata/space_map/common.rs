@@ -1,5 +1,6 @@
 use byteorder::{LittleEndian, WriteBytesExt};
 use nom::{number::complete::*, IResult};
+use std::fmt;
 use std::io::{self, Cursor};
 
 use crate::checksum;
@@ -46,6 +47,16 @@ impl Unpack for IndexEntry {
     }
 }
 
+impl fmt::Display for IndexEntry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "block = {}, free = {}, none_free_before = {}",
+            self.blocknr, self.nr_free, self.none_free_before
+        )
+    }
+}
+
 impl Pack for IndexEntry {
     fn pack<W: WriteBytesExt>(&self, w: &mut W) -> io::Result<()> {
         w.write_u64::<LittleEndian>(self.blocknr)?;
@@ -89,6 +89,7 @@ pub trait ReportInner {
     fn progress(&mut self, percent: u8);
     fn log(&mut self, txt: &str, level: LogLevel);
     fn to_stdout(&mut self, txt: &str);
+    fn to_stderr(&mut self, txt: &str);
     fn complete(&mut self);
     fn get_prompt_input(&mut self, prompt: &str) -> io::Result<String>;
 }
@@ -170,6 +171,14 @@ impl Report {
         inner.to_stdout(txt)
     }
 
+    // Force a message to be printed to stderr, whatever the log level.
+    // For output that was asked for, but doesn't belong with the results
+    // on stdout.
+    pub fn to_stderr(&self, txt: &str) {
+        let mut inner = self.inner.lock().unwrap();
+        inner.to_stderr(txt)
+    }
+
     pub fn get_prompt_input(&self, prompt: &str) -> io::Result<String> {
         let mut inner = self.inner.lock().unwrap();
         inner.get_prompt_input(prompt)
@@ -251,6 +260,11 @@ impl ReportInner for PBInner {
         println!("{}", txt);
     }
 
+    // println() on the bar prints nothing once the bar is hidden
+    fn to_stderr(&mut self, txt: &str) {
+        self.bar.suspend(|| eprintln!("{}", txt));
+    }
+
     fn complete(&mut self) {
         self.bar.finish_and_clear();
     }
@@ -311,6 +325,10 @@ impl ReportInner for SimpleInner {
         println!("{}", txt);
     }
 
+    fn to_stderr(&mut self, txt: &str) {
+        eprintln!("{}", txt);
+    }
+
     fn complete(&mut self) {}
 
     fn get_prompt_input(&mut self, prompt: &str) -> io::Result<String> {
@@ -339,6 +357,8 @@ impl ReportInner for QuietInner {
 
     fn to_stdout(&mut self, _txt: &str) {}
 
+    fn to_stderr(&mut self, _txt: &str) {}
+
     fn complete(&mut self) {}
 
     fn get_prompt_input(&mut self, _prompt: &str) -> io::Result<String> {
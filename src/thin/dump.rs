@@ -1,13 +1,12 @@
 use anyhow::{anyhow, Context, Result};
 use std::fs::File;
-use std::io::BufWriter;
-use std::io::Write;
 use std::path::Path;
 use std::str::FromStr;
 use std::sync::{Arc, Mutex};
 
 use crate::checksum;
 use crate::commands::engine::*;
+use crate::compression;
 use crate::dump_utils::*;
 use crate::io_engine::*;
 use crate::pdata::btree::*;
@@ -409,19 +408,21 @@ pub fn dump_with_formatter(opts: ThinDumpOptions, out: &mut dyn MetadataVisitor)
 }
 
 pub fn dump(opts: ThinDumpOptions) -> Result<()> {
-    let writer: Box<dyn Write> = if opts.output.is_some() {
-        let f = File::create(opts.output.unwrap()).context(OutputError)?;
-        Box::new(BufWriter::new(f))
+    let mut writer = if let Some(path) = opts.output {
+        let f = File::create(path).context(OutputError)?;
+        compression::mk_writer(f, path).context(OutputError)?
     } else {
-        Box::new(BufWriter::new(std::io::stdout()))
+        compression::Writer::stdout()
     };
 
     let mut out: Box<dyn MetadataVisitor> = match opts.format {
-        OutputFormat::XML => Box::new(xml::XmlWriter::new(writer)),
-        OutputFormat::HumanReadable => Box::new(HumanReadableWriter::new(writer)),
+        OutputFormat::XML => Box::new(xml::XmlWriter::new(&mut writer)),
+        OutputFormat::HumanReadable => Box::new(HumanReadableWriter::new(&mut writer)),
     };
 
-    dump_with_formatter(opts, out.as_mut())
+    dump_with_formatter(opts, out.as_mut())?;
+    drop(out);
+    writer.finish().context(OutputError)
 }
 
 //------------------------------------------
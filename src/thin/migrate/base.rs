@@ -4,15 +4,17 @@ use std::os::fd::AsRawFd;
 use std::os::unix::fs::{FileTypeExt, OpenOptionsExt};
 use std::path::{Path, PathBuf};
 use std::sync::mpsc;
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 
+use crate::commands::engine::*;
 use crate::copier::batcher::*;
-use crate::copier::sync_copier::*;
+use crate::copier::rescue_copier::pass_granularity;
+use crate::copier::rescue_map::BlockState;
 use crate::copier::wrapper::ThreadedCopier;
 use crate::copier::*;
-use crate::file_utils;
-use crate::io_engine::utils::*;
+use crate::file_utils::{self, zero_range};
 use crate::io_engine::*;
+use crate::math::div_up;
 use crate::report::*;
 use crate::thin::metadata::*;
 use crate::thin::migrate::devices::*;
@@ -38,12 +40,21 @@ pub struct ThinMigrateOptions {
     pub dest: DestArgs,
     pub zero_dest: bool,
     pub buffer_size: Option<usize>, // in sectors
+    pub copier: CopierOptions,
+    pub rescue_map: Option<PathBuf>,
+    pub retry_count: u32,
     pub report: Arc<Report>,
+    pub engine_opts: EngineOptions,
 }
 
-fn mk_engine<P: AsRef<Path>>(path: P) -> Result<Arc<dyn IoEngine + Send + Sync>> {
-    let engine = SyncIoEngine::new_with(path, false, false)?;
-    Ok(Arc::new(engine))
+// The pool is live, so its metadata is opened shared
+fn mk_engine<P: AsRef<Path>>(
+    path: P,
+    engine_opts: &EngineOptions,
+) -> Result<Arc<dyn IoEngine + Send + Sync>> {
+    EngineBuilder::new(path, engine_opts)
+        .exclusive(false)
+        .build()
 }
 
 fn ensure_device_size(file: &File, expected_len: u64) -> Result<()> {
@@ -61,10 +72,15 @@ fn ensure_device_size(file: &File, expected_len: u64) -> Result<()> {
 struct Source {
     file: File,
     stream: Box<dyn Stream>,
+    id: String,
     block_size: usize, // in sectors
 }
 
-fn open_source(scanner: &mut DmScanner, src: &SourceArgs) -> Result<Source> {
+fn open_source(
+    scanner: &mut DmScanner,
+    src: &SourceArgs,
+    engine_opts: &EngineOptions,
+) -> Result<Source> {
     let thin = OpenOptions::new()
         .read(true)
         .write(false)
@@ -75,17 +91,19 @@ fn open_source(scanner: &mut DmScanner, src: &SourceArgs) -> Result<Source> {
     let pool_name = scanner.dev_to_name(&thin_table.pool_dev)?.clone();
     let pool_table = get_pool_table(scanner, &pool_name)?;
     let metadata_path = scanner.dev_to_path(&pool_table.metadata_dev)?.unwrap();
-    let metadata_engine = mk_engine(metadata_path)?;
+    let metadata_engine = mk_engine(metadata_path, engine_opts)?;
 
     if !get_device_info(scanner, &thin_name)?.read_only {
         return Err(anyhow!("not a read-only device"));
     }
 
-    let stream = Box::new(ThinStream::new(&metadata_engine, thin_table.thin_id)?);
+    let stream = ThinStream::new(&metadata_engine, thin_table.thin_id)?;
+    let id = stream.source_id();
 
     Ok(Source {
         file: thin,
-        stream,
+        stream: Box::new(stream),
+        id,
         block_size: pool_table.data_block_size as usize,
     })
 }
@@ -106,7 +124,9 @@ fn open_dest_dev(path: &PathBuf, expected_len: u64) -> Result<File> {
 }
 
 fn open_dest_file(path: &PathBuf, expected_len: u64) -> Result<File> {
+    // read too, for the compare copier
     let out = OpenOptions::new()
+        .read(true)
         .write(true)
         .create(true)
         .truncate(false)
@@ -132,30 +152,57 @@ fn open_dest(_scanner: &mut DmScanner, dst: &DestArgs, expected_len: u64) -> Res
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 fn copy_regions(
-    mut stream: Box<dyn Stream>,
+    stream: Box<dyn Stream>,
     in_file: File,
     out_file: File,
     block_size: usize,
     buffer_size: usize,
+    copier: &CopierOptions,
+    map: Option<Arc<Mutex<RescueMap>>>,
     report: Arc<Report>,
 ) -> Result<()> {
-    let in_vio: VectoredBlockIo<File> = in_file.into();
-    let out_vio: VectoredBlockIo<File> = out_file.into();
-    let copier = SyncCopier::new(
+    // Discards are issued alongside the copier
+    let out = out_file.try_clone()?;
+    let limits = &copier.limits;
+    let c = mk_copier(
+        copier.copier_type,
         buffer_size << SECTOR_SHIFT,
         block_size << SECTOR_SHIFT,
-        in_vio,
-        out_vio,
+        CopierDevs::Files {
+            src: in_file,
+            dst: out_file,
+        },
     )?;
 
+    // With a rescue map, errors are recorded in it rather than stopping
+    // the copy.
+    let c = match &map {
+        Some(map) => {
+            ThreadedCopier::new(Box::new(MappedCopier::new(c, map.clone())) as _).keep_going(true)
+        }
+        None => ThreadedCopier::new(c),
+    };
+    let c = c.limits(limits, block_size << SECTOR_SHIFT);
+    run_copier(c, stream, out, block_size, buffer_size, report)
+}
+
+fn run_copier(
+    copier: ThreadedCopier<Box<dyn Copier + Send>>,
+    mut stream: Box<dyn Stream>,
+    out_file: File,
+    block_size: usize,
+    buffer_size: usize,
+    report: Arc<Report>,
+) -> Result<()> {
     let (tx, rx) = mpsc::sync_channel::<Vec<CopyOp>>(1);
     let mut batcher = CopyOpBatcher::new(buffer_size / block_size, tx);
 
-    let copier = ThreadedCopier::new(copier);
     let progress = Arc::new(ProgressReporter::new(
         report,
         stream.size_hint() / block_size as u64,
+        block_size << SECTOR_SHIFT,
     ));
     let handle = copier.run(rx, progress);
 
@@ -172,8 +219,11 @@ fn copy_regions(
                 }
             }
             ChunkContents::Discard => {
-                // Only needed when migrating a delta
-                todo!();
+                zero_range(
+                    &out_file,
+                    chunk.offset << SECTOR_SHIFT,
+                    chunk.len << SECTOR_SHIFT,
+                )?;
             }
         }
     }
@@ -186,22 +236,100 @@ fn copy_regions(
 
 pub fn migrate(opts: ThinMigrateOptions) -> Result<()> {
     let mut scanner = DmScanner::new()?;
-    let src = open_source(&mut scanner, &opts.source)?;
-    let expected_len = file_utils::file_size(opts.source.path)?;
+    let src = open_source(&mut scanner, &opts.source, &opts.engine_opts)?;
+    let expected_len = file_utils::file_size(&opts.source.path)?;
     let out_file = open_dest(&mut scanner, &opts.dest, expected_len)?;
 
     let buffer_size = opts
         .buffer_size
         .unwrap_or_else(|| std::cmp::max(src.block_size, DEFAULT_BUFFER_SIZE));
 
+    let map = match &opts.rescue_map {
+        Some(path) => {
+            let bs = src.block_size << SECTOR_SHIFT;
+            let nr_blocks = div_up(expected_len, bs as u64);
+            Some(Arc::new(Mutex::new(RescueMap::open(
+                path, &src.id, bs, nr_blocks,
+            )?)))
+        }
+        None => None,
+    };
+
+    // kept for the rescue passes
+    let files = match map {
+        Some(_) => Some((src.file.try_clone()?, out_file.try_clone()?)),
+        None => None,
+    };
+
     copy_regions(
         src.stream,
         src.file,
         out_file,
         src.block_size,
         buffer_size,
-        opts.report,
-    )
+        &opts.copier,
+        map.clone(),
+        opts.report.clone(),
+    )?;
+
+    match (map, files) {
+        (Some(map), Some((in_file, out_file))) => {
+            rescue_bad_blocks(in_file, out_file, src.block_size, buffer_size, &opts, map)
+        }
+        _ => Ok(()),
+    }
+}
+
+// Retries the blocks the map says failed, working backwards through them
+// with smaller pieces each pass.
+fn rescue_bad_blocks(
+    in_file: File,
+    out_file: File,
+    block_size: usize,
+    buffer_size: usize,
+    opts: &ThinMigrateOptions,
+    map: Arc<Mutex<RescueMap>>,
+) -> Result<()> {
+    let bs = block_size << SECTOR_SHIFT;
+    for pass in 0..opts.retry_count {
+        let bad = map.lock().unwrap().blocks(BlockState::Bad);
+        if bad.is_empty() {
+            break;
+        }
+
+        opts.report
+            .set_title(&format!("Retrying failed blocks, pass {}", pass + 1));
+        let c = RescueCopier::new(bs, in_file.try_clone()?, out_file.try_clone()?)?
+            .granularity(pass_granularity(pass, opts.retry_count, bs))?
+            .reverse(true);
+        let c = MappedCopier::new(c, map.clone()).retry_bad(true);
+        let c = ThreadedCopier::new(c)
+            .limits(&opts.copier.limits, bs)
+            .keep_going(true);
+
+        let (tx, rx) = mpsc::sync_channel::<Vec<CopyOp>>(1);
+        let progress = Arc::new(ProgressReporter::new(
+            opts.report.clone(),
+            bad.len() as u64,
+            bs,
+        ));
+        let handle = c.run(rx, progress);
+        let mut batcher = CopyOpBatcher::new(buffer_size / block_size, tx);
+        for b in bad {
+            batcher.push(CopyOp { src: b, dst: b })?;
+        }
+        batcher.complete()?;
+        handle.join().unwrap()?;
+    }
+
+    let nr_bad = map.lock().unwrap().nr_blocks_in(BlockState::Bad);
+    if nr_bad > 0 {
+        return Err(anyhow!(
+            "{} blocks couldn't be copied, they're listed in the rescue map",
+            nr_bad
+        ));
+    }
+    Ok(())
 }
 
 //------------------------------------------
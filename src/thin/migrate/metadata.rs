@@ -42,7 +42,7 @@ pub struct ThinIterator {
     pub thin_id: u32,
     pub data_block_size: u64,
     pub mappings: BTreeIterator<BlockTime>,
-    pub mapped_blocks: u64,
+    pub details: DeviceDetail,
 }
 
 impl ThinIterator {
@@ -56,7 +56,7 @@ impl ThinIterator {
             thin_id,
             data_block_size: sb.data_block_size as u64,
             mappings,
-            mapped_blocks: details.mapped_blocks,
+            details,
         })
     }
 }
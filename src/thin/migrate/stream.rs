@@ -77,6 +77,21 @@ impl ThinStream {
         })
     }
 
+    /// Identifies the state of the thin device, for the rescue map.
+    /// Provisioning or unmapping blocks gives it a new transaction id, but
+    /// writes over provisioned blocks don't show.
+    pub fn source_id(&self) -> String {
+        let d = &self.iter.details;
+        format!(
+            "thin device {}, transaction {}, created {}, snapshotted {}, {} mapped blocks",
+            self.iter.thin_id,
+            d.transaction_id,
+            d.creation_time,
+            d.snapshotted_time,
+            d.mapped_blocks
+        )
+    }
+
     fn contiguous_run(&mut self, begin: u64) -> Result<u64> {
         let mut count = 0u64;
         loop {
@@ -127,7 +142,7 @@ impl Stream for ThinStream {
     }
 
     fn size_hint(&self) -> u64 {
-        self.iter.mapped_blocks * self.iter.data_block_size
+        self.iter.details.mapped_blocks * self.iter.data_block_size
     }
 }
 
@@ -58,7 +58,7 @@ pub fn repair(opts: ThinRepairOptions) -> Result<()> {
 
     let sm = core_metadata_sm(ctx.engine_out.get_nr_blocks(), u32::MAX);
     let batch_size = ctx.engine_out.get_batch_size();
-    let mut w = WriteBatcher::new(ctx.engine_out, sm.clone(), batch_size);
+    let mut w = WriteBatcher::new_pipelined(ctx.engine_out, sm.clone(), batch_size, PIPELINE_DEPTH);
     let mut restorer = Restorer::new(&mut w, ctx.report);
 
     dump_metadata(ctx.engine_in, &mut restorer, &sb, &md)
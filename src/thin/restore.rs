@@ -7,6 +7,7 @@ use std::path::Path;
 use std::sync::{Arc, Mutex};
 
 use crate::commands::engine::*;
+use crate::compression;
 use crate::io_engine::*;
 use crate::pdata::btree_builder::*;
 use crate::pdata::space_map::common::pack_root;
@@ -397,9 +398,14 @@ pub fn restore(opts: ThinRestoreOptions) -> Result<()> {
     let max_count = u32::MAX;
 
     let sm = core_metadata_sm(ctx.engine.get_nr_blocks(), max_count);
-    let mut w = WriteBatcher::new(ctx.engine.clone(), sm.clone(), ctx.engine.get_batch_size());
+    let mut w = WriteBatcher::new_pipelined(
+        ctx.engine.clone(),
+        sm.clone(),
+        ctx.engine.get_batch_size(),
+        PIPELINE_DEPTH,
+    );
     let mut restorer = Restorer::new_with(&mut w, &opts.overrides, ctx.report);
-    xml::read(input, &mut restorer)?;
+    xml::read(compression::mk_reader(input)?, &mut restorer)?;
 
     Ok(())
 }
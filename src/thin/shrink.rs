@@ -2,7 +2,7 @@ use anyhow::Result;
 use rangemap::RangeSet;
 use std::fs::File;
 use std::fs::OpenOptions;
-use std::io::{BufWriter, Read, SeekFrom};
+use std::io::Read;
 use std::ops::Range;
 use std::os::unix::fs::OpenOptionsExt;
 use std::path::{Path, PathBuf};
@@ -10,10 +10,11 @@ use std::sync::mpsc;
 use std::sync::Arc;
 use std::vec::Vec;
 
+use crate::compression;
 use crate::copier::batcher::CopyOpBatcher;
 use crate::copier::wrapper::ThreadedCopier;
 use crate::copier::*;
-use crate::io_engine::utils::VectoredBlockIo;
+use crate::io_engine::stats::IoStats;
 use crate::io_engine::{IoEngine, SyncIoEngine, SECTOR_SHIFT};
 use crate::pdata::space_map::metadata::core_metadata_sm;
 use crate::report::Report;
@@ -32,17 +33,32 @@ fn copy_regions(
     data_dev: &Path,
     remaps: &[(BlockRange, u64)],
     block_size: usize,
+    copier: &CopierOptions,
     progress: Arc<dyn CopyProgress + Send + Sync>,
 ) -> Result<()> {
     let file = OpenOptions::new().read(true).write(true).open(data_dev)?;
-    let vio: VectoredBlockIo<File> = file.into();
     let buffer_size = std::cmp::max(block_size, 64 * 1024 * 1024);
-    let copier = SyncCopier::in_file(buffer_size, block_size, vio)?;
+    let c = mk_copier(
+        copier.copier_type,
+        buffer_size,
+        block_size,
+        CopierDevs::InFile(file),
+    )?;
+    run_copier(
+        ThreadedCopier::new(c).limits(&copier.limits, block_size),
+        remaps,
+        progress,
+    )
+}
 
+fn run_copier<C: Copier + Send + 'static>(
+    copier: ThreadedCopier<C>,
+    remaps: &[(BlockRange, u64)],
+    progress: Arc<dyn CopyProgress + Send + Sync>,
+) -> Result<()> {
     let (tx, rx) = mpsc::sync_channel::<Vec<CopyOp>>(1);
     let mut batcher = CopyOpBatcher::new(1_000_000, tx);
 
-    let copier = ThreadedCopier::new(copier);
     let handle = copier.run(rx, progress);
 
     for (from, to) in remaps {
@@ -256,38 +272,42 @@ pub struct ThinShrinkOptions {
     pub nr_blocks: u64,
     pub do_copy: bool,
     pub binary_mode: bool,
+    pub copier: CopierOptions,
     pub report: Arc<Report>,
+    // Only the binary rebuild reads and writes metadata through engines
+    pub io_stats: Option<Arc<IoStats>>,
 }
 
 fn rewrite_xml(opts: ThinShrinkOptions) -> Result<()> {
-    use std::io::Seek;
-
     // 1st pass
-    let mut input = OpenOptions::new()
+    let input = OpenOptions::new()
         .read(true)
         .write(false)
         .custom_flags(libc::O_EXCL)
         .open(&opts.input)?;
-    let sb = xml::read_superblock(input.try_clone()?)?;
-    input.seek(SeekFrom::Start(0))?;
-    let remaps = build_remaps_from_xml(input.try_clone()?, opts.nr_blocks)?;
+    let sb = xml::read_superblock(compression::mk_reader(input.try_clone()?)?)?;
+    let remaps =
+        build_remaps_from_xml(compression::mk_reader(input.try_clone()?)?, opts.nr_blocks)?;
 
     let progress = Arc::new(IgnoreProgress {});
     if opts.do_copy {
         let bs = (sb.data_block_size as usize) << SECTOR_SHIFT;
-        copy_regions(&opts.data_device, &remaps, bs, progress)?;
+        copy_regions(&opts.data_device, &remaps, bs, &opts.copier, progress)?;
     }
 
     // 2nd pass
-    let writer = BufWriter::new(File::create(&opts.output)?);
-    let mut xml_writer = xml::XmlWriter::new(writer);
+    let mut writer = compression::mk_writer(File::create(&opts.output)?, &opts.output)?;
+    let mut xml_writer = xml::XmlWriter::new(&mut writer);
     let mut remapper = DataRemapper::new(&mut xml_writer, opts.nr_blocks, remaps);
-    input.seek(SeekFrom::Start(0))?;
-    xml::read(input, &mut remapper)
+    xml::read(compression::mk_reader(input)?, &mut remapper)?;
+    drop(xml_writer);
+    writer.finish()?;
+    Ok(())
 }
 
 fn rebuild_metadata(opts: ThinShrinkOptions) -> Result<()> {
-    let input = Arc::new(SyncIoEngine::new(&opts.input, false)?);
+    let stats = opts.io_stats.clone().unwrap_or_default();
+    let input = Arc::new(SyncIoEngine::new(&opts.input, false)?.with_stats(stats.clone()));
     let sb = read_superblock(input.as_ref(), SUPERBLOCK_LOCATION)?;
     let md = build_metadata(input.clone(), &ThinSuperblock::OnDisk(sb.clone()))?;
     let md = optimise_metadata(md)?;
@@ -298,11 +318,11 @@ fn rebuild_metadata(opts: ThinShrinkOptions) -> Result<()> {
     let progress = Arc::new(IgnoreProgress {});
     if opts.do_copy {
         let bs = (sb.data_block_size as usize) << SECTOR_SHIFT;
-        copy_regions(&opts.data_device, &remaps, bs, progress)?;
+        copy_regions(&opts.data_device, &remaps, bs, &opts.copier, progress)?;
     }
 
     // 2nd pass
-    let output = Arc::new(SyncIoEngine::new(&opts.output, true)?);
+    let output = Arc::new(SyncIoEngine::new(&opts.output, true)?.with_stats(stats));
     let sm = core_metadata_sm(output.get_nr_blocks(), u32::MAX);
     let mut w = WriteBatcher::new(output.clone(), sm, output.get_batch_size());
     let mut restorer = Restorer::new(&mut w, opts.report);
@@ -1,6 +1,8 @@
 use anyhow::{anyhow, Result};
 use rangemap::RangeSet;
-use std::sync::{Arc, Mutex};
+use std::sync::mpsc::{sync_channel, SyncSender};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
 
 use crate::checksum;
 use crate::io_engine::*;
@@ -11,6 +13,126 @@ mod tests;
 
 //------------------------------------------
 
+/// How many batches a pipelined batcher lets queue up behind the one
+/// being written before the caller has to wait.
+pub const PIPELINE_DEPTH: usize = 4;
+
+// Engines may report failures block by block, as well as for the whole
+// batch.
+fn write_batch(engine: &dyn IoEngine, batch: &[Block]) -> std::io::Result<()> {
+    for r in engine.write_many(batch)? {
+        r?;
+    }
+    Ok(())
+}
+
+#[derive(Default)]
+struct WriterState {
+    in_flight: usize,
+    error: Option<String>,
+}
+
+// Wakes up anyone waiting in drain() if write_many() panics, since the
+// batches still queued will never be written.
+struct PanicGuard(Arc<(Mutex<WriterState>, Condvar)>);
+
+impl Drop for PanicGuard {
+    fn drop(&mut self) {
+        if thread::panicking() {
+            let (lock, cvar) = &*self.0;
+            let mut s = lock.lock().unwrap();
+            s.error.get_or_insert("writer thread panicked".to_string());
+            s.in_flight = 0;
+            cvar.notify_all();
+        }
+    }
+}
+
+// Writes batches on a background thread, so the caller can carry on
+// building the next batch while the engine is busy with earlier ones.
+// Batches are written in the order they're submitted.  An error is
+// held until the next submit or drain, which returns it.
+struct Writer {
+    tx: Option<SyncSender<Vec<Block>>>,
+    state: Arc<(Mutex<WriterState>, Condvar)>,
+    thread: Option<thread::JoinHandle<()>>,
+}
+
+impl Writer {
+    fn new(engine: Arc<dyn IoEngine + Send + Sync>, depth: usize) -> Writer {
+        let (tx, rx) = sync_channel::<Vec<Block>>(depth);
+        let state = Arc::new((Mutex::new(WriterState::default()), Condvar::new()));
+
+        let thread = {
+            let state = state.clone();
+            thread::spawn(move || {
+                let _guard = PanicGuard(state.clone());
+                for batch in rx {
+                    let r = write_batch(engine.as_ref(), &batch);
+                    let (lock, cvar) = &*state;
+                    let mut s = lock.lock().unwrap();
+                    if let Err(e) = r {
+                        s.error.get_or_insert(e.to_string());
+                    }
+                    s.in_flight -= 1;
+                    cvar.notify_all();
+                }
+            })
+        };
+
+        Writer {
+            tx: Some(tx),
+            state,
+            thread: Some(thread),
+        }
+    }
+
+    fn check(s: &mut WriterState) -> Result<()> {
+        match s.error.take() {
+            Some(e) => Err(anyhow!("write error: {}", e)),
+            None => Ok(()),
+        }
+    }
+
+    fn submit(&self, batch: Vec<Block>) -> Result<()> {
+        {
+            let mut s = self.state.0.lock().unwrap();
+            Self::check(&mut s)?;
+            s.in_flight += 1;
+        }
+
+        // Blocks if the queue is full
+        if self.tx.as_ref().unwrap().send(batch).is_err() {
+            let mut s = self.state.0.lock().unwrap();
+            s.in_flight -= 1;
+            Self::check(&mut s)?;
+            return Err(anyhow!("writer thread has gone"));
+        }
+        Ok(())
+    }
+
+    // Waits for all submitted batches to be written
+    fn drain(&self) -> Result<()> {
+        let (lock, cvar) = &*self.state;
+        let mut s = cvar
+            .wait_while(lock.lock().unwrap(), |s| s.in_flight > 0)
+            .unwrap();
+        Self::check(&mut s)
+    }
+}
+
+impl Drop for Writer {
+    fn drop(&mut self) {
+        // closing the channel stops the thread once it's written the backlog
+        drop(self.tx.take());
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+//------------------------------------------
+
 pub struct WriteBatcher {
     pub engine: Arc<dyn IoEngine + Send + Sync>,
 
@@ -24,6 +146,8 @@ pub struct WriteBatcher {
     // The blocks in allocations doesn't necessarily have non-zero ref counts,
     // if the caller returns the allocated blocks via SpaceMap::dec().
     allocations: RangeSet<u64>,
+
+    writer: Option<Writer>,
 }
 
 impl WriteBatcher {
@@ -31,6 +155,28 @@ impl WriteBatcher {
         engine: Arc<dyn IoEngine + Send + Sync>,
         sm: Arc<Mutex<dyn SpaceMap>>,
         batch_size: usize,
+    ) -> WriteBatcher {
+        Self::new_with(engine, sm, batch_size, None)
+    }
+
+    /// Like new(), but full batches are written on a background thread,
+    /// with up to 'depth' of them queued.  Reads of blocks that have
+    /// left the queue wait for the writes to complete, as does flush().
+    pub fn new_pipelined(
+        engine: Arc<dyn IoEngine + Send + Sync>,
+        sm: Arc<Mutex<dyn SpaceMap>>,
+        batch_size: usize,
+        depth: usize,
+    ) -> WriteBatcher {
+        let writer = Writer::new(engine.clone(), depth);
+        Self::new_with(engine, sm, batch_size, Some(writer))
+    }
+
+    fn new_with(
+        engine: Arc<dyn IoEngine + Send + Sync>,
+        sm: Arc<Mutex<dyn SpaceMap>>,
+        batch_size: usize,
+        writer: Option<Writer>,
     ) -> WriteBatcher {
         WriteBatcher {
             engine,
@@ -38,6 +184,7 @@ impl WriteBatcher {
             batch_size,
             queue: Vec::with_capacity(batch_size),
             allocations: RangeSet::<u64>::new(),
+            writer,
         }
     }
 
@@ -109,23 +256,34 @@ impl WriteBatcher {
             }
         }
 
+        if let Some(writer) = &self.writer {
+            writer.drain()?;
+        }
+
         self.engine
             .read(blocknr)
             .map_err(|_| anyhow!("read block error"))
     }
 
     fn flush_(&mut self, queue: Vec<Block>) -> Result<()> {
-        self.engine.write_many(&queue)?;
+        if let Some(writer) = &self.writer {
+            return writer.submit(queue);
+        }
+        write_batch(self.engine.as_ref(), &queue)?;
         Ok(())
     }
 
     pub fn flush(&mut self) -> Result<()> {
-        if self.queue.is_empty() {
-            return Ok(());
+        if !self.queue.is_empty() {
+            let mut tmp = Vec::new();
+            std::mem::swap(&mut tmp, &mut self.queue);
+            self.flush_(tmp)?;
+        }
+
+        if let Some(writer) = &self.writer {
+            writer.drain()?;
         }
-        let mut tmp = Vec::new();
-        std::mem::swap(&mut tmp, &mut self.queue);
-        self.flush_(tmp)?;
+        self.engine.flush()?;
         Ok(())
     }
 }
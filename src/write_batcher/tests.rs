@@ -4,6 +4,7 @@ use mockall::*;
 use rand::prelude::*;
 use std::io;
 
+use crate::io_engine::core::CoreIoEngine;
 use checksum::BT;
 
 //-----------------------------------------
@@ -176,3 +177,97 @@ fn read_hit() {
 }
 
 //-----------------------------------------
+
+fn stamped(loc: u64) -> Block {
+    let b = Block::zeroed(loc);
+    b.get_data()[8..16].copy_from_slice(&loc.to_le_bytes());
+    b
+}
+
+fn stamp(b: &Block) -> u64 {
+    u64::from_le_bytes(b.get_data()[8..16].try_into().unwrap())
+}
+
+#[test]
+fn pipelined_writes_reach_the_engine() {
+    let engine = Arc::new(CoreIoEngine::new(1024));
+    let sm = Arc::new(Mutex::new(MockTestSpaceMap::new()));
+    let mut w = WriteBatcher::new_pipelined(engine.clone(), sm, 16, 2);
+    for loc in 0..1000 {
+        assert!(w.write(stamped(loc), BT::NODE).is_ok());
+    }
+    assert!(w.flush().is_ok());
+
+    for loc in 0..1000 {
+        assert_eq!(stamp(&engine.read(loc).unwrap()), loc);
+    }
+}
+
+#[test]
+fn pipelined_reads_see_earlier_writes() {
+    let engine = Arc::new(CoreIoEngine::new(1024));
+    let sm = Arc::new(Mutex::new(MockTestSpaceMap::new()));
+    let mut w = WriteBatcher::new_pipelined(engine, sm, 4, 2);
+    for loc in 0..100 {
+        assert!(w.write(stamped(loc), BT::NODE).is_ok());
+    }
+
+    // Both queued and submitted blocks
+    for loc in 0..100 {
+        assert_eq!(stamp(&w.read(loc).unwrap()), loc);
+    }
+}
+
+#[test]
+fn pipelined_write_errors_are_returned() {
+    let mut engine = MockEngine::new();
+    engine
+        .expect_write_many()
+        .times(1)
+        .returning(|_| Err(io::Error::other("bang")));
+
+    let sm = Arc::new(Mutex::new(MockTestSpaceMap::new()));
+    let mut w = WriteBatcher::new_pipelined(Arc::new(engine), sm, 16, 1);
+    for i in 0..16 {
+        assert!(w.write(Block::zeroed(i), BT::NODE).is_ok());
+    }
+    assert!(w.flush().is_err());
+}
+
+#[test]
+fn pipelined_block_errors_are_returned() {
+    let mut engine = MockEngine::new();
+    engine.expect_write_many().times(1).returning(|blocks| {
+        let mut results: Vec<io::Result<()>> = blocks.iter().map(|_| Ok(())).collect();
+        results[3] = Err(io::Error::other("bang"));
+        Ok(results)
+    });
+
+    let sm = Arc::new(Mutex::new(MockTestSpaceMap::new()));
+    let mut w = WriteBatcher::new_pipelined(Arc::new(engine), sm, 16, 1);
+    for i in 0..16 {
+        assert!(w.write(Block::zeroed(i), BT::NODE).is_ok());
+    }
+    assert!(w.flush().is_err());
+}
+
+#[test]
+fn pipelined_writer_panics_are_returned() {
+    let mut engine = MockEngine::new();
+    engine.expect_write_many().returning(|_| panic!("bang"));
+
+    let sm = Arc::new(Mutex::new(MockTestSpaceMap::new()));
+    let mut w = WriteBatcher::new_pipelined(Arc::new(engine), sm, 16, 1);
+    for i in 0..16 {
+        assert!(w.write(Block::zeroed(i), BT::NODE).is_ok());
+    }
+    assert!(w.flush().is_err());
+
+    // Later batches can't be written either
+    for i in 0..16 {
+        assert!(w.write(Block::zeroed(i), BT::NODE).is_ok());
+    }
+    assert!(w.write(Block::zeroed(16), BT::NODE).is_err());
+}
+
+//-----------------------------------------
@@ -22,9 +22,13 @@ Arguments:
 
 Options:
       --auto-repair              Auto repair trivial issues
+      --block-cache <MEGABYTES>  Keep up to this many megabytes of metadata in memory
       --clear-needs-check-flag   Clears the 'needs_check' flag in the superblock
   -h, --help                     Print help
       --ignore-non-fatal-errors  Only return a non-zero exit code if a fatal error is found.
+      --io-engine <IO_ENGINE>    Select an io engine to use: auto, sync or spindle
+      --io-stats                 Report io statistics at exit
+      --overlay <FILE>           Send writes to an overlay file, leaving the device untouched
   -q, --quiet                    Suppress output messages, return only exit code.
       --skip-discards            Don't check the discard bitset
       --skip-hints               Don't check the hint array
@@ -444,3 +448,100 @@ fn metadata_v2_without_slow_dev_size_info() -> Result<()> {
 }
 
 //------------------------------------------
+
+// Restores metadata with two mapped blocks, the second with the given hint
+fn mk_md_with_hint(
+    td: &mut TestDir,
+    policy: &str,
+    hint_width: u32,
+    hint: &str,
+) -> Result<std::path::PathBuf> {
+    let xml = td.mk_path("meta.xml");
+    let content = format!(
+        "<superblock uuid=\"\" block_size=\"128\" nr_cache_blocks=\"1024\" policy=\"{}\" hint_width=\"{}\">
+  <mappings>
+    <mapping cache_block=\"0\" origin_block=\"0\" dirty=\"false\"/>
+    <mapping cache_block=\"1\" origin_block=\"1\" dirty=\"false\"/>
+  </mappings>
+  <hints>
+    <hint cache_block=\"0\" data=\"AAAAAA==\"/>
+    <hint cache_block=\"1\" data=\"{}\"/>
+  </hints>
+</superblock>",
+        policy, hint_width, hint
+    );
+    write_file(&xml, content.as_bytes())?;
+
+    let md = td.mk_path("meta.bin");
+    thinp::file_utils::create_sized_file(&md, 4096 * 4096)?;
+    run_ok(cache_restore_cmd(args!["-i", &xml, "-o", &md]))?;
+
+    Ok(md)
+}
+
+// smq levels 63 and 64, little endian
+const TOP_SMQ_LEVEL: &str = "PwAAAA==";
+const BAD_SMQ_LEVEL: &str = "QAAAAA==";
+
+#[test]
+fn smq_hint_levels_in_range_pass() -> Result<()> {
+    let mut td = TestDir::new()?;
+    let md = mk_md_with_hint(&mut td, "smq", 4, TOP_SMQ_LEVEL)?;
+    run_ok(cache_check_cmd(args![&md]))?;
+    Ok(())
+}
+
+#[test]
+fn smq_hint_level_out_of_range_fails() -> Result<()> {
+    for policy in ["smq", "mq", "default"] {
+        let mut td = TestDir::new()?;
+        let md = mk_md_with_hint(&mut td, policy, 4, BAD_SMQ_LEVEL)?;
+        let stderr = run_fail(cache_check_cmd(args![&md]))?;
+        assert!(stderr.contains("smq hint level 64 out of range for cache block 1"));
+    }
+    Ok(())
+}
+
+#[test]
+fn skip_hints_ignores_bad_smq_hints() -> Result<()> {
+    let mut td = TestDir::new()?;
+    let md = mk_md_with_hint(&mut td, "smq", 4, BAD_SMQ_LEVEL)?;
+    run_ok(cache_check_cmd(args![&md, "--skip-hints"]))?;
+    Ok(())
+}
+
+#[test]
+fn hints_of_unknown_policies_are_not_decoded() -> Result<()> {
+    let mut td = TestDir::new()?;
+    let md = mk_md_with_hint(&mut td, "foo", 4, BAD_SMQ_LEVEL)?;
+    run_ok(cache_check_cmd(args![&md]))?;
+    Ok(())
+}
+
+#[test]
+fn smq_with_wrong_hint_width_fails() -> Result<()> {
+    let mut td = TestDir::new()?;
+    let md = mk_md_with_hint(&mut td, "smq", 8, TOP_SMQ_LEVEL)?;
+    let stderr = run_fail(cache_check_cmd(args![&md]))?;
+    assert!(stderr.contains("expects a hint size of 4"));
+    Ok(())
+}
+
+#[test]
+fn hint_width_mismatch_with_array_fails() -> Result<()> {
+    let mut td = TestDir::new()?;
+    let md = mk_md_with_hint(&mut td, "foo", 8, TOP_SMQ_LEVEL)?;
+    run_fail(cache_check_cmd(args![&md]))?;
+    Ok(())
+}
+
+#[test]
+fn bad_hint_width_fails() -> Result<()> {
+    let mut td = TestDir::new()?;
+    let md = mk_md_with_hint(&mut td, "foo", 6, TOP_SMQ_LEVEL)?;
+    let stderr = run_fail(cache_check_cmd(args![&md]))?;
+    assert!(stderr.contains("invalid policy hint size 6"));
+    Ok(())
+}
+
+//------------------------------------------
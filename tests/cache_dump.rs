@@ -21,10 +21,13 @@ Arguments:
   <INPUT>  Specify the input device to dump
 
 Options:
-  -h, --help           Print help
-  -o, --output <FILE>  Specify the output file rather than stdout
-  -r, --repair         Repair the metadata whilst dumping it
-  -V, --version        Print version";
+      --block-cache <MEGABYTES>  Keep up to this many megabytes of metadata in memory
+  -h, --help                     Print help
+      --io-engine <IO_ENGINE>    Select an io engine to use: auto, sync or spindle
+      --io-stats                 Report io statistics at exit
+  -o, --output <FILE>            Specify the output file rather than stdout
+  -r, --repair                   Repair the metadata whilst dumping it
+  -V, --version                  Print version";
 
 //------------------------------------------
 
@@ -108,6 +111,26 @@ fn dump_restore_cycle() -> Result<()> {
     Ok(())
 }
 
+#[test]
+fn dump_restore_cycle_compressed() -> Result<()> {
+    let mut td = TestDir::new()?;
+    let md = mk_valid_md(&mut td)?;
+    let output = run_ok_raw(cache_dump_cmd(args![&md]))?;
+
+    let xml_gz = td.mk_path("meta.xml.gz");
+    run_ok(cache_dump_cmd(args![&md, "-o", &xml_gz]))?;
+    let compressed = std::fs::read(&xml_gz)?;
+    assert_eq!(&compressed[0..2], &[0x1f, 0x8b]);
+
+    let md2 = mk_zeroed_md(&mut td)?;
+    run_ok(cache_restore_cmd(args!["-i", &xml_gz, "-o", &md2]))?;
+
+    let output2 = run_ok_raw(cache_dump_cmd(args![&md2]))?;
+    assert_eq!(output.stdout, output2.stdout);
+
+    Ok(())
+}
+
 //------------------------------------------
 // test no stderr on broken pipe errors
 
@@ -0,0 +1,106 @@
+use anyhow::Result;
+
+mod common;
+
+use common::cache::*;
+use common::common_args::*;
+use common::input_arg::*;
+use common::output_option::*;
+use common::process::*;
+use common::program::*;
+use common::target::*;
+use common::test_dir::*;
+
+//------------------------------------------
+
+const USAGE: &str = "Produces a compressed file of cache metadata.  Only packs metadata blocks that are actually used.
+
+Usage: cache_metadata_pack [OPTIONS] --input <DEV> --output <FILE>
+
+Options:
+      --base <FILE>    Only pack blocks changed since the given base pack(s)
+  -f, --force          Force overwrite the output file
+  -h, --help           Print help
+  -i, --input <DEV>    Specify cache metadata binary device/file
+      --io-stats       Report io statistics at exit
+  -o, --output <FILE>  Specify packed output file
+  -V, --version        Print version";
+
+//------------------------------------------
+
+struct CacheMetadataPack;
+
+impl<'a> Program<'a> for CacheMetadataPack {
+    fn name() -> &'a str {
+        "cache_metadata_pack"
+    }
+
+    fn cmd<I>(args: I) -> Command
+    where
+        I: IntoIterator,
+        I::Item: Into<std::ffi::OsString>,
+    {
+        cache_metadata_pack_cmd(args)
+    }
+
+    fn usage() -> &'a str {
+        USAGE
+    }
+
+    fn arg_type() -> ArgType {
+        ArgType::IoOptions
+    }
+
+    fn bad_option_hint(option: &str) -> String {
+        msg::bad_option_hint(option)
+    }
+}
+
+impl<'a> InputProgram<'a> for CacheMetadataPack {
+    fn mk_valid_input(td: &mut TestDir) -> Result<std::path::PathBuf> {
+        mk_valid_md(td)
+    }
+
+    fn file_not_found() -> &'a str {
+        msg::FILE_NOT_FOUND
+    }
+
+    fn missing_input_arg() -> &'a str {
+        msg::MISSING_INPUT_ARG
+    }
+
+    fn corrupted_input() -> &'a str {
+        msg::BAD_SUPERBLOCK
+    }
+}
+
+impl<'a> OutputProgram<'a> for CacheMetadataPack {
+    fn missing_output_arg() -> &'a str {
+        msg::MISSING_OUTPUT_ARG
+    }
+}
+
+//------------------------------------------
+
+test_accepts_help!(CacheMetadataPack);
+test_accepts_version!(CacheMetadataPack);
+test_rejects_bad_option!(CacheMetadataPack);
+
+test_missing_input_option!(CacheMetadataPack);
+test_missing_output_option!(CacheMetadataPack);
+test_input_file_not_found!(CacheMetadataPack);
+test_corrupted_input_data!(CacheMetadataPack);
+
+//------------------------------------------
+
+#[test]
+fn rejects_thin_metadata() -> Result<()> {
+    let mut td = TestDir::new()?;
+    let md = common::thin::mk_valid_md(&mut td)?;
+    let packed = td.mk_path("meta.pack");
+    let stderr = run_fail(cache_metadata_pack_cmd(args!["-i", &md, "-o", &packed]))?;
+    assert!(stderr.contains("input does not contain cache metadata"));
+    Ok(())
+}
+
+//------------------------------------------
@@ -0,0 +1,119 @@
+use anyhow::Result;
+
+mod common;
+
+use common::cache::*;
+use common::common_args::*;
+use common::input_arg::*;
+use common::output_option::*;
+use common::process::*;
+use common::program::*;
+use common::target::*;
+use common::test_dir::*;
+
+//------------------------------------------
+
+const USAGE: &str = "Unpack a compressed file of cache metadata.
+
+Usage: cache_metadata_unpack [OPTIONS] --input <FILE> --output <DEV>
+
+Options:
+      --base <FILE>   Specify the base pack(s) of an incremental input
+  -f, --force         Force overwrite the output file
+  -h, --help          Print help
+  -i, --input <FILE>  Specify packed input file
+      --io-stats      Report io statistics at exit
+  -o, --output <DEV>  Specify cache metadata binary device/file
+  -V, --version       Print version";
+
+//------------------------------------------
+
+struct CacheMetadataUnpack;
+
+impl<'a> Program<'a> for CacheMetadataUnpack {
+    fn name() -> &'a str {
+        "cache_metadata_unpack"
+    }
+
+    fn cmd<I>(args: I) -> Command
+    where
+        I: IntoIterator,
+        I::Item: Into<std::ffi::OsString>,
+    {
+        cache_metadata_unpack_cmd(args)
+    }
+
+    fn usage() -> &'a str {
+        USAGE
+    }
+
+    fn arg_type() -> ArgType {
+        ArgType::IoOptions
+    }
+
+    fn bad_option_hint(option: &str) -> String {
+        msg::bad_option_hint(option)
+    }
+}
+
+impl<'a> InputProgram<'a> for CacheMetadataUnpack {
+    fn mk_valid_input(td: &mut TestDir) -> Result<std::path::PathBuf> {
+        let md = mk_valid_md(td)?;
+        let packed = td.mk_path("meta.pack");
+        run_ok(cache_metadata_pack_cmd(args!["-i", &md, "-o", &packed]))?;
+        Ok(packed)
+    }
+
+    fn file_not_found() -> &'a str {
+        msg::FILE_NOT_FOUND
+    }
+
+    fn missing_input_arg() -> &'a str {
+        msg::MISSING_INPUT_ARG
+    }
+
+    fn corrupted_input() -> &'a str {
+        "Not a pack file"
+    }
+}
+
+impl<'a> OutputProgram<'a> for CacheMetadataUnpack {
+    fn missing_output_arg() -> &'a str {
+        msg::MISSING_OUTPUT_ARG
+    }
+}
+
+//------------------------------------------
+
+test_accepts_help!(CacheMetadataUnpack);
+test_accepts_version!(CacheMetadataUnpack);
+test_rejects_bad_option!(CacheMetadataUnpack);
+
+test_missing_input_option!(CacheMetadataUnpack);
+test_input_file_not_found!(CacheMetadataUnpack);
+test_corrupted_input_data!(CacheMetadataUnpack);
+
+test_missing_output_option!(CacheMetadataUnpack);
+
+//------------------------------------------
+
+#[test]
+fn end_to_end() -> Result<()> {
+    let mut td = TestDir::new()?;
+    let md_in = mk_valid_md(&mut td)?;
+    let md_packed = td.mk_path("meta.pack");
+    let md_out = td.mk_path("meta.out");
+    run_ok(cache_metadata_pack_cmd(args![
+        "-i", &md_in, "-o", &md_packed
+    ]))?;
+    run_ok(cache_metadata_unpack_cmd(args![
+        "-i", &md_packed, "-o", &md_out
+    ]))?;
+
+    let dump1 = run_ok(cache_dump_cmd(args![&md_in]))?;
+    let dump2 = run_ok(cache_dump_cmd(args![&md_out]))?;
+    assert_eq!(dump1, dump2);
+    Ok(())
+}
+
+//------------------------------------------
@@ -18,11 +18,15 @@ const USAGE: &str = "Repair binary cache metadata, and write it to a different d
 Usage: cache_repair [OPTIONS] --input <FILE> --output <FILE>
 
 Options:
-  -h, --help           Print help
-  -i, --input <FILE>   Specify the input device
-  -o, --output <FILE>  Specify the output device
-  -q, --quiet          Suppress output messages, return only exit code.
-  -V, --version        Print version";
+      --block-cache <MEGABYTES>  Keep up to this many megabytes of metadata in memory
+  -h, --help                     Print help
+  -i, --input <FILE>             Specify the input device
+      --io-engine <IO_ENGINE>    Select an io engine to use: auto, sync or spindle
+      --io-stats                 Report io statistics at exit
+  -o, --output <FILE>            Specify the output device
+      --overlay <FILE>           Send writes to an overlay file, leaving the device untouched
+  -q, --quiet                    Suppress output messages, return only exit code.
+  -V, --version                  Print version";
 
 //-----------------------------------------
 
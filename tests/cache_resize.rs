@@ -0,0 +1,304 @@
+use anyhow::Result;
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+mod common;
+
+use common::common_args::*;
+use common::fixture::*;
+use common::process::*;
+use common::program::*;
+use common::target::*;
+use common::test_dir::*;
+
+//------------------------------------------
+
+const USAGE: &str = "Rewrite cache metadata for a new number of cache blocks, moving data on the fast device
+
+Usage: cache_resize [OPTIONS] --input <FILE> --output <FILE> --fast-device <FILE> --nr-cache-blocks <NUM>
+
+Options:
+      --block-cache <MEGABYTES>  Keep up to this many megabytes of metadata in memory
+      --copier <COPIER>          Select how data is copied: sync or compare
+      --fast-device <FILE>       Specify the fast device holding the cached data
+  -h, --help                     Print help
+  -i, --input <FILE>             Specify the input device
+      --io-engine <IO_ENGINE>    Select an io engine to use: auto, sync or spindle
+      --io-stats                 Report io statistics at exit
+      --ioprio <CLASS>           Set the io priority of the copy: idle, best-effort[:LEVEL] or realtime[:LEVEL]
+      --max-bandwidth <MB>       Limit the copy to this many megabytes per second
+      --max-iops <IOPS>          Limit the copy to this many reads and writes per second
+      --nr-cache-blocks <NUM>    Specify the new number of cache blocks
+  -o, --output <FILE>            Specify the output device
+      --overlay <FILE>           Send writes to an overlay file, leaving the device untouched
+  -q, --quiet                    Suppress output messages, return only exit code.
+  -V, --version                  Print version";
+
+//------------------------------------------
+
+struct CacheResize;
+
+impl<'a> Program<'a> for CacheResize {
+    fn name() -> &'a str {
+        "cache_resize"
+    }
+
+    fn cmd<I>(args: I) -> Command
+    where
+        I: IntoIterator,
+        I::Item: Into<std::ffi::OsString>,
+    {
+        cache_resize_cmd(args)
+    }
+
+    fn usage() -> &'a str {
+        USAGE
+    }
+
+    fn arg_type() -> ArgType {
+        ArgType::IoOptions
+    }
+
+    fn bad_option_hint(option: &str) -> String {
+        msg::bad_option_hint(option)
+    }
+}
+
+//------------------------------------------
+
+test_accepts_help!(CacheResize);
+test_accepts_version!(CacheResize);
+test_rejects_bad_option!(CacheResize);
+
+//------------------------------------------
+
+// 64k cache blocks
+const BLOCK_SIZE: u64 = 128 << 9;
+
+// Cache blocks 0 and 10 are dirty.  The hints give the smq levels.
+fn mk_md(td: &mut TestDir, clean_shutdown: bool) -> Result<PathBuf> {
+    let xml = td.mk_path("meta.xml");
+    let content = b"<superblock uuid=\"\" block_size=\"128\" nr_cache_blocks=\"16\" policy=\"smq\" hint_width=\"4\">
+  <mappings>
+    <mapping cache_block=\"0\" origin_block=\"100\" dirty=\"true\"/>
+    <mapping cache_block=\"1\" origin_block=\"101\" dirty=\"false\"/>
+    <mapping cache_block=\"2\" origin_block=\"102\" dirty=\"false\"/>
+    <mapping cache_block=\"10\" origin_block=\"110\" dirty=\"true\"/>
+    <mapping cache_block=\"12\" origin_block=\"112\" dirty=\"false\"/>
+    <mapping cache_block=\"14\" origin_block=\"114\" dirty=\"false\"/>
+  </mappings>
+  <hints>
+    <hint cache_block=\"0\" data=\"AQAAAA==\"/>
+    <hint cache_block=\"1\" data=\"AQAAAA==\"/>
+    <hint cache_block=\"2\" data=\"PwAAAA==\"/>
+    <hint cache_block=\"10\" data=\"BQAAAA==\"/>
+    <hint cache_block=\"12\" data=\"AAAAAA==\"/>
+    <hint cache_block=\"14\" data=\"AgAAAA==\"/>
+  </hints>
+</superblock>";
+    write_file(&xml, content)?;
+
+    let md = td.mk_path("meta.bin");
+    thinp::file_utils::create_sized_file(&md, 4096 * 4096)?;
+    if clean_shutdown {
+        run_ok(cache_restore_cmd(args!["-i", &xml, "-o", &md]))?;
+    } else {
+        run_ok(cache_restore_cmd(args![
+            "-i",
+            &xml,
+            "-o",
+            &md,
+            "--omit-clean-shutdown"
+        ]))?;
+    }
+    Ok(md)
+}
+
+// Each block of the fast device is filled with its index
+fn mk_fast_dev(td: &mut TestDir, nr_blocks: u64) -> Result<PathBuf> {
+    let path = td.mk_path("fast.bin");
+    let mut f = File::create(&path)?;
+    for b in 0..nr_blocks {
+        f.write_all(&vec![b as u8; BLOCK_SIZE as usize])?;
+    }
+    Ok(path)
+}
+
+fn read_block(path: &Path, b: u64) -> Result<Vec<u8>> {
+    let mut f = File::open(path)?;
+    let mut buf = vec![0; BLOCK_SIZE as usize];
+    f.seek(SeekFrom::Start(b * BLOCK_SIZE))?;
+    f.read_exact(&mut buf)?;
+    Ok(buf)
+}
+
+fn resize(td: &mut TestDir, md: &Path, fast: &Path, nr_cache_blocks: u32) -> Result<String> {
+    let output = mk_zeroed_md(td)?;
+    let nr_cache_blocks = nr_cache_blocks.to_string();
+    run_ok(cache_resize_cmd(args![
+        "-i",
+        md,
+        "-o",
+        &output,
+        "--fast-device",
+        fast,
+        "--nr-cache-blocks",
+        &nr_cache_blocks
+    ]))?;
+    run_ok(cache_dump_cmd(args![&output]))
+}
+
+fn resize_fail(td: &mut TestDir, md: &Path, fast: &Path, nr_cache_blocks: u32) -> Result<String> {
+    let output = mk_zeroed_md(td)?;
+    let nr_cache_blocks = nr_cache_blocks.to_string();
+    run_fail(cache_resize_cmd(args![
+        "-i",
+        md,
+        "-o",
+        &output,
+        "--fast-device",
+        fast,
+        "--nr-cache-blocks",
+        &nr_cache_blocks
+    ]))
+}
+
+fn mapping(cblock: u32, oblock: u64, dirty: bool) -> String {
+    format!(
+        "<mapping cache_block=\"{}\" origin_block=\"{}\" dirty=\"{}\"/>",
+        cblock, oblock, dirty
+    )
+}
+
+fn hint(cblock: u32, data: &str) -> String {
+    format!("<hint cache_block=\"{}\" data=\"{}\"/>", cblock, data)
+}
+
+//------------------------------------------
+
+#[test]
+fn grow_keeps_mappings() -> Result<()> {
+    let mut td = TestDir::new()?;
+    let md = mk_md(&mut td, true)?;
+    let fast = mk_fast_dev(&mut td, 32)?;
+    let dump = resize(&mut td, &md, &fast, 32)?;
+    assert!(dump.contains("nr_cache_blocks=\"32\""));
+    assert!(dump.contains(&mapping(10, 110, true)));
+    assert!(dump.contains(&mapping(14, 114, false)));
+    assert!(dump.contains(&hint(14, "AgAAAA==")));
+    assert_eq!(read_block(&fast, 14)?, vec![14; BLOCK_SIZE as usize]);
+    Ok(())
+}
+
+#[test]
+fn shrink_moves_blocks() -> Result<()> {
+    let mut td = TestDir::new()?;
+    let md = mk_md(&mut td, true)?;
+    let fast = mk_fast_dev(&mut td, 16)?;
+    let dump = resize(&mut td, &md, &fast, 8)?;
+    assert!(dump.contains("nr_cache_blocks=\"8\""));
+    assert!(dump.contains(&mapping(0, 100, true)));
+    assert!(dump.contains(&mapping(1, 101, false)));
+    assert!(dump.contains(&mapping(2, 102, false)));
+    assert!(dump.contains(&mapping(3, 110, true)));
+    assert!(dump.contains(&mapping(4, 112, false)));
+    assert!(dump.contains(&mapping(5, 114, false)));
+    assert!(dump.contains(&hint(3, "BQAAAA==")));
+    assert!(dump.contains(&hint(5, "AgAAAA==")));
+
+    assert_eq!(read_block(&fast, 3)?, vec![10; BLOCK_SIZE as usize]);
+    assert_eq!(read_block(&fast, 4)?, vec![12; BLOCK_SIZE as usize]);
+    assert_eq!(read_block(&fast, 5)?, vec![14; BLOCK_SIZE as usize]);
+    assert_eq!(read_block(&fast, 2)?, vec![2; BLOCK_SIZE as usize]);
+    Ok(())
+}
+
+#[test]
+fn shrink_evicts_coldest_clean_blocks() -> Result<()> {
+    let mut td = TestDir::new()?;
+    let md = mk_md(&mut td, true)?;
+    let fast = mk_fast_dev(&mut td, 16)?;
+    let dump = resize(&mut td, &md, &fast, 4)?;
+
+    // only cblock 3 is free, so the clean blocks beyond the end go
+    assert!(!dump.contains("origin_block=\"112\""));
+    assert!(!dump.contains("origin_block=\"114\""));
+    assert!(dump.contains(&mapping(0, 100, true)));
+    assert!(dump.contains(&mapping(1, 101, false)));
+    assert!(dump.contains(&mapping(2, 102, false)));
+    assert!(dump.contains(&mapping(3, 110, true)));
+    assert!(dump.contains(&hint(3, "BQAAAA==")));
+
+    // blocks mapped by the input are left alone
+    assert_eq!(read_block(&fast, 1)?, vec![1; BLOCK_SIZE as usize]);
+    assert_eq!(read_block(&fast, 3)?, vec![10; BLOCK_SIZE as usize]);
+    Ok(())
+}
+
+#[test]
+fn shrink_fails_if_dirty_blocks_do_not_fit() -> Result<()> {
+    let mut td = TestDir::new()?;
+    let md = mk_md(&mut td, true)?;
+    let fast = mk_fast_dev(&mut td, 16)?;
+    let stderr = resize_fail(&mut td, &md, &fast, 1)?;
+    assert!(stderr.contains("1 dirty blocks beyond the new end do not fit in 0 free cache blocks"));
+
+    // nothing was copied
+    assert_eq!(read_block(&fast, 0)?, vec![0; BLOCK_SIZE as usize]);
+    Ok(())
+}
+
+#[test]
+fn unclean_shutdown_keeps_every_block() -> Result<()> {
+    let mut td = TestDir::new()?;
+    let md = mk_md(&mut td, false)?;
+    let fast = mk_fast_dev(&mut td, 16)?;
+    let stderr = resize_fail(&mut td, &md, &fast, 4)?;
+    assert!(stderr.contains("3 dirty blocks beyond the new end do not fit in 1 free cache blocks"));
+
+    let dump = resize(&mut td, &md, &fast, 6)?;
+    assert!(dump.contains(&mapping(5, 114, false)));
+    Ok(())
+}
+
+#[test]
+fn fast_device_too_small() -> Result<()> {
+    let mut td = TestDir::new()?;
+    let md = mk_md(&mut td, true)?;
+    let fast = mk_fast_dev(&mut td, 16)?;
+    let stderr = resize_fail(&mut td, &md, &fast, 32)?;
+    assert!(stderr.contains("fast device is too small"));
+    Ok(())
+}
+
+#[test]
+fn metadata_version_1() -> Result<()> {
+    let mut td = TestDir::new()?;
+    let xml = td.mk_path("meta.xml");
+    write_file(
+        &xml,
+        b"<superblock uuid=\"\" block_size=\"128\" nr_cache_blocks=\"16\" policy=\"smq\" hint_width=\"4\">
+  <mappings>
+    <mapping cache_block=\"9\" origin_block=\"5\" dirty=\"true\"/>
+  </mappings>
+</superblock>",
+    )?;
+    let md = td.mk_path("meta.bin");
+    thinp::file_utils::create_sized_file(&md, 4096 * 4096)?;
+    run_ok(cache_restore_cmd(args![
+        "-i",
+        &xml,
+        "-o",
+        &md,
+        "--metadata-version",
+        "1"
+    ]))?;
+    let fast = mk_fast_dev(&mut td, 16)?;
+    let dump = resize(&mut td, &md, &fast, 2)?;
+    assert!(dump.contains(&mapping(0, 5, true)));
+    assert_eq!(read_block(&fast, 0)?, vec![9; BLOCK_SIZE as usize]);
+    Ok(())
+}
+
+//------------------------------------------
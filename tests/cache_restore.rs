@@ -1,4 +1,7 @@
 use anyhow::Result;
+use std::fs::File;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
 
 mod common;
 
@@ -19,13 +22,23 @@ const USAGE: &str = "Convert XML format metadata to binary.
 Usage: cache_restore [OPTIONS] --input <FILE> --output <FILE>
 
 Options:
-  -h, --help                    Print help
-  -i, --input <FILE>            Specify the input xml
-      --metadata-version <NUM>  Specify the output metadata version [default: 2] [possible values: 1, 2]
-  -o, --output <FILE>           Specify the output device
-      --omit-clean-shutdown     Don't set the clean shutdown flag
-  -q, --quiet                   Suppress output messages, return only exit code
-  -V, --version                 Print version";
+      --block-cache <MEGABYTES>  Keep up to this many megabytes of metadata in memory
+      --block-size <SECTORS>     Convert the metadata to a different cache block size
+      --copier <COPIER>          Select how data is copied: sync or compare
+      --fast-device <FILE>       Specify the fast device, to move the data of merged blocks
+  -h, --help                     Print help
+  -i, --input <FILE>             Specify the input xml
+      --io-engine <IO_ENGINE>    Select an io engine to use: auto, sync or spindle
+      --io-stats                 Report io statistics at exit
+      --ioprio <CLASS>           Set the io priority of the copy: idle, best-effort[:LEVEL] or realtime[:LEVEL]
+      --max-bandwidth <MB>       Limit the copy to this many megabytes per second
+      --max-iops <IOPS>          Limit the copy to this many reads and writes per second
+      --metadata-version <NUM>   Specify the output metadata version [default: 2] [possible values: 1, 2]
+  -o, --output <FILE>            Specify the output device
+      --omit-clean-shutdown      Don't set the clean shutdown flag
+      --overlay <FILE>           Send writes to an overlay file, leaving the device untouched
+  -q, --quiet                    Suppress output messages, return only exit code
+  -V, --version                  Print version";
 
 //------------------------------------------
 
@@ -155,3 +168,235 @@ fn omit_clean_shutdown() -> Result<()> {
 }
 
 //-----------------------------------------
+
+// Converting between block sizes
+
+// Each 32k block of the fast device is filled with its index
+fn mk_fast_dev(td: &mut TestDir, nr_blocks: u64) -> Result<PathBuf> {
+    let path = td.mk_path("fast.bin");
+    let mut f = File::create(&path)?;
+    for b in 0..nr_blocks {
+        f.write_all(&vec![b as u8; 32768])?;
+    }
+    Ok(path)
+}
+
+// The index held by each 32k block of the fast device
+fn read_fast_dev(path: &Path) -> Result<Vec<u8>> {
+    let mut buf = Vec::new();
+    File::open(path)?.read_to_end(&mut buf)?;
+    Ok(buf.chunks(32768).map(|b| b[0]).collect())
+}
+
+fn restore_with_block_size(
+    td: &mut TestDir,
+    content: &[u8],
+    block_size: &str,
+    fast_dev: Option<&Path>,
+) -> Result<PathBuf> {
+    let xml = td.mk_path("meta.xml");
+    write_file(&xml, content)?;
+    let md = mk_zeroed_md(td)?;
+    match fast_dev {
+        Some(fast_dev) => run_ok(cache_restore_cmd(args![
+            "-i",
+            &xml,
+            "-o",
+            &md,
+            "--block-size",
+            block_size,
+            "--fast-device",
+            fast_dev
+        ]))?,
+        None => run_ok(cache_restore_cmd(args![
+            "-i",
+            &xml,
+            "-o",
+            &md,
+            "--block-size",
+            block_size
+        ]))?,
+    };
+    Ok(md)
+}
+
+#[test]
+fn splits_blocks() -> Result<()> {
+    let mut td = TestDir::new()?;
+    let md = restore_with_block_size(
+        &mut td,
+        b"<superblock uuid=\"\" block_size=\"128\" nr_cache_blocks=\"4\" policy=\"smq\" hint_width=\"4\">
+  <mappings>
+    <mapping cache_block=\"1\" origin_block=\"3\" dirty=\"true\"/>
+  </mappings>
+  <hints>
+    <hint cache_block=\"1\" data=\"BQAAAA==\"/>
+  </hints>
+</superblock>",
+        "64",
+        None,
+    )?;
+
+    let dump = run_ok(cache_dump_cmd(args![&md]))?;
+    assert!(dump.contains("block_size=\"64\" nr_cache_blocks=\"8\""));
+    assert!(dump.contains("<mapping cache_block=\"2\" origin_block=\"6\" dirty=\"true\"/>"));
+    assert!(dump.contains("<mapping cache_block=\"3\" origin_block=\"7\" dirty=\"true\"/>"));
+    assert!(dump.contains("<hint cache_block=\"2\" data=\"BQAAAA==\"/>"));
+    assert!(dump.contains("<hint cache_block=\"3\" data=\"BQAAAA==\"/>"));
+    Ok(())
+}
+
+// Origin blocks 0, 1 and 4 are cached in full at the larger block size,
+// block 4 in place and the others out of order.  Origin block 6 is only half
+// cached.
+const MERGE_XML: &[u8] = b"<superblock uuid=\"\" block_size=\"64\" nr_cache_blocks=\"16\" policy=\"smq\" hint_width=\"4\">
+  <mappings>
+    <mapping cache_block=\"0\" origin_block=\"3\" dirty=\"false\"/>
+    <mapping cache_block=\"1\" origin_block=\"2\" dirty=\"false\"/>
+    <mapping cache_block=\"2\" origin_block=\"1\" dirty=\"false\"/>
+    <mapping cache_block=\"5\" origin_block=\"0\" dirty=\"true\"/>
+    <mapping cache_block=\"6\" origin_block=\"8\" dirty=\"false\"/>
+    <mapping cache_block=\"7\" origin_block=\"9\" dirty=\"false\"/>
+    <mapping cache_block=\"11\" origin_block=\"13\" dirty=\"false\"/>
+  </mappings>
+  <hints>
+    <hint cache_block=\"2\" data=\"BQAAAA==\"/>
+    <hint cache_block=\"5\" data=\"AQAAAA==\"/>
+  </hints>
+</superblock>";
+
+#[test]
+fn merges_blocks() -> Result<()> {
+    let mut td = TestDir::new()?;
+    let fast_dev = mk_fast_dev(&mut td, 16)?;
+    let md = restore_with_block_size(&mut td, MERGE_XML, "128", Some(&fast_dev))?;
+
+    let dump = run_ok(cache_dump_cmd(args![&md]))?;
+    assert!(dump.contains("block_size=\"128\" nr_cache_blocks=\"8\""));
+    assert!(dump.contains("<mapping cache_block=\"3\" origin_block=\"4\" dirty=\"false\"/>"));
+    assert!(dump.contains("<mapping cache_block=\"4\" origin_block=\"0\" dirty=\"true\"/>"));
+    assert!(dump.contains("<mapping cache_block=\"6\" origin_block=\"1\" dirty=\"false\"/>"));
+    assert!(!dump.contains("origin_block=\"6\""));
+    assert!(dump.contains("<hint cache_block=\"4\" data=\"BQAAAA==\"/>"));
+
+    // the blocks mapped by the XML are left alone
+    assert_eq!(
+        read_fast_dev(&fast_dev)?,
+        [0, 1, 2, 3, 4, 5, 6, 7, 5, 2, 10, 11, 1, 0, 14, 15]
+    );
+    Ok(())
+}
+
+#[test]
+fn merging_fails_if_dirty_blocks_have_no_room() -> Result<()> {
+    let mut td = TestDir::new()?;
+    let xml = td.mk_path("meta.xml");
+    write_file(
+        &xml,
+        b"<superblock uuid=\"\" block_size=\"64\" nr_cache_blocks=\"8\" policy=\"smq\" hint_width=\"4\">
+  <mappings>
+    <mapping cache_block=\"0\" origin_block=\"3\" dirty=\"false\"/>
+    <mapping cache_block=\"1\" origin_block=\"2\" dirty=\"false\"/>
+    <mapping cache_block=\"2\" origin_block=\"1\" dirty=\"false\"/>
+    <mapping cache_block=\"5\" origin_block=\"0\" dirty=\"true\"/>
+    <mapping cache_block=\"7\" origin_block=\"9\" dirty=\"false\"/>
+  </mappings>
+</superblock>",
+    )?;
+    let md = mk_zeroed_md(&mut td)?;
+    let fast_dev = mk_fast_dev(&mut td, 8)?;
+    let stderr = run_fail(cache_restore_cmd(args![
+        "-i",
+        &xml,
+        "-o",
+        &md,
+        "--block-size",
+        "128",
+        "--fast-device",
+        &fast_dev
+    ]))?;
+    assert!(stderr.contains("no unmapped cache blocks left to gather origin block 0 into"));
+    assert_eq!(read_fast_dev(&fast_dev)?, [0, 1, 2, 3, 4, 5, 6, 7]);
+    Ok(())
+}
+
+#[test]
+fn merging_needs_the_fast_device() -> Result<()> {
+    let mut td = TestDir::new()?;
+    let xml = td.mk_path("meta.xml");
+    write_file(&xml, MERGE_XML)?;
+    let md = mk_zeroed_md(&mut td)?;
+    let stderr = run_fail(cache_restore_cmd(args![
+        "-i",
+        &xml,
+        "-o",
+        &md,
+        "--block-size",
+        "128"
+    ]))?;
+    assert!(stderr.contains("the fast device is needed to merge cache blocks"));
+    Ok(())
+}
+
+#[test]
+fn merging_rejects_partly_cached_dirty_blocks() -> Result<()> {
+    let mut td = TestDir::new()?;
+    let xml = td.mk_path("meta.xml");
+    write_file(
+        &xml,
+        b"<superblock uuid=\"\" block_size=\"64\" nr_cache_blocks=\"8\" policy=\"smq\" hint_width=\"4\">
+  <mappings>
+    <mapping cache_block=\"3\" origin_block=\"5\" dirty=\"true\"/>
+  </mappings>
+</superblock>",
+    )?;
+    let md = mk_zeroed_md(&mut td)?;
+    let fast_dev = mk_fast_dev(&mut td, 8)?;
+    let stderr = run_fail(cache_restore_cmd(args![
+        "-i",
+        &xml,
+        "-o",
+        &md,
+        "--block-size",
+        "128",
+        "--fast-device",
+        &fast_dev
+    ]))?;
+    assert!(stderr.contains("partly cached and dirty"));
+    assert_eq!(read_fast_dev(&fast_dev)?, [0, 1, 2, 3, 4, 5, 6, 7]);
+    Ok(())
+}
+
+#[test]
+fn rejects_unrelated_block_sizes() -> Result<()> {
+    let mut td = TestDir::new()?;
+    let xml = td.mk_path("meta.xml");
+    write_file(
+        &xml,
+        b"<superblock uuid=\"\" block_size=\"128\" nr_cache_blocks=\"4\" policy=\"smq\" hint_width=\"4\">
+</superblock>",
+    )?;
+    let md = mk_zeroed_md(&mut td)?;
+    let stderr = run_fail(cache_restore_cmd(args![
+        "-i",
+        &xml,
+        "-o",
+        &md,
+        "--block-size",
+        "192"
+    ]))?;
+    assert!(stderr.contains("one must be a multiple of the other"));
+
+    let stderr = run_fail(cache_restore_cmd(args![
+        "-i",
+        &xml,
+        "-o",
+        &md,
+        "--block-size",
+        "100"
+    ]))?;
+    assert!(stderr.contains("invalid cache block size"));
+    Ok(())
+}
+
+//-----------------------------------------
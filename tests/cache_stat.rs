@@ -0,0 +1,209 @@
+use anyhow::Result;
+
+mod common;
+
+use common::cache::*;
+use common::common_args::*;
+use common::fixture::*;
+use common::input_arg::*;
+use common::process::*;
+use common::program::*;
+use common::target::*;
+use common::test_dir::*;
+
+//------------------------------------------
+
+const USAGE: &str = "Summarises the contents of cache metadata.
+
+Usage: cache_stat [OPTIONS] <INPUT>
+
+Arguments:
+  <INPUT>  Specify the input device
+
+Options:
+      --block-cache <MEGABYTES>  Keep up to this many megabytes of metadata in memory
+      --format <TYPE>            Choose the output format [default: text] [possible values: text, json]
+  -h, --help                     Print help
+      --io-engine <IO_ENGINE>    Select an io engine to use: auto, sync or spindle
+      --io-stats                 Report io statistics at exit
+  -V, --version                  Print version";
+
+//------------------------------------------
+
+struct CacheStat;
+
+impl<'a> Program<'a> for CacheStat {
+    fn name() -> &'a str {
+        "cache_stat"
+    }
+
+    fn cmd<I>(args: I) -> Command
+    where
+        I: IntoIterator,
+        I::Item: Into<std::ffi::OsString>,
+    {
+        cache_stat_cmd(args)
+    }
+
+    fn usage() -> &'a str {
+        USAGE
+    }
+
+    fn arg_type() -> ArgType {
+        ArgType::InputArg
+    }
+
+    fn bad_option_hint(option: &str) -> String {
+        msg::bad_option_hint(option)
+    }
+}
+
+impl<'a> InputProgram<'a> for CacheStat {
+    fn mk_valid_input(td: &mut TestDir) -> Result<std::path::PathBuf> {
+        mk_valid_md(td)
+    }
+
+    fn file_not_found() -> &'a str {
+        msg::FILE_NOT_FOUND
+    }
+
+    fn missing_input_arg() -> &'a str {
+        msg::MISSING_INPUT_ARG
+    }
+
+    fn corrupted_input() -> &'a str {
+        msg::BAD_SUPERBLOCK
+    }
+}
+
+impl MetadataReader<'_> for CacheStat {}
+
+//------------------------------------------
+
+test_accepts_help!(CacheStat);
+test_accepts_version!(CacheStat);
+test_rejects_bad_option!(CacheStat);
+
+test_missing_input_arg!(CacheStat);
+test_input_file_not_found!(CacheStat);
+test_input_cannot_be_a_directory!(CacheStat);
+test_unreadable_input_file!(CacheStat);
+
+test_help_message_for_tiny_input_file!(CacheStat);
+test_spot_xml_data!(CacheStat);
+test_corrupted_input_data!(CacheStat);
+
+test_readonly_input_file!(CacheStat);
+
+//------------------------------------------
+
+// Blocks 0..3 and 100..102 of the origin are cached, in two runs, and the
+// first of each run is dirty.
+fn mk_md_with(
+    td: &mut TestDir,
+    metadata_version: &str,
+    clean_shutdown: bool,
+) -> Result<std::path::PathBuf> {
+    let xml = td.mk_path("meta.xml");
+    let content = b"<superblock uuid=\"\" block_size=\"128\" nr_cache_blocks=\"16\" policy=\"smq\" hint_width=\"4\">
+  <mappings>
+    <mapping cache_block=\"0\" origin_block=\"0\" dirty=\"true\"/>
+    <mapping cache_block=\"1\" origin_block=\"1\" dirty=\"false\"/>
+    <mapping cache_block=\"2\" origin_block=\"2\" dirty=\"false\"/>
+    <mapping cache_block=\"5\" origin_block=\"100\" dirty=\"true\"/>
+    <mapping cache_block=\"6\" origin_block=\"101\" dirty=\"false\"/>
+  </mappings>
+  <hints>
+    <hint cache_block=\"0\" data=\"AQAAAA==\"/>
+    <hint cache_block=\"1\" data=\"AQAAAA==\"/>
+    <hint cache_block=\"2\" data=\"PwAAAA==\"/>
+  </hints>
+</superblock>";
+    write_file(&xml, content)?;
+
+    let md = td.mk_path("meta.bin");
+    thinp::file_utils::create_sized_file(&md, 4096 * 4096)?;
+    let mut args = args![
+        "-i",
+        &xml,
+        "-o",
+        &md,
+        "--metadata-version",
+        metadata_version
+    ]
+    .to_vec();
+    if !clean_shutdown {
+        args.push(std::ffi::OsStr::new("--omit-clean-shutdown"));
+    }
+    run_ok(cache_restore_cmd(args))?;
+    Ok(md)
+}
+
+fn mk_md(td: &mut TestDir, metadata_version: &str) -> Result<std::path::PathBuf> {
+    mk_md_with(td, metadata_version, true)
+}
+
+fn counts_blocks(metadata_version: &str) -> Result<()> {
+    let mut td = TestDir::new()?;
+    let md = mk_md(&mut td, metadata_version)?;
+    let stdout = run_ok(cache_stat_cmd(args![&md]))?;
+    assert!(stdout.contains("valid\t\t5\t31.25%"));
+    assert!(stdout.contains("dirty\t\t2\t12.50%"));
+    assert!(stdout.contains("clean\t\t3\t18.75%"));
+    Ok(())
+}
+
+#[test]
+fn counts_blocks_v1() -> Result<()> {
+    counts_blocks("1")
+}
+
+#[test]
+fn counts_blocks_v2() -> Result<()> {
+    counts_blocks("2")
+}
+
+#[test]
+fn counts_all_blocks_dirty_after_a_crash() -> Result<()> {
+    let mut td = TestDir::new()?;
+    let md = mk_md_with(&mut td, "2", false)?;
+    let stdout = run_ok(cache_stat_cmd(args![&md]))?;
+    assert!(stdout.contains("clean shutdown\tno"));
+    assert!(stdout.contains("dirty\t\t5\t31.25%"));
+    assert!(stdout.contains("clean\t\t0\t0.00%"));
+    Ok(())
+}
+
+#[test]
+fn reports_runs_and_hint_levels() -> Result<()> {
+    let mut td = TestDir::new()?;
+    let md = mk_md(&mut td, "2")?;
+    let stdout = run_ok(cache_stat_cmd(args![&md]))?;
+    assert!(stdout.contains("2-3\t\t2\t100.0000"));
+    assert!(stdout.contains("2 runs in 102 origin blocks, longest 3"));
+
+    // the unmapped blocks' hints aren't counted
+    assert!(stdout.contains(
+        "hint level\tcounts\tpercentage\n0\t\t2\t40.0000\n1\t\t2\t40.0000\n63\t\t1\t20.0000"
+    ));
+    Ok(())
+}
+
+#[test]
+fn json_output() -> Result<()> {
+    let mut td = TestDir::new()?;
+    let md = mk_md(&mut td, "2")?;
+    let stdout = run_ok(cache_stat_cmd(args!["--format", "json", &md]))?;
+    assert!(stdout.starts_with(
+        "{\"cache_blocks\": 16, \"block_size\": 128, \"policy\": \"smq\", \"version\": 2, \"clean_shutdown\": true, \"valid\": 5, \"dirty\": 2, \"clean\": 3, \"origin_blocks\": 102, "
+    ));
+    assert!(stdout.contains("{\"begin\": 0, \"end\": 7, \"mapped\": 3, \"dirty\": 1}"));
+    assert!(stdout.contains("{\"begin\": 98, \"end\": 102, \"mapped\": 2, \"dirty\": 1}"));
+    assert!(stdout.contains(
+        "\"runs\": {\"count\": 2, \"longest\": 3, \"lengths\": [{\"min\": 2, \"max\": 3, \"count\": 2}]}"
+    ));
+    assert!(stdout.contains("\"discards\": null}"));
+    Ok(())
+}
+
+//------------------------------------------
@@ -159,6 +159,8 @@ struct WritebackTest {
     origin_dev: PathBuf,
     fast_dev_offset: u64,   // bytes
     origin_dev_offset: u64, // bytes
+    copier: Option<&'static str>,
+    extra_args: Vec<String>,
     seed: u64,
 }
 
@@ -179,6 +181,8 @@ impl WritebackTest {
             origin_dev,
             fast_dev_offset: 0,
             origin_dev_offset: 0,
+            copier: None,
+            extra_args: Vec::new(),
             seed: rand::rng().random::<u64>(),
         })
     }
@@ -244,6 +248,16 @@ impl WritebackTest {
         Ok(())
     }
 
+    // The source line of a rescue map for the current metadata
+    fn rescue_map_source(&self) -> Result<String> {
+        let mut csum = [0u8; 4];
+        File::open(&self.metadata_dev)?.read_exact_at(&mut csum, 0)?;
+        Ok(format!(
+            "# source: cache superblock {:08x}\n",
+            u32::from_le_bytes(csum)
+        ))
+    }
+
     fn set_unclean_shutdown(&self) -> Result<()> {
         run_ok(cache_generate_metadata_cmd(args![
             "--set-clean-shutdown=false",
@@ -283,6 +297,14 @@ impl WritebackTest {
         Ok(())
     }
 
+    fn set_copier(&mut self, copier: &'static str) {
+        self.copier = Some(copier);
+    }
+
+    fn set_extra_args(&mut self, args: &[&str]) {
+        self.extra_args = args.iter().map(|s| s.to_string()).collect();
+    }
+
     fn stamp_cache_blocks(&self) -> Result<()> {
         let cache_size =
             self.nr_cache_blocks as u64 * self.cache_block_size as u64 + self.fast_dev_offset;
@@ -308,14 +330,17 @@ impl WritebackTest {
     }
 
     fn writeback(&self, update_metadata: bool) -> Result<()> {
-        self.writeback_(update_metadata, true)
+        self.writeback_(update_metadata, true)?;
+        Ok(())
     }
 
     fn writeback_fail(&self, update_metadata: bool) -> Result<()> {
-        self.writeback_(update_metadata, false)
+        self.writeback_(update_metadata, false)?;
+        Ok(())
     }
 
-    fn writeback_(&self, update_metadata: bool, expect_ok: bool) -> Result<()> {
+    // Returns stdout
+    fn writeback_(&self, update_metadata: bool, expect_ok: bool) -> Result<String> {
         use std::ffi::OsStr;
 
         let mut args = args![
@@ -344,13 +369,22 @@ impl WritebackTest {
             args.push(OsStr::new(&origin_dev_offset));
         }
 
-        if expect_ok {
-            run_ok(cache_writeback_cmd(args))?;
-        } else {
-            run_fail(cache_writeback_cmd(args))?;
+        if let Some(copier) = self.copier {
+            args.push(OsStr::new("--copier"));
+            args.push(OsStr::new(copier));
         }
 
-        Ok(())
+        for arg in &self.extra_args {
+            args.push(OsStr::new(arg));
+        }
+
+        let output = if expect_ok {
+            run_ok_raw(cache_writeback_cmd(args))?
+        } else {
+            run_fail_raw(cache_writeback_cmd(args))?
+        };
+
+        Ok(String::from_utf8(output.stdout)?)
     }
 
     // verify the origin device
@@ -592,6 +626,439 @@ mod format1 {
 
         Ok(())
     }
+
+    #[cfg(feature = "io_uring")]
+    #[test]
+    fn writeback_with_async_copier() -> Result<()> {
+        let cache_block_size: usize = 32768; // 32 KiB
+        let nr_cache_blocks: u32 = 1024;
+        let nr_origin_blocks: u64 = 4096;
+
+        let mut t = WritebackTest::new()?;
+        t.format_metadata(cache_block_size, nr_cache_blocks, nr_origin_blocks, 1)?;
+        t.set_fast_dev_offset(1048576)?;
+        t.set_origin_dev_offset(4194304)?;
+        t.set_copier("async");
+        t.stamp_cache_blocks()?;
+        t.stamp_origin_blocks()?;
+        t.writeback(false)?;
+
+        let (rmap, dirty_bits) = read_rmap_and_dirty_bits(&t.metadata_dev, t.nr_cache_blocks)?;
+        let indicator = Box::new(DirtySourceIndicator::new(
+            rmap,
+            dirty_bits,
+            t.nr_origin_blocks,
+        ));
+        t.verify(indicator)?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn writeback_with_compare_copier() -> Result<()> {
+        let cache_block_size: usize = 32768; // 32 KiB
+        let nr_cache_blocks: u32 = 1024;
+        let nr_origin_blocks: u64 = 4096;
+
+        let mut t = WritebackTest::new()?;
+        t.format_metadata(cache_block_size, nr_cache_blocks, nr_origin_blocks, 1)?;
+        t.set_fast_dev_offset(1048576)?;
+        t.set_origin_dev_offset(4194304)?;
+        t.set_copier("compare");
+        t.stamp_cache_blocks()?;
+        t.stamp_origin_blocks()?;
+
+        // the second run finds the origin up to date
+        for _ in 0..2 {
+            t.writeback(false)?;
+
+            let (rmap, dirty_bits) = read_rmap_and_dirty_bits(&t.metadata_dev, t.nr_cache_blocks)?;
+            let indicator = Box::new(DirtySourceIndicator::new(
+                rmap,
+                dirty_bits,
+                t.nr_origin_blocks,
+            ));
+            t.verify(indicator)?;
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn writeback_with_rescue_map() -> Result<()> {
+        let cache_block_size: usize = 32768; // 32 KiB
+        let nr_cache_blocks: u32 = 1024;
+        let nr_origin_blocks: u64 = 4096;
+
+        let mut t = WritebackTest::new()?;
+        let map = t.td.mk_path("rescue.map");
+        t.format_metadata(cache_block_size, nr_cache_blocks, nr_origin_blocks, 1)?;
+        t.set_extra_args(&["--rescue-map", map.to_str().unwrap()]);
+        t.stamp_cache_blocks()?;
+        t.stamp_origin_blocks()?;
+        t.writeback(false)?;
+
+        let (rmap, dirty_bits) = read_rmap_and_dirty_bits(&t.metadata_dev, t.nr_cache_blocks)?;
+        let indicator = Box::new(DirtySourceIndicator::new(
+            rmap,
+            dirty_bits,
+            t.nr_origin_blocks,
+        ));
+        t.verify(indicator)?;
+
+        // nothing failed
+        let text = std::fs::read_to_string(&map)?;
+        assert!(text.lines().any(|l| l.ends_with(" +")));
+        assert!(!text.lines().any(|l| l.ends_with(" -")));
+
+        Ok(())
+    }
+
+    #[test]
+    fn rescue_map_blocks_are_retried() -> Result<()> {
+        let cache_block_size: usize = 32768; // 32 KiB
+        let nr_cache_blocks: u32 = 1024;
+        let nr_origin_blocks: u64 = 4096;
+
+        let mut t = WritebackTest::new()?;
+        t.format_metadata(cache_block_size, nr_cache_blocks, nr_origin_blocks, 1)?;
+        t.stamp_cache_blocks()?;
+        t.stamp_origin_blocks()?;
+
+        // a map left by an earlier run that failed on every block
+        let map = t.td.mk_path("rescue.map");
+        std::fs::write(
+            &map,
+            t.rescue_map_source()? + "# block_size: 32768\n# nr_blocks: 1024\n0 1024 -\n",
+        )?;
+
+        // the failed blocks are left alone without retries
+        t.set_extra_args(&["--rescue-map", map.to_str().unwrap()]);
+        t.writeback_fail(false)?;
+        t.verify(Box::new(NoopIndicator))?;
+
+        t.set_extra_args(&["--rescue-map", map.to_str().unwrap(), "--retry-count", "2"]);
+        t.writeback(false)?;
+
+        let (rmap, dirty_bits) = read_rmap_and_dirty_bits(&t.metadata_dev, t.nr_cache_blocks)?;
+        let nr_dirty = rmap
+            .values()
+            .filter(|cblock| dirty_bits.contains(**cblock as usize))
+            .count() as u64;
+        let indicator = Box::new(DirtySourceIndicator::new(
+            rmap,
+            dirty_bits,
+            t.nr_origin_blocks,
+        ));
+        t.verify(indicator)?;
+
+        // every dirty block is marked good now
+        let nr_good: u64 = std::fs::read_to_string(&map)?
+            .lines()
+            .filter(|l| l.ends_with(" +"))
+            .map(|l| l.split(' ').nth(1).unwrap().parse::<u64>().unwrap())
+            .sum();
+        assert_eq!(nr_good, nr_dirty);
+
+        Ok(())
+    }
+
+    #[test]
+    fn rescue_map_for_another_device() -> Result<()> {
+        let cache_block_size: usize = 32768; // 32 KiB
+        let nr_cache_blocks: u32 = 1024;
+        let nr_origin_blocks: u64 = 4096;
+
+        let mut t = WritebackTest::new()?;
+        t.format_metadata(cache_block_size, nr_cache_blocks, nr_origin_blocks, 1)?;
+        t.stamp_cache_blocks()?;
+        t.stamp_origin_blocks()?;
+
+        let map = t.td.mk_path("rescue.map");
+        std::fs::write(
+            &map,
+            t.rescue_map_source()? + "# block_size: 65536\n# nr_blocks: 1024\n",
+        )?;
+        t.set_extra_args(&["--rescue-map", map.to_str().unwrap()]);
+        t.writeback_fail(false)?;
+        t.verify(Box::new(NoopIndicator))?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn rescue_map_for_a_changed_cache() -> Result<()> {
+        let cache_block_size: usize = 32768; // 32 KiB
+        let nr_cache_blocks: u32 = 1024;
+        let nr_origin_blocks: u64 = 4096;
+
+        let mut t = WritebackTest::new()?;
+        t.format_metadata(cache_block_size, nr_cache_blocks, nr_origin_blocks, 1)?;
+        t.stamp_cache_blocks()?;
+        t.stamp_origin_blocks()?;
+
+        // a map that says every block was copied, before the cache was used
+        let map = t.td.mk_path("rescue.map");
+        std::fs::write(
+            &map,
+            "# source: cache superblock 00000000\n# block_size: 32768\n# nr_blocks: 1024\n0 1024 +\n",
+        )?;
+        t.set_extra_args(&["--rescue-map", map.to_str().unwrap()]);
+        t.writeback_fail(false)?;
+        t.verify(Box::new(NoopIndicator))?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn unknown_copier() -> Result<()> {
+        let cache_block_size: usize = 32768; // 32 KiB
+        let nr_cache_blocks: u32 = 1024;
+        let nr_origin_blocks: u64 = 4096;
+
+        let mut t = WritebackTest::new()?;
+        t.format_metadata(cache_block_size, nr_cache_blocks, nr_origin_blocks, 1)?;
+        t.set_copier("warp");
+        t.stamp_cache_blocks()?;
+        t.stamp_origin_blocks()?;
+        t.writeback_fail(false)?;
+        t.verify(Box::new(NoopIndicator))?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn writeback_with_limits() -> Result<()> {
+        let cache_block_size: usize = 32768; // 32 KiB
+        let nr_cache_blocks: u32 = 1024;
+        let nr_origin_blocks: u64 = 4096;
+
+        let mut t = WritebackTest::new()?;
+        t.format_metadata(cache_block_size, nr_cache_blocks, nr_origin_blocks, 1)?;
+        t.set_extra_args(&[
+            "--max-bandwidth",
+            "64",
+            "--max-iops",
+            "8192",
+            "--ioprio",
+            "best-effort:7",
+        ]);
+        t.stamp_cache_blocks()?;
+        t.stamp_origin_blocks()?;
+        t.writeback(false)?;
+
+        let (rmap, dirty_bits) = read_rmap_and_dirty_bits(&t.metadata_dev, t.nr_cache_blocks)?;
+        let indicator = Box::new(DirtySourceIndicator::new(
+            rmap,
+            dirty_bits,
+            t.nr_origin_blocks,
+        ));
+        t.verify(indicator)?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn unknown_ioprio() -> Result<()> {
+        let cache_block_size: usize = 32768; // 32 KiB
+        let nr_cache_blocks: u32 = 1024;
+        let nr_origin_blocks: u64 = 4096;
+
+        let mut t = WritebackTest::new()?;
+        t.format_metadata(cache_block_size, nr_cache_blocks, nr_origin_blocks, 1)?;
+        t.set_extra_args(&["--ioprio", "bulk"]);
+        t.stamp_cache_blocks()?;
+        t.stamp_origin_blocks()?;
+        t.writeback_fail(false)?;
+        t.verify(Box::new(NoopIndicator))?;
+
+        Ok(())
+    }
+
+    // Keeps the dirty bits of the mappings picked out by the predicate
+    fn select_dirty_bits<F: Fn(u64, u32) -> bool>(
+        rmap: &BTreeMap<u64, u32>,
+        dirty_bits: &FixedBitSet,
+        selected: F,
+    ) -> FixedBitSet {
+        let mut bits = FixedBitSet::with_capacity(dirty_bits.len());
+        for (oblock, cblock) in rmap {
+            if dirty_bits.contains(*cblock as usize) && selected(*oblock, *cblock) {
+                bits.insert(*cblock as usize);
+            }
+        }
+        bits
+    }
+
+    #[test]
+    fn writeback_origin_range() -> Result<()> {
+        let cache_block_size: usize = 32768; // 32 KiB
+        let nr_cache_blocks: u32 = 1024;
+        let nr_origin_blocks: u64 = 4096;
+
+        let mut t = WritebackTest::new()?;
+        t.format_metadata(cache_block_size, nr_cache_blocks, nr_origin_blocks, 1)?;
+        let (rmap, dirty_bits) = read_rmap_and_dirty_bits(&t.metadata_dev, t.nr_cache_blocks)?;
+
+        // oblocks 1000..2000, and the blocks touched by a partial range
+        // from 3000.5 to 3001.5
+        t.set_extra_args(&[
+            "--origin-range",
+            "64000..128000",
+            "--origin-range",
+            "192032..192096",
+        ]);
+        t.stamp_cache_blocks()?;
+        t.stamp_origin_blocks()?;
+        t.writeback(true)?;
+
+        let in_range =
+            |oblock: u64| (1000..2000).contains(&oblock) || (3000..3002).contains(&oblock);
+        let written = select_dirty_bits(&rmap, &dirty_bits, |oblock, _| in_range(oblock));
+        let indicator = Box::new(DirtySourceIndicator::new(
+            rmap.clone(),
+            written,
+            t.nr_origin_blocks,
+        ));
+        t.verify(indicator)?;
+
+        // only the blocks written back were cleaned
+        let (_, dirty_bits_upd) = read_rmap_and_dirty_bits(&t.metadata_dev, t.nr_cache_blocks)?;
+        let remaining = select_dirty_bits(&rmap, &dirty_bits, |oblock, _| !in_range(oblock));
+        assert_eq!(dirty_bits_upd, remaining);
+
+        Ok(())
+    }
+
+    #[test]
+    fn writeback_cblocks_file() -> Result<()> {
+        let cache_block_size: usize = 32768; // 32 KiB
+        let nr_cache_blocks: u32 = 1024;
+        let nr_origin_blocks: u64 = 4096;
+
+        let mut t = WritebackTest::new()?;
+        t.format_metadata(cache_block_size, nr_cache_blocks, nr_origin_blocks, 1)?;
+        let (rmap, dirty_bits) = read_rmap_and_dirty_bits(&t.metadata_dev, t.nr_cache_blocks)?;
+
+        let list = t.td.mk_path("cblocks.txt");
+        std::fs::write(
+            &list,
+            "# the first few\n0..100\n\n500 # and one more\n1023\n",
+        )?;
+        t.set_extra_args(&["--cblocks-file", list.to_str().unwrap()]);
+        t.stamp_cache_blocks()?;
+        t.stamp_origin_blocks()?;
+        t.writeback(true)?;
+
+        let listed = |cblock: u32| cblock < 100 || cblock == 500 || cblock == 1023;
+        let written = select_dirty_bits(&rmap, &dirty_bits, |_, cblock| listed(cblock));
+        let indicator = Box::new(DirtySourceIndicator::new(
+            rmap.clone(),
+            written,
+            t.nr_origin_blocks,
+        ));
+        t.verify(indicator)?;
+
+        let (_, dirty_bits_upd) = read_rmap_and_dirty_bits(&t.metadata_dev, t.nr_cache_blocks)?;
+        let remaining = select_dirty_bits(&rmap, &dirty_bits, |_, cblock| !listed(cblock));
+        assert_eq!(dirty_bits_upd, remaining);
+
+        Ok(())
+    }
+
+    #[test]
+    fn writeback_with_verify() -> Result<()> {
+        let cache_block_size: usize = 32768; // 32 KiB
+        let nr_cache_blocks: u32 = 1024;
+        let nr_origin_blocks: u64 = 4096;
+
+        let mut t = WritebackTest::new()?;
+        t.format_metadata(cache_block_size, nr_cache_blocks, nr_origin_blocks, 1)?;
+        let (_, dirty_bits) = read_rmap_and_dirty_bits(&t.metadata_dev, t.nr_cache_blocks)?;
+        t.set_fast_dev_offset(1048576)?;
+        t.set_origin_dev_offset(4194304)?;
+        t.set_extra_args(&["--verify"]);
+        t.stamp_cache_blocks()?;
+        t.stamp_origin_blocks()?;
+        t.writeback(true)?;
+
+        let (rmap, dirty_bits_upd) = read_rmap_and_dirty_bits(&t.metadata_dev, t.nr_cache_blocks)?;
+        let indicator = Box::new(DirtySourceIndicator::new(
+            rmap,
+            dirty_bits,
+            t.nr_origin_blocks,
+        ));
+        t.verify(indicator)?;
+        assert!(dirty_bits_upd.is_clear());
+
+        Ok(())
+    }
+
+    #[test]
+    fn list_failed_blocks() -> Result<()> {
+        let cache_block_size: usize = 32768; // 32 KiB
+        let nr_cache_blocks: u32 = 1024;
+        let nr_origin_blocks: u64 = 4096;
+
+        let mut t = WritebackTest::new()?;
+        t.format_metadata(cache_block_size, nr_cache_blocks, nr_origin_blocks, 1)?;
+        let (rmap, dirty_bits) = read_rmap_and_dirty_bits(&t.metadata_dev, t.nr_cache_blocks)?;
+        t.stamp_cache_blocks()?;
+        t.stamp_origin_blocks()?;
+
+        // the second half of the cache can't be read
+        let half = nr_cache_blocks / 2;
+        OpenOptions::new()
+            .write(true)
+            .open(&t.fast_dev)?
+            .set_len(half as u64 * cache_block_size as u64)?;
+
+        t.set_extra_args(&["--verify", "--list-failed-blocks"]);
+        let stdout = t.writeback_(true, false)?;
+        let listed: Vec<&str> = stdout.lines().filter(|l| l.contains('#')).collect();
+
+        let mut expected: Vec<u32> = rmap
+            .values()
+            .filter(|cblock| **cblock >= half && dirty_bits.contains(**cblock as usize))
+            .copied()
+            .collect();
+        expected.sort();
+        let expected: Vec<String> = expected
+            .iter()
+            .map(|cblock| format!("{} # read error", cblock))
+            .collect();
+        assert_eq!(listed, expected);
+
+        // the blocks that were written back are clean, the others aren't
+        let (_, dirty_bits_upd) = read_rmap_and_dirty_bits(&t.metadata_dev, t.nr_cache_blocks)?;
+        let remaining = select_dirty_bits(&rmap, &dirty_bits, |_, cblock| cblock >= half);
+        assert_eq!(dirty_bits_upd, remaining);
+
+        Ok(())
+    }
+
+    #[test]
+    fn bad_cblocks_file() -> Result<()> {
+        let cache_block_size: usize = 32768; // 32 KiB
+        let nr_cache_blocks: u32 = 1024;
+        let nr_origin_blocks: u64 = 4096;
+
+        let mut t = WritebackTest::new()?;
+        t.format_metadata(cache_block_size, nr_cache_blocks, nr_origin_blocks, 1)?;
+        t.stamp_cache_blocks()?;
+        t.stamp_origin_blocks()?;
+
+        // beyond the end of the cache, or unparseable
+        let list = t.td.mk_path("cblocks.txt");
+        for text in ["1000..1025\n", "10\nfive\n", "20..10\n"] {
+            std::fs::write(&list, text)?;
+            t.set_extra_args(&["--cblocks-file", list.to_str().unwrap()]);
+            t.writeback_fail(true)?;
+            t.verify(Box::new(NoopIndicator))?;
+        }
+
+        Ok(())
+    }
 }
 
 //------------------------------------------
@@ -68,6 +68,22 @@ where
     rust_devel_cmd("thin_generate_damage", args)
 }
 
+pub fn thin_explore_cmd<I>(args: I) -> Command
+where
+    I: IntoIterator,
+    I::Item: Into<OsString>,
+{
+    rust_devel_cmd("thin_explore", args)
+}
+
+pub fn io_replay_cmd<I>(args: I) -> Command
+where
+    I: IntoIterator,
+    I::Item: Into<OsString>,
+{
+    rust_devel_cmd("io_replay", args)
+}
+
 pub fn thin_restore_cmd<I>(args: I) -> Command
 where
     I: IntoIterator,
@@ -172,6 +188,14 @@ where
     rust_devel_cmd("cache_generate_damage", args)
 }
 
+pub fn cache_metadata_pack_cmd<I>(args: I) -> Command
+where
+    I: IntoIterator,
+    I::Item: Into<OsString>,
+{
+    rust_cmd("cache_metadata_pack", args)
+}
+
 pub fn cache_metadata_size_cmd<I>(args: I) -> Command
 where
     I: IntoIterator,
@@ -180,6 +204,22 @@ where
     rust_cmd("cache_metadata_size", args)
 }
 
+pub fn cache_metadata_unpack_cmd<I>(args: I) -> Command
+where
+    I: IntoIterator,
+    I::Item: Into<OsString>,
+{
+    rust_cmd("cache_metadata_unpack", args)
+}
+
+pub fn cache_resize_cmd<I>(args: I) -> Command
+where
+    I: IntoIterator,
+    I::Item: Into<OsString>,
+{
+    rust_cmd("cache_resize", args)
+}
+
 pub fn cache_restore_cmd<I>(args: I) -> Command
 where
     I: IntoIterator,
@@ -188,6 +228,14 @@ where
     rust_cmd("cache_restore", args)
 }
 
+pub fn cache_stat_cmd<I>(args: I) -> Command
+where
+    I: IntoIterator,
+    I::Item: Into<OsString>,
+{
+    rust_cmd("cache_stat", args)
+}
+
 pub fn cache_repair_cmd<I>(args: I) -> Command
 where
     I: IntoIterator,
@@ -228,6 +276,22 @@ where
     rust_cmd("era_invalidate", args)
 }
 
+pub fn era_metadata_pack_cmd<I>(args: I) -> Command
+where
+    I: IntoIterator,
+    I::Item: Into<OsString>,
+{
+    rust_cmd("era_metadata_pack", args)
+}
+
+pub fn era_metadata_unpack_cmd<I>(args: I) -> Command
+where
+    I: IntoIterator,
+    I::Item: Into<OsString>,
+{
+    rust_cmd("era_metadata_unpack", args)
+}
+
 pub fn era_restore_cmd<I>(args: I) -> Command
 where
     I: IntoIterator,
@@ -236,6 +300,22 @@ where
     rust_cmd("era_restore", args)
 }
 
+pub fn overlay_commit_cmd<I>(args: I) -> Command
+where
+    I: IntoIterator,
+    I::Item: Into<OsString>,
+{
+    rust_cmd("overlay_commit", args)
+}
+
+pub fn overlay_discard_cmd<I>(args: I) -> Command
+where
+    I: IntoIterator,
+    I::Item: Into<OsString>,
+{
+    rust_cmd("overlay_discard", args)
+}
+
 pub fn era_repair_cmd<I>(args: I) -> Command
 where
     I: IntoIterator,
@@ -21,8 +21,11 @@ Arguments:
   <INPUT>  Specify the input device to check
 
 Options:
+      --block-cache <MEGABYTES>  Keep up to this many megabytes of metadata in memory
   -h, --help                     Print help
       --ignore-non-fatal-errors  Only return a non-zero exit code if a fatal error is found.
+      --io-engine <IO_ENGINE>    Select an io engine to use: auto, sync or spindle
+      --io-stats                 Report io statistics at exit
   -q, --quiet                    Suppress output messages, return only exit code.
       --super-block-only         Only check the superblock.
   -V, --version                  Print version";
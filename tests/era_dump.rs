@@ -21,11 +21,14 @@ Arguments:
   <INPUT>  Specify the input device to dump
 
 Options:
-  -h, --help           Print help
-      --logical        Fold any unprocessed write sets into the final era array
-  -o, --output <FILE>  Specify the output file rather than stdout
-  -r, --repair         Repair the metadata whilst dumping it
-  -V, --version        Print version";
+      --block-cache <MEGABYTES>  Keep up to this many megabytes of metadata in memory
+  -h, --help                     Print help
+      --io-engine <IO_ENGINE>    Select an io engine to use: auto, sync or spindle
+      --io-stats                 Report io statistics at exit
+      --logical                  Fold any unprocessed write sets into the final era array
+  -o, --output <FILE>            Specify the output file rather than stdout
+  -r, --repair                   Repair the metadata whilst dumping it
+  -V, --version                  Print version";
 
 //------------------------------------------
 
@@ -112,6 +115,26 @@ fn dump_restore_cycle() -> Result<()> {
     Ok(())
 }
 
+#[test]
+fn dump_restore_cycle_compressed() -> Result<()> {
+    let mut td = TestDir::new()?;
+    let md = mk_valid_md(&mut td)?;
+    let output = run_ok_raw(era_dump_cmd(args![&md]))?;
+
+    let xml_gz = td.mk_path("meta.xml.gz");
+    run_ok(era_dump_cmd(args![&md, "-o", &xml_gz]))?;
+    let compressed = std::fs::read(&xml_gz)?;
+    assert_eq!(&compressed[0..2], &[0x1f, 0x8b]);
+
+    let md2 = mk_zeroed_md(&mut td)?;
+    run_ok(era_restore_cmd(args!["-i", &xml_gz, "-o", &md2]))?;
+
+    let output2 = run_ok_raw(era_dump_cmd(args![&md2]))?;
+    assert_eq!(output.stdout, output2.stdout);
+
+    Ok(())
+}
+
 //------------------------------------------
 // test no stderr on broken pipe errors
 
@@ -19,11 +19,14 @@ Arguments:
   <INPUT>  Specify the input device
 
 Options:
-  -h, --help                 Print help
-      --metadata-snapshot    Use the metadata snapshot rather than the current superblock
-  -o, --output <FILE>        Specify the output file rather than stdout
-  -V, --version              Print version
-      --written-since <ERA>  Blocks written since the given era will be listed";
+      --block-cache <MEGABYTES>  Keep up to this many megabytes of metadata in memory
+  -h, --help                     Print help
+      --io-engine <IO_ENGINE>    Select an io engine to use: auto, sync or spindle
+      --io-stats                 Report io statistics at exit
+      --metadata-snapshot        Use the metadata snapshot rather than the current superblock
+  -o, --output <FILE>            Specify the output file rather than stdout
+  -V, --version                  Print version
+      --written-since <ERA>      Blocks written since the given era will be listed";
 
 //------------------------------------------
 
@@ -19,11 +19,15 @@ const USAGE: &str = "Convert XML format metadata to binary.
 Usage: era_restore [OPTIONS] --input <FILE> --output <FILE>
 
 Options:
-  -h, --help           Print help
-  -i, --input <FILE>   Specify the input xml
-  -o, --output <FILE>  Specify the output device
-  -q, --quiet          Suppress output messages, return only exit code.
-  -V, --version        Print version";
+      --block-cache <MEGABYTES>  Keep up to this many megabytes of metadata in memory
+  -h, --help                     Print help
+  -i, --input <FILE>             Specify the input xml
+      --io-engine <IO_ENGINE>    Select an io engine to use: auto, sync or spindle
+      --io-stats                 Report io statistics at exit
+  -o, --output <FILE>            Specify the output device
+      --overlay <FILE>           Send writes to an overlay file, leaving the device untouched
+  -q, --quiet                    Suppress output messages, return only exit code.
+  -V, --version                  Print version";
 
 //------------------------------------------
 
@@ -0,0 +1,131 @@
+use anyhow::Result;
+
+mod common;
+
+use common::fixture::*;
+use common::process::*;
+use common::target::*;
+use common::test_dir::*;
+use common::thin::*;
+
+//------------------------------------------
+
+fn get_field<'a>(output: &'a str, field: &str) -> &'a str {
+    output
+        .lines()
+        .find_map(|line| line.strip_prefix(field))
+        .map(|value| value.trim())
+        .unwrap()
+}
+
+//------------------------------------------
+
+#[test]
+fn trace_records_check_reads() -> Result<()> {
+    let mut td = TestDir::new()?;
+    let md = prep_metadata(&mut td)?;
+    let trace = td.mk_path("check.trace");
+    run_ok(thin_check_cmd(args!["--io-trace", &trace, &md]))?;
+
+    let output = run_ok(io_replay_cmd(args![&trace]))?;
+    assert_ne!(get_field(&output, "reads:"), "0");
+    assert_eq!(get_field(&output, "writes:"), "0");
+    assert!(!output.contains("replayed ios:"));
+    Ok(())
+}
+
+#[test]
+fn replay_check_trace() -> Result<()> {
+    let mut td = TestDir::new()?;
+    let md = prep_metadata(&mut td)?;
+    let trace = td.mk_path("check.trace");
+    run_ok(thin_check_cmd(args!["--io-trace", &trace, &md]))?;
+
+    let output = run_ok(io_replay_cmd(args!["-i", &md, &trace]))?;
+    assert_eq!(
+        get_field(&output, "replayed ios:"),
+        get_field(&output, "reads:")
+    );
+    assert_eq!(get_field(&output, "replay errors:"), "0");
+    Ok(())
+}
+
+#[test]
+fn trace_records_restore_writes() -> Result<()> {
+    let mut td = TestDir::new()?;
+    let xml = mk_valid_xml(&mut td)?;
+    let md = mk_zeroed_md(&mut td)?;
+    let trace = td.mk_path("restore.trace");
+    run_ok(thin_restore_cmd(args![
+        "-i",
+        &xml,
+        "-o",
+        &md,
+        "--io-trace",
+        &trace
+    ]))?;
+
+    let output = run_ok(io_replay_cmd(args![&trace]))?;
+    assert_ne!(get_field(&output, "writes:"), "0");
+    Ok(())
+}
+
+#[test]
+fn replay_writes_to_a_scratch_device() -> Result<()> {
+    let mut td = TestDir::new()?;
+    let xml = mk_valid_xml(&mut td)?;
+    let md = mk_zeroed_md(&mut td)?;
+    let trace = td.mk_path("restore.trace");
+    run_ok(thin_restore_cmd(args![
+        "-i",
+        &xml,
+        "-o",
+        &md,
+        "--io-trace",
+        &trace
+    ]))?;
+
+    let scratch = mk_zeroed_md(&mut td)?;
+    let output = run_ok(io_replay_cmd(args![
+        "--replay-writes",
+        "-i",
+        &scratch,
+        &trace
+    ]))?;
+    assert_eq!(get_field(&output, "replay errors:"), "0");
+    Ok(())
+}
+
+#[test]
+fn replay_writes_refuses_to_overwrite_metadata() -> Result<()> {
+    let mut td = TestDir::new()?;
+    let md = prep_metadata(&mut td)?;
+    let trace = td.mk_path("check.trace");
+    run_ok(thin_check_cmd(args!["--io-trace", &trace, &md]))?;
+
+    // Answer 'n' to the prompt
+    let before = std::fs::read(&md)?;
+    let output = io_replay_cmd(args!["--replay-writes", "-i", &md, &trace])
+        .to_expr()
+        .stdin_bytes("n\n")
+        .stdout_capture()
+        .stderr_capture()
+        .unchecked()
+        .run()?;
+    assert!(!output.status.success());
+    assert!(std::str::from_utf8(&output.stderr)?.contains("not overwritten"));
+    assert_eq!(std::fs::read(&md)?, before);
+    Ok(())
+}
+
+#[test]
+fn rejects_bad_trace() -> Result<()> {
+    let mut td = TestDir::new()?;
+    let trace = td.mk_path("bad.trace");
+    std::fs::write(&trace, "0 0 0 r 1\nnonsense\n")?;
+    let stderr = run_fail(io_replay_cmd(args![&trace]))?;
+    assert!(stderr.contains("bad trace record at line 2"));
+    Ok(())
+}
+
+//------------------------------------------
@@ -0,0 +1,93 @@
+use anyhow::Result;
+
+mod common;
+
+use common::fixture::*;
+use common::process::*;
+use common::target::*;
+use common::test_dir::*;
+use common::thin::*;
+
+//------------------------------------------
+
+// Restores some thin metadata into an overlay over a zeroed device
+fn restore_to_overlay(td: &mut TestDir) -> Result<(std::path::PathBuf, std::path::PathBuf)> {
+    let xml = mk_valid_xml(td)?;
+    let md = mk_zeroed_md(td)?;
+    let overlay = td.mk_path("meta.overlay");
+    run_ok(thin_restore_cmd(args![
+        "-i",
+        &xml,
+        "-o",
+        &md,
+        "--overlay",
+        &overlay
+    ]))?;
+    Ok((md, overlay))
+}
+
+//------------------------------------------
+
+#[test]
+fn writes_go_to_the_overlay() -> Result<()> {
+    let mut td = TestDir::new()?;
+    let (md, overlay) = restore_to_overlay(&mut td)?;
+    run_fail(thin_check_cmd(args![&md]))?;
+    run_ok(thin_check_cmd(args!["--overlay", &overlay, &md]))?;
+    Ok(())
+}
+
+#[test]
+fn commit_writes_to_the_device() -> Result<()> {
+    let mut td = TestDir::new()?;
+    let (md, overlay) = restore_to_overlay(&mut td)?;
+    run_ok(overlay_commit_cmd(args!["-o", &md, &overlay]))?;
+    assert!(!overlay.exists());
+    run_ok(thin_check_cmd(args![&md]))?;
+    Ok(())
+}
+
+#[test]
+fn commit_rejects_a_different_device() -> Result<()> {
+    let mut td = TestDir::new()?;
+    let (_md, overlay) = restore_to_overlay(&mut td)?;
+    let other = mk_zeroed_md_sized(&mut td, 8 * 1024 * 1024)?;
+    let stderr = run_fail(overlay_commit_cmd(args!["-o", &other, &overlay]))?;
+    assert!(stderr.contains("the overlay was made for a device of"));
+    assert!(overlay.exists());
+    Ok(())
+}
+
+#[test]
+fn commit_rejects_a_changed_device() -> Result<()> {
+    let mut td = TestDir::new()?;
+    let (md, overlay) = restore_to_overlay(&mut td)?;
+    let xml = mk_valid_xml(&mut td)?;
+    run_ok(thin_restore_cmd(args!["-i", &xml, "-o", &md]))?;
+    let stderr = run_fail(overlay_commit_cmd(args!["-o", &md, &overlay]))?;
+    assert!(stderr.contains("has changed since the overlay was made"));
+    assert!(overlay.exists());
+    Ok(())
+}
+
+#[test]
+fn discard_leaves_the_device_alone() -> Result<()> {
+    let mut td = TestDir::new()?;
+    let (md, overlay) = restore_to_overlay(&mut td)?;
+    run_ok(overlay_discard_cmd(args![&overlay]))?;
+    assert!(!overlay.exists());
+    run_fail(thin_check_cmd(args![&md]))?;
+    Ok(())
+}
+
+#[test]
+fn discard_rejects_other_files() -> Result<()> {
+    let mut td = TestDir::new()?;
+    let md = mk_zeroed_md(&mut td)?;
+    let stderr = run_fail(overlay_discard_cmd(args![&md]))?;
+    assert!(stderr.contains("not an overlay file"));
+    assert!(md.exists());
+    Ok(())
+}
+
+//------------------------------------------
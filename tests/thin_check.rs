@@ -1,4 +1,5 @@
 use anyhow::Result;
+use thinp::io_engine::nbd::server::NbdServer;
 
 mod common;
 
@@ -21,17 +22,36 @@ Arguments:
   <INPUT>  Specify the input device to check
 
 Options:
-      --auto-repair                      Auto repair trivial issues.
-      --clear-needs-check-flag           Clears the 'needs_check' flag in the superblock
-  -h, --help                             Print help
-      --ignore-non-fatal-errors          Only return a non-zero exit code if a fatal error is found.
-  -m, --metadata-snap                    Check the metadata snapshot on a live pool
-      --override-details-root <BLOCKNR>  Specify a details root to use
-      --override-mapping-root <BLOCKNR>  Specify a mapping root to use
-  -q, --quiet                            Suppress output messages, return only exit code.
-      --skip-mappings                    Don't check the mapping tree
-      --super-block-only                 Only check the superblock.
-  -V, --version                          Print version";
+      --auto-repair
+          Auto repair trivial issues.
+      --block-cache <MEGABYTES>
+          Keep up to this many megabytes of metadata in memory
+      --clear-needs-check-flag
+          Clears the 'needs_check' flag in the superblock
+  -h, --help
+          Print help
+      --ignore-non-fatal-errors
+          Only return a non-zero exit code if a fatal error is found.
+      --io-engine <IO_ENGINE>
+          Select an io engine to use: auto, sync or spindle
+      --io-stats
+          Report io statistics at exit
+  -m, --metadata-snap
+          Check the metadata snapshot on a live pool
+      --overlay <FILE>
+          Send writes to an overlay file, leaving the device untouched
+      --override-details-root <BLOCKNR>
+          Specify a details root to use
+      --override-mapping-root <BLOCKNR>
+          Specify a mapping root to use
+  -q, --quiet
+          Suppress output messages, return only exit code.
+      --skip-mappings
+          Don't check the mapping tree
+      --super-block-only
+          Only check the superblock.
+  -V, --version
+          Print version";
 
 //-----------------------------------------
 
@@ -389,6 +409,24 @@ fn rejects_clear_needs_check_with_override_details_root() -> Result<()> {
     Ok(())
 }
 
+//------------------------------------------
+// test checking a pack file
+
+#[test]
+fn checks_pack_file() -> Result<()> {
+    let pack = path_to(TestData::PackedMetadata)?;
+    run_ok(thin_check_cmd(args![&pack]))?;
+    Ok(())
+}
+
+#[test]
+fn rejects_repairing_pack_file() -> Result<()> {
+    let pack = path_to(TestData::PackedMetadata)?;
+    let stderr = run_fail(thin_check_cmd(args!["--auto-repair", &pack]))?;
+    assert!(stderr.contains("pack files can only be opened read only"));
+    Ok(())
+}
+
 //------------------------------------------
 // test clear-needs-check
 
@@ -660,3 +698,160 @@ fn offline_check_metadata_with_deleted_snapshot_should_success() -> Result<()> {
 }
 
 //------------------------------------------
+// test injected io faults
+
+#[test]
+fn check_passes_without_injected_faults() -> Result<()> {
+    let mut td = TestDir::new()?;
+    let md = prep_metadata(&mut td)?;
+    run_ok(thin_check_cmd(args!["--io-engine", "fault:seed=1", &md]))?;
+    Ok(())
+}
+
+#[test]
+fn check_reports_injected_read_errors() -> Result<()> {
+    let mut td = TestDir::new()?;
+    let md = prep_metadata(&mut td)?;
+    let stderr = run_fail(thin_check_cmd(args!["--io-engine", "fault:read=5", &md]))?;
+    assert!(stderr.contains("io error"));
+    run_ok(thin_check_cmd(args![&md]))?;
+    Ok(())
+}
+
+#[test]
+fn check_reports_flipped_bits() -> Result<()> {
+    let mut td = TestDir::new()?;
+    let md = prep_metadata(&mut td)?;
+    let stderr = run_fail(thin_check_cmd(args!["--io-engine", "fault:flip=1", &md]))?;
+    assert!(stderr.contains("checksum error"));
+    Ok(())
+}
+
+#[test]
+fn rejects_bad_fault_spec() -> Result<()> {
+    let mut td = TestDir::new()?;
+    let md = prep_metadata(&mut td)?;
+    let stderr = run_fail(thin_check_cmd(args!["--io-engine", "fault:bogus=1", &md]))?;
+    assert!(stderr.contains("unknown fault setting"));
+    Ok(())
+}
+
+//------------------------------------------
+// test io stats
+
+#[test]
+fn check_reports_io_stats() -> Result<()> {
+    let mut td = TestDir::new()?;
+    let md = prep_metadata(&mut td)?;
+    let output = run_ok_raw(thin_check_cmd(args!["--io-stats", &md]))?;
+    let stderr = std::str::from_utf8(&output.stderr)?;
+    assert!(stderr.contains("IO stats:"));
+    assert!(stderr.contains("reads latency: p50"));
+    assert!(stderr.contains("writes: 0 ios"));
+    Ok(())
+}
+
+#[test]
+fn check_with_block_cache() -> Result<()> {
+    let mut td = TestDir::new()?;
+    let md = prep_metadata(&mut td)?;
+    let output = run_ok_raw(thin_check_cmd(args![
+        "--block-cache",
+        "4",
+        "--io-stats",
+        &md
+    ]))?;
+    let stderr = std::str::from_utf8(&output.stderr)?;
+    assert!(stderr.contains("cache:"));
+    assert!(stderr.contains("hit rate"));
+    Ok(())
+}
+
+#[test]
+fn quiet_check_omits_io_stats() -> Result<()> {
+    let mut td = TestDir::new()?;
+    let md = prep_metadata(&mut td)?;
+    let output = run_ok_raw(thin_check_cmd(args!["-q", "--io-stats", &md]))?;
+    assert!(output.stderr.is_empty());
+    Ok(())
+}
+
+#[test]
+fn check_omits_io_stats_by_default() -> Result<()> {
+    let mut td = TestDir::new()?;
+    let md = prep_metadata(&mut td)?;
+    let output = run_ok_raw(thin_check_cmd(args![&md]))?;
+    assert!(!std::str::from_utf8(&output.stderr)?.contains("IO stats:"));
+    Ok(())
+}
+
+//------------------------------------------
+// test io engine selection
+
+#[test]
+fn check_logs_auto_engine_choice() -> Result<()> {
+    let mut td = TestDir::new()?;
+    let md = prep_metadata(&mut td)?;
+    let output = run_ok_raw(thin_check_cmd(args!["-v", &md]))?;
+    let stderr = std::str::from_utf8(&output.stderr)?;
+    assert!(stderr.contains("using the sync io engine"));
+    assert!(stderr.contains("which is a regular file"));
+    Ok(())
+}
+
+#[test]
+fn check_with_spindle_engine() -> Result<()> {
+    let mut td = TestDir::new()?;
+    let md = prep_metadata(&mut td)?;
+    let output = run_ok_raw(thin_check_cmd(args!["-v", "--io-engine", "spindle", &md]))?;
+    let stderr = std::str::from_utf8(&output.stderr)?;
+    assert!(!stderr.contains("using the"));
+    Ok(())
+}
+
+#[test]
+fn check_rejects_unknown_engine() -> Result<()> {
+    let mut td = TestDir::new()?;
+    let md = prep_metadata(&mut td)?;
+    let stderr = run_fail(thin_check_cmd(args!["--io-engine", "warp", &md]))?;
+    assert!(stderr.contains("unknown io engine type 'warp'"));
+    Ok(())
+}
+
+//------------------------------------------
+// test remote metadata
+
+#[test]
+fn check_nbd_export() -> Result<()> {
+    let mut td = TestDir::new()?;
+    let md = prep_metadata(&mut td)?;
+    let server = NbdServer::start(&md, "pool-meta", true)?;
+    let url = server.url();
+    run_ok(thin_check_cmd(args![&url]))?;
+    assert!(server.nr_requests() > 0);
+    Ok(())
+}
+
+#[test]
+fn check_nbd_export_is_read_only() -> Result<()> {
+    let mut td = TestDir::new()?;
+    let md = prep_metadata(&mut td)?;
+    let server = NbdServer::start(&md, "pool-meta", true)?;
+    let url = server.url();
+    let stderr = run_fail(thin_check_cmd(args!["--clear-needs-check-flag", &url]))?;
+    assert!(stderr.contains("read only"));
+    Ok(())
+}
+
+#[test]
+fn check_unknown_nbd_export() -> Result<()> {
+    let mut td = TestDir::new()?;
+    let md = prep_metadata(&mut td)?;
+    let server = NbdServer::start(&md, "pool-meta", true)?;
+    let url = server.url().replace("pool-meta", "other");
+    let stderr = run_fail(thin_check_cmd(args![&url]))?;
+    assert!(stderr.contains("no export 'other'"));
+    Ok(())
+}
+
+//------------------------------------------
@@ -19,14 +19,17 @@ Arguments:
   <INPUT>  Specify the input device
 
 Options:
-  -h, --help             Print help
-  -m, --metadata-snap    Use metadata snapshot
-      --root1 <BLOCKNR>  The root block for the first thin volume to diff
-      --root2 <BLOCKNR>  The root block for the second thin volume to diff
-      --thin1 <DEV_ID>   The numeric identifier for the first thin volume to diff [aliases: --snap1]
-      --thin2 <DEV_ID>   The numeric identifier for the second thin volume to diff [aliases: --snap2]
-  -V, --version          Print version
-      --verbose          Provide extra information on the mappings";
+      --block-cache <MEGABYTES>  Keep up to this many megabytes of metadata in memory
+  -h, --help                     Print help
+      --io-engine <IO_ENGINE>    Select an io engine to use: auto, sync or spindle
+      --io-stats                 Report io statistics at exit
+  -m, --metadata-snap            Use metadata snapshot
+      --root1 <BLOCKNR>          The root block for the first thin volume to diff
+      --root2 <BLOCKNR>          The root block for the second thin volume to diff
+      --thin1 <DEV_ID>           The numeric identifier for the first thin volume to diff [aliases: --snap1]
+      --thin2 <DEV_ID>           The numeric identifier for the second thin volume to diff [aliases: --snap2]
+  -V, --version                  Print version
+      --verbose                  Provide extra information on the mappings";
 
 //------------------------------------------
 
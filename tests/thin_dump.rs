@@ -21,10 +21,13 @@ Arguments:
   <INPUT>  Specify the input device to dump
 
 Options:
+      --block-cache <MEGABYTES>    Keep up to this many megabytes of metadata in memory
       --data-block-size <SECTORS>  Provide the data block size for repairing
       --dev-id <THIN_ID>           Dump the specified device
   -f, --format <TYPE>              Choose the output format
   -h, --help                       Print help
+      --io-engine <IO_ENGINE>      Select an io engine to use: auto, sync or spindle
+      --io-stats                   Report io statistics at exit
   -m, --metadata-snap[=<BLOCKNR>]  Access the metadata snapshot on a live pool
       --nr-data-blocks <NUM>       Override the number of data blocks if needed
   -o, --output <FILE>              Specify the output file rather than stdout
@@ -117,6 +120,48 @@ fn dump_restore_cycle() -> Result<()> {
     Ok(())
 }
 
+#[test]
+fn dump_restore_cycle_compressed() -> Result<()> {
+    let mut td = TestDir::new()?;
+    let md = prep_rebuilt_metadata(&mut td)?;
+    let output = run_ok_raw(thin_dump_cmd(args![&md]))?;
+
+    let xml_gz = td.mk_path("meta.xml.gz");
+    run_ok(thin_dump_cmd(args![&md, "-o", &xml_gz]))?;
+    let compressed = std::fs::read(&xml_gz)?;
+    assert_eq!(&compressed[0..2], &[0x1f, 0x8b]);
+
+    let md2 = mk_zeroed_md(&mut td)?;
+    run_ok(thin_restore_cmd(args!["-i", &xml_gz, "-o", &md2]))?;
+
+    let output2 = run_ok_raw(thin_dump_cmd(args![&md2]))?;
+    assert_eq!(output.stdout, output2.stdout);
+
+    Ok(())
+}
+
+//------------------------------------------
+// test dumping straight from a pack file
+
+#[test]
+fn dump_from_pack() -> Result<()> {
+    let mut td = TestDir::new()?;
+    let md = prep_metadata(&mut td)?;
+    let expected = run_ok_raw(thin_dump_cmd(args![&md]))?;
+
+    // packs from older releases have no index
+    let old_pack = path_to(TestData::PackedMetadata)?;
+    let output = run_ok_raw(thin_dump_cmd(args![&old_pack]))?;
+    assert_eq!(expected.stdout, output.stdout);
+
+    let pack = td.mk_path("meta.pack");
+    run_ok(thin_metadata_pack_cmd(args!["-i", &md, "-o", &pack]))?;
+    let output = run_ok_raw(thin_dump_cmd(args![&pack]))?;
+    assert_eq!(expected.stdout, output.stdout);
+
+    Ok(())
+}
+
 //------------------------------------------
 // test no stderr with a normal dump
 
@@ -0,0 +1,228 @@
+use anyhow::Result;
+
+mod common;
+
+use common::process::*;
+use common::target::*;
+use common::test_dir::*;
+use common::thin::*;
+
+//------------------------------------------
+
+fn get_field<'a>(output: &'a str, field: &str) -> &'a str {
+    output
+        .lines()
+        .find_map(|line| line.trim_start().strip_prefix(field))
+        .map(|value| value.trim())
+        .unwrap()
+}
+
+//------------------------------------------
+
+#[test]
+fn query_superblock() -> Result<()> {
+    let mut td = TestDir::new()?;
+    let md = prep_metadata(&mut td)?;
+    let output = run_ok(thin_explore_cmd(args!["--superblock", &md]))?;
+    assert!(output.starts_with("Superblock"));
+    assert_eq!(get_field(&output, "mapping root"), "5");
+    assert_eq!(get_field(&output, "details root"), "1");
+    Ok(())
+}
+
+#[test]
+fn query_devices_as_json() -> Result<()> {
+    let mut td = TestDir::new()?;
+    let md = prep_metadata(&mut td)?;
+    let output = run_ok(thin_explore_cmd(args![
+        "--devices",
+        "--format",
+        "json",
+        &md
+    ]))?;
+    assert!(output.starts_with("{\"title\": \"Device Details\""));
+    assert!(output.contains("\"fields\": {\"nr devices\": \"11\"}"));
+    Ok(())
+}
+
+#[test]
+fn find_thin_and_data_blocks_agree() -> Result<()> {
+    let mut td = TestDir::new()?;
+    let md = prep_metadata(&mut td)?;
+
+    let output = run_ok(thin_explore_cmd(args![
+        "--find-thin",
+        "0",
+        "--dev-id",
+        "1",
+        &md
+    ]))?;
+    let (data_block, time) = get_field(&output, "mapped to").split_once(" @ ").unwrap();
+    let leaf = get_field(&output, "block");
+
+    let output = run_ok(thin_explore_cmd(args!["--find-data", data_block, &md]))?;
+    let expected = format!("1 -> 0 @ {}, leaf {}, node path", time, leaf);
+    assert!(output.contains(&expected));
+    Ok(())
+}
+
+#[test]
+fn query_block_matches_node_path() -> Result<()> {
+    let mut td = TestDir::new()?;
+    let md = prep_metadata(&mut td)?;
+
+    let output = run_ok(thin_explore_cmd(args![
+        "--find-thin",
+        "100",
+        "--dev-id",
+        "2",
+        &md
+    ]))?;
+    let leaf = get_field(&output, "block");
+    let node_path = get_field(&output, "node path");
+
+    let by_block = run_ok(thin_explore_cmd(args!["--block", leaf, &md]))?;
+    let by_path = run_ok(thin_explore_cmd(args!["--print", "-p", node_path, &md]))?;
+    assert_eq!(by_block, by_path);
+    assert!(by_block.starts_with("Thin dev #2"));
+    Ok(())
+}
+
+#[test]
+fn query_needs_thin_id() -> Result<()> {
+    let mut td = TestDir::new()?;
+    let md = prep_metadata(&mut td)?;
+    let stderr = run_fail(thin_explore_cmd(args!["--find-thin", "0", &md]))?;
+    assert!(stderr.contains("--dev-id"));
+    Ok(())
+}
+
+#[test]
+fn query_space_map_blocks() -> Result<()> {
+    let mut td = TestDir::new()?;
+    let md = prep_metadata(&mut td)?;
+
+    let output = run_ok(thin_explore_cmd(args!["--superblock", &md]))?;
+    let roots = get_field(&output, "metadata roots");
+    let (index, _) = roots
+        .trim_matches(|c| c == '[' || c == ']')
+        .split_once(", ")
+        .unwrap();
+
+    let output = run_ok(thin_explore_cmd(args!["--block", index, &md]))?;
+    assert!(output.starts_with("Metadata Space Map"));
+    let bitmap = output
+        .lines()
+        .find_map(|line| line.trim_start().strip_prefix("0 -> block = "))
+        .and_then(|rest| rest.split_once(','))
+        .map(|(b, _)| b)
+        .unwrap();
+
+    let output = run_ok(thin_explore_cmd(args!["--block", bitmap, &md]))?;
+    assert!(output.starts_with("Bitmap"));
+    assert_eq!(get_field(&output, "first entry"), "0");
+    Ok(())
+}
+
+#[test]
+fn set_field_recomputes_checksum() -> Result<()> {
+    let mut td = TestDir::new()?;
+    let md = prep_metadata(&mut td)?;
+
+    let output = run_ok(thin_explore_cmd(args![
+        "--edit",
+        "--block",
+        "0",
+        "--set-field",
+        "transaction_id=99",
+        &md
+    ]))?;
+    assert_eq!(get_field(&output, "transaction_id"), "99");
+
+    let output = run_ok(thin_explore_cmd(args!["--superblock", &md]))?;
+    assert_eq!(get_field(&output, "transaction_id"), "99");
+    run_ok(thin_check_cmd(args![&md]))?;
+    Ok(())
+}
+
+#[test]
+fn set_field_needs_edit() -> Result<()> {
+    let mut td = TestDir::new()?;
+    let md = prep_metadata(&mut td)?;
+    let stderr = run_fail(thin_explore_cmd(args![
+        "--block",
+        "0",
+        "--set-field",
+        "transaction_id=99",
+        &md
+    ]))?;
+    assert!(stderr.contains("--edit"));
+    Ok(())
+}
+
+#[test]
+fn set_bytes_rejects_checksum() -> Result<()> {
+    let mut td = TestDir::new()?;
+    let md = prep_metadata(&mut td)?;
+    let before = std::fs::read(&md)?;
+    let stderr = run_fail(thin_explore_cmd(args![
+        "--edit",
+        "--block",
+        "0",
+        "--set-bytes",
+        "0=00000000",
+        &md
+    ]))?;
+    assert!(stderr.contains("outside the editable part of the block"));
+    assert_eq!(std::fs::read(&md)?, before);
+    Ok(())
+}
+
+#[test]
+fn query_cache_mappings() -> Result<()> {
+    let mut td = TestDir::new()?;
+    let md = common::cache::mk_valid_md(&mut td)?;
+
+    let output = run_ok(thin_explore_cmd(args!["--superblock", &md]))?;
+    assert!(output.starts_with("Cache Superblock"));
+    let mapping_root = get_field(&output, "mapping root");
+
+    let output = run_ok(thin_explore_cmd(args!["--block", mapping_root, &md]))?;
+    assert!(output.starts_with("Mappings"));
+    let ablock = output
+        .lines()
+        .find_map(|line| line.trim_start().strip_prefix("0 "))
+        .and_then(|rest| rest.rsplit_once("-> "))
+        .map(|(_, b)| b)
+        .unwrap();
+
+    let output = run_ok(thin_explore_cmd(args!["--block", ablock, &md]))?;
+    assert!(output.starts_with("Mappings Block"));
+    assert_eq!(get_field(&output, "first entry"), "0");
+    Ok(())
+}
+
+#[test]
+fn query_era_writesets() -> Result<()> {
+    let mut td = TestDir::new()?;
+    let md = common::era::mk_valid_md(&mut td)?;
+
+    let output = run_ok(thin_explore_cmd(args!["--superblock", &md]))?;
+    assert!(output.starts_with("Era Superblock"));
+    let root = get_field(&output, "writeset root");
+
+    let output = run_ok(thin_explore_cmd(args!["--block", root, &md]))?;
+    assert!(output.starts_with("Writesets"));
+    Ok(())
+}
+
+#[test]
+fn thin_queries_need_thin_metadata() -> Result<()> {
+    let mut td = TestDir::new()?;
+    let md = common::cache::mk_valid_md(&mut td)?;
+    let stderr = run_fail(thin_explore_cmd(args!["--devices", &md]))?;
+    assert!(stderr.contains("needs thin metadata"));
+    Ok(())
+}
+
+//------------------------------------------
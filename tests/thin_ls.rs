@@ -21,11 +21,14 @@ Arguments:
   <INPUT>  Specify the input device
 
 Options:
-  -h, --help             Print help
-  -m, --metadata-snap    Use metadata snapshot
-      --no-headers       Don't output headers
-  -o, --format <FIELDS>  Give a comma separated list of fields to be output
-  -V, --version          Print version";
+      --block-cache <MEGABYTES>  Keep up to this many megabytes of metadata in memory
+  -h, --help                     Print help
+      --io-engine <IO_ENGINE>    Select an io engine to use: auto, sync or spindle
+      --io-stats                 Report io statistics at exit
+  -m, --metadata-snap            Use metadata snapshot
+      --no-headers               Don't output headers
+  -o, --format <FIELDS>          Give a comma separated list of fields to be output
+  -V, --version                  Print version";
 
 //-----------------------------------------
 
@@ -17,9 +17,11 @@ const USAGE: &str = "Produces a compressed file of thin metadata.  Only packs me
 Usage: thin_metadata_pack [OPTIONS] --input <DEV> --output <FILE>
 
 Options:
+      --base <FILE>    Only pack blocks changed since the given base pack(s)
   -f, --force          Force overwrite the output file
   -h, --help           Print help
   -i, --input <DEV>    Specify thinp metadata binary device/file
+      --io-stats       Report io statistics at exit
   -o, --output <FILE>  Specify packed output file
   -V, --version        Print version";
 
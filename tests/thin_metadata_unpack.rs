@@ -3,6 +3,7 @@ use anyhow::Result;
 mod common;
 
 use common::common_args::*;
+use common::fixture::*;
 use common::input_arg::*;
 use common::output_option::*;
 use common::process::*;
@@ -18,9 +19,11 @@ const USAGE: &str = "Unpack a compressed file of thin metadata.
 Usage: thin_metadata_unpack [OPTIONS] --input <FILE> --output <DEV>
 
 Options:
+      --base <FILE>   Specify the base pack(s) of an incremental input
   -f, --force         Force overwrite the output file
   -h, --help          Print help
   -i, --input <FILE>  Specify packed input file
+      --io-stats      Report io statistics at exit
   -o, --output <DEV>  Specify thinp metadata binary device/file
   -V, --version       Print version";
 
@@ -113,4 +116,118 @@ fn end_to_end() -> Result<()> {
     Ok(())
 }
 
+#[test]
+fn accepts_engine_options() -> Result<()> {
+    let mut td = TestDir::new()?;
+    let md_in = mk_valid_md(&mut td)?;
+    let md_packed = td.mk_path("meta.pack");
+    let md_out = td.mk_path("meta.out");
+    run_ok(thin_metadata_pack_cmd(args![
+        "-i", &md_in, "-o", &md_packed
+    ]))?;
+    run_ok(thin_metadata_unpack_cmd(args![
+        "-i",
+        &md_packed,
+        "-o",
+        &md_out,
+        "--io-engine",
+        "sync"
+    ]))?;
+
+    let dump1 = run_ok(thin_dump_cmd(args![&md_in]))?;
+    let dump2 = run_ok(thin_dump_cmd(args![&md_out]))?;
+    assert_eq!(dump1, dump2);
+    Ok(())
+}
+
+#[test]
+fn end_to_end_reports_io_stats() -> Result<()> {
+    let mut td = TestDir::new()?;
+    let md_in = mk_valid_md(&mut td)?;
+    let md_packed = td.mk_path("meta.pack");
+    let md_out = td.mk_path("meta.out");
+
+    let output = run_ok_raw(thin_metadata_pack_cmd(args![
+        "-i",
+        &md_in,
+        "-o",
+        &md_packed,
+        "--io-stats"
+    ]))?;
+    let stderr = std::str::from_utf8(&output.stderr)?;
+    assert!(stderr.contains("IO stats:"));
+    assert!(stderr.contains("reads latency: p50"));
+    assert!(stderr.contains("writes: 0 ios"));
+
+    let output = run_ok_raw(thin_metadata_unpack_cmd(args![
+        "-i",
+        &md_packed,
+        "-o",
+        &md_out,
+        "--io-stats"
+    ]))?;
+    let stderr = std::str::from_utf8(&output.stderr)?;
+    assert!(stderr.contains("reads: 0 ios"));
+    assert!(stderr.contains("writes latency: p50"));
+    Ok(())
+}
+
+#[test]
+fn incremental_end_to_end() -> Result<()> {
+    let mut td = TestDir::new()?;
+    let md_in = mk_valid_md(&mut td)?;
+
+    let full = td.mk_path("full.pack");
+    run_ok(thin_metadata_pack_cmd(args!["-i", &md_in, "-o", &full]))?;
+
+    set_needs_check(&md_in)?;
+    let incr1 = td.mk_path("incr1.pack");
+    run_ok(thin_metadata_pack_cmd(args![
+        "-i", &md_in, "-o", &incr1, "--base", &full
+    ]))?;
+    assert!(std::fs::metadata(&incr1)?.len() < std::fs::metadata(&full)?.len());
+
+    generate_metadata_leaks(&md_in, 16, 0, 1)?;
+    let incr2 = td.mk_path("incr2.pack");
+    run_ok(thin_metadata_pack_cmd(args![
+        "-i", &md_in, "-o", &incr2, "--base", &full, "--base", &incr1
+    ]))?;
+
+    // unpacking the chain gives the same image as a full pack
+    let md_full = td.mk_path("meta_full.out");
+    let latest = td.mk_path("latest.pack");
+    run_ok(thin_metadata_pack_cmd(args!["-i", &md_in, "-o", &latest]))?;
+    run_ok(thin_metadata_unpack_cmd(args![
+        "-i", &latest, "-o", &md_full
+    ]))?;
+
+    let md_out = td.mk_path("meta.out");
+    run_ok(thin_metadata_unpack_cmd(args![
+        "-i", &incr2, "-o", &md_out, "--base", &incr1, "--base", &full
+    ]))?;
+
+    assert_eq!(sha256sum(&md_full)?, sha256sum(&md_out)?);
+    Ok(())
+}
+
+#[test]
+fn incremental_needs_base() -> Result<()> {
+    let mut td = TestDir::new()?;
+    let md_in = mk_valid_md(&mut td)?;
+
+    let full = td.mk_path("full.pack");
+    run_ok(thin_metadata_pack_cmd(args!["-i", &md_in, "-o", &full]))?;
+
+    set_needs_check(&md_in)?;
+    let incr = td.mk_path("incr.pack");
+    run_ok(thin_metadata_pack_cmd(args![
+        "-i", &md_in, "-o", &incr, "--base", &full
+    ]))?;
+
+    let md_out = td.mk_path("meta.out");
+    let stderr = run_fail(thin_metadata_unpack_cmd(args!["-i", &incr, "-o", &md_out]))?;
+    assert!(stderr.contains("was not given"));
+    Ok(())
+}
+
 //------------------------------------------
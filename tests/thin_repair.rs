@@ -19,11 +19,15 @@ const USAGE: &str = "Repair thin-provisioning metadata, and write it to differen
 Usage: thin_repair [OPTIONS] --input <FILE> --output <FILE>
 
 Options:
+      --block-cache <MEGABYTES>    Keep up to this many megabytes of metadata in memory
       --data-block-size <SECTORS>  Provide the data block size for repairing
   -h, --help                       Print help
   -i, --input <FILE>               Specify the input device
+      --io-engine <IO_ENGINE>      Select an io engine to use: auto, sync or spindle
+      --io-stats                   Report io statistics at exit
       --nr-data-blocks <NUM>       Override the number of data blocks if needed
   -o, --output <FILE>              Specify the output device
+      --overlay <FILE>             Send writes to an overlay file, leaving the device untouched
   -q, --quiet                      Suppress output messages, return only exit code.
       --transaction-id <NUM>       Override the transaction id if needed
   -V, --version                    Print version";
@@ -366,4 +370,22 @@ fn repair_device_details_tree() -> Result<()> {
 
     Ok(())
 }
+
+#[test]
+fn repair_reports_injected_write_errors() -> Result<()> {
+    let mut td = TestDir::new()?;
+    let src = mk_valid_md(&mut td)?;
+    let dest = mk_zeroed_md(&mut td)?;
+    let stderr = run_fail(thin_repair_cmd(args![
+        "--io-engine",
+        "fault:write=0",
+        "-i",
+        &src,
+        "-o",
+        &dest
+    ]))?;
+    assert!(stderr.contains("injected write failure"));
+    Ok(())
+}
+
 //-----------------------------------------
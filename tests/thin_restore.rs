@@ -19,11 +19,15 @@ const USAGE: &str = "Convert XML format metadata to binary.
 Usage: thin_restore [OPTIONS] --input <FILE> --output <FILE>
 
 Options:
+      --block-cache <MEGABYTES>    Keep up to this many megabytes of metadata in memory
       --data-block-size <SECTORS>  Override the data block size if needed
   -h, --help                       Print help
   -i, --input <FILE>               Specify the input xml
+      --io-engine <IO_ENGINE>      Select an io engine to use: auto, sync or spindle
+      --io-stats                   Report io statistics at exit
       --nr-data-blocks <NUM>       Override the number of data blocks if needed
   -o, --output <FILE>              Specify the output device
+      --overlay <FILE>             Send writes to an overlay file, leaving the device untouched
   -q, --quiet                      Suppress output messages, return only exit code.
       --transaction-id <NUM>       Override the transaction id if needed
   -V, --version                    Print version";
@@ -159,3 +163,17 @@ fn override_nr_data_blocks() -> Result<()> {
 }
 
 //-----------------------------------------
+
+#[test]
+fn restore_reports_io_stats() -> Result<()> {
+    let mut td = TestDir::new()?;
+    let xml = mk_valid_xml(&mut td)?;
+    let md = mk_zeroed_md(&mut td)?;
+    let output = run_ok_raw(thin_restore_cmd(args!["-i", &xml, "-o", &md, "--io-stats"]))?;
+    let stderr = std::str::from_utf8(&output.stderr)?;
+    assert!(stderr.contains("IO stats:"));
+    assert!(!stderr.contains("writes: 0 ios"));
+    Ok(())
+}
+
+//-----------------------------------------
@@ -13,15 +13,18 @@ use common::thin::*;
 
 const USAGE: &str = "Output reverse map of a thin provisioned region of blocks
 
-Usage: thin_rmap --region <BLOCK_RANGE> <INPUT>
+Usage: thin_rmap [OPTIONS] --region <BLOCK_RANGE> <INPUT>
 
 Arguments:
   <INPUT>  Specify the input device
 
 Options:
-  -h, --help                  Print help
-      --region <BLOCK_RANGE>  Specify range of blocks on the data device
-  -V, --version               Print version";
+      --block-cache <MEGABYTES>  Keep up to this many megabytes of metadata in memory
+  -h, --help                     Print help
+      --io-engine <IO_ENGINE>    Select an io engine to use: auto, sync or spindle
+      --io-stats                 Report io statistics at exit
+      --region <BLOCK_RANGE>     Specify range of blocks on the data device
+  -V, --version                  Print version";
 
 //------------------------------------------
 